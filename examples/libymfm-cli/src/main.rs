@@ -9,12 +9,27 @@ use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
 use std::{env, io, process};
-use clap::{App, Arg};
-use crate::libymfm::driver::{VgmPlay, VGM_TICK_RATE, XgmPlay, XGM_NTSC_TICK_RATE};
+use clap::{App, AppSettings, Arg, SubCommand};
+use crate::libymfm::driver::{get_vgm_analysis_json, Error, Gd3, VDPMode, VgmPlay, VGM_TICK_RATE, XgmPlay, Xgm2Play, XGM_NTSC_TICK_RATE, GymPlay, GYM_TICK_RATE, S98Play, S98_TICK_RATE, DroPlay, DRO_TICK_RATE, ImfPlay, ImfRate, IMF_TICK_RATE, MdxPlay, MDX_TICK_RATE, PlaylistPlay, PackReader, PackFormat};
 use crate::libymfm::sound::SoundSlot;
 
 const MAX_SAMPLE_SIZE: usize = 2048;
 
+// GD3 tag option name and help
+const GD3_FIELDS: [(&str, &str); 11] = [
+    ("track-name", "Track name"),
+    ("track-name-j", "Track name (Japanese)"),
+    ("game-name", "Game name"),
+    ("game-name-j", "Game name (Japanese)"),
+    ("system-name", "System name"),
+    ("system-name-j", "System name (Japanese)"),
+    ("author", "Track author"),
+    ("author-j", "Track author (Japanese)"),
+    ("date", "Release date"),
+    ("converted", "Name of person who converted it to a VGM file"),
+    ("notes", "Notes"),
+];
+
 fn main() {
     let app = App::new(crate_name!())
         .version(crate_version!())
@@ -22,8 +37,14 @@ fn main() {
         .about(crate_description!())
         .arg(
             Arg::with_name("filename")
-                .help("Play .vgm/.vzg/.xgm/.xgz file path")
-                .required(true),
+                .help("Play .vgm/.vzg/.xgm/.xgz/.xgm2/.gym/.s98/.dro/.imf/.wlf/.mdx/.m3u/.m3u8/.zip file path")
+                .required_unless("playlist"),
+        )
+        .arg(
+            Arg::with_name("playlist")
+                .help("Play the entries of .m3u/.m3u8 playlist file path")
+                .long("playlist")
+                .takes_value(true),
         )
         .arg(
             Arg::with_name("rate")
@@ -38,16 +59,187 @@ fn main() {
                 .long("loop")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("track")
+                .help("Track number (from 1) of multi tracks .xgm/.xgz/.xgm2 file, or entry number of .zip file")
+                .long("track")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("vdp mode")
+                .help("Force NTSC or PAL timing of .xgm/.xgz/.xgm2 file")
+                .long("vdp-mode")
+                .possible_values(&["ntsc", "pal"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("imf rate")
+                .help("Timer rate of .imf/.wlf file (default: 560)")
+                .long("imf-rate")
+                .possible_values(&["280", "560", "700"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ym2608 rhythm rom")
+                .help("YM2608 internal rhythm ROM file path")
+                .long("ym2608-rhythm-rom")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("output filepath")
                 .help("Output file path")
                 .short("o")
                 .long("output")
                 .takes_value(true),
+        )
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .subcommand(
+            SubCommand::with_name("info")
+                .about("Print the analysis of .vgm/.vgz file as JSON")
+                .arg(
+                    Arg::with_name("filename")
+                        .help("Analyze .vgm/.vgz file path")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("tag")
+                .about("Print the GD3 tag of .vgm/.vgz file as JSON, or rewrite it with the options")
+                .arg(
+                    Arg::with_name("filename")
+                        .help("Tag .vgm/.vgz file path")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output filepath")
+                        .help("Output file path (default: overwrite the input file)")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true),
+                )
+                .args(
+                    &GD3_FIELDS
+                        .iter()
+                        .map(|(name, help)| {
+                            Arg::with_name(name).help(help).long(name).takes_value(true)
+                        })
+                        .collect::<Vec<Arg>>(),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("pack")
+                .about("Print the entries of .zip file as JSON, or render all entries with the output option")
+                .arg(
+                    Arg::with_name("filename")
+                        .help("Pack .zip file path")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output directory")
+                        .help("Output directory path of the .pcm files")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("rate")
+                        .help("Output sampling rate")
+                        .short("r")
+                        .long("rate")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("loop")
+                        .help("Loop count")
+                        .long("loop")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("xgm2vgm")
+                .about("Convert .xgm/.xgz file to .vgm file")
+                .arg(
+                    Arg::with_name("filename")
+                        .help("Convert .xgm/.xgz file path")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output filepath")
+                        .help("Output file path (default: input file path with .vgm extension)")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("track")
+                        .help("Track number (from 1) of multi tracks file")
+                        .long("track")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("vdp mode")
+                        .help("Force NTSC or PAL timing")
+                        .long("vdp-mode")
+                        .possible_values(&["ntsc", "pal"])
+                        .takes_value(true),
+                ),
         );
 
     let matches = app.get_matches();
 
+    // analysis
+    if let Some(matches) = matches.subcommand_matches("info") {
+        // wasmer run libymfm-cli.wasm --mapdir /:../../docs/vgm -- info /ym2612.vgm
+        info(matches.value_of("filename").unwrap());
+        return;
+    }
+
+    // GD3 tag
+    if let Some(matches) = matches.subcommand_matches("tag") {
+        // wasmer run libymfm-cli.wasm --mapdir /:../../docs/vgm -- tag /ym2612.vgm --track-name "Title"
+        let file_name = matches.value_of("filename").unwrap();
+        let output_file_name = matches.value_of("output filepath").unwrap_or(file_name);
+        let fields: Vec<(&str, &str)> = GD3_FIELDS
+            .iter()
+            .filter_map(|(name, _)| matches.value_of(name).map(|value| (*name, value)))
+            .collect();
+        tag(file_name, output_file_name, &fields);
+        return;
+    }
+
+    // pack list or batch rendering
+    if let Some(matches) = matches.subcommand_matches("pack") {
+        // wasmer run libymfm-cli.wasm --mapdir /:../../docs/vgm -- pack /pack.zip -o /pcm
+        let sampling_rate: u32 = match matches.value_of("rate") {
+            Some(rate) => String::from(rate).parse().unwrap(),
+            None => 44100,
+        };
+        let loop_count: usize = match matches.value_of("loop") {
+            Some(loop_count) => String::from(loop_count).parse().unwrap(),
+            None => 1,
+        };
+        pack(
+            matches.value_of("filename").unwrap(),
+            matches.value_of("output directory"),
+            sampling_rate,
+            loop_count,
+        );
+        return;
+    }
+
+    // XGM to VGM
+    if let Some(matches) = matches.subcommand_matches("xgm2vgm") {
+        // wasmer run libymfm-cli.wasm --mapdir /:../../docs/vgm -- xgm2vgm /sor2.xgm -o /sor2.vgm
+        let file_name = matches.value_of("filename").unwrap();
+        let output_file_name = match matches.value_of("output filepath") {
+            Some(output_file_name) => String::from(output_file_name),
+            None => Path::new(file_name).with_extension("vgm").to_string_lossy().into_owned(),
+        };
+        let track = get_track_index(matches.value_of("track")).unwrap_or(0);
+        xgm2vgm(file_name, &output_file_name, track, get_vdp_mode(matches.value_of("vdp mode")));
+        return;
+    }
+
     // sampling rate
     let sampling_rate: u32 = match matches.value_of("rate") {
         Some(rate) => String::from(rate).parse().unwrap(),
@@ -60,8 +252,20 @@ fn main() {
         None => 1,
     };
 
-    // filename
-    let file_name = matches.value_of("filename").unwrap();
+    // track number (from 1)
+    let track = get_track_index(matches.value_of("track"));
+
+    // VDP mode (default: header flags)
+    let vdp_mode = get_vdp_mode(matches.value_of("vdp mode"));
+
+    // IMF timer rate (default: 560Hz)
+    let imf_rate = matches
+        .value_of("imf rate")
+        .and_then(|rate| ImfRate::from_tick_rate(rate.parse().unwrap()));
+
+    // filename (or playlist)
+    let playlist_name = matches.value_of("playlist");
+    let file_name = playlist_name.or_else(|| matches.value_of("filename")).unwrap();
     let mut file = match File::open(file_name) {
         Ok(file) => file,
         Err(error) => {
@@ -71,7 +275,7 @@ fn main() {
     };
 
     // output file
-    let output_file: Option<File>;
+    let mut output_file: Option<File>;
     if let Some(filepath) = matches.value_of("output filepath") {
         // wasmer run libymfm-cli.wasm --mapdir /:../../docs/vgm -- /ym2612.vgm -o ym2612.pcm
         // ffplay -f f32le -ar 44100 -ac 2 ../../docs/vgm/ym2612.pcm
@@ -88,12 +292,29 @@ fn main() {
         output_file = None;
     }
 
+    // YM2608 internal rhythm ROM
+    let mut ym2608_rhythm_rom = Vec::new();
+    if let Some(filepath) = matches.value_of("ym2608 rhythm rom") {
+        match File::open(filepath) {
+            Ok(mut file) => {
+                let _ = file.read_to_end(&mut ym2608_rhythm_rom).unwrap();
+            }
+            Err(error) => {
+                eprintln!("There was a problem opening the file: {:?}", error);
+                process::exit(1);
+            }
+        };
+    }
+
     // read file
     let mut buffer = Vec::new();
     let _ = file.read_to_end(&mut buffer).unwrap();
 
     // get file type
-    let file_type = Path::new(file_name).extension().and_then(OsStr::to_str);
+    let file_type = match playlist_name {
+        Some(_) => Some("m3u"),
+        None => Path::new(file_name).extension().and_then(OsStr::to_str),
+    };
 
     match file_type {
         Some("vgm") | Some("vgz") => {
@@ -101,52 +322,401 @@ fn main() {
                 SoundSlot::new(VGM_TICK_RATE, sampling_rate, MAX_SAMPLE_SIZE),
                 buffer.as_slice(),
             ).expect("vgm file is not valid error.");
-            play(&mut vgmplay, output_file, loop_count);
+            vgmplay.add_ym2608_rhythm_rom(&ym2608_rhythm_rom);
+            play(&mut vgmplay, &mut output_file, loop_count);
         },
-        Some("xgm") | Some("xgz") => {
-            let mut xgmplay = XgmPlay::new(
-                SoundSlot::new(XGM_NTSC_TICK_RATE, sampling_rate, MAX_SAMPLE_SIZE),
+        Some("xgm") | Some("xgz") | Some("xgm2") => {
+            let sound_slot = || SoundSlot::new(XGM_NTSC_TICK_RATE, sampling_rate, MAX_SAMPLE_SIZE);
+            // XGM2 shares the extension with XGM
+            if let Ok(mut xgmplay) = XgmPlay::new_with_vdp_mode(sound_slot(), buffer.as_slice(), vdp_mode) {
+                xgmplay.select_track(track.unwrap_or(0)).expect("track number is not valid error.");
+                play(&mut xgmplay, &mut output_file, loop_count);
+            } else {
+                let mut xgm2play = Xgm2Play::new_with_vdp_mode(sound_slot(), buffer.as_slice(), vdp_mode)
+                    .expect("xgm file is not valid error.");
+                xgm2play.select_track(track.unwrap_or(0)).expect("track number is not valid error.");
+                play(&mut xgm2play, &mut output_file, loop_count);
+            }
+        },
+        Some("gym") => {
+            let mut gymplay = GymPlay::new(
+                SoundSlot::new(GYM_TICK_RATE, sampling_rate, MAX_SAMPLE_SIZE),
                 buffer.as_slice(),
-            ).expect("xgm file is not valid error.");
-            play(&mut xgmplay, output_file, loop_count);
+            ).expect("gym file is not valid error.");
+            play(&mut gymplay, &mut output_file, loop_count);
+        },
+        Some("s98") => {
+            let mut s98play = S98Play::new(
+                SoundSlot::new(S98_TICK_RATE, sampling_rate, MAX_SAMPLE_SIZE),
+                buffer.as_slice(),
+            ).expect("s98 file is not valid error.");
+            s98play.add_ym2608_rhythm_rom(&ym2608_rhythm_rom);
+            play(&mut s98play, &mut output_file, loop_count);
+        },
+        Some("dro") => {
+            let mut droplay = DroPlay::new(
+                SoundSlot::new(DRO_TICK_RATE, sampling_rate, MAX_SAMPLE_SIZE),
+                buffer.as_slice(),
+            ).expect("dro file is not valid error.");
+            play(&mut droplay, &mut output_file, loop_count);
+        },
+        Some("imf") | Some("wlf") => {
+            let mut imfplay = ImfPlay::new_with_rate(
+                SoundSlot::new(IMF_TICK_RATE, sampling_rate, MAX_SAMPLE_SIZE),
+                buffer.as_slice(),
+                imf_rate,
+            ).expect("imf file is not valid error.");
+            play(&mut imfplay, &mut output_file, loop_count);
+        },
+        Some("mdx") => {
+            let mut mdxplay = MdxPlay::new(
+                SoundSlot::new(MDX_TICK_RATE, sampling_rate, MAX_SAMPLE_SIZE),
+                buffer.as_slice(),
+            ).expect("mdx file is not valid error.");
+            // PDX is in the same directory as MDX
+            let pdx_file_name = mdxplay.get_mdx_meta().0.pdx_file_name.clone();
+            if let Some(pdx) = read_pdx(file_name, &pdx_file_name) {
+                mdxplay.add_pdx(&pdx).expect("pdx file is not valid error.");
+            }
+            play(&mut mdxplay, &mut output_file, loop_count);
+        },
+        Some("m3u") | Some("m3u8") => {
+            let mut playlistplay = PlaylistPlay::new(
+                sampling_rate,
+                MAX_SAMPLE_SIZE,
+                buffer.as_slice(),
+            ).expect("m3u file is not valid error.");
+            // entries are relative to the playlist
+            let directory = Path::new(file_name).parent().unwrap_or_else(|| Path::new(""));
+            let entries: Vec<String> = playlistplay
+                .get_playlist()
+                .entries
+                .iter()
+                .map(|entry| entry.file_name.clone())
+                .collect();
+            for entry in entries {
+                match File::open(directory.join(&entry)) {
+                    Ok(mut file) => {
+                        let mut buffer = Vec::new();
+                        let _ = file.read_to_end(&mut buffer).unwrap();
+                        playlistplay.add_file(&entry, &buffer);
+                    }
+                    Err(error) => eprintln!("There was a problem opening the file: {}: {:?}", entry, error),
+                }
+            }
+            playlistplay.add_ym2608_rhythm_rom(&ym2608_rhythm_rom);
+            playlistplay.set_default_loop_count(loop_count);
+            play_playlist(&mut playlistplay, &mut output_file);
+        },
+        Some("zip") => {
+            let packreader = PackReader::new(buffer.as_slice()).expect("zip file is not valid error.");
+            // entry number is from 1 (as the file names of the pack), or all entries
+            let entries = match track {
+                Some(index) => {
+                    if index >= packreader.get_pack().entries.len() {
+                        eprintln!("track number is not valid error.");
+                        process::exit(1);
+                    }
+                    index..index + 1
+                }
+                None => 0..packreader.get_pack().entries.len(),
+            };
+            for index in entries {
+                if let Err(error) = play_pack_entry(&packreader, index, sampling_rate, &ym2608_rhythm_rom, &mut output_file, loop_count) {
+                    eprintln!("{} is not valid error: {}", packreader.get_pack().entries[index].file_name, error);
+                }
+            }
         },
         Some(_) | None => eprintln!("Known extention type: {:?}", file_type),
     }
 }
 
-fn play(player: &mut impl Player, mut output_file: Option<File>, loop_count: usize) {
-    loop {
-        let loop_now = player.play(true);
-        for i in 0..MAX_SAMPLE_SIZE {
-            let sampling_l = player.get_sampling_l_ref();
-            let sampling_r = player.get_sampling_r_ref();
-            unsafe {
-                let slice_l = std::slice::from_raw_parts(sampling_l.add(i) as *const u8, 4);
-                let slice_r = std::slice::from_raw_parts(sampling_r.add(i) as *const u8, 4);
-                if let Some(ref mut output_file) = output_file {
-                    output_file.write_all(slice_l).expect("file write error");
-                    output_file.write_all(slice_r).expect("file write error");
-                } else {
-                    io::stdout().write_all(slice_l).expect("stdout error");
-                    io::stdout().write_all(slice_r).expect("stdout error");
-                }
+///
+/// Read PDX file of MDX (.PDX is omitted, and the case of the file name is ignored).
+///
+fn read_pdx(mdx_file_name: &str, pdx_file_name: &str) -> Option<Vec<u8>> {
+    if pdx_file_name.is_empty() {
+        return None;
+    }
+    let pdx_file_name = if Path::new(pdx_file_name).extension().is_none() {
+        format!("{}.PDX", pdx_file_name)
+    } else {
+        String::from(pdx_file_name)
+    };
+    let directory = Path::new(mdx_file_name).parent().unwrap_or_else(|| Path::new(""));
+    for name in [
+        pdx_file_name.clone(),
+        pdx_file_name.to_uppercase(),
+        pdx_file_name.to_lowercase(),
+    ] {
+        if let Ok(mut file) = File::open(directory.join(name)) {
+            let mut pdx = Vec::new();
+            if file.read_to_end(&mut pdx).is_ok() {
+                return Some(pdx);
             }
         }
+    }
+    eprintln!("PDX file is not found: {}", pdx_file_name);
+    None
+}
+
+fn info(file_name: &str) {
+    let mut file = match File::open(file_name) {
+        Ok(file) => file,
+        Err(error) => {
+            eprintln!("There was a problem opening the file: {:?}", error);
+            process::exit(1);
+        }
+    };
+    let mut buffer = Vec::new();
+    let _ = file.read_to_end(&mut buffer).unwrap();
+
+    match get_vgm_analysis_json(buffer.as_slice()) {
+        Ok(json) => println!("{}", json),
+        Err(error) => {
+            eprintln!("vgm file is not valid error: {}", error);
+            process::exit(1);
+        }
+    }
+}
+
+fn tag(file_name: &str, output_file_name: &str, fields: &[(&str, &str)]) {
+    let mut file = match File::open(file_name) {
+        Ok(file) => file,
+        Err(error) => {
+            eprintln!("There was a problem opening the file: {:?}", error);
+            process::exit(1);
+        }
+    };
+    let mut buffer = Vec::new();
+    let _ = file.read_to_end(&mut buffer).unwrap();
+
+    let vgmplay = match VgmPlay::new(
+        SoundSlot::new(VGM_TICK_RATE, VGM_TICK_RATE, MAX_SAMPLE_SIZE),
+        buffer.as_slice(),
+    ) {
+        Ok(vgmplay) => vgmplay,
+        Err(error) => {
+            eprintln!("vgm file is not valid error: {}", error);
+            process::exit(1);
+        }
+    };
+    // print current tag
+    if fields.is_empty() {
+        println!("{}", vgmplay.get_vgm_gd3_json());
+        return;
+    }
+    // rewrite tag
+    let mut gd3: Gd3 = vgmplay.get_vgm_meta().1.clone();
+    for (name, value) in fields {
+        let field = match *name {
+            "track-name" => &mut gd3.track_name,
+            "track-name-j" => &mut gd3.track_name_j,
+            "game-name" => &mut gd3.game_name,
+            "game-name-j" => &mut gd3.game_name_j,
+            "system-name" => &mut gd3.system_name,
+            "system-name-j" => &mut gd3.system_name_j,
+            "author" => &mut gd3.track_author,
+            "author-j" => &mut gd3.track_author_j,
+            "date" => &mut gd3.date,
+            "converted" => &mut gd3.converted,
+            _ => &mut gd3.notes,
+        };
+        *field = String::from(*value);
+    }
+    let vgm = match vgmplay.update_gd3(&gd3) {
+        Ok(vgm) => vgm,
+        Err(error) => {
+            eprintln!("vgm file is not valid error: {}", error);
+            process::exit(1);
+        }
+    };
+    match File::create(output_file_name) {
+        Ok(mut file) => file.write_all(&vgm).expect("file write error"),
+        Err(error) => {
+            eprintln!("There was a problem opening the file: {:?}", error);
+            process::exit(1);
+        }
+    }
+}
+
+fn xgm2vgm(file_name: &str, output_file_name: &str, track: usize, vdp_mode: Option<VDPMode>) {
+    let mut file = match File::open(file_name) {
+        Ok(file) => file,
+        Err(error) => {
+            eprintln!("There was a problem opening the file: {:?}", error);
+            process::exit(1);
+        }
+    };
+    let mut buffer = Vec::new();
+    let _ = file.read_to_end(&mut buffer).unwrap();
+
+    let vgm = XgmPlay::new_with_vdp_mode(
+        SoundSlot::new(XGM_NTSC_TICK_RATE, VGM_TICK_RATE, MAX_SAMPLE_SIZE),
+        buffer.as_slice(),
+        vdp_mode,
+    )
+    .and_then(|mut xgmplay| {
+        xgmplay.select_track(track)?;
+        xgmplay.convert_vgm()
+    });
+    let vgm = match vgm {
+        Ok(vgm) => vgm,
+        Err(error) => {
+            eprintln!("xgm file is not valid error: {}", error);
+            process::exit(1);
+        }
+    };
+    match File::create(output_file_name) {
+        Ok(mut file) => file.write_all(&vgm).expect("file write error"),
+        Err(error) => {
+            eprintln!("There was a problem opening the file: {:?}", error);
+            process::exit(1);
+        }
+    }
+}
+
+fn get_track_index(track: Option<&str>) -> Option<usize> {
+    // track number is from 1 (0 is not valid)
+    let track = track?;
+    match track.parse::<usize>() {
+        Ok(number) if number >= 1 => Some(number - 1),
+        _ => {
+            eprintln!("track number is from 1 error: {}", track);
+            process::exit(1);
+        }
+    }
+}
+
+fn get_vdp_mode(vdp_mode: Option<&str>) -> Option<VDPMode> {
+    match vdp_mode {
+        Some("ntsc") => Some(VDPMode::NTSC),
+        Some("pal") => Some(VDPMode::PAL),
+        _ => None,
+    }
+}
+
+fn pack(file_name: &str, output_directory: Option<&str>, sampling_rate: u32, loop_count: usize) {
+    let mut file = match File::open(file_name) {
+        Ok(file) => file,
+        Err(error) => {
+            eprintln!("There was a problem opening the file: {:?}", error);
+            process::exit(1);
+        }
+    };
+    let mut buffer = Vec::new();
+    let _ = file.read_to_end(&mut buffer).unwrap();
+
+    let packreader = match PackReader::new(buffer.as_slice()) {
+        Ok(packreader) => packreader,
+        Err(error) => {
+            eprintln!("zip file is not valid error: {}", error);
+            process::exit(1);
+        }
+    };
+    let output_directory = match output_directory {
+        Some(output_directory) => Path::new(output_directory),
+        None => {
+            println!("{}", packreader.get_pack_json());
+            return;
+        }
+    };
+    // render all entries to {output directory}/{file stem}.pcm
+    for (index, entry) in packreader.get_pack().entries.iter().enumerate() {
+        let output_file_name = output_directory
+            .join(Path::new(&entry.file_name).file_name().unwrap())
+            .with_extension("pcm");
+        let mut output_file = match File::create(&output_file_name) {
+            Ok(file) => Some(file),
+            Err(error) => {
+                eprintln!("There was a problem opening the file: {:?}", error);
+                process::exit(1);
+            }
+        };
+        match play_pack_entry(&packreader, index, sampling_rate, &[], &mut output_file, loop_count) {
+            Ok(_) => eprintln!("{}", output_file_name.display()),
+            Err(error) => eprintln!("{} is not valid error: {}", entry.file_name, error),
+        }
+    }
+}
+
+fn play_pack_entry(
+    packreader: &PackReader,
+    index: usize,
+    sampling_rate: u32,
+    ym2608_rhythm_rom: &[u8],
+    output_file: &mut Option<File>,
+    loop_count: usize,
+) -> Result<(), Error> {
+    match packreader.get_pack().entries[index].format {
+        PackFormat::Vgm => {
+            let mut vgmplay = packreader.open_vgm(
+                index,
+                SoundSlot::new(VGM_TICK_RATE, sampling_rate, MAX_SAMPLE_SIZE),
+            )?;
+            vgmplay.add_ym2608_rhythm_rom(ym2608_rhythm_rom);
+            play(&mut vgmplay, output_file, loop_count);
+        }
+        PackFormat::Xgm => {
+            let mut xgmplay = packreader.open_xgm(
+                index,
+                SoundSlot::new(XGM_NTSC_TICK_RATE, sampling_rate, MAX_SAMPLE_SIZE),
+            )?;
+            play(&mut xgmplay, output_file, loop_count);
+        }
+    }
+    Ok(())
+}
+
+fn play(player: &mut impl Player, output_file: &mut Option<File>, loop_count: usize) {
+    loop {
+        let loop_now = player.play(true);
+        write_sampling(player.get_sampling_l_ref(), player.get_sampling_r_ref(), output_file);
         if loop_now >= loop_count {
             break;
         }
     }
 }
 
+fn play_playlist(playlistplay: &mut PlaylistPlay, output_file: &mut Option<File>) {
+    loop {
+        let end = playlistplay.play();
+        write_sampling(
+            playlistplay.get_sampling_l_ref(),
+            playlistplay.get_sampling_r_ref(),
+            output_file,
+        );
+        if end == usize::MAX {
+            break;
+        }
+    }
+}
+
+fn write_sampling(sampling_l: *const f32, sampling_r: *const f32, output_file: &mut Option<File>) {
+    for i in 0..MAX_SAMPLE_SIZE {
+        unsafe {
+            let slice_l = std::slice::from_raw_parts(sampling_l.add(i) as *const u8, 4);
+            let slice_r = std::slice::from_raw_parts(sampling_r.add(i) as *const u8, 4);
+            if let Some(output_file) = output_file {
+                output_file.write_all(slice_l).expect("file write error");
+                output_file.write_all(slice_r).expect("file write error");
+            } else {
+                io::stdout().write_all(slice_l).expect("stdout error");
+                io::stdout().write_all(slice_r).expect("stdout error");
+            }
+        }
+    }
+}
+
 trait Player {
-    fn new(sound_slot: SoundSlot, file: &[u8]) -> Result<Self, &'static str> where Self: std::marker::Sized;
+    fn new(sound_slot: SoundSlot, file: &[u8]) -> Result<Self, Error> where Self: std::marker::Sized;
     fn get_sampling_l_ref(&self) -> *const f32;
     fn get_sampling_r_ref(&self) -> *const f32;
     fn play(&mut self, repeat: bool) -> usize;
 }
 
 impl Player for VgmPlay {
-    fn new(sound_slot: SoundSlot, file: &[u8]) -> Result<Self, &'static str> {
+    fn new(sound_slot: SoundSlot, file: &[u8]) -> Result<Self, Error> {
         VgmPlay::new(sound_slot, file)
     }
 
@@ -164,7 +734,7 @@ impl Player for VgmPlay {
 }
 
 impl Player for XgmPlay {
-    fn new(sound_slot: SoundSlot, file: &[u8]) -> Result<Self, &'static str> {
+    fn new(sound_slot: SoundSlot, file: &[u8]) -> Result<Self, Error> {
         XgmPlay::new(sound_slot, file)
     }
 
@@ -180,3 +750,111 @@ impl Player for XgmPlay {
         self.play(repeat)
     }
 }
+
+impl Player for Xgm2Play {
+    fn new(sound_slot: SoundSlot, file: &[u8]) -> Result<Self, Error> {
+        Xgm2Play::new(sound_slot, file)
+    }
+
+    fn get_sampling_l_ref(&self) -> *const f32 {
+        self.get_sampling_l_ref()
+    }
+
+    fn get_sampling_r_ref(&self) -> *const f32 {
+        self.get_sampling_r_ref()
+    }
+
+    fn play(&mut self, repeat: bool) -> usize {
+        self.play(repeat)
+    }
+}
+
+impl Player for GymPlay {
+    fn new(sound_slot: SoundSlot, file: &[u8]) -> Result<Self, Error> {
+        GymPlay::new(sound_slot, file)
+    }
+
+    fn get_sampling_l_ref(&self) -> *const f32 {
+        self.get_sampling_l_ref()
+    }
+
+    fn get_sampling_r_ref(&self) -> *const f32 {
+        self.get_sampling_r_ref()
+    }
+
+    fn play(&mut self, repeat: bool) -> usize {
+        self.play(repeat)
+    }
+}
+
+impl Player for S98Play {
+    fn new(sound_slot: SoundSlot, file: &[u8]) -> Result<Self, Error> {
+        S98Play::new(sound_slot, file)
+    }
+
+    fn get_sampling_l_ref(&self) -> *const f32 {
+        self.get_sampling_l_ref()
+    }
+
+    fn get_sampling_r_ref(&self) -> *const f32 {
+        self.get_sampling_r_ref()
+    }
+
+    fn play(&mut self, repeat: bool) -> usize {
+        self.play(repeat)
+    }
+}
+
+impl Player for DroPlay {
+    fn new(sound_slot: SoundSlot, file: &[u8]) -> Result<Self, Error> {
+        DroPlay::new(sound_slot, file)
+    }
+
+    fn get_sampling_l_ref(&self) -> *const f32 {
+        self.get_sampling_l_ref()
+    }
+
+    fn get_sampling_r_ref(&self) -> *const f32 {
+        self.get_sampling_r_ref()
+    }
+
+    fn play(&mut self, repeat: bool) -> usize {
+        self.play(repeat)
+    }
+}
+
+impl Player for ImfPlay {
+    fn new(sound_slot: SoundSlot, file: &[u8]) -> Result<Self, Error> {
+        ImfPlay::new(sound_slot, file)
+    }
+
+    fn get_sampling_l_ref(&self) -> *const f32 {
+        self.get_sampling_l_ref()
+    }
+
+    fn get_sampling_r_ref(&self) -> *const f32 {
+        self.get_sampling_r_ref()
+    }
+
+    fn play(&mut self, repeat: bool) -> usize {
+        self.play(repeat)
+    }
+}
+
+impl Player for MdxPlay {
+    fn new(sound_slot: SoundSlot, file: &[u8]) -> Result<Self, Error> {
+        MdxPlay::new(sound_slot, file)
+    }
+
+    fn get_sampling_l_ref(&self) -> *const f32 {
+        self.get_sampling_l_ref()
+    }
+
+    fn get_sampling_r_ref(&self) -> *const f32 {
+        self.get_sampling_r_ref()
+    }
+
+    fn play(&mut self, repeat: bool) -> usize {
+        self.play(repeat)
+    }
+}
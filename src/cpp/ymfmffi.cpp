@@ -36,6 +36,11 @@ enum chip_type
     CHIP_YM3812,
     CHIP_YMF262,
     CHIP_YMF278B,
+    CHIP_YM2610B,
+    CHIP_YM3438,
+    CHIP_YMF288,
+    CHIP_YM2164,
+    CHIP_YMF289B,
     CHIP_TYPES
 };
 
@@ -156,7 +161,7 @@ public:
             *buffer++ += out0 + (out1 + out2 + out3) / 2;
             *buffer++ += out0 + (out1 + out2 + out3) / 2;
         }
-        else if (m_type == CHIP_YM2608 || m_type == CHIP_YM2610)
+        else if (m_type == CHIP_YM2608 || m_type == CHIP_YM2610 || m_type == CHIP_YM2610B || m_type == CHIP_YMF288)
         {
             int32_t out0 = m_output.data[0];
             int32_t out1 = m_output.data[1 % ChipType::OUTPUTS];
@@ -230,27 +235,9 @@ uint32_t add_chips(uint32_t clock, chip_type type, char const *chipname)
 {
     uint32_t clockval = clock & 0x3fffffff;
     vgm_chip<ChipType> *chip = new vgm_chip<ChipType>(clockval, type, chipname);
-    active_chips.push_back(new vgm_chip<ChipType>(clockval, type, chipname));
+    active_chips.push_back(chip);
 
-    if (type == CHIP_YM2608)
-    {
-        fprintf(/* for output pcm stdout */ stderr, "load ym2608_adpcm_rom.bin using WASI\n");
-        FILE *rom = fopen("ym2608_adpcm_rom.bin", "rb");
-        if (rom == nullptr)
-            fprintf(stderr, "Warning: YM2608 enabled but ym2608_adpcm_rom.bin not found\n");
-        else
-        {
-            fseek(rom, 0, SEEK_END);
-            uint32_t size = ftell(rom);
-            fseek(rom, 0, SEEK_SET);
-            std::vector<uint8_t> temp(size);
-            fread(&temp[0], 1, size, rom);
-            fclose(rom);
-            for (auto chip : active_chips)
-                if (chip->type() == type)
-                    chip->write_data(ymfm::ACCESS_ADPCM_A, 0, size, &temp[0]);
-        }
-    }
+    // YM2608 internal rhythm ROM is provided by ymfm_add_rom_data (0xf0: YM2608_RHYTHM)
 
     return chip->sample_rate();
 }
@@ -298,10 +285,7 @@ uint32_t ymfm_add_chip(uint16_t chip_num, uint32_t clock)
             sampling_rate = add_chips<ymfm::ym2608>(clock, static_cast<chip_type>(chip_num), "YM2608");
             break;
         case CHIP_YM2610:
-            if (clock & 0x80000000)
-                sampling_rate = add_chips<ymfm::ym2610b>(clock, static_cast<chip_type>(chip_num), "YM2610B");
-            else
-                sampling_rate = add_chips<ymfm::ym2610>(clock, static_cast<chip_type>(chip_num), "YM2610");
+            sampling_rate = add_chips<ymfm::ym2610>(clock, static_cast<chip_type>(chip_num), "YM2610");
             break;
         case CHIP_YM2612:
             sampling_rate = add_chips<ymfm::ym2612>(clock, static_cast<chip_type>(chip_num), "YM2612");
@@ -321,6 +305,21 @@ uint32_t ymfm_add_chip(uint16_t chip_num, uint32_t clock)
         case CHIP_YMF278B:
             sampling_rate = add_chips<ymfm::ymf278b>(clock, static_cast<chip_type>(chip_num), "YMF278B");
             break;
+        case CHIP_YM2610B:
+            sampling_rate = add_chips<ymfm::ym2610b>(clock, static_cast<chip_type>(chip_num), "YM2610B");
+            break;
+        case CHIP_YM3438:
+            sampling_rate = add_chips<ymfm::ym3438>(clock, static_cast<chip_type>(chip_num), "YM3438");
+            break;
+        case CHIP_YMF288:
+            sampling_rate = add_chips<ymfm::ymf288>(clock, static_cast<chip_type>(chip_num), "YMF288");
+            break;
+        case CHIP_YM2164:
+            sampling_rate = add_chips<ymfm::ym2164>(clock, static_cast<chip_type>(chip_num), "YM2164");
+            break;
+        case CHIP_YMF289B:
+            sampling_rate = add_chips<ymfm::ymf289b>(clock, static_cast<chip_type>(chip_num), "YMF289B");
+            break;
     }
     return sampling_rate;
 }
@@ -364,6 +363,9 @@ void ymfm_add_rom_data(uint16_t chip_num, uint16_t access_type, uint8_t *buffer,
         case 0x88: // Y8950_ROM
             type = ymfm::ACCESS_ADPCM_B;
             break;
+        case 0xf0: // YM2608_RHYTHM
+            type = ymfm::ACCESS_ADPCM_A;
+            break;
     }
 
     for (int index = 0; index < 2; index++)
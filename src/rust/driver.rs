@@ -1,13 +1,59 @@
 // license:BSD-3-Clause
 // copyright-holders:Hiromasa Tanaka
 mod meta;
+mod error;
 mod vgmplay;
 mod xgmplay;
+mod xgm2play;
+mod gymplay;
+mod s98play;
+mod droplay;
+mod imfplay;
+mod mdxplay;
+mod playlistplay;
+mod packreader;
 mod vgmmeta;
+mod vgmanalyze;
+mod vgmwrite;
+mod vgmdecompress;
 mod xgmmeta;
+mod xgm2meta;
+mod gymmeta;
+mod s98meta;
+mod drometa;
+mod imfmeta;
+mod mdxmeta;
+mod m3umeta;
+mod zipmeta;
 mod gd3meta;
 
+pub use crate::driver::error::Error as Error;
+pub use crate::driver::gd3meta::Gd3 as Gd3;
+pub use crate::driver::vgmanalyze::VgmAnalysis as VgmAnalysis;
+pub use crate::driver::vgmanalyze::get_vgm_analysis_json as get_vgm_analysis_json;
 pub use crate::driver::vgmplay::VgmPlay as VgmPlay;
+pub use crate::driver::vgmmeta::update_vgm_gd3 as update_vgm_gd3;
 pub use crate::driver::vgmplay::VGM_TICK_RATE as VGM_TICK_RATE;
+pub use crate::driver::vgmwrite::create_vgm as create_vgm;
 pub use crate::driver::xgmplay::XgmPlay as XgmPlay;
 pub use crate::driver::xgmplay::XGM_NTSC_TICK_RATE as XGM_NTSC_TICK_RATE;
+pub use crate::driver::xgm2play::Xgm2Play as Xgm2Play;
+pub use crate::driver::xgmmeta::VDPMode as VDPMode;
+pub use crate::driver::gymplay::GymPlay as GymPlay;
+pub use crate::driver::gymplay::GYM_TICK_RATE as GYM_TICK_RATE;
+pub use crate::driver::s98play::S98Play as S98Play;
+pub use crate::driver::s98play::S98_TICK_RATE as S98_TICK_RATE;
+pub use crate::driver::droplay::DroPlay as DroPlay;
+pub use crate::driver::droplay::DRO_TICK_RATE as DRO_TICK_RATE;
+pub use crate::driver::imfplay::ImfPlay as ImfPlay;
+pub use crate::driver::imfplay::IMF_TICK_RATE as IMF_TICK_RATE;
+pub use crate::driver::imfmeta::ImfRate as ImfRate;
+pub use crate::driver::mdxplay::MdxPlay as MdxPlay;
+pub use crate::driver::mdxplay::MDX_TICK_RATE as MDX_TICK_RATE;
+pub use crate::driver::playlistplay::PlaylistPlay as PlaylistPlay;
+pub use crate::driver::m3umeta::Playlist as Playlist;
+pub use crate::driver::m3umeta::PlaylistEntry as PlaylistEntry;
+pub use crate::driver::packreader::PackReader as PackReader;
+pub use crate::driver::packreader::Pack as Pack;
+pub use crate::driver::packreader::PackEntry as PackEntry;
+pub use crate::driver::packreader::PackFormat as PackFormat;
@@ -0,0 +1,223 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use nom::bytes::complete::{tag, take};
+use nom::combinator::map_opt;
+use nom::number::complete::{le_u16, le_u32, le_u8};
+use nom::IResult;
+
+use crate::driver::error::Error;
+use crate::driver::gd3meta::Gd3;
+use crate::driver::meta::Jsonlize;
+
+///
+/// DRO hardware type
+///
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum DroHardware {
+    OPL2,
+    DualOPL2,
+    OPL3,
+}
+
+///
+/// DOSBox Raw OPL
+///
+/// v1 (0.1)
+/// $0000 "DBRAWOPL"
+/// $0008 version (0x00010000)
+/// $000C length in milliseconds
+/// $0010 length in bytes
+/// $0014 hardware type (0 OPL2, 1 OPL3, 2 dual OPL2, 1 or 4 bytes)
+/// $0015 or $0018 data
+///
+/// v2 (2.0)
+/// $0000 "DBRAWOPL"
+/// $0008 version major (2), minor (0)
+/// $000C length in register/value pairs
+/// $0010 length in milliseconds
+/// $0014 hardware type (0 OPL2, 1 dual OPL2, 2 OPL3)
+/// $0015 format (0 is interleaved)
+/// $0016 compression (0 is none)
+/// $0017 short delay code
+/// $0018 long delay code
+/// $0019 codemap length
+/// $001A codemap (index to register)
+///
+#[derive(Deserialize, Serialize, Debug)]
+pub struct DroHeader {
+    pub version: u32,
+    pub length_ms: u32,
+    pub data_length: usize,
+    pub hardware_type: DroHardware,
+    pub short_delay_code: u8,
+    pub long_delay_code: u8,
+    pub codemap: Vec<u8>,
+    pub data_offset: usize,
+}
+
+///
+/// Parse DRO v1 header
+///
+fn parse_dro_v1_header(all: &[u8]) -> IResult<&[u8], DroHeader> {
+    let (i, _) = tag("DBRAWOPL")(all)?;
+    let (i, _) = le_u32(i)?;
+    let (i, length_ms) = le_u32(i)?;
+    let (i, data_length) = le_u32(i)?;
+    let (i, hardware_type) = map_opt(le_u8, |hardware_type| match hardware_type {
+        0 => Some(DroHardware::OPL2),
+        1 => Some(DroHardware::OPL3),
+        2 => Some(DroHardware::DualOPL2),
+        _ => None,
+    })(i)?;
+    // some early files have the 1 byte hardware type
+    let i = match i.get(0..3) {
+        Some(padding) if padding == [0, 0, 0] => &i[3..],
+        _ => i,
+    };
+
+    Ok((
+        i,
+        DroHeader {
+            version: 1,
+            length_ms,
+            data_length: data_length as usize,
+            hardware_type,
+            short_delay_code: 0x00,
+            long_delay_code: 0x01,
+            codemap: Vec::new(),
+            data_offset: all.len() - i.len(),
+        },
+    ))
+}
+
+///
+/// Parse DRO v2 header
+///
+fn parse_dro_v2_header(all: &[u8]) -> IResult<&[u8], DroHeader> {
+    let (i, _) = tag("DBRAWOPL")(all)?;
+    let (i, _) = le_u16(i)?;
+    let (i, _) = le_u16(i)?;
+    let (i, length_pairs) = le_u32(i)?;
+    let (i, length_ms) = le_u32(i)?;
+    let (i, hardware_type) = map_opt(le_u8, |hardware_type| match hardware_type {
+        0 => Some(DroHardware::OPL2),
+        1 => Some(DroHardware::DualOPL2),
+        2 => Some(DroHardware::OPL3),
+        _ => None,
+    })(i)?;
+    // interleaved format and no compression only
+    let (i, _) = tag(&[0x00, 0x00][..])(i)?;
+    let (i, short_delay_code) = le_u8(i)?;
+    let (i, long_delay_code) = le_u8(i)?;
+    let (i, codemap_length) = le_u8(i)?;
+    let (i, codemap) = take(codemap_length)(i)?;
+
+    Ok((
+        i,
+        DroHeader {
+            version: 2,
+            length_ms,
+            data_length: length_pairs as usize * 2,
+            hardware_type,
+            short_delay_code,
+            long_delay_code,
+            codemap: codemap.to_vec(),
+            data_offset: all.len() - i.len(),
+        },
+    ))
+}
+
+///
+/// Parse DRO meta
+///
+pub(crate) fn parse_dro_meta(drodata: &[u8]) -> Result<(DroHeader, Gd3), Error> {
+    if !drodata.starts_with(b"DBRAWOPL") || drodata.len() < 12 {
+        return Err(Error::InvalidHeader("dro"));
+    }
+    // major and minor version (v1 is 0.1)
+    let version = u32::from_le_bytes(drodata[8..12].try_into().unwrap());
+    let header = match version {
+        0x00010000 => parse_dro_v1_header(drodata),
+        0x00000002 => parse_dro_v2_header(drodata),
+        _ => return Err(Error::UnsupportedVersion(version)),
+    };
+    let header = match header {
+        Ok((_, header)) => header,
+        Err(_) => return Err(Error::InvalidHeader("dro")),
+    };
+    if header.data_offset + header.data_length > drodata.len() {
+        return Err(Error::BadOffset(header.data_offset + header.data_length));
+    }
+    let gd3 = Gd3 {
+        system_name: String::from("DOSBox"),
+        ..Default::default()
+    };
+
+    Ok((header, gd3))
+}
+
+impl Jsonlize for DroHeader {}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_dro_meta, DroHardware};
+    use crate::driver::error::Error;
+
+    #[test]
+    fn dro_v1() {
+        let mut dro = b"DBRAWOPL".to_vec();
+        for value in [0x00010000_u32, 10, 4, 2] {
+            dro.extend_from_slice(&value.to_le_bytes());
+        }
+        dro.extend_from_slice(&[0x02, 0xb0, 0x20, 0x00, 0x09]);
+
+        let (header, _) = parse_dro_meta(&dro).unwrap();
+        assert_eq!(header.version, 1);
+        assert_eq!(header.hardware_type, DroHardware::DualOPL2);
+        assert_eq!(header.data_offset, 0x18);
+
+        // 1 byte hardware type
+        let mut dro = dro[..0x15].to_vec();
+        dro.extend_from_slice(&[0x02, 0xb0, 0x20, 0x00, 0x09]);
+        let (header, _) = parse_dro_meta(&dro).unwrap();
+        assert_eq!(header.data_offset, 0x15);
+        // data starts with a zero byte after the 1 byte hardware type
+        let mut dro_zero = dro[..0x15].to_vec();
+        dro_zero.extend_from_slice(&[0xb0, 0x20, 0x00, 0x01, 0x09]);
+        let (header, _) = parse_dro_meta(&dro_zero).unwrap();
+        assert_eq!(header.data_offset, 0x15);
+
+        dro.truncate(0x17);
+        assert_eq!(parse_dro_meta(&dro).unwrap_err(), Error::BadOffset(0x19));
+    }
+
+    #[test]
+    fn dro_v2() {
+        let mut dro = b"DBRAWOPL".to_vec();
+        for value in [2_u32, 2, 10] {
+            dro.extend_from_slice(&value.to_le_bytes());
+        }
+        dro.extend_from_slice(&[2, 0, 0, 0xfe, 0xff, 2, 0xb0, 0xbd]);
+        dro.extend_from_slice(&[0x00, 0x20, 0xfe, 0x09]);
+
+        let (header, _) = parse_dro_meta(&dro).unwrap();
+        assert_eq!(header.version, 2);
+        assert_eq!(header.hardware_type, DroHardware::OPL3);
+        assert_eq!(header.codemap, vec![0xb0, 0xbd]);
+        assert_eq!(header.data_offset, 0x1c);
+        assert_eq!(header.data_length, 4);
+
+        // compressed
+        dro[0x16] = 1;
+        assert_eq!(
+            parse_dro_meta(&dro).unwrap_err(),
+            Error::InvalidHeader("dro")
+        );
+        dro[8] = 3;
+        assert_eq!(
+            parse_dro_meta(&dro).unwrap_err(),
+            Error::UnsupportedVersion(3)
+        );
+    }
+}
@@ -0,0 +1,324 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use crate::driver::drometa::{self, DroHardware, DroHeader};
+use crate::driver::error::Error;
+use crate::driver::gd3meta::Gd3;
+use crate::driver::meta::Jsonlize;
+use crate::sound::{SoundChipType, SoundSlot};
+
+pub const DRO_TICK_RATE: u32 = 1000;
+
+const CLOCK_OPL2: u32 = 3579545;
+const CLOCK_OPL3: u32 = 14318180;
+
+///
+/// DRO Driver
+///
+pub struct DroPlay {
+    sound_slot: SoundSlot,
+    dro_pos: usize,
+    dro_bank: usize,
+    dro_end: bool,
+    dro_data: Vec<u8>,
+    dro_header: Option<DroHeader>,
+    dro_gd3: Option<Gd3>,
+    dro_error: Option<Error>,
+    remain_tick_count: usize,
+}
+
+#[allow(dead_code)]
+impl DroPlay {
+    ///
+    /// Create sound driver.
+    ///
+    pub fn new(sound_slot: SoundSlot, dro_file: &[u8]) -> Result<Self, Error> {
+        let mut droplay = DroPlay {
+            sound_slot,
+            dro_pos: 0,
+            dro_bank: 0,
+            dro_end: false,
+            dro_data: Vec::new(),
+            dro_header: None,
+            dro_gd3: None,
+            dro_error: None,
+            remain_tick_count: 0,
+        };
+        // parse dro header and soundchip init
+        droplay.init(dro_file)?;
+
+        Ok(droplay)
+    }
+
+    ///
+    /// Return sampling_l buffer referance.
+    ///
+    pub fn get_sampling_l_ref(&self) -> *const f32 {
+        self.sound_slot.get_output_sampling_l_ref()
+    }
+
+    ///
+    /// Return sampling buffer referance.
+    ///
+    pub fn get_sampling_r_ref(&self) -> *const f32 {
+        self.sound_slot.get_output_sampling_r_ref()
+    }
+
+    ///
+    /// Return s16le sampling buffer referance.
+    ///
+    pub fn get_output_sampling_s16le_ref(&mut self) -> *const i16 {
+        self.sound_slot.get_output_sampling_s16le_ref()
+    }
+
+    ///
+    /// Get DRO meta.
+    ///
+    pub fn get_dro_meta(&self) -> (&DroHeader, &Gd3) {
+        (
+            self.dro_header.as_ref().unwrap(/* There always is */),
+            self.dro_gd3.as_ref().unwrap(/* There always is */),
+        )
+    }
+
+    ///
+    /// Get DRO header JSON.
+    ///
+    pub fn get_dro_header_json(&self) -> String {
+        self.dro_header.as_ref().unwrap(/* There always is */).get_json()
+    }
+
+    ///
+    /// Get DRO GD3 JSON (DRO has no tag, system name only).
+    ///
+    pub fn get_dro_gd3_json(&self) -> String {
+        self.dro_gd3.as_ref().unwrap(/* There always is */).get_json()
+    }
+
+    ///
+    /// Get the error that stopped playback (broken DRO data).
+    ///
+    pub fn get_error(&self) -> Option<&Error> {
+        self.dro_error.as_ref()
+    }
+
+    ///
+    /// Play Sound.
+    ///
+    /// DRO has no loop point, so the repeat flag is ignored.
+    ///
+    pub fn play(&mut self, _repeat: bool) -> usize {
+        while !self.sound_slot.is_stream_filled() && !self.dro_end {
+            for _ in 0..self.remain_tick_count {
+                self.sound_slot.update(1);
+                self.remain_tick_count -= 1;
+                if self.sound_slot.is_stream_filled() {
+                    break;
+                }
+            }
+            if self.remain_tick_count == 0 {
+                let parse = match self.dro_header.as_ref().unwrap().version {
+                    1 => self.parse_dro_v1(),
+                    _ => self.parse_dro_v2(),
+                };
+                match parse {
+                    Ok(wait) => self.remain_tick_count = wait as usize,
+                    Err(error) => {
+                        // stop playback on broken data
+                        self.dro_error = Some(error);
+                        self.dro_end = true;
+                    }
+                }
+            };
+        }
+        self.sound_slot.stream();
+
+        if self.dro_end {
+            usize::MAX
+        } else {
+            0
+        }
+    }
+
+    ///
+    /// Initialize sound driver.
+    ///
+    fn init(&mut self, dro_file: &[u8]) -> Result<(), Error> {
+        // parse dro header
+        let (header, gd3) = drometa::parse_dro_meta(dro_file)?;
+
+        // register data only
+        self.dro_data =
+            dro_file[header.data_offset..header.data_offset + header.data_length].to_vec();
+
+        // add sound chip
+        match header.hardware_type {
+            DroHardware::OPL2 => {
+                self.sound_slot
+                    .add_sound_device(SoundChipType::YM3812, 1, CLOCK_OPL2);
+            }
+            DroHardware::DualOPL2 => {
+                self.sound_slot
+                    .add_sound_device(SoundChipType::YM3812, 2, CLOCK_OPL2);
+            }
+            DroHardware::OPL3 => {
+                self.sound_slot
+                    .add_sound_device(SoundChipType::YMF262, 1, CLOCK_OPL3);
+            }
+        }
+        self.dro_header = Some(header);
+        self.dro_gd3 = Some(gd3);
+
+        Ok(())
+    }
+
+    fn get_dro_u8(&mut self) -> Result<u8, Error> {
+        let ret = match self.dro_data.get(self.dro_pos) {
+            Some(ret) => *ret,
+            None => return Err(Error::UnexpectedEof(self.dro_pos)),
+        };
+        self.dro_pos += 1;
+        Ok(ret)
+    }
+
+    ///
+    /// Write register to OPL2 / dual OPL2 / OPL3 (bank is the second chip or port 1).
+    ///
+    fn write_opl(&mut self, reg: u8, dat: u8) {
+        match self.dro_header.as_ref().unwrap().hardware_type {
+            DroHardware::OPL2 => {
+                if self.dro_bank == 0 {
+                    self.sound_slot
+                        .write(SoundChipType::YM3812, 0, reg as u32, dat.into());
+                }
+            }
+            DroHardware::DualOPL2 => {
+                self.sound_slot
+                    .write(SoundChipType::YM3812, self.dro_bank, reg as u32, dat.into());
+            }
+            DroHardware::OPL3 => {
+                self.sound_slot.write(
+                    SoundChipType::YMF262,
+                    0,
+                    reg as u32 | (self.dro_bank as u32) << 8,
+                    dat.into(),
+                );
+            }
+        }
+    }
+
+    fn parse_dro_v1(&mut self) -> Result<u32, Error> {
+        let mut wait: u32 = 0;
+
+        // end of data
+        if self.dro_pos >= self.dro_data.len() {
+            self.dro_end = true;
+            return Ok(wait);
+        }
+
+        let command = self.get_dro_u8()?;
+        match command {
+            0x00 => {
+                // delay (1 byte, n + 1 ms)
+                wait = self.get_dro_u8()? as u32 + 1;
+            }
+            0x01 => {
+                // delay (2 bytes, n + 1 ms)
+                let low = self.get_dro_u8()?;
+                let high = self.get_dro_u8()?;
+                wait = u16::from_le_bytes([low, high]) as u32 + 1;
+            }
+            0x02 | 0x03 => {
+                // switch to low / high chip
+                self.dro_bank = (command - 0x02) as usize;
+            }
+            0x04 => {
+                // escape (register 0x00 - 0x04 write)
+                let reg = self.get_dro_u8()?;
+                let dat = self.get_dro_u8()?;
+                self.write_opl(reg, dat);
+            }
+            _ => {
+                let dat = self.get_dro_u8()?;
+                self.write_opl(command, dat);
+            }
+        }
+
+        Ok(wait)
+    }
+
+    fn parse_dro_v2(&mut self) -> Result<u32, Error> {
+        let mut wait: u32 = 0;
+
+        // end of data
+        if self.dro_pos >= self.dro_data.len() {
+            self.dro_end = true;
+            return Ok(wait);
+        }
+
+        let index = self.get_dro_u8()?;
+        let dat = self.get_dro_u8()?;
+        let header = self.dro_header.as_ref().unwrap();
+        if index == header.short_delay_code {
+            // delay (n + 1 ms)
+            wait = dat as u32 + 1;
+        } else if index == header.long_delay_code {
+            // delay ((n + 1) * 256 ms)
+            wait = (dat as u32 + 1) << 8;
+        } else {
+            // bit 7 is the high chip, others are the codemap index
+            let reg = match header.codemap.get((index & 0x7f) as usize) {
+                Some(reg) => *reg,
+                None => return Err(Error::UnknownCommand(self.dro_pos - 2, index)),
+            };
+            self.dro_bank = (index >> 7) as usize;
+            self.write_opl(reg, dat);
+        }
+
+        Ok(wait)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sound::SoundSlot;
+
+    use super::{DroPlay, Error, DRO_TICK_RATE};
+
+    const MAX_SAMPLE_SIZE: usize = 2048;
+
+    #[test]
+    fn dro_v1() {
+        let mut dro = b"DBRAWOPL".to_vec();
+        for value in [0x00010000_u32, 1500, 12, 1] {
+            dro.extend_from_slice(&value.to_le_bytes());
+        }
+        // OPL3 port 1 write, 1000 ms, key on, 500 ms
+        dro.extend_from_slice(&[0x03, 0x05, 0x01, 0x01, 0xe7, 0x03, 0x02, 0xb0, 0x20]);
+        dro.extend_from_slice(&[0x01, 0xf3, 0x01]);
+        let mut droplay =
+            DroPlay::new(SoundSlot::new(DRO_TICK_RATE, 44100, MAX_SAMPLE_SIZE), &dro).unwrap();
+        assert_eq!(droplay.get_dro_meta().0.length_ms, 1500);
+        // 1500 ms (66150 samples)
+        let mut chunk = 1;
+        while droplay.play(true) != usize::MAX {
+            chunk += 1;
+        }
+        assert_eq!(chunk, 66150 / MAX_SAMPLE_SIZE + 1);
+        assert_eq!(droplay.get_error(), None);
+    }
+
+    #[test]
+    fn dro_v2() {
+        let mut dro = b"DBRAWOPL".to_vec();
+        for value in [2_u32, 3, 10] {
+            dro.extend_from_slice(&value.to_le_bytes());
+        }
+        dro.extend_from_slice(&[1, 0, 0, 0xfe, 0xff, 1, 0xb0]);
+        // second chip write, 10 ms and unknown codemap index
+        dro.extend_from_slice(&[0x80, 0x20, 0xfe, 0x09, 0x01, 0x00]);
+        let mut droplay =
+            DroPlay::new(SoundSlot::new(DRO_TICK_RATE, 44100, MAX_SAMPLE_SIZE), &dro).unwrap();
+        assert_eq!(droplay.play(true), usize::MAX);
+        assert_eq!(droplay.get_error(), Some(&Error::UnknownCommand(4, 0x01)));
+    }
+}
@@ -0,0 +1,66 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use std::fmt;
+
+///
+/// Driver error
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    ///
+    /// Not a supported file or the header is broken.
+    ///
+    InvalidHeader(&'static str),
+    ///
+    /// File format version is not supported.
+    ///
+    UnsupportedVersion(u32),
+    ///
+    /// Data ended in the middle of a command or block (position).
+    ///
+    UnexpectedEof(usize),
+    ///
+    /// Offset points outside of the data (offset).
+    ///
+    BadOffset(usize),
+    ///
+    /// Data block is broken (position).
+    ///
+    InvalidDataBlock(usize),
+    ///
+    /// Unknown command (position, command).
+    ///
+    UnknownCommand(usize, u8),
+    ///
+    /// File referenced by the playlist or the archive is not found (file name).
+    ///
+    NotFound(String),
+    ///
+    /// Failed to compress the output file (reason).
+    ///
+    Compress(String),
+    ///
+    /// Value does not fit in the file format field (field name).
+    ///
+    OutOfRange(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidHeader(format) => write!(f, "{format} header parse error"),
+            Error::UnsupportedVersion(version) => write!(f, "unsupported version: {version}"),
+            Error::UnexpectedEof(pos) => write!(f, "unexpected end of data at {pos:#x}"),
+            Error::BadOffset(offset) => write!(f, "bad offset: {offset:#x}"),
+            Error::InvalidDataBlock(pos) => write!(f, "invalid data block at {pos:#x}"),
+            Error::UnknownCommand(pos, command) => {
+                write!(f, "unknown command at {pos:#x}: {command:#04x}")
+            }
+            Error::NotFound(file_name) => write!(f, "file not found: {file_name}"),
+            Error::Compress(reason) => write!(f, "compress error: {reason}"),
+            Error::OutOfRange(field) => write!(f, "{field} is out of range"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
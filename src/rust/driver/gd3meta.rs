@@ -1,6 +1,7 @@
 // license:BSD-3-Clause
 // copyright-holders:Hiromasa Tanaka
 use nom::bytes::complete::{tag, take};
+use nom::combinator::opt;
 use nom::IResult;
 
 use crate::driver::meta::Jsonlize;
@@ -8,7 +9,8 @@ use crate::driver::meta::Jsonlize;
 ///
 /// https://vgmrips.net/wiki/GD3_Specification
 ///
-#[derive(Deserialize, Serialize, Default, Debug)]
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+#[serde(default)]
 pub struct Gd3 {
     pub track_name: String,
     pub track_name_j: String,
@@ -20,6 +22,7 @@ pub struct Gd3 {
     pub track_author_j: String,
     pub date: String,
     pub converted: String,
+    pub notes: String,
 }
 
 ///
@@ -62,6 +65,8 @@ pub fn parse_gd3(i: &[u8]) -> IResult<&[u8], Gd3> {
     let (i, track_author_j) = parse_utf16_until_null(i)?;
     let (i, date) = parse_utf16_until_null(i)?;
     let (i, converted) = parse_utf16_until_null(i)?;
+    // some files end without notes
+    let (i, notes) = opt(parse_utf16_until_null)(i)?;
 
     Ok((
         i,
@@ -76,6 +81,37 @@ pub fn parse_gd3(i: &[u8]) -> IResult<&[u8], Gd3> {
             track_author_j,
             date,
             converted,
+            notes: notes.unwrap_or_default(),
         },
     ))
 }
+
+///
+/// Serialize GD3 tag (version 1.00)
+///
+pub fn serialize_gd3(gd3: &Gd3) -> Vec<u8> {
+    let mut strings: Vec<u8> = Vec::new();
+    for string in [
+        &gd3.track_name,
+        &gd3.track_name_j,
+        &gd3.game_name,
+        &gd3.game_name_j,
+        &gd3.system_name,
+        &gd3.system_name_j,
+        &gd3.track_author,
+        &gd3.track_author_j,
+        &gd3.date,
+        &gd3.converted,
+        &gd3.notes,
+    ] {
+        for utf16 in string.encode_utf16().chain([0]) {
+            strings.extend_from_slice(&utf16.to_le_bytes());
+        }
+    }
+    let mut gd3_data: Vec<u8> = Vec::with_capacity(strings.len() + 12);
+    gd3_data.extend_from_slice(b"Gd3 ");
+    gd3_data.extend_from_slice(&0x100_u32.to_le_bytes());
+    gd3_data.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+    gd3_data.extend_from_slice(&strings);
+    gd3_data
+}
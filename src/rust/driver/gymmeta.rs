@@ -0,0 +1,150 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use nom::bytes::complete::{tag, take};
+use nom::number::complete::le_u32;
+use nom::IResult;
+
+use crate::driver::error::Error;
+use crate::driver::gd3meta::Gd3;
+use crate::driver::meta::Jsonlize;
+
+pub const GYMX_HEADER_SIZE: usize = 0x1ac;
+
+///
+/// GYMX header (GYM files without "GYMX" have no header)
+///
+/// $0000 "GYMX"
+/// $0004 song title (32 bytes)
+/// $0024 game title (32 bytes)
+/// $0044 copyright (32 bytes)
+/// $0064 emulator (32 bytes)
+/// $0084 dumper (32 bytes)
+/// $00A4 comment (256 bytes)
+/// $01A4 loop start frame (1 origin, 0 is no loop)
+/// $01A8 packed flag (uncompressed size of zlib packed data, 0 is not packed)
+/// $01AC data
+///
+#[derive(Deserialize, Serialize, Default, Debug)]
+pub struct GymHeader {
+    pub gymx: bool,
+    pub song_title: String,
+    pub game_title: String,
+    pub copyright: String,
+    pub emulator: String,
+    pub dumper: String,
+    pub comment: String,
+    pub loop_start: u32,
+    pub packed_size: u32,
+}
+
+///
+/// Parse null padded string (ISO-8859-1)
+///
+fn parse_string(i: &[u8], length: usize) -> IResult<&[u8], String> {
+    let (i, string) = take(length)(i)?;
+    let string = string
+        .iter()
+        .take_while(|c| **c != 0)
+        .map(|c| *c as char)
+        .collect();
+    Ok((i, string))
+}
+
+///
+/// Parse GYMX header
+///
+fn parse_gymx_header(i: &[u8]) -> IResult<&[u8], GymHeader> {
+    let (i, _) = tag("GYMX")(i)?;
+    let (i, song_title) = parse_string(i, 32)?;
+    let (i, game_title) = parse_string(i, 32)?;
+    let (i, copyright) = parse_string(i, 32)?;
+    let (i, emulator) = parse_string(i, 32)?;
+    let (i, dumper) = parse_string(i, 32)?;
+    let (i, comment) = parse_string(i, 256)?;
+    let (i, loop_start) = le_u32(i)?;
+    let (i, packed_size) = le_u32(i)?;
+
+    Ok((
+        i,
+        GymHeader {
+            gymx: true,
+            song_title,
+            game_title,
+            copyright,
+            emulator,
+            dumper,
+            comment,
+            loop_start,
+            packed_size,
+        },
+    ))
+}
+
+///
+/// Parse GYM meta
+///
+pub(crate) fn parse_gym_meta(gymdata: &[u8]) -> Result<(GymHeader, Gd3), Error> {
+    let header = if gymdata.starts_with(b"GYMX") {
+        match parse_gymx_header(gymdata) {
+            Ok((_, header)) => header,
+            Err(_) => return Err(Error::InvalidHeader("gym")),
+        }
+    } else {
+        // no header, the first byte must be a command
+        match gymdata.first() {
+            Some(0x00..=0x03) => GymHeader::default(),
+            _ => return Err(Error::InvalidHeader("gym")),
+        }
+    };
+    let gd3 = Gd3 {
+        track_name: header.song_title.clone(),
+        game_name: header.game_title.clone(),
+        system_name: if header.gymx {
+            String::from("Sega Mega Drive / Genesis")
+        } else {
+            String::from("")
+        },
+        converted: header.dumper.clone(),
+        notes: header.comment.clone(),
+        ..Default::default()
+    };
+
+    Ok((header, gd3))
+}
+
+impl Jsonlize for GymHeader {}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_gym_meta, GYMX_HEADER_SIZE};
+    use crate::driver::error::Error;
+
+    #[test]
+    fn gymx() {
+        let mut gym = b"GYMX".to_vec();
+        for string in [&b"Title"[..], b"Game", b"Copyright", b"Emulator", b"Dumper"] {
+            let mut field = string.to_vec();
+            field.resize(32, 0);
+            gym.extend_from_slice(&field);
+        }
+        gym.extend_from_slice(&[0; 256]);
+        gym.extend_from_slice(&2_u32.to_le_bytes());
+        gym.extend_from_slice(&0_u32.to_le_bytes());
+        assert_eq!(gym.len(), GYMX_HEADER_SIZE);
+
+        let (header, gd3) = parse_gym_meta(&gym).unwrap();
+        assert_eq!(header.copyright, "Copyright");
+        assert_eq!(header.loop_start, 2);
+        assert_eq!(gd3.track_name, "Title");
+        assert_eq!(gd3.game_name, "Game");
+        assert_eq!(gd3.converted, "Dumper");
+
+        // no header
+        let (header, _) = parse_gym_meta(&[0x01, 0x28, 0xf0, 0x00]).unwrap();
+        assert!(!header.gymx);
+        assert_eq!(
+            parse_gym_meta(&[0x04]).unwrap_err(),
+            Error::InvalidHeader("gym")
+        );
+    }
+}
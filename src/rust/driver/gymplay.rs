@@ -0,0 +1,302 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use crate::driver::error::Error;
+use crate::driver::gd3meta::Gd3;
+use crate::driver::gymmeta::{self, GymHeader, GYMX_HEADER_SIZE};
+use crate::driver::meta::Jsonlize;
+use crate::driver::xgmmeta::VDPMode;
+use crate::driver::xgmplay::get_vdp_timing;
+use crate::sound::{SoundChipType, SoundSlot};
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+
+pub const GYM_TICK_RATE: u32 = 60;
+
+///
+/// GYM Driver
+///
+pub struct GymPlay {
+    sound_slot: SoundSlot,
+    gym_pos: usize,
+    gym_frame: u32,
+    gym_loop_pos: Option<usize>,
+    gym_loop_count: usize,
+    gym_end: bool,
+    gym_data: Vec<u8>,
+    gym_header: Option<GymHeader>,
+    gym_gd3: Option<Gd3>,
+    gym_error: Option<Error>,
+    remain_tick_count: usize,
+}
+
+#[allow(dead_code)]
+impl GymPlay {
+    ///
+    /// Create sound driver.
+    ///
+    pub fn new(sound_slot: SoundSlot, gym_file: &[u8]) -> Result<Self, Error> {
+        let mut gymplay = GymPlay {
+            sound_slot,
+            gym_pos: 0,
+            gym_frame: 0,
+            gym_loop_pos: None,
+            gym_loop_count: 0,
+            gym_end: false,
+            gym_data: Vec::new(),
+            gym_header: None,
+            gym_gd3: None,
+            gym_error: None,
+            remain_tick_count: 0,
+        };
+        // unpack gym_file and soundchip init
+        gymplay.init(gym_file)?;
+
+        Ok(gymplay)
+    }
+
+    ///
+    /// Return sampling_l buffer referance.
+    ///
+    pub fn get_sampling_l_ref(&self) -> *const f32 {
+        self.sound_slot.get_output_sampling_l_ref()
+    }
+
+    ///
+    /// Return sampling buffer referance.
+    ///
+    pub fn get_sampling_r_ref(&self) -> *const f32 {
+        self.sound_slot.get_output_sampling_r_ref()
+    }
+
+    ///
+    /// Return s16le sampling buffer referance.
+    ///
+    pub fn get_output_sampling_s16le_ref(&mut self) -> *const i16 {
+        self.sound_slot.get_output_sampling_s16le_ref()
+    }
+
+    ///
+    /// Get GYM meta.
+    ///
+    pub fn get_gym_meta(&self) -> (&GymHeader, &Gd3) {
+        (
+            self.gym_header.as_ref().unwrap(/* There always is */),
+            self.gym_gd3.as_ref().unwrap(/* There always is */),
+        )
+    }
+
+    ///
+    /// Get GYM header JSON.
+    ///
+    pub fn get_gym_header_json(&self) -> String {
+        self.gym_header.as_ref().unwrap(/* There always is */).get_json()
+    }
+
+    ///
+    /// Get GYM GD3 JSON (converted from the GYMX header).
+    ///
+    pub fn get_gym_gd3_json(&self) -> String {
+        self.gym_gd3.as_ref().unwrap(/* There always is */).get_json()
+    }
+
+    ///
+    /// Get the error that stopped playback (broken GYM data).
+    ///
+    pub fn get_error(&self) -> Option<&Error> {
+        self.gym_error.as_ref()
+    }
+
+    ///
+    /// Play Sound.
+    ///
+    pub fn play(&mut self, repeat: bool) -> usize {
+        while !self.sound_slot.is_stream_filled() && !self.gym_end {
+            for _ in 0..self.remain_tick_count {
+                self.sound_slot.update(1);
+                self.remain_tick_count -= 1;
+                if self.sound_slot.is_stream_filled() {
+                    break;
+                }
+            }
+            if self.remain_tick_count == 0 {
+                match self.parse_gym(repeat) {
+                    Ok(wait) => self.remain_tick_count = wait as usize,
+                    Err(error) => {
+                        // stop playback on broken data
+                        self.gym_error = Some(error);
+                        self.gym_end = true;
+                    }
+                }
+            };
+        }
+        self.sound_slot.stream();
+
+        if self.gym_loop_count == usize::MAX {
+            self.gym_loop_count = 0;
+        }
+        if self.gym_end {
+            usize::MAX
+        } else {
+            self.gym_loop_count
+        }
+    }
+
+    ///
+    /// Unpack GYM data and initialize sound driver.
+    ///
+    fn init(&mut self, gym_file: &[u8]) -> Result<(), Error> {
+        // parse gym header
+        let (header, gd3) = gymmeta::parse_gym_meta(gym_file)?;
+
+        // command data after GYMX header (zlib packed)
+        if !header.gymx {
+            self.gym_data = gym_file.to_vec();
+        } else if header.packed_size == 0 {
+            self.gym_data = gym_file[GYMX_HEADER_SIZE..].to_vec();
+        } else {
+            let mut d = ZlibDecoder::new(&gym_file[GYMX_HEADER_SIZE..]);
+            if d.read_to_end(&mut self.gym_data).is_err() {
+                return Err(Error::InvalidDataBlock(GYMX_HEADER_SIZE));
+            }
+        }
+        self.gym_header = Some(header);
+        self.gym_gd3 = Some(gd3);
+
+        // add sound chip (GYM is always NTSC 60Hz)
+        let (clock_ym2612, clock_sn76489, _) = get_vdp_timing(VDPMode::NTSC);
+        self.sound_slot
+            .add_sound_device(SoundChipType::YM2612, 1, clock_ym2612);
+        self.sound_slot
+            .add_sound_device(SoundChipType::SEGAPSG, 1, clock_sn76489);
+
+        Ok(())
+    }
+
+    fn get_gym_u8(&mut self) -> Result<u8, Error> {
+        let ret = match self.gym_data.get(self.gym_pos) {
+            Some(ret) => *ret,
+            None => return Err(Error::UnexpectedEof(self.gym_pos)),
+        };
+        self.gym_pos += 1;
+        Ok(ret)
+    }
+
+    fn parse_gym(&mut self, repeat: bool) -> Result<u16, Error> {
+        let mut wait: u16 = 0;
+
+        // loop start frame (1 origin)
+        let loop_start = self.gym_header.as_ref().unwrap().loop_start;
+        if self.gym_loop_pos.is_none() && loop_start != 0 && self.gym_frame + 1 == loop_start {
+            self.gym_loop_pos = Some(self.gym_pos);
+        }
+
+        // end of data
+        if self.gym_pos >= self.gym_data.len() {
+            match self.gym_loop_pos {
+                Some(loop_pos) if repeat && loop_pos < self.gym_pos => {
+                    self.gym_pos = loop_pos;
+                    self.gym_loop_count += 1;
+                }
+                _ => self.gym_end = true,
+            }
+            return Ok(wait);
+        }
+
+        let command = self.get_gym_u8()?;
+        match command {
+            0x00 => {
+                // frame wait (1/60 of second)
+                wait = 1;
+                self.gym_frame += 1;
+            }
+            0x01 => {
+                // YM2612 port 0 register write
+                let reg = self.get_gym_u8()?;
+                let dat = self.get_gym_u8()?;
+                self.sound_slot
+                    .write(SoundChipType::YM2612, 0, reg as u32, dat.into());
+            }
+            0x02 => {
+                // YM2612 port 1 register write
+                let reg = self.get_gym_u8()?;
+                let dat = self.get_gym_u8()?;
+                self.sound_slot
+                    .write(SoundChipType::YM2612, 0, reg as u32 | 0x100, dat.into());
+            }
+            0x03 => {
+                // PSG register write
+                let dat = self.get_gym_u8()?;
+                self.sound_slot
+                    .write(SoundChipType::SEGAPSG, 0, 0, dat.into());
+            }
+            _ => return Err(Error::UnknownCommand(self.gym_pos - 1, command)),
+        }
+
+        Ok(wait)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sound::SoundSlot;
+
+    use super::{Error, GymPlay, GYM_TICK_RATE};
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    const MAX_SAMPLE_SIZE: usize = 2048;
+
+    #[test]
+    fn gymx_1() {
+        let commands = [0x01, 0x28, 0xf0, 0x00, 0x03, 0x9f, 0x00, 0x00];
+        // loop from the second frame
+        let mut gymplay = GymPlay::new(
+            SoundSlot::new(GYM_TICK_RATE, 44100, MAX_SAMPLE_SIZE),
+            &gymx(&commands, 2, false),
+        )
+        .unwrap();
+        assert_eq!(gymplay.get_gym_meta().0.loop_start, 2);
+        // 3 frames (2205 samples) and then 2 frames (1470 samples) loop
+        assert_eq!(gymplay.play(true), 1);
+        assert_eq!(gymplay.play(true), 2);
+        assert_eq!(gymplay.get_error(), None);
+
+        // zlib packed and no loop
+        let mut gymplay = GymPlay::new(
+            SoundSlot::new(GYM_TICK_RATE, 44100, MAX_SAMPLE_SIZE),
+            &gymx(&commands, 0, true),
+        )
+        .unwrap();
+        assert_eq!(gymplay.play(true), usize::MAX);
+        assert_eq!(gymplay.get_error(), None);
+    }
+
+    #[test]
+    fn broken_1() {
+        // YM2612 port 1 write without data
+        let mut gymplay = GymPlay::new(
+            SoundSlot::new(GYM_TICK_RATE, 44100, MAX_SAMPLE_SIZE),
+            &[0x00, 0x02, 0x2b],
+        )
+        .unwrap();
+        assert_eq!(gymplay.play(false), usize::MAX);
+        assert_eq!(gymplay.get_error(), Some(&Error::UnexpectedEof(3)));
+    }
+
+    fn gymx(commands: &[u8], loop_start: u32, packed: bool) -> Vec<u8> {
+        let mut gym = b"GYMX".to_vec();
+        gym.extend_from_slice(&[0; 32 * 5 + 256]);
+        gym.extend_from_slice(&loop_start.to_le_bytes());
+        if packed {
+            let mut e = ZlibEncoder::new(Vec::new(), Compression::default());
+            e.write_all(commands).unwrap();
+            gym.extend_from_slice(&(commands.len() as u32).to_le_bytes());
+            gym.extend_from_slice(&e.finish().unwrap());
+        } else {
+            gym.extend_from_slice(&0_u32.to_le_bytes());
+            gym.extend_from_slice(commands);
+        }
+        gym
+    }
+}
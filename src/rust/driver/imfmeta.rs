@@ -0,0 +1,127 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use crate::driver::error::Error;
+use crate::driver::gd3meta::Gd3;
+use crate::driver::meta::Jsonlize;
+
+///
+/// IMF timer rate
+///
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ImfRate {
+    Hz280 = 280,
+    Hz560 = 560,
+    Hz700 = 700,
+}
+
+impl ImfRate {
+    ///
+    /// Get IMF rate from the tick rate (280, 560 or 700).
+    ///
+    pub fn from_tick_rate(tick_rate: u32) -> Option<ImfRate> {
+        match tick_rate {
+            280 => Some(ImfRate::Hz280),
+            560 => Some(ImfRate::Hz560),
+            700 => Some(ImfRate::Hz700),
+            _ => None,
+        }
+    }
+}
+
+///
+/// id Software Music Format
+///
+/// type 0: register/value/delay (4 bytes) until the end of the file
+/// type 1: $0000 data length (2 bytes), data, and the optional footer
+///
+/// footer: 0x1A, title, composer, remarks (null terminated)
+///
+/// The timer rate is not in the file (Commander Keen 560Hz, Wolfenstein 3D 700Hz,
+/// Duke Nukem II 280Hz).
+///
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ImfHeader {
+    pub imf_type: u32,
+    pub data_offset: usize,
+    pub data_length: usize,
+    pub tick_rate: u32,
+}
+
+///
+/// Parse null terminated strings of the footer
+///
+fn parse_imf_footer(i: &[u8]) -> Gd3 {
+    let mut gd3 = Gd3::default();
+    let mut strings = match i.strip_prefix(b"\x1a") {
+        Some(footer) => footer
+            .split(|c| *c == 0)
+            .map(|string| string.iter().map(|c| *c as char).collect::<String>()),
+        None => return gd3,
+    };
+    gd3.track_name = strings.next().unwrap_or_default();
+    gd3.track_author = strings.next().unwrap_or_default();
+    gd3.notes = strings.next().unwrap_or_default();
+    gd3
+}
+
+///
+/// Parse IMF meta
+///
+pub(crate) fn parse_imf_meta(imfdata: &[u8]) -> Result<(ImfHeader, Gd3), Error> {
+    if imfdata.len() < 4 {
+        return Err(Error::InvalidHeader("imf"));
+    }
+    let data_length = u16::from_le_bytes([imfdata[0], imfdata[1]]) as usize;
+    let header = if data_length == 0 {
+        ImfHeader {
+            imf_type: 0,
+            data_offset: 0,
+            data_length: imfdata.len(),
+            tick_rate: ImfRate::Hz560 as u32,
+        }
+    } else {
+        ImfHeader {
+            imf_type: 1,
+            data_offset: 2,
+            data_length,
+            tick_rate: ImfRate::Hz560 as u32,
+        }
+    };
+    if header.data_offset + header.data_length > imfdata.len() {
+        return Err(Error::BadOffset(header.data_offset + header.data_length));
+    }
+    let gd3 = parse_imf_footer(&imfdata[header.data_offset + header.data_length..]);
+
+    Ok((header, gd3))
+}
+
+impl Jsonlize for ImfHeader {}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_imf_meta;
+    use crate::driver::error::Error;
+
+    #[test]
+    fn imf() {
+        // type 0
+        let imf = [0x00, 0x00, 0x00, 0x00, 0xb0, 0x20, 0x10, 0x00];
+        let (header, gd3) = parse_imf_meta(&imf).unwrap();
+        assert_eq!(header.imf_type, 0);
+        assert_eq!(header.data_length, 8);
+        assert_eq!(gd3.track_name, "");
+
+        // type 1 and footer
+        let mut imf = vec![0x04, 0x00, 0xb0, 0x20, 0x10, 0x00];
+        imf.extend_from_slice(b"\x1aTitle\0Composer\0Remarks\0Program\0");
+        let (header, gd3) = parse_imf_meta(&imf).unwrap();
+        assert_eq!(header.imf_type, 1);
+        assert_eq!(header.data_offset, 2);
+        assert_eq!(gd3.track_name, "Title");
+        assert_eq!(gd3.track_author, "Composer");
+        assert_eq!(gd3.notes, "Remarks");
+
+        imf[0] = 0x40;
+        assert_eq!(parse_imf_meta(&imf).unwrap_err(), Error::BadOffset(0x42));
+    }
+}
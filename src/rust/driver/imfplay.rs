@@ -0,0 +1,244 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use crate::driver::error::Error;
+use crate::driver::gd3meta::Gd3;
+use crate::driver::imfmeta::{self, ImfHeader, ImfRate};
+use crate::driver::meta::Jsonlize;
+use crate::sound::{SoundChipType, SoundSlot};
+
+pub const IMF_TICK_RATE: u32 = ImfRate::Hz560 as u32;
+
+const CLOCK_OPL2: u32 = 3579545;
+
+///
+/// IMF Driver
+///
+pub struct ImfPlay {
+    sound_slot: SoundSlot,
+    imf_pos: usize,
+    imf_end: bool,
+    imf_data: Vec<u8>,
+    imf_header: Option<ImfHeader>,
+    imf_gd3: Option<Gd3>,
+    imf_error: Option<Error>,
+    remain_tick_count: usize,
+}
+
+#[allow(dead_code)]
+impl ImfPlay {
+    ///
+    /// Create sound driver.
+    ///
+    pub fn new(sound_slot: SoundSlot, imf_file: &[u8]) -> Result<Self, Error> {
+        Self::new_with_rate(sound_slot, imf_file, None)
+    }
+
+    ///
+    /// Create sound driver with the timer rate (default is 560Hz).
+    ///
+    pub fn new_with_rate(
+        sound_slot: SoundSlot,
+        imf_file: &[u8],
+        imf_rate: Option<ImfRate>,
+    ) -> Result<Self, Error> {
+        let mut imfplay = ImfPlay {
+            sound_slot,
+            imf_pos: 0,
+            imf_end: false,
+            imf_data: Vec::new(),
+            imf_header: None,
+            imf_gd3: None,
+            imf_error: None,
+            remain_tick_count: 0,
+        };
+        // parse imf header and soundchip init
+        imfplay.init(imf_file, imf_rate)?;
+
+        Ok(imfplay)
+    }
+
+    ///
+    /// Return sampling_l buffer referance.
+    ///
+    pub fn get_sampling_l_ref(&self) -> *const f32 {
+        self.sound_slot.get_output_sampling_l_ref()
+    }
+
+    ///
+    /// Return sampling buffer referance.
+    ///
+    pub fn get_sampling_r_ref(&self) -> *const f32 {
+        self.sound_slot.get_output_sampling_r_ref()
+    }
+
+    ///
+    /// Return s16le sampling buffer referance.
+    ///
+    pub fn get_output_sampling_s16le_ref(&mut self) -> *const i16 {
+        self.sound_slot.get_output_sampling_s16le_ref()
+    }
+
+    ///
+    /// Get IMF meta.
+    ///
+    pub fn get_imf_meta(&self) -> (&ImfHeader, &Gd3) {
+        (
+            self.imf_header.as_ref().unwrap(/* There always is */),
+            self.imf_gd3.as_ref().unwrap(/* There always is */),
+        )
+    }
+
+    ///
+    /// Get IMF header JSON.
+    ///
+    pub fn get_imf_header_json(&self) -> String {
+        self.imf_header.as_ref().unwrap(/* There always is */).get_json()
+    }
+
+    ///
+    /// Get IMF GD3 JSON (converted from the footer).
+    ///
+    pub fn get_imf_gd3_json(&self) -> String {
+        self.imf_gd3.as_ref().unwrap(/* There always is */).get_json()
+    }
+
+    ///
+    /// Get the error that stopped playback (broken IMF data).
+    ///
+    pub fn get_error(&self) -> Option<&Error> {
+        self.imf_error.as_ref()
+    }
+
+    ///
+    /// Play Sound.
+    ///
+    /// IMF has no loop point, so the repeat flag is ignored.
+    ///
+    pub fn play(&mut self, _repeat: bool) -> usize {
+        while !self.sound_slot.is_stream_filled() && !self.imf_end {
+            for _ in 0..self.remain_tick_count {
+                self.sound_slot.update(1);
+                self.remain_tick_count -= 1;
+                if self.sound_slot.is_stream_filled() {
+                    break;
+                }
+            }
+            if self.remain_tick_count == 0 {
+                match self.parse_imf() {
+                    Ok(wait) => self.remain_tick_count = wait as usize,
+                    Err(error) => {
+                        // stop playback on broken data
+                        self.imf_error = Some(error);
+                        self.imf_end = true;
+                    }
+                }
+            };
+        }
+        self.sound_slot.stream();
+
+        if self.imf_end {
+            usize::MAX
+        } else {
+            0
+        }
+    }
+
+    ///
+    /// Initialize sound driver.
+    ///
+    fn init(&mut self, imf_file: &[u8], imf_rate: Option<ImfRate>) -> Result<(), Error> {
+        // parse imf header
+        let (mut header, gd3) = imfmeta::parse_imf_meta(imf_file)?;
+
+        // register data only
+        self.imf_data =
+            imf_file[header.data_offset..header.data_offset + header.data_length].to_vec();
+
+        // set external tick rate (timer rate)
+        if let Some(imf_rate) = imf_rate {
+            header.tick_rate = imf_rate as u32;
+        }
+        self.sound_slot.change_external_tick_rate(header.tick_rate);
+
+        // add sound chip
+        self.sound_slot
+            .add_sound_device(SoundChipType::YM3812, 1, CLOCK_OPL2);
+        self.imf_header = Some(header);
+        self.imf_gd3 = Some(gd3);
+
+        Ok(())
+    }
+
+    fn get_imf_u8(&mut self) -> Result<u8, Error> {
+        let ret = match self.imf_data.get(self.imf_pos) {
+            Some(ret) => *ret,
+            None => return Err(Error::UnexpectedEof(self.imf_pos)),
+        };
+        self.imf_pos += 1;
+        Ok(ret)
+    }
+
+    fn parse_imf(&mut self) -> Result<u16, Error> {
+        // end of data
+        if self.imf_pos >= self.imf_data.len() {
+            self.imf_end = true;
+            return Ok(0);
+        }
+
+        // register, value and delay
+        let reg = self.get_imf_u8()?;
+        let dat = self.get_imf_u8()?;
+        let low = self.get_imf_u8()?;
+        let high = self.get_imf_u8()?;
+        self.sound_slot
+            .write(SoundChipType::YM3812, 0, reg as u32, dat.into());
+
+        Ok(u16::from_le_bytes([low, high]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::driver::imfmeta::ImfRate;
+    use crate::sound::SoundSlot;
+
+    use super::{Error, ImfPlay, IMF_TICK_RATE};
+
+    const MAX_SAMPLE_SIZE: usize = 2048;
+
+    #[test]
+    fn imf_1() {
+        // key on and 700 ticks wait
+        let imf = [0x08, 0x00, 0xb0, 0x20, 0xbc, 0x02, 0xb0, 0x00, 0x00, 0x00];
+        let sound_slot = || SoundSlot::new(IMF_TICK_RATE, 44100, MAX_SAMPLE_SIZE);
+        // 1.25 sec (55125 samples)
+        let mut imfplay = ImfPlay::new(sound_slot(), &imf).unwrap();
+        let mut chunk = 1;
+        while imfplay.play(false) != usize::MAX {
+            chunk += 1;
+        }
+        assert_eq!(chunk, 55125 / MAX_SAMPLE_SIZE + 1);
+        assert_eq!(imfplay.get_error(), None);
+
+        // 1 sec (44100 samples)
+        let mut imfplay = ImfPlay::new_with_rate(sound_slot(), &imf, Some(ImfRate::Hz700)).unwrap();
+        assert_eq!(imfplay.get_imf_meta().0.tick_rate, 700);
+        let mut chunk = 1;
+        while imfplay.play(false) != usize::MAX {
+            chunk += 1;
+        }
+        assert_eq!(chunk, 44100 / MAX_SAMPLE_SIZE + 1);
+    }
+
+    #[test]
+    fn broken_1() {
+        // type 0 without the last delay
+        let mut imfplay = ImfPlay::new(
+            SoundSlot::new(IMF_TICK_RATE, 44100, MAX_SAMPLE_SIZE),
+            &[0x00, 0x00, 0x00, 0x00, 0xb0, 0x20],
+        )
+        .unwrap();
+        assert_eq!(imfplay.play(false), usize::MAX);
+        assert_eq!(imfplay.get_error(), Some(&Error::UnexpectedEof(6)));
+    }
+}
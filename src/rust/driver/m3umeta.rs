@@ -0,0 +1,217 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use encoding_rs::SHIFT_JIS;
+
+use crate::driver::error::Error;
+use crate::driver::meta::Jsonlize;
+
+///
+/// Playlist entry
+///
+/// track is the track number of the multi tracks file (XGM).
+/// length_ms, fade_ms and loop_count override the defaults of the player.
+///
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct PlaylistEntry {
+    pub file_name: String,
+    pub track: usize,
+    pub title: String,
+    pub length_ms: Option<u32>,
+    pub fade_ms: Option<u32>,
+    pub loop_count: Option<usize>,
+}
+
+///
+/// M3U / M3U8 playlist
+///
+/// #EXTINF:length (sec),title
+/// file name
+///
+/// NEZplug extended entry
+/// file name::type,track,title,length,loop,fade,loop count
+///
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Playlist {
+    pub entries: Vec<PlaylistEntry>,
+}
+
+///
+/// Parse time (h:mm:ss, m:ss or s, and the fraction of the second) to milliseconds
+///
+fn parse_time(time: &str) -> Option<u32> {
+    let time = time.trim();
+    if time.is_empty() {
+        return None;
+    }
+    // seconds first
+    let mut parts = time.rsplit(':');
+    let seconds = parts.next()?;
+    let (seconds, fraction) = seconds.split_once(['.', '\'']).unwrap_or((seconds, ""));
+    // overflow is an invalid time
+    let mut ms = seconds.trim().parse::<u64>().ok()?.checked_mul(1000)?;
+    if !fraction.is_empty() {
+        let fraction: String = fraction.chars().chain("000".chars()).take(3).collect();
+        ms = ms.checked_add(fraction.parse::<u64>().ok()?)?;
+    }
+    let mut unit: u64 = 60 * 1000;
+    for part in parts {
+        ms = ms.checked_add(part.trim().parse::<u64>().ok()?.checked_mul(unit)?)?;
+        unit = unit.checked_mul(60)?;
+    }
+    u32::try_from(ms).ok()
+}
+
+///
+/// Parse number (decimal or $hex)
+///
+fn parse_number(number: &str) -> Option<usize> {
+    let number = number.trim();
+    match number.strip_prefix('$') {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => number.parse().ok(),
+    }
+}
+
+///
+/// Split fields of NEZplug extended entry (comma is escaped by backslash)
+///
+fn split_fields(fields: &str) -> Vec<String> {
+    let mut split = vec![String::new()];
+    let mut chars = fields.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    split.last_mut().unwrap().push(c);
+                }
+            }
+            ',' => split.push(String::new()),
+            _ => split.last_mut().unwrap().push(c),
+        }
+    }
+    split
+}
+
+///
+/// Parse M3U meta
+///
+pub(crate) fn parse_m3u_meta(m3udata: &[u8]) -> Result<Playlist, Error> {
+    // M3U8 is UTF-8 (with BOM), others are Shift-JIS in Japan
+    let m3udata = m3udata.strip_prefix(b"\xef\xbb\xbf").unwrap_or(m3udata);
+    let m3u = match std::str::from_utf8(m3udata) {
+        Ok(m3u) => m3u.to_string(),
+        Err(_) => SHIFT_JIS
+            .decode_without_bom_handling(m3udata)
+            .0
+            .into_owned(),
+    };
+
+    let mut entries = Vec::new();
+    let mut extinf: Option<(Option<u32>, String)> = None;
+    for line in m3u.lines().map(|line| line.trim()) {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            // -1 is unknown length
+            let (length, title) = info.split_once(',').unwrap_or((info, ""));
+            let length = match length.trim().parse::<i64>() {
+                Ok(length) if length > 0 => length
+                    .checked_mul(1000)
+                    .and_then(|length| u32::try_from(length).ok()),
+                _ => None,
+            };
+            extinf = Some((length, String::from(title.trim())));
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut entry = PlaylistEntry::default();
+        match line.split_once("::") {
+            Some((file_name, fields)) => {
+                // type, track, title, length, loop, fade, loop count
+                let fields = split_fields(fields);
+                let field =
+                    |index: usize| fields.get(index).map(|field| field.trim()).unwrap_or("");
+                entry.file_name = String::from(file_name);
+                entry.track = parse_number(field(1)).unwrap_or(0);
+                entry.title = String::from(field(2));
+                entry.length_ms = parse_time(field(3));
+                entry.fade_ms = parse_time(field(5));
+                entry.loop_count = parse_number(field(6));
+            }
+            None => entry.file_name = String::from(line),
+        }
+        if let Some((length_ms, title)) = extinf.take() {
+            entry.length_ms = entry.length_ms.or(length_ms);
+            if entry.title.is_empty() {
+                entry.title = title;
+            }
+        }
+        entry.file_name = entry.file_name.trim().replace('\\', "/");
+        entries.push(entry);
+    }
+    if entries.is_empty() {
+        return Err(Error::InvalidHeader("m3u"));
+    }
+
+    Ok(Playlist { entries })
+}
+
+impl Jsonlize for Playlist {}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_m3u_meta, parse_time};
+    use crate::driver::error::Error;
+
+    #[test]
+    fn m3u() {
+        let m3u = "\u{feff}#EXTM3U\r\n\
+            #EXTINF:90,Opening\r\n\
+            01 Opening.vgz\r\n\
+            \r\n\
+            # comment\r\n\
+            sub\\02 Stage 1.vgz::VGM,0,Stage 1\\, Forest,2:30,,10,\r\n\
+            sound.xgm::XGM,$0a,,,,0.5,3\r\n";
+        let playlist = parse_m3u_meta(m3u.as_bytes()).unwrap();
+        assert_eq!(playlist.entries.len(), 3);
+        assert_eq!(playlist.entries[0].file_name, "01 Opening.vgz");
+        assert_eq!(playlist.entries[0].title, "Opening");
+        assert_eq!(playlist.entries[0].length_ms, Some(90000));
+        assert_eq!(playlist.entries[1].file_name, "sub/02 Stage 1.vgz");
+        assert_eq!(playlist.entries[1].title, "Stage 1, Forest");
+        assert_eq!(playlist.entries[1].length_ms, Some(150000));
+        assert_eq!(playlist.entries[1].fade_ms, Some(10000));
+        assert_eq!(playlist.entries[1].loop_count, None);
+        assert_eq!(playlist.entries[2].track, 10);
+        assert_eq!(playlist.entries[2].length_ms, None);
+        assert_eq!(playlist.entries[2].fade_ms, Some(500));
+        assert_eq!(playlist.entries[2].loop_count, Some(3));
+
+        // Shift-JIS
+        let playlist = parse_m3u_meta(b"\x83e\x83X\x83g.vgz\n").unwrap();
+        assert_eq!(playlist.entries[0].file_name, "テスト.vgz");
+
+        // EXTINF length overflow
+        let playlist = parse_m3u_meta(b"#EXTINF:9223372036854775807,Long\nlong.vgz\n").unwrap();
+        assert_eq!(playlist.entries[0].length_ms, None);
+
+        assert_eq!(
+            parse_m3u_meta(b"#EXTM3U\n").unwrap_err(),
+            Error::InvalidHeader("m3u")
+        );
+    }
+
+    #[test]
+    fn time() {
+        assert_eq!(parse_time("1:02:03"), Some(3723000));
+        assert_eq!(parse_time("2:05.25"), Some(125250));
+        assert_eq!(parse_time("7'5"), Some(7500));
+        assert_eq!(parse_time(""), None);
+        assert_eq!(parse_time("-"), None);
+        // overflow
+        assert_eq!(parse_time("18446744073709551615"), None);
+        assert_eq!(parse_time("18446744073709551:00"), None);
+        assert_eq!(parse_time(&("1:".repeat(20) + "0")), None);
+    }
+}
@@ -0,0 +1,255 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use encoding_rs::SHIFT_JIS;
+use nom::bytes::complete::{tag, take, take_until};
+use nom::multi::count;
+use nom::number::complete::{be_u16, le_u8};
+use nom::IResult;
+
+use crate::driver::error::Error;
+use crate::driver::gd3meta::Gd3;
+use crate::driver::meta::Jsonlize;
+
+pub const MDX_CHANNEL: usize = 9;
+pub const MDX_PCM8_CHANNEL: usize = 16;
+pub const PDX_SAMPLE_MAX: usize = 96;
+
+const MDX_VOICE_SIZE: usize = 27;
+
+///
+/// MDX (X68000 MXDRV)
+///
+/// title (Shift-JIS) + 0x0d 0x0a 0x1a
+/// PDX file name + 0x00
+/// $0000 voice data offset (big endian, from here)
+/// $0002 MML data offset of channel A-H (FM) and P (ADPCM)
+/// $0014 MML data offset of channel Q-W (PCM8 extended, when the offset of A is 0x22)
+///
+#[derive(Deserialize, Serialize, Debug)]
+pub struct MdxHeader {
+    pub title: String,
+    pub pdx_file_name: String,
+    pub data_offset: usize,
+    pub voice_offset: usize,
+    pub channel_offset: Vec<usize>,
+}
+
+///
+/// MDX voice (OPM operator registers in M1, M2, C1, C2 order)
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MdxVoice {
+    pub number: u8,
+    pub fl_con: u8,
+    pub slot_mask: u8,
+    pub dt1_mul: [u8; 4],
+    pub tl: [u8; 4],
+    pub ks_ar: [u8; 4],
+    pub ame_d1r: [u8; 4],
+    pub dt2_d2r: [u8; 4],
+    pub d1l_rr: [u8; 4],
+}
+
+///
+/// Parse 4 operators
+///
+fn parse_operator(i: &[u8]) -> IResult<&[u8], [u8; 4]> {
+    let (i, operator) = take(4usize)(i)?;
+    Ok((i, operator.try_into().unwrap()))
+}
+
+///
+/// Parse MDX voice
+///
+fn parse_mdx_voice(i: &[u8]) -> IResult<&[u8], MdxVoice> {
+    let (i, number) = le_u8(i)?;
+    let (i, fl_con) = le_u8(i)?;
+    let (i, slot_mask) = le_u8(i)?;
+    let (i, dt1_mul) = parse_operator(i)?;
+    let (i, tl) = parse_operator(i)?;
+    let (i, ks_ar) = parse_operator(i)?;
+    let (i, ame_d1r) = parse_operator(i)?;
+    let (i, dt2_d2r) = parse_operator(i)?;
+    let (i, d1l_rr) = parse_operator(i)?;
+
+    Ok((
+        i,
+        MdxVoice {
+            number,
+            fl_con,
+            slot_mask,
+            dt1_mul,
+            tl,
+            ks_ar,
+            ame_d1r,
+            dt2_d2r,
+            d1l_rr,
+        },
+    ))
+}
+
+///
+/// Parse MDX header
+///
+fn parse_mdx_header(all: &[u8]) -> IResult<&[u8], MdxHeader> {
+    let (i, title) = take_until(&b"\r\n\x1a"[..])(all)?;
+    let (i, _) = tag(b"\r\n\x1a")(i)?;
+    let (i, pdx_file_name) = take_until(&b"\0"[..])(i)?;
+    let (i, _) = tag(b"\0")(i)?;
+    let data_offset = all.len() - i.len();
+    let (_, voice_offset) = be_u16(i)?;
+    let (_, first_offset) = be_u16(&i[2..])?;
+    // PCM8 extended has 16 channels
+    let channel = if first_offset as usize == 2 + 2 * MDX_PCM8_CHANNEL {
+        MDX_PCM8_CHANNEL
+    } else {
+        MDX_CHANNEL
+    };
+    let (i, channel_offset) = count(be_u16, channel)(&i[2..])?;
+
+    Ok((
+        i,
+        MdxHeader {
+            title: SHIFT_JIS.decode_without_bom_handling(title).0.into_owned(),
+            pdx_file_name: SHIFT_JIS
+                .decode_without_bom_handling(pdx_file_name)
+                .0
+                .into_owned(),
+            data_offset,
+            voice_offset: data_offset + voice_offset as usize,
+            channel_offset: channel_offset
+                .iter()
+                .map(|offset| data_offset + *offset as usize)
+                .collect(),
+        },
+    ))
+}
+
+///
+/// Parse MDX meta
+///
+pub(crate) fn parse_mdx_meta(mdxdata: &[u8]) -> Result<(MdxHeader, Gd3), Error> {
+    let header = match parse_mdx_header(mdxdata) {
+        Ok((_, header)) => header,
+        Err(_) => return Err(Error::InvalidHeader("mdx")),
+    };
+    if header.voice_offset > mdxdata.len() {
+        return Err(Error::BadOffset(header.voice_offset));
+    }
+    if let Some(offset) = header
+        .channel_offset
+        .iter()
+        .find(|offset| **offset >= mdxdata.len())
+    {
+        return Err(Error::BadOffset(*offset));
+    }
+    let gd3 = Gd3 {
+        track_name: header.title.clone(),
+        system_name: String::from("Sharp X68000"),
+        ..Default::default()
+    };
+
+    Ok((header, gd3))
+}
+
+///
+/// Parse MDX voices until the end of data
+///
+pub(crate) fn parse_mdx_voices(mdxdata: &[u8], voice_offset: usize) -> Vec<MdxVoice> {
+    let mut voices = Vec::new();
+    let mut i = &mdxdata[voice_offset..];
+    while i.len() >= MDX_VOICE_SIZE {
+        match parse_mdx_voice(i) {
+            Ok((rest, voice)) => {
+                voices.push(voice);
+                i = rest;
+            }
+            Err(_) => break,
+        }
+    }
+    voices
+}
+
+///
+/// Parse PDX (96 entries of the offset and length, big endian)
+///
+pub(crate) fn parse_pdx(pdxdata: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    let mut samples = Vec::with_capacity(PDX_SAMPLE_MAX);
+    for entry in 0..PDX_SAMPLE_MAX {
+        let (offset, length) = match pdxdata.get(entry * 8..entry * 8 + 8) {
+            Some(entry) => (
+                u32::from_be_bytes(entry[0..4].try_into().unwrap()) as usize,
+                u32::from_be_bytes(entry[4..8].try_into().unwrap()) as usize,
+            ),
+            None => return Err(Error::InvalidHeader("pdx")),
+        };
+        match pdxdata.get(offset..offset.saturating_add(length)) {
+            Some(sample) if offset != 0 => samples.push(sample.to_vec()),
+            Some(_) => samples.push(Vec::new()),
+            None => return Err(Error::BadOffset(offset)),
+        }
+    }
+    Ok(samples)
+}
+
+impl Jsonlize for MdxHeader {}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_mdx_meta, parse_mdx_voices, parse_pdx, MDX_CHANNEL};
+    use crate::driver::error::Error;
+
+    #[test]
+    fn mdx() {
+        let mut mdx = b"\x83e\x83X\x83g\r\n\x1aSAMPLE.PDX\0".to_vec();
+        let base = mdx.len();
+        // voice data and 9 channels
+        mdx.extend_from_slice(&[0x00, 0x1a]);
+        for _ in 0..MDX_CHANNEL {
+            mdx.extend_from_slice(&[0x00, 0x14]);
+        }
+        mdx.extend_from_slice(&[0xf1, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        mdx.extend_from_slice(&[0x01, 0x3c, 0x0f]);
+        mdx.extend_from_slice(&[0x00; 24]);
+
+        let (header, gd3) = parse_mdx_meta(&mdx).unwrap();
+        assert_eq!(header.title, "テスト");
+        assert_eq!(header.pdx_file_name, "SAMPLE.PDX");
+        assert_eq!(header.data_offset, base);
+        assert_eq!(header.voice_offset, base + 0x1a);
+        assert_eq!(header.channel_offset, vec![base + 0x14; MDX_CHANNEL]);
+        assert_eq!(gd3.track_name, "テスト");
+
+        let voices = parse_mdx_voices(&mdx, header.voice_offset);
+        assert_eq!(voices.len(), 1);
+        assert_eq!(voices[0].number, 1);
+        assert_eq!(voices[0].fl_con, 0x3c);
+        assert_eq!(voices[0].slot_mask, 0x0f);
+
+        mdx.truncate(base + 0x14);
+        assert_eq!(
+            parse_mdx_meta(&mdx).unwrap_err(),
+            Error::BadOffset(base + 0x1a)
+        );
+        assert_eq!(
+            parse_mdx_meta(b"title\0").unwrap_err(),
+            Error::InvalidHeader("mdx")
+        );
+    }
+
+    #[test]
+    fn pdx() {
+        let mut pdx = vec![0; 96 * 8];
+        pdx[2] = 0x03;
+        pdx[4..8].copy_from_slice(&4_u32.to_be_bytes());
+        pdx.extend_from_slice(&[0x08, 0x80, 0x08, 0x80]);
+
+        let samples = parse_pdx(&pdx).unwrap();
+        assert_eq!(samples.len(), 96);
+        assert_eq!(samples[0], vec![0x08, 0x80, 0x08, 0x80]);
+        assert!(samples[1].is_empty());
+
+        pdx[7] = 5;
+        assert_eq!(parse_pdx(&pdx).unwrap_err(), Error::BadOffset(0x300));
+    }
+}
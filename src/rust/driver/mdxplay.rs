@@ -0,0 +1,1033 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use crate::driver::error::Error;
+use crate::driver::gd3meta::Gd3;
+use crate::driver::mdxmeta::{self, MdxHeader, MdxVoice, MDX_CHANNEL};
+use crate::driver::meta::Jsonlize;
+use crate::sound::{SoundChipType, SoundSlot};
+
+pub const MDX_TICK_RATE: u32 = 44100;
+
+const CLOCK_YM2151: u32 = 4000000;
+const CLOCK_OKIM6258: u32 = 8000000;
+
+const NOISE_CHANNEL: usize = 7;
+const ADPCM_CHANNEL: usize = 8;
+const ADPCM_STREAM: usize = 0;
+const LENGTH_MODE_TO_END: u8 = 0x03;
+
+/// OPM timer B value (the clock is 1024 * (256 - tempo) / 4MHz sec)
+const DEFAULT_TEMPO: u8 = 200;
+
+/// OPM key code of the semitone from C#
+const KEY_CODE: [u8; 12] = [0, 1, 2, 4, 5, 6, 8, 9, 10, 12, 13, 14];
+
+/// Semitones from the key code 0x00 (o0 c+) to the note 0x80 (o0 d+)
+const NOTE_KEY_CODE_OFFSET: i32 = 2;
+
+/// Total level of volume 0 - 15
+const VOLUME: [u8; 16] = [
+    0x2a, 0x28, 0x25, 0x22, 0x20, 0x1d, 0x1a, 0x18, 0x15, 0x12, 0x10, 0x0d, 0x0a, 0x08, 0x05, 0x02,
+];
+
+/// Carrier slots of the algorithm (bit 0 - 3: M1, M2, C1, C2)
+const CARRIER: [u8; 8] = [0x08, 0x08, 0x08, 0x08, 0x0c, 0x0e, 0x0e, 0x0f];
+
+/// ADPCM frequency 3.9kHz, 5.2kHz, 7.8kHz, 10.4kHz and 15.6kHz (clock and divider)
+const ADPCM_FREQUENCY: [(u32, u32); 5] = [
+    (4000000, 0),
+    (4000000, 1),
+    (4000000, 2),
+    (8000000, 1),
+    (8000000, 2),
+];
+const ADPCM_DIVIDER: [u32; 3] = [1024, 768, 512];
+
+///
+/// Software LFO (wave 0: saw, 1: square, 2: triangle)
+///
+#[derive(Default)]
+struct MdxLfo {
+    wave: u8,
+    period: u16,
+    amplitude: i32,
+    enable: bool,
+    phase: u16,
+}
+
+impl MdxLfo {
+    fn value(&self) -> i32 {
+        if !self.enable || self.period == 0 {
+            return 0;
+        }
+        // 16-bit amplitude and phase overflow i32
+        let phase = self.phase as i64;
+        let period = self.period as i64;
+        let amplitude = self.amplitude as i64;
+        let value = match self.wave {
+            0 => -amplitude + 2 * amplitude * phase / period,
+            1 => {
+                if phase < period / 2 {
+                    amplitude
+                } else {
+                    -amplitude
+                }
+            }
+            _ => {
+                let value = 4 * amplitude * phase / period;
+                if phase < period / 4 {
+                    value
+                } else if phase < period * 3 / 4 {
+                    2 * amplitude - value
+                } else {
+                    value - 4 * amplitude
+                }
+            }
+        };
+        value.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    }
+
+    fn step(&mut self) {
+        if self.enable && self.period != 0 {
+            self.phase = (self.phase + 1) % self.period;
+        }
+    }
+}
+
+///
+/// MDX channel state
+///
+#[derive(Default)]
+struct MdxChannel {
+    pos: usize,
+    end: bool,
+    loop_count: usize,
+    wait: u32,
+    gate: u32,
+    tie: bool,
+    note: u8,
+    voice: Option<usize>,
+    volume: u8,
+    pan: u8,
+    q: u8,
+    detune: i32,
+    portamento: i32,
+    portamento_delta: i32,
+    portamento_offset: i32,
+    key_on_delay: u8,
+    key_on_delay_count: u32,
+    pitch_lfo: MdxLfo,
+    amp_lfo: MdxLfo,
+    lfo_delay: u8,
+    lfo_delay_count: u32,
+    opm_lfo_sync: bool,
+    opm_lfo_pms_ams: u8,
+    sync_wait: bool,
+    sync_received: bool,
+    adpcm_frequency: u8,
+    adpcm_playing: bool,
+    last_pitch: Option<i32>,
+    last_attenuation: Option<u32>,
+}
+
+impl MdxChannel {
+    fn new(pos: usize) -> Self {
+        MdxChannel {
+            pos,
+            volume: 8,
+            pan: 3,
+            q: 8,
+            adpcm_frequency: 4,
+            ..Default::default()
+        }
+    }
+
+    ///
+    /// Total level attenuation of the carrier (volume and amplitude LFO)
+    ///
+    fn attenuation(&self) -> u32 {
+        let volume = if self.volume & 0x80 != 0 {
+            (self.volume & 0x7f) as i32
+        } else {
+            VOLUME[self.volume.min(15) as usize] as i32
+        };
+        let lfo = (self.amp_lfo.value() + self.amp_lfo.amplitude.abs()) / 2;
+        (volume + lfo).clamp(0, 127) as u32
+    }
+}
+
+///
+/// MDX Driver
+///
+pub struct MdxPlay {
+    sound_slot: SoundSlot,
+    mdx_loop_count: usize,
+    mdx_end: bool,
+    mdx_data: Vec<u8>,
+    mdx_header: Option<MdxHeader>,
+    mdx_gd3: Option<Gd3>,
+    mdx_error: Option<Error>,
+    mdx_voice: Vec<MdxVoice>,
+    mdx_channel: Vec<MdxChannel>,
+    mdx_tempo: u8,
+    tempo_remainder: u64,
+    remain_tick_count: usize,
+}
+
+#[allow(dead_code)]
+impl MdxPlay {
+    ///
+    /// Create sound driver.
+    ///
+    pub fn new(sound_slot: SoundSlot, mdx_file: &[u8]) -> Result<Self, Error> {
+        let mut mdxplay = MdxPlay {
+            sound_slot,
+            mdx_loop_count: 0,
+            mdx_end: false,
+            mdx_data: mdx_file.to_vec(),
+            mdx_header: None,
+            mdx_gd3: None,
+            mdx_error: None,
+            mdx_voice: Vec::new(),
+            mdx_channel: Vec::new(),
+            mdx_tempo: DEFAULT_TEMPO,
+            tempo_remainder: 0,
+            remain_tick_count: 0,
+        };
+        // parse mdx header and soundchip init
+        mdxplay.init()?;
+
+        Ok(mdxplay)
+    }
+
+    ///
+    /// Add PDX (ADPCM samples of the channel P).
+    ///
+    pub fn add_pdx(&mut self, pdx_file: &[u8]) -> Result<(), Error> {
+        for (sample, data) in mdxmeta::parse_pdx(pdx_file)?.iter().enumerate() {
+            if !data.is_empty() {
+                self.sound_slot.add_data_block(sample, data);
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Return sampling_l buffer referance.
+    ///
+    pub fn get_sampling_l_ref(&self) -> *const f32 {
+        self.sound_slot.get_output_sampling_l_ref()
+    }
+
+    ///
+    /// Return sampling buffer referance.
+    ///
+    pub fn get_sampling_r_ref(&self) -> *const f32 {
+        self.sound_slot.get_output_sampling_r_ref()
+    }
+
+    ///
+    /// Return s16le sampling buffer referance.
+    ///
+    pub fn get_output_sampling_s16le_ref(&mut self) -> *const i16 {
+        self.sound_slot.get_output_sampling_s16le_ref()
+    }
+
+    ///
+    /// Get MDX meta.
+    ///
+    pub fn get_mdx_meta(&self) -> (&MdxHeader, &Gd3) {
+        (
+            self.mdx_header.as_ref().unwrap(/* There always is */),
+            self.mdx_gd3.as_ref().unwrap(/* There always is */),
+        )
+    }
+
+    ///
+    /// Get MDX header JSON.
+    ///
+    pub fn get_mdx_header_json(&self) -> String {
+        self.mdx_header.as_ref().unwrap(/* There always is */).get_json()
+    }
+
+    ///
+    /// Get MDX GD3 JSON (converted from the title).
+    ///
+    pub fn get_mdx_gd3_json(&self) -> String {
+        self.mdx_gd3.as_ref().unwrap(/* There always is */).get_json()
+    }
+
+    ///
+    /// Get the error that stopped playback (broken MDX data).
+    ///
+    pub fn get_error(&self) -> Option<&Error> {
+        self.mdx_error.as_ref()
+    }
+
+    ///
+    /// Play Sound.
+    ///
+    /// The loop count is the smallest one of the looping channels.
+    ///
+    pub fn play(&mut self, repeat: bool) -> usize {
+        while !self.sound_slot.is_stream_filled() && !self.mdx_end {
+            for _ in 0..self.remain_tick_count {
+                self.sound_slot.update(1);
+                self.remain_tick_count -= 1;
+                if self.sound_slot.is_stream_filled() {
+                    break;
+                }
+            }
+            if self.remain_tick_count == 0 {
+                match self.parse_mdx(repeat) {
+                    Ok(()) => self.remain_tick_count = self.clock_to_tick(),
+                    Err(error) => {
+                        // stop playback on broken data
+                        self.mdx_error = Some(error);
+                        self.mdx_end = true;
+                    }
+                }
+            };
+        }
+        self.sound_slot.stream();
+
+        if self.mdx_end {
+            usize::MAX
+        } else {
+            self.mdx_loop_count
+        }
+    }
+
+    ///
+    /// Initialize sound driver.
+    ///
+    fn init(&mut self) -> Result<(), Error> {
+        // parse mdx header
+        let (header, gd3) = mdxmeta::parse_mdx_meta(&self.mdx_data)?;
+        self.mdx_voice = mdxmeta::parse_mdx_voices(&self.mdx_data, header.voice_offset);
+        // FM A-H and ADPCM P (PCM8 extended channels are not played)
+        self.mdx_channel = header
+            .channel_offset
+            .iter()
+            .take(MDX_CHANNEL)
+            .map(|offset| MdxChannel::new(*offset))
+            .collect();
+
+        // add sound chip
+        self.sound_slot
+            .add_sound_device(SoundChipType::YM2151, 1, CLOCK_YM2151);
+        self.sound_slot
+            .add_sound_device(SoundChipType::OKIM6258, 1, CLOCK_OKIM6258);
+        self.sound_slot.write(
+            SoundChipType::OKIM6258,
+            0,
+            0x10, /* set_divider */
+            ADPCM_FREQUENCY[4].1,
+        );
+        self.sound_slot
+            .write(SoundChipType::OKIM6258, 0, 0x11 /* set_outbits */, 12);
+        self.sound_slot
+            .write(SoundChipType::OKIM6258, 0, 0x12 /* set_type */, 0);
+        // PDX sample is written to the data port
+        self.sound_slot
+            .add_data_stream(SoundChipType::OKIM6258, 0, ADPCM_STREAM, 0, 1);
+        self.mdx_header = Some(header);
+        self.mdx_gd3 = Some(gd3);
+
+        Ok(())
+    }
+
+    ///
+    /// Convert the OPM timer B period to the tick (with the remainder).
+    ///
+    fn clock_to_tick(&mut self) -> usize {
+        let tick =
+            1024 * (256 - self.mdx_tempo as u64) * MDX_TICK_RATE as u64 + self.tempo_remainder;
+        self.tempo_remainder = tick % CLOCK_YM2151 as u64;
+        (tick / CLOCK_YM2151 as u64) as usize
+    }
+
+    fn get_mdx_u8(&mut self, channel: usize) -> Result<u8, Error> {
+        let pos = self.mdx_channel[channel].pos;
+        let ret = match self.mdx_data.get(pos) {
+            Some(ret) => *ret,
+            None => return Err(Error::UnexpectedEof(pos)),
+        };
+        self.mdx_channel[channel].pos += 1;
+        Ok(ret)
+    }
+
+    fn get_mdx_i16(&mut self, channel: usize) -> Result<i16, Error> {
+        let high = self.get_mdx_u8(channel)?;
+        let low = self.get_mdx_u8(channel)?;
+        Ok(i16::from_be_bytes([high, low]))
+    }
+
+    ///
+    /// Relative position from the current position of the channel.
+    ///
+    fn get_mdx_offset(&self, channel: usize, offset: i16) -> Result<usize, Error> {
+        let pos = self.mdx_channel[channel].pos;
+        match pos.checked_add_signed(offset as isize) {
+            Some(target) if target < self.mdx_data.len() => Ok(target),
+            _ => Err(Error::BadOffset(pos)),
+        }
+    }
+
+    fn write_opm(&mut self, reg: u8, dat: u8) {
+        self.sound_slot
+            .write(SoundChipType::YM2151, 0, reg as u32, dat.into());
+    }
+
+    ///
+    /// Parse one clock of all channels.
+    ///
+    fn parse_mdx(&mut self, repeat: bool) -> Result<(), Error> {
+        for channel in 0..self.mdx_channel.len() {
+            self.parse_channel(channel, repeat)?;
+            self.update_channel(channel);
+        }
+        match self
+            .mdx_channel
+            .iter()
+            .filter(|channel| !channel.end)
+            .map(|channel| channel.loop_count)
+            .min()
+        {
+            Some(loop_count) => self.mdx_loop_count = loop_count,
+            None => self.mdx_end = true,
+        }
+        Ok(())
+    }
+
+    fn parse_channel(&mut self, channel: usize, repeat: bool) -> Result<(), Error> {
+        let state = &self.mdx_channel[channel];
+        if state.end || state.sync_wait || state.wait > 0 {
+            return Ok(());
+        }
+
+        let mut jump = 0;
+        loop {
+            let command = self.get_mdx_u8(channel)?;
+            match command {
+                0x00..=0x7f => {
+                    // rest (n + 1 clocks)
+                    self.key_off(channel);
+                    let state = &mut self.mdx_channel[channel];
+                    state.tie = false;
+                    state.gate = 0;
+                    state.wait = command as u32 + 1;
+                    break;
+                }
+                0x80..=0xdf => {
+                    // note (or ADPCM sample number) and n + 1 clocks
+                    let duration = self.get_mdx_u8(channel)? as u32 + 1;
+                    self.note_on(channel, command - 0x80, duration);
+                    break;
+                }
+                0xff => {
+                    // tempo (OPM timer B)
+                    self.mdx_tempo = self.get_mdx_u8(channel)?;
+                }
+                0xfe => {
+                    // OPM register write
+                    let reg = self.get_mdx_u8(channel)?;
+                    let dat = self.get_mdx_u8(channel)?;
+                    self.write_opm(reg, dat);
+                }
+                0xfd => {
+                    // voice
+                    let number = self.get_mdx_u8(channel)?;
+                    self.set_voice(channel, number);
+                }
+                0xfc => {
+                    // pan (bit 0: left, bit 1: right)
+                    let pan = self.get_mdx_u8(channel)? & 0x03;
+                    self.set_pan(channel, pan);
+                }
+                0xfb => {
+                    // volume (0 - 15 or 0x80 + total level)
+                    self.mdx_channel[channel].volume = self.get_mdx_u8(channel)?;
+                }
+                0xfa | 0xf9 => {
+                    // volume down / up
+                    let state = &mut self.mdx_channel[channel];
+                    let louder = command == 0xf9;
+                    state.volume = match (state.volume & 0x80 != 0, louder) {
+                        (true, true) => 0x80 | (state.volume & 0x7f).saturating_sub(1),
+                        (true, false) => 0x80 | ((state.volume & 0x7f) + 1).min(0x7f),
+                        (false, true) => (state.volume + 1).min(15),
+                        (false, false) => state.volume.saturating_sub(1),
+                    };
+                }
+                0xf8 => {
+                    // q (1 - 8 is the proportion, others are the clocks before the end)
+                    self.mdx_channel[channel].q = self.get_mdx_u8(channel)?;
+                }
+                0xf7 => {
+                    // legato (it is read with the note)
+                    self.mdx_channel[channel].tie = true;
+                }
+                0xf6 => {
+                    // repeat start (count and the work of the counter)
+                    let count = self.get_mdx_u8(channel)?;
+                    let pos = self.mdx_channel[channel].pos;
+                    match self.mdx_data.get_mut(pos) {
+                        Some(counter) => *counter = count,
+                        None => return Err(Error::UnexpectedEof(pos)),
+                    }
+                    self.mdx_channel[channel].pos += 1;
+                }
+                0xf5 => {
+                    // repeat end (offset to the repeat start)
+                    let offset = self.get_mdx_i16(channel)?;
+                    let target = self.get_mdx_offset(channel, offset)?;
+                    if target < 2 {
+                        return Err(Error::BadOffset(target));
+                    }
+                    self.mdx_data[target - 1] = self.mdx_data[target - 1].wrapping_sub(1);
+                    if self.mdx_data[target - 1] != 0 {
+                        self.mdx_channel[channel].pos = target;
+                    } else {
+                        // restore the counter for the next time
+                        self.mdx_data[target - 1] = self.mdx_data[target - 2];
+                    }
+                }
+                0xf4 => {
+                    // repeat escape (offset to the offset of the repeat end)
+                    let offset = self.get_mdx_i16(channel)?;
+                    let end = self.get_mdx_offset(channel, offset)?;
+                    let end_offset = match self.mdx_data.get(end..end + 2) {
+                        Some(end_offset) => i16::from_be_bytes([end_offset[0], end_offset[1]]),
+                        None => return Err(Error::BadOffset(end)),
+                    };
+                    let target = match (end + 2).checked_add_signed(end_offset as isize) {
+                        Some(target) if (2..self.mdx_data.len()).contains(&target) => target,
+                        _ => return Err(Error::BadOffset(end)),
+                    };
+                    if self.mdx_data[target - 1] == 1 {
+                        self.mdx_data[target - 1] = self.mdx_data[target - 2];
+                        self.mdx_channel[channel].pos = end + 2;
+                    }
+                }
+                0xf3 => {
+                    // detune (1/64 semitone)
+                    self.mdx_channel[channel].detune = self.get_mdx_i16(channel)? as i32;
+                }
+                0xf2 => {
+                    // portamento of the next note (1/16384 semitone per clock)
+                    self.mdx_channel[channel].portamento = self.get_mdx_i16(channel)? as i32;
+                }
+                0xf1 => {
+                    // end (0x00) or loop (offset to the loop point)
+                    let high = self.get_mdx_u8(channel)?;
+                    if high == 0x00 {
+                        self.end_channel(channel);
+                        break;
+                    }
+                    let low = self.get_mdx_u8(channel)?;
+                    let target = self.get_mdx_offset(channel, i16::from_be_bytes([high, low]))?;
+                    self.mdx_channel[channel].loop_count += 1;
+                    // loop without any wait is the end
+                    jump += 1;
+                    if !repeat || jump > 1 {
+                        self.end_channel(channel);
+                        break;
+                    }
+                    self.mdx_channel[channel].pos = target;
+                }
+                0xf0 => {
+                    // key on delay
+                    self.mdx_channel[channel].key_on_delay = self.get_mdx_u8(channel)?;
+                }
+                0xef => {
+                    // sync send
+                    let target = self.get_mdx_u8(channel)? as usize;
+                    if let Some(state) = self.mdx_channel.get_mut(target) {
+                        if state.sync_wait {
+                            state.sync_wait = false;
+                        } else {
+                            state.sync_received = true;
+                        }
+                    }
+                }
+                0xee => {
+                    // sync wait
+                    let state = &mut self.mdx_channel[channel];
+                    if state.sync_received {
+                        state.sync_received = false;
+                    } else {
+                        state.sync_wait = true;
+                        break;
+                    }
+                }
+                0xed => {
+                    // noise frequency (channel H) / ADPCM frequency (channel P)
+                    let frequency = self.get_mdx_u8(channel)?;
+                    match channel {
+                        NOISE_CHANNEL => self.write_opm(0x0f, frequency),
+                        ADPCM_CHANNEL => self.mdx_channel[channel].adpcm_frequency = frequency,
+                        _ => { /* nothing to do */ }
+                    }
+                }
+                0xec | 0xeb => {
+                    // pitch / amplitude LFO (0x80: off, 0x81: on, or wave, period and amplitude)
+                    let wave = self.get_mdx_u8(channel)?;
+                    let parameter = match wave {
+                        0x80 | 0x81 => None,
+                        _ => Some((
+                            self.get_mdx_i16(channel)? as u16,
+                            self.get_mdx_i16(channel)? as i32,
+                        )),
+                    };
+                    let state = &mut self.mdx_channel[channel];
+                    let lfo = if command == 0xec {
+                        &mut state.pitch_lfo
+                    } else {
+                        &mut state.amp_lfo
+                    };
+                    match parameter {
+                        None => lfo.enable = wave == 0x81,
+                        Some((period, amplitude)) => {
+                            *lfo = MdxLfo {
+                                wave: wave & 0x03,
+                                period,
+                                amplitude,
+                                enable: true,
+                                phase: 0,
+                            };
+                        }
+                    }
+                }
+                0xea => {
+                    // OPM LFO (0x80: off, 0x81: on, or sync/wave, LFRQ, PMD, AMD, PMS/AMS)
+                    let sync_wave = self.get_mdx_u8(channel)?;
+                    match sync_wave {
+                        0x80 => self.write_opm(0x38 + channel as u8, 0x00),
+                        0x81 => {
+                            let pms_ams = self.mdx_channel[channel].opm_lfo_pms_ams;
+                            self.write_opm(0x38 + channel as u8, pms_ams);
+                        }
+                        _ => {
+                            let lfrq = self.get_mdx_u8(channel)?;
+                            let pmd = self.get_mdx_u8(channel)?;
+                            let amd = self.get_mdx_u8(channel)?;
+                            let pms_ams = self.get_mdx_u8(channel)?;
+                            self.write_opm(0x1b, sync_wave & 0x03);
+                            self.write_opm(0x18, lfrq);
+                            self.write_opm(0x19, pmd | 0x80);
+                            self.write_opm(0x19, amd & 0x7f);
+                            self.write_opm(0x38 + channel as u8, pms_ams);
+                            let state = &mut self.mdx_channel[channel];
+                            state.opm_lfo_sync = sync_wave & 0x40 != 0;
+                            state.opm_lfo_pms_ams = pms_ams;
+                        }
+                    }
+                }
+                0xe9 => {
+                    // LFO delay
+                    self.mdx_channel[channel].lfo_delay = self.get_mdx_u8(channel)?;
+                }
+                0xe8 => {
+                    // PCM8 mode (not supported)
+                }
+                0xe7 => {
+                    // fade out (not supported)
+                    self.get_mdx_u8(channel)?;
+                    self.get_mdx_u8(channel)?;
+                }
+                _ => {
+                    return Err(Error::UnknownCommand(
+                        self.mdx_channel[channel].pos - 1,
+                        command,
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn end_channel(&mut self, channel: usize) {
+        self.key_off(channel);
+        self.mdx_channel[channel].end = true;
+    }
+
+    ///
+    /// Update the software LFO, portamento, key on delay and gate time (every clock).
+    ///
+    fn update_channel(&mut self, channel: usize) {
+        let state = &self.mdx_channel[channel];
+        if state.end || state.sync_wait {
+            return;
+        }
+        if channel == ADPCM_CHANNEL {
+            if state.adpcm_playing
+                && self
+                    .sound_slot
+                    .is_stop_data_stream(SoundChipType::OKIM6258, 0, ADPCM_STREAM)
+            {
+                self.sound_slot
+                    .write(SoundChipType::OKIM6258, 0, 0x00, 0x01 /* stop */);
+                self.mdx_channel[channel].adpcm_playing = false;
+            }
+            let state = &mut self.mdx_channel[channel];
+            state.wait = state.wait.saturating_sub(1);
+            return;
+        }
+
+        self.update_pitch(channel);
+        self.update_volume(channel);
+
+        let state = &mut self.mdx_channel[channel];
+        state.portamento_offset += state.portamento_delta;
+        if state.lfo_delay_count > 0 {
+            state.lfo_delay_count -= 1;
+        } else {
+            state.pitch_lfo.step();
+            state.amp_lfo.step();
+        }
+        state.wait = state.wait.saturating_sub(1);
+        if state.key_on_delay_count > 0 {
+            state.key_on_delay_count -= 1;
+            if state.key_on_delay_count == 0 {
+                self.key_on(channel);
+            }
+        }
+        let state = &mut self.mdx_channel[channel];
+        if state.gate > 0 {
+            state.gate -= 1;
+            if state.gate == 0 {
+                self.key_off(channel);
+            }
+        }
+    }
+
+    fn note_on(&mut self, channel: usize, note: u8, duration: u32) {
+        // legato follows the note
+        let pos = self.mdx_channel[channel].pos;
+        let tie = self.mdx_data.get(pos) == Some(&0xf7);
+        let state = &mut self.mdx_channel[channel];
+        if tie {
+            state.pos += 1;
+        }
+        state.wait = duration;
+        state.gate = match state.q {
+            _ if tie => 0,
+            1..=8 => (duration * state.q as u32 / 8).max(1),
+            _ => duration.saturating_sub(256 - state.q as u32).max(1),
+        };
+
+        if channel == ADPCM_CHANNEL {
+            self.adpcm_key_on(note);
+            return;
+        }
+
+        let legato = state.tie;
+        state.tie = tie;
+        state.note = note;
+        state.portamento_delta = std::mem::take(&mut state.portamento);
+        state.portamento_offset = 0;
+        if !legato {
+            state.pitch_lfo.phase = 0;
+            state.amp_lfo.phase = 0;
+            state.lfo_delay_count = state.lfo_delay as u32;
+            self.key_off(channel);
+        }
+        self.update_pitch(channel);
+        self.update_volume(channel);
+        if !legato {
+            let state = &mut self.mdx_channel[channel];
+            if state.key_on_delay > 0 {
+                state.key_on_delay_count = state.key_on_delay as u32;
+            } else {
+                self.key_on(channel);
+            }
+        }
+    }
+
+    fn key_on(&mut self, channel: usize) {
+        let state = &self.mdx_channel[channel];
+        let slot_mask = match state.voice {
+            Some(voice) => self.mdx_voice[voice].slot_mask & 0x0f,
+            None => 0x0f,
+        };
+        if state.opm_lfo_sync {
+            // reset the LFO phase
+            self.write_opm(0x01, 0x02);
+            self.write_opm(0x01, 0x00);
+        }
+        self.write_opm(0x08, slot_mask << 3 | channel as u8);
+    }
+
+    fn key_off(&mut self, channel: usize) {
+        if channel != ADPCM_CHANNEL {
+            self.write_opm(0x08, channel as u8);
+        }
+    }
+
+    fn adpcm_key_on(&mut self, sample: u8) {
+        let sample = sample as usize;
+        match self.sound_slot.get_data_block(sample) {
+            Some(data_block) if !data_block.is_empty() => {}
+            _ => return,
+        }
+        let state = &mut self.mdx_channel[ADPCM_CHANNEL];
+        let (clock, divider) =
+            ADPCM_FREQUENCY[(state.adpcm_frequency as usize).min(ADPCM_FREQUENCY.len() - 1)];
+        let pan = Self::adpcm_pan(state.pan);
+        state.adpcm_playing = true;
+
+        self.sound_slot
+            .write(SoundChipType::OKIM6258, 0, 0x00, 0x01 /* stop */);
+        self.sound_slot
+            .stop_data_stream(SoundChipType::OKIM6258, 0, ADPCM_STREAM);
+        self.sound_slot.write(
+            SoundChipType::OKIM6258,
+            0,
+            0x10, /* set_divider */
+            divider,
+        );
+        self.sound_slot
+            .write(SoundChipType::OKIM6258, 0, 0x13 /* set_clock */, clock);
+        self.sound_slot.write(SoundChipType::OKIM6258, 0, 0x02, pan);
+        // 2 samples per byte
+        self.sound_slot.set_data_stream_frequency(
+            SoundChipType::OKIM6258,
+            0,
+            ADPCM_STREAM,
+            clock / ADPCM_DIVIDER[divider as usize] / 2,
+        );
+        self.sound_slot.attach_data_block_to_stream(
+            SoundChipType::OKIM6258,
+            0,
+            ADPCM_STREAM,
+            sample,
+            0,
+            1,
+        );
+        self.sound_slot
+            .write(SoundChipType::OKIM6258, 0, 0x00, 0x02 /* play */);
+        self.sound_slot.start_data_stream(
+            SoundChipType::OKIM6258,
+            0,
+            ADPCM_STREAM,
+            Some(0),
+            LENGTH_MODE_TO_END,
+            0,
+        );
+    }
+
+    ///
+    /// MDX pan (bit 0: left, bit 1: right) to the output channel of OKIM6258.
+    ///
+    fn adpcm_pan(pan: u8) -> u32 {
+        match pan {
+            1 => 1, /* left */
+            2 => 2, /* right */
+            3 => 0, /* stereo */
+            _ => 3, /* mute */
+        }
+    }
+
+    fn set_pan(&mut self, channel: usize, pan: u8) {
+        self.mdx_channel[channel].pan = pan;
+        if channel == ADPCM_CHANNEL {
+            self.sound_slot
+                .write(SoundChipType::OKIM6258, 0, 0x02, Self::adpcm_pan(pan));
+        } else if let Some(voice) = self.mdx_channel[channel].voice {
+            let fl_con = self.mdx_voice[voice].fl_con & 0x3f;
+            self.write_opm(0x20 + channel as u8, pan << 6 | fl_con);
+        }
+    }
+
+    fn set_voice(&mut self, channel: usize, number: u8) {
+        if channel == ADPCM_CHANNEL {
+            return;
+        }
+        let voice = match self
+            .mdx_voice
+            .iter()
+            .position(|voice| voice.number == number)
+        {
+            Some(voice) => voice,
+            None => return,
+        };
+        let state = &mut self.mdx_channel[channel];
+        state.voice = Some(voice);
+        state.last_attenuation = None;
+        let pan = state.pan;
+        let v = &self.mdx_voice[voice];
+        let mut registers = vec![(0x20 + channel as u8, pan << 6 | (v.fl_con & 0x3f))];
+        for op in 0..4 {
+            let slot = channel as u8 + op as u8 * 8;
+            registers.push((0x40 + slot, v.dt1_mul[op]));
+            registers.push((0x60 + slot, v.tl[op]));
+            registers.push((0x80 + slot, v.ks_ar[op]));
+            registers.push((0xa0 + slot, v.ame_d1r[op]));
+            registers.push((0xc0 + slot, v.dt2_d2r[op]));
+            registers.push((0xe0 + slot, v.d1l_rr[op]));
+        }
+        for (reg, dat) in registers {
+            self.write_opm(reg, dat);
+        }
+        self.update_volume(channel);
+    }
+
+    ///
+    /// Write the key code and key fraction (note, detune, portamento and pitch LFO).
+    ///
+    fn update_pitch(&mut self, channel: usize) {
+        let state = &mut self.mdx_channel[channel];
+        let pitch = ((state.note as i32 + NOTE_KEY_CODE_OFFSET) * 64
+            + state.detune
+            + (state.portamento_offset >> 8)
+            + (state.pitch_lfo.value() >> 8))
+            .clamp(0, 8 * 12 * 64 - 1);
+        if state.last_pitch == Some(pitch) {
+            return;
+        }
+        state.last_pitch = Some(pitch);
+        let semitone = (pitch >> 6) as usize;
+        let key_code = ((semitone / 12) as u8) << 4 | KEY_CODE[semitone % 12];
+        self.write_opm(0x28 + channel as u8, key_code);
+        self.write_opm(0x30 + channel as u8, ((pitch & 0x3f) << 2) as u8);
+    }
+
+    ///
+    /// Write the total level of the carrier (volume and amplitude LFO).
+    ///
+    fn update_volume(&mut self, channel: usize) {
+        let state = &mut self.mdx_channel[channel];
+        let voice = match state.voice {
+            Some(voice) => &self.mdx_voice[voice],
+            None => return,
+        };
+        let attenuation = state.attenuation();
+        if state.last_attenuation == Some(attenuation) {
+            return;
+        }
+        state.last_attenuation = Some(attenuation);
+        let carrier = CARRIER[(voice.fl_con & 0x07) as usize];
+        let tl = voice.tl;
+        for (op, tl) in tl.iter().enumerate() {
+            if carrier & (1 << op) != 0 {
+                let tl = (*tl as u32 + attenuation).min(0x7f) as u8;
+                self.write_opm(0x60 + (channel + op * 8) as u8, tl);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sound::{SoundEvent, SoundSlot};
+
+    use super::{Error, MdxLfo, MdxPlay, MDX_TICK_RATE};
+
+    const MAX_SAMPLE_SIZE: usize = 2048;
+
+    fn mdx(channel_a: &[u8]) -> Vec<u8> {
+        let mut mdx = b"test\r\n\x1a\0".to_vec();
+        // voice, A, B-H and P
+        mdx.extend_from_slice(&[0x00, 0x21, 0x00, 0x14]);
+        for _ in 0..7 {
+            mdx.extend_from_slice(&[0x00, 0x1f]);
+        }
+        mdx.extend_from_slice(&[0x00, 0x1b]);
+        // 7 bytes of channel A
+        mdx.extend_from_slice(channel_a);
+        // ADPCM sample 0 (48 clocks) and end
+        mdx.extend_from_slice(&[0x80, 0x2f, 0xf1, 0x00]);
+        // end
+        mdx.extend_from_slice(&[0xf1, 0x00]);
+        // voice 1 (algorithm 7)
+        mdx.extend_from_slice(&[0x01, 0x07, 0x0f]);
+        mdx.extend_from_slice(&[0x1f; 24]);
+        mdx
+    }
+
+    #[test]
+    fn mdx_1() {
+        // voice 1, o4 a (48 clocks) and loop to the note
+        let mdx = mdx(&[0xfd, 0x01, 0xb6, 0x2f, 0xf1, 0xff, 0xfb]);
+        let mut pdx = vec![0; 96 * 8];
+        pdx[2] = 0x03;
+        pdx[7] = 0x04;
+        pdx.extend_from_slice(&[0x08, 0x80, 0x08, 0x80]);
+        let sound_slot = || SoundSlot::new(MDX_TICK_RATE, 44100, MAX_SAMPLE_SIZE);
+
+        // 48 clocks of tempo 200 (30346 samples)
+        let mut mdxplay = MdxPlay::new(sound_slot(), &mdx).unwrap();
+        mdxplay.add_pdx(&pdx).unwrap();
+        assert_eq!(mdxplay.get_mdx_meta().1.track_name, "test");
+        let mut chunk = 1;
+        while mdxplay.play(true) == 0 {
+            chunk += 1;
+        }
+        assert_eq!(chunk, 30346 / MAX_SAMPLE_SIZE + 1);
+        assert_eq!(mdxplay.play(true), 1);
+        assert_eq!(mdxplay.get_error(), None);
+
+        let mut mdxplay = MdxPlay::new(sound_slot(), &mdx).unwrap();
+        let mut chunk = 1;
+        while mdxplay.play(false) != usize::MAX {
+            chunk += 1;
+        }
+        assert_eq!(chunk, 30346 / MAX_SAMPLE_SIZE + 1);
+        assert_eq!(mdxplay.get_error(), None);
+    }
+
+    #[test]
+    fn key_code() {
+        // 0x80 is o0 d+ (note, OPM key code)
+        for (note, key_code) in [(0x80, 0x02), (0x89, 0x0e), (0x8a, 0x10), (0xb6, 0x4a)] {
+            let mdx = mdx(&[0xfd, 0x01, note, 0x2f, 0xf1, 0x00, 0x00]);
+            let mut mdxplay =
+                MdxPlay::new(SoundSlot::new(MDX_TICK_RATE, 44100, MAX_SAMPLE_SIZE), &mdx)
+                    .unwrap();
+            mdxplay.sound_slot.start_record();
+            mdxplay.play(false);
+            let sound_log = mdxplay.sound_slot.stop_record().unwrap();
+            let written: Vec<u32> = sound_log
+                .event
+                .iter()
+                .filter_map(|(_, sound_event)| match sound_event {
+                    SoundEvent::Write {
+                        port: 0x28, data, ..
+                    } => Some(*data),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(written, vec![key_code]);
+        }
+    }
+
+    #[test]
+    fn lfo_overflow() {
+        // 16-bit amplitude and phase
+        let lfo = MdxLfo {
+            wave: 2,
+            period: 0xffff,
+            amplitude: i16::MAX as i32,
+            enable: true,
+            phase: 0x3000,
+        };
+        assert_eq!(lfo.value(), 24575);
+        let lfo = MdxLfo { wave: 0, ..lfo };
+        assert_eq!(lfo.value(), -20480);
+    }
+
+    #[test]
+    fn broken_1() {
+        // unknown command
+        let mdx = mdx(&[0xfd, 0x01, 0xe0, 0x00, 0x00, 0x00, 0x00]);
+        let mut mdxplay =
+            MdxPlay::new(SoundSlot::new(MDX_TICK_RATE, 44100, MAX_SAMPLE_SIZE), &mdx).unwrap();
+        assert_eq!(mdxplay.play(true), usize::MAX);
+        assert_eq!(
+            mdxplay.get_error(),
+            Some(&Error::UnknownCommand(0x08 + 0x16, 0xe0))
+        );
+    }
+}
@@ -0,0 +1,328 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use flate2::read::GzDecoder;
+use std::io::{self, prelude::*, BufReader};
+use std::path::Path;
+
+use crate::driver::error::Error;
+use crate::driver::gd3meta::{parse_gd3, Gd3};
+use crate::driver::meta::Jsonlize;
+use crate::driver::vgmmeta;
+use crate::driver::vgmplay::{VgmPlay, VGM_TICK_RATE};
+use crate::driver::xgmmeta;
+use crate::driver::xgmplay::XgmPlay;
+use crate::driver::zipmeta::{self, ZipEntry};
+use crate::sound::SoundSlot;
+
+/// VGM header or XGM header with the sample table (for the listing)
+const PACK_HEADER_SIZE: u64 = 0x104;
+/// GD3 tag (for the listing)
+const PACK_GD3_SIZE_MAX: u64 = 0x10000;
+
+///
+/// Format of the pack entry
+///
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackFormat {
+    Vgm,
+    Xgm,
+}
+
+///
+/// Pack entry
+///
+/// length_ms and loop_length_ms are from the VGM header (XGM has no length in the header).
+///
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PackEntry {
+    pub file_name: String,
+    pub format: PackFormat,
+    pub title: String,
+    pub length_ms: Option<u32>,
+    pub loop_length_ms: Option<u32>,
+}
+
+///
+/// VGM/VGZ/XGM entries of the pack (in file name order)
+///
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Pack {
+    pub entries: Vec<PackEntry>,
+}
+
+///
+/// Pack Reader
+///
+/// Reads ZIP archive of the music files (as distributed by vgmrips).
+/// Files other than VGM/VGZ/XGM (images, text, m3u or broken headers) are not listed.
+/// The listing reads only the header and GD3, the data is checked when the entry is opened.
+///
+pub struct PackReader {
+    pack_data: Vec<u8>,
+    pack: Pack,
+    pack_zip_entries: Vec<ZipEntry>,
+}
+
+#[allow(dead_code)]
+impl PackReader {
+    ///
+    /// Create pack reader.
+    ///
+    pub fn new(zip_file: &[u8]) -> Result<Self, Error> {
+        let mut zip_entries = zipmeta::parse_zip_meta(zip_file)?;
+        zip_entries.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+
+        let mut pack = Pack::default();
+        let mut pack_zip_entries = Vec::new();
+        for zip_entry in zip_entries {
+            let extension = Path::new(&zip_entry.file_name)
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .map(|extension| extension.to_lowercase());
+            if !matches!(
+                extension.as_deref(),
+                Some("vgm") | Some("vgz") | Some("xgm") | Some("xgz")
+            ) {
+                continue;
+            }
+            // only the header and GD3 are read for the listing
+            let reader = match zipmeta::open_zip_entry(zip_file, &zip_entry) {
+                Ok(reader) => reader,
+                Err(_) => continue,
+            };
+            if let Some(entry) = Self::parse_entry(&zip_entry.file_name, reader) {
+                pack.entries.push(entry);
+                pack_zip_entries.push(zip_entry);
+            }
+        }
+        if pack.entries.is_empty() {
+            return Err(Error::NotFound(String::from("*.vgm/*.vgz/*.xgm")));
+        }
+
+        Ok(PackReader {
+            pack_data: zip_file.to_vec(),
+            pack,
+            pack_zip_entries,
+        })
+    }
+
+    ///
+    /// Get pack entries.
+    ///
+    pub fn get_pack(&self) -> &Pack {
+        &self.pack
+    }
+
+    ///
+    /// Get pack entries JSON.
+    ///
+    pub fn get_pack_json(&self) -> String {
+        self.pack.get_json()
+    }
+
+    ///
+    /// Read the file of the entry.
+    ///
+    pub fn read_entry(&self, index: usize) -> Result<Vec<u8>, Error> {
+        match self.pack_zip_entries.get(index) {
+            Some(zip_entry) => zipmeta::read_zip_entry(&self.pack_data, zip_entry),
+            None => Err(Error::NotFound(format!("pack entry {index}"))),
+        }
+    }
+
+    ///
+    /// Open the VGM/VGZ entry.
+    ///
+    pub fn open_vgm(&self, index: usize, sound_slot: SoundSlot) -> Result<VgmPlay, Error> {
+        VgmPlay::new(sound_slot, &self.read_entry(index)?)
+    }
+
+    ///
+    /// Open the XGM entry.
+    ///
+    pub fn open_xgm(&self, index: usize, sound_slot: SoundSlot) -> Result<XgmPlay, Error> {
+        XgmPlay::new(sound_slot, &self.read_entry(index)?)
+    }
+
+    fn parse_entry(file_name: &str, reader: impl Read) -> Option<PackEntry> {
+        let mut reader = BufReader::new(reader);
+        let mut reader: Box<dyn Read> = if reader.fill_buf().ok()?.starts_with(&[0x1f, 0x8b]) {
+            Box::new(GzDecoder::new(reader))
+        } else {
+            Box::new(reader)
+        };
+        let mut data = Vec::new();
+        reader
+            .by_ref()
+            .take(PACK_HEADER_SIZE)
+            .read_to_end(&mut data)
+            .ok()?;
+
+        let (format, gd3, length_ms, loop_length_ms) = if data.starts_with(b"Vgm ") {
+            // GD3 is moved to the end of the header (music data is skipped)
+            let offset_gd3 = u32::from_le_bytes(data.get(0x14..0x18)?.try_into().unwrap());
+            let gd3_pos = offset_gd3 as u64 + 0x14;
+            if offset_gd3 != 0 && gd3_pos >= data.len() as u64 {
+                if Self::skip(&mut reader, gd3_pos - data.len() as u64) {
+                    let offset_gd3 = data.len() as u32 - 0x14;
+                    data[0x14..0x18].copy_from_slice(&offset_gd3.to_le_bytes());
+                    Self::read_gd3(&mut reader, &mut data);
+                }
+            } else if offset_gd3 != 0 {
+                // GD3 is in the middle of the header
+                Self::read_gd3(&mut reader, &mut data);
+            }
+            let (header, gd3) = vgmmeta::parse_vgm_meta(&data).ok()?;
+            let ms = |samples: u32| (samples as u64 * 1000 / VGM_TICK_RATE as u64) as u32;
+            let loop_length_ms = (header.offset_loop != 0).then(|| ms(header.loop_samples));
+            (
+                PackFormat::Vgm,
+                gd3,
+                Some(ms(header.total_samples)),
+                loop_length_ms,
+            )
+        } else if data.starts_with(b"XGM ") && data.len() as u64 == PACK_HEADER_SIZE {
+            // sample data bloc size, version and flags follow the sample table
+            let sample_data_bloc_size = u16::from_le_bytes(data[0x100..0x102].try_into().unwrap());
+            let (version, flags) = (data[0x102], data[0x103]);
+            if version > 1 {
+                return None;
+            }
+            let mut gd3 = Gd3::default();
+            let mut music_data_bloc_size = [0; 4];
+            if flags & 0b00000010 != 0
+                && Self::skip(&mut reader, sample_data_bloc_size as u64 * 256)
+                && reader.read_exact(&mut music_data_bloc_size).is_ok()
+            {
+                let track_table = if flags & 0b00000100 != 0 {
+                    xgmmeta::XGM_TRACK_MAX as u64 * 4
+                } else {
+                    0
+                };
+                let music_data_bloc_size = u32::from_le_bytes(music_data_bloc_size) as u64;
+                let mut data = Vec::new();
+                if Self::skip(&mut reader, track_table + music_data_bloc_size)
+                    && Self::read_gd3(&mut reader, &mut data)
+                {
+                    if let Ok((_, tag)) = parse_gd3(&data) {
+                        gd3 = tag;
+                    }
+                }
+            }
+            (PackFormat::Xgm, gd3, None, None)
+        } else {
+            return None;
+        };
+        let title = if gd3.track_name.is_empty() {
+            gd3.track_name_j
+        } else {
+            gd3.track_name
+        };
+
+        Some(PackEntry {
+            file_name: String::from(file_name),
+            format,
+            title,
+            length_ms,
+            loop_length_ms,
+        })
+    }
+
+    fn skip(reader: &mut impl Read, length: u64) -> bool {
+        matches!(io::copy(&mut reader.take(length), &mut io::sink()), Ok(skip) if skip == length)
+    }
+
+    fn read_gd3(reader: &mut impl Read, data: &mut Vec<u8>) -> bool {
+        reader.take(PACK_GD3_SIZE_MAX).read_to_end(data).is_ok()
+    }
+}
+
+impl Jsonlize for Pack {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, PackFormat, PackReader};
+    use crate::driver::gd3meta::{serialize_gd3, Gd3};
+    use crate::driver::vgmmeta::replace_vgm_gd3;
+    use crate::driver::vgmplay::VGM_TICK_RATE;
+    use crate::driver::zipmeta::tests::zip;
+    use crate::sound::SoundSlot;
+
+    ///
+    /// VGM of the wait (samples) with GD3
+    ///
+    fn vgm(wait: u16, repeat: bool, title: &str, compress: bool) -> Vec<u8> {
+        let mut vgm = vec![0; 0x40];
+        vgm[0..4].copy_from_slice(b"Vgm ");
+        vgm[0x08..0x0c].copy_from_slice(&0x150_u32.to_le_bytes());
+        vgm[0x18..0x1c].copy_from_slice(&(wait as u32).to_le_bytes());
+        vgm[0x34..0x38].copy_from_slice(&0x0c_u32.to_le_bytes());
+        if repeat {
+            vgm[0x1c..0x20].copy_from_slice(&(0x40 - 0x1c_u32).to_le_bytes());
+            vgm[0x20..0x24].copy_from_slice(&(wait as u32).to_le_bytes());
+        }
+        // 1/100 sec waits (GD3 of the long file is after the header)
+        for _ in 0..wait / 441 {
+            vgm.push(0x61);
+            vgm.extend_from_slice(&441_u16.to_le_bytes());
+        }
+        vgm.push(0x66);
+        let gd3 = Gd3 {
+            track_name: String::from(title),
+            ..Default::default()
+        };
+        replace_vgm_gd3(&vgm, &gd3, compress).unwrap()
+    }
+
+    ///
+    /// XGM (empty sample table) with GD3
+    ///
+    fn xgm(title: &str) -> Vec<u8> {
+        let mut xgm = b"XGM ".to_vec();
+        for _ in 0..63 {
+            xgm.extend_from_slice(&[0xff, 0xff, 0x01, 0x00]);
+        }
+        xgm.extend_from_slice(&[0x00, 0x00, 0x01, 0b00000010]);
+        xgm.extend_from_slice(&2_u32.to_le_bytes());
+        xgm.extend_from_slice(&[0x00, 0x7f]);
+        xgm.extend_from_slice(&serialize_gd3(&Gd3 {
+            track_name: String::from(title),
+            ..Default::default()
+        }));
+        xgm
+    }
+
+    #[test]
+    fn pack_1() {
+        let pack = zip(&[
+            ("02 Stage.vgz", &vgm(44100, true, "Stage", true)),
+            ("01 Title.vgm", &vgm(22050, false, "Title", false)),
+            ("03 Sound.xgm", &xgm("Sound")),
+            ("Pack.txt", b"not music"),
+        ]);
+        let packreader = PackReader::new(&pack).unwrap();
+        let entries = &packreader.get_pack().entries;
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].file_name, "01 Title.vgm");
+        assert_eq!(entries[0].format, PackFormat::Vgm);
+        assert_eq!(entries[0].title, "Title");
+        assert_eq!(entries[0].length_ms, Some(500));
+        assert_eq!(entries[0].loop_length_ms, None);
+        assert_eq!(entries[1].title, "Stage");
+        assert_eq!(entries[1].length_ms, Some(1000));
+        assert_eq!(entries[1].loop_length_ms, Some(1000));
+        assert_eq!(entries[2].format, PackFormat::Xgm);
+        assert_eq!(entries[2].title, "Sound");
+        assert_eq!(entries[2].length_ms, None);
+
+        let vgmplay = packreader
+            .open_vgm(1, SoundSlot::new(VGM_TICK_RATE, 44100, 2048))
+            .unwrap();
+        assert_eq!(vgmplay.get_vgm_meta().1.track_name, "Stage");
+        assert_eq!(
+            packreader.read_entry(3).unwrap_err(),
+            Error::NotFound(String::from("pack entry 3"))
+        );
+        assert!(PackReader::new(&zip(&[("Pack.txt", b"not music")])).is_err());
+    }
+}
@@ -0,0 +1,394 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::driver::error::Error;
+use crate::driver::m3umeta::{self, Playlist, PlaylistEntry};
+use crate::driver::meta::Jsonlize;
+use crate::driver::vgmplay::{VgmPlay, VGM_TICK_RATE};
+use crate::driver::xgm2play::Xgm2Play;
+use crate::driver::xgmplay::{XgmPlay, XGM_NTSC_TICK_RATE};
+use crate::sound::{convert_sample_f2i, SoundSlot};
+
+/// Fade out of the looped track (ms)
+const DEFAULT_FADE_MS: u32 = 5000;
+
+///
+/// Track driver of the playlist
+///
+enum PlaylistTrack {
+    Vgm(Box<VgmPlay>),
+    Xgm(Box<XgmPlay>),
+    Xgm2(Box<Xgm2Play>),
+}
+
+impl PlaylistTrack {
+    fn play(&mut self, repeat: bool) -> usize {
+        match self {
+            PlaylistTrack::Vgm(vgmplay) => vgmplay.play(repeat),
+            PlaylistTrack::Xgm(xgmplay) => xgmplay.play(repeat),
+            PlaylistTrack::Xgm2(xgm2play) => xgm2play.play(repeat),
+        }
+    }
+
+    fn get_output_sampling(&self) -> (&[f32], &[f32]) {
+        match self {
+            PlaylistTrack::Vgm(vgmplay) => vgmplay.get_sound_slot().get_output_sampling(),
+            PlaylistTrack::Xgm(xgmplay) => xgmplay.get_sound_slot().get_output_sampling(),
+            PlaylistTrack::Xgm2(xgm2play) => xgm2play.get_sound_slot().get_output_sampling(),
+        }
+    }
+}
+
+///
+/// Playlist Driver
+///
+/// Plays VGM/VGZ/XGM entries of M3U/M3U8 in order without gaps between the tracks.
+/// The files of the entries are added by the file name written in the playlist.
+///
+pub struct PlaylistPlay {
+    output_sampling_rate: u32,
+    output_sample_chunk_size: usize,
+    output_sampling_l: Vec<f32>,
+    output_sampling_r: Vec<f32>,
+    output_sampling_s16le: Vec<i16>,
+    playlist: Playlist,
+    playlist_file: HashMap<String, Vec<u8>>,
+    playlist_end: bool,
+    playlist_error: Option<Error>,
+    ym2608_rhythm_rom: Vec<u8>,
+    loop_count: usize,
+    fade_ms: u32,
+    track_index: usize,
+    track: Option<PlaylistTrack>,
+    track_end: bool,
+    track_sampling_l: Vec<f32>,
+    track_sampling_r: Vec<f32>,
+    track_sampling_pos: usize,
+    track_sample: usize,
+    fade_start: Option<usize>,
+    fade_length: usize,
+}
+
+#[allow(dead_code)]
+impl PlaylistPlay {
+    ///
+    /// Create playlist driver.
+    ///
+    pub fn new(
+        output_sampling_rate: u32,
+        output_sample_chunk_size: usize,
+        m3u_file: &[u8],
+    ) -> Result<Self, Error> {
+        Ok(PlaylistPlay {
+            output_sampling_rate,
+            output_sample_chunk_size,
+            output_sampling_l: vec![0_f32; output_sample_chunk_size],
+            output_sampling_r: vec![0_f32; output_sample_chunk_size],
+            output_sampling_s16le: vec![0; output_sample_chunk_size * 2],
+            playlist: m3umeta::parse_m3u_meta(m3u_file)?,
+            playlist_file: HashMap::new(),
+            playlist_end: false,
+            playlist_error: None,
+            ym2608_rhythm_rom: Vec::new(),
+            loop_count: 1,
+            fade_ms: DEFAULT_FADE_MS,
+            track_index: 0,
+            track: None,
+            track_end: false,
+            track_sampling_l: Vec::new(),
+            track_sampling_r: Vec::new(),
+            track_sampling_pos: 0,
+            track_sample: 0,
+            fade_start: None,
+            fade_length: 0,
+        })
+    }
+
+    ///
+    /// Add the file of the entry (file name as written in the playlist).
+    ///
+    pub fn add_file(&mut self, file_name: &str, file: &[u8]) {
+        self.playlist_file
+            .insert(file_name.replace('\\', "/"), file.to_vec());
+    }
+
+    ///
+    /// Add YM2608 internal rhythm ROM (for VGM).
+    ///
+    pub fn add_ym2608_rhythm_rom(&mut self, rom: &[u8]) {
+        self.ym2608_rhythm_rom = rom.to_vec();
+    }
+
+    ///
+    /// Set the loop count of the entry without the override (default is 1).
+    ///
+    pub fn set_default_loop_count(&mut self, loop_count: usize) {
+        self.loop_count = loop_count;
+    }
+
+    ///
+    /// Set the fade out (ms) of the entry without the override (default is 5000).
+    ///
+    pub fn set_default_fade(&mut self, fade_ms: u32) {
+        self.fade_ms = fade_ms;
+    }
+
+    ///
+    /// Get playlist.
+    ///
+    pub fn get_playlist(&self) -> &Playlist {
+        &self.playlist
+    }
+
+    ///
+    /// Get playlist JSON.
+    ///
+    pub fn get_playlist_json(&self) -> String {
+        self.playlist.get_json()
+    }
+
+    ///
+    /// Get the index of the playing entry.
+    ///
+    pub fn get_track_index(&self) -> usize {
+        self.track_index
+    }
+
+    ///
+    /// Get the last error of the skipped entry.
+    ///
+    pub fn get_error(&self) -> Option<&Error> {
+        self.playlist_error.as_ref()
+    }
+
+    ///
+    /// Return sampling_l buffer referance.
+    ///
+    pub fn get_sampling_l_ref(&self) -> *const f32 {
+        self.output_sampling_l.as_ptr()
+    }
+
+    ///
+    /// Return sampling buffer referance.
+    ///
+    pub fn get_sampling_r_ref(&self) -> *const f32 {
+        self.output_sampling_r.as_ptr()
+    }
+
+    ///
+    /// Return s16le sampling buffer referance.
+    ///
+    pub fn get_output_sampling_s16le_ref(&mut self) -> *const i16 {
+        for i in 0..self.output_sample_chunk_size {
+            self.output_sampling_s16le[i * 2] = convert_sample_f2i(self.output_sampling_l[i]);
+            self.output_sampling_s16le[i * 2 + 1] = convert_sample_f2i(self.output_sampling_r[i]);
+        }
+        self.output_sampling_s16le.as_ptr()
+    }
+
+    ///
+    /// Play Sound.
+    ///
+    /// The next track continues in the same chunk. Broken or missing entries are skipped.
+    /// At the end of the playlist, the std::usize::MAX value is returned.
+    ///
+    pub fn play(&mut self) -> usize {
+        let mut pos = 0;
+        while pos < self.output_sample_chunk_size && !self.playlist_end {
+            if self.track_sampling_pos >= self.track_sampling_l.len() {
+                self.play_track();
+                continue;
+            }
+            let gain = self.fade_gain();
+            self.output_sampling_l[pos] = self.track_sampling_l[self.track_sampling_pos] * gain;
+            self.output_sampling_r[pos] = self.track_sampling_r[self.track_sampling_pos] * gain;
+            self.track_sampling_pos += 1;
+            self.track_sample += 1;
+            pos += 1;
+            if let Some(fade_start) = self.fade_start {
+                if self.track_sample >= fade_start + self.fade_length {
+                    self.next_track();
+                }
+            }
+        }
+        self.output_sampling_l[pos..].fill(0_f32);
+        self.output_sampling_r[pos..].fill(0_f32);
+
+        if self.playlist_end {
+            usize::MAX
+        } else {
+            0
+        }
+    }
+
+    ///
+    /// Play the chunk of the track (open the entry if needed).
+    ///
+    fn play_track(&mut self) {
+        if self.track_end {
+            self.next_track();
+        }
+        if self.track.is_none() {
+            let entry = match self.playlist.entries.get(self.track_index) {
+                Some(entry) => entry.clone(),
+                None => {
+                    self.playlist_end = true;
+                    return;
+                }
+            };
+            match self.open_track(&entry) {
+                Ok(track) => self.track = Some(track),
+                Err(error) => {
+                    // skip the entry
+                    self.playlist_error = Some(error);
+                    self.track_index += 1;
+                    return;
+                }
+            }
+            let rate = self.output_sampling_rate as u64;
+            self.fade_length =
+                (entry.fade_ms.unwrap_or(self.fade_ms) as u64 * rate / 1000) as usize;
+            self.fade_start = entry
+                .length_ms
+                .map(|length_ms| (length_ms as u64 * rate / 1000) as usize);
+        }
+
+        let entry_loop_count = self.playlist.entries[self.track_index]
+            .loop_count
+            .unwrap_or(self.loop_count);
+        let track = self.track.as_mut().unwrap();
+        let loop_count = track.play(true);
+        let (sampling_l, sampling_r) = track.get_output_sampling();
+        self.track_sampling_l.clear();
+        self.track_sampling_l.extend_from_slice(sampling_l);
+        self.track_sampling_r.clear();
+        self.track_sampling_r.extend_from_slice(sampling_r);
+        self.track_sampling_pos = 0;
+        if loop_count == usize::MAX {
+            // the next track starts after this chunk
+            self.track_end = true;
+        } else if self.fade_start.is_none() && loop_count >= entry_loop_count {
+            // fade out after this chunk
+            self.fade_start = Some(self.track_sample + self.track_sampling_l.len());
+        }
+    }
+
+    fn open_track(&self, entry: &PlaylistEntry) -> Result<PlaylistTrack, Error> {
+        let file = match self.playlist_file.get(&entry.file_name) {
+            Some(file) => file,
+            None => {
+                // file name case of the playlist is not reliable
+                let file_name = entry.file_name.to_lowercase();
+                match self
+                    .playlist_file
+                    .iter()
+                    .find(|(name, _)| name.to_lowercase() == file_name)
+                {
+                    Some((_, file)) => file,
+                    None => return Err(Error::NotFound(entry.file_name.clone())),
+                }
+            }
+        };
+        let extension = Path::new(&entry.file_name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        let sound_slot = |tick_rate| {
+            SoundSlot::new(
+                tick_rate,
+                self.output_sampling_rate,
+                self.output_sample_chunk_size,
+            )
+        };
+        match extension.as_deref() {
+            Some("xgm") | Some("xgz") | Some("xgm2") => {
+                // XGM2 shares the extension with XGM
+                match XgmPlay::new(sound_slot(XGM_NTSC_TICK_RATE), file) {
+                    Ok(mut xgmplay) => {
+                        xgmplay.select_track(entry.track)?;
+                        Ok(PlaylistTrack::Xgm(Box::new(xgmplay)))
+                    }
+                    Err(_) => {
+                        let mut xgm2play = Xgm2Play::new(sound_slot(XGM_NTSC_TICK_RATE), file)?;
+                        xgm2play.select_track(entry.track)?;
+                        Ok(PlaylistTrack::Xgm2(Box::new(xgm2play)))
+                    }
+                }
+            }
+            _ => {
+                let mut vgmplay = VgmPlay::new(sound_slot(VGM_TICK_RATE), file)?;
+                vgmplay.add_ym2608_rhythm_rom(&self.ym2608_rhythm_rom);
+                Ok(PlaylistTrack::Vgm(Box::new(vgmplay)))
+            }
+        }
+    }
+
+    fn next_track(&mut self) {
+        self.track = None;
+        self.track_end = false;
+        self.track_index += 1;
+        self.track_sampling_l.clear();
+        self.track_sampling_r.clear();
+        self.track_sampling_pos = 0;
+        self.track_sample = 0;
+        self.fade_start = None;
+    }
+
+    fn fade_gain(&self) -> f32 {
+        match self.fade_start {
+            Some(fade_start) if self.track_sample >= fade_start => {
+                let fade = self.track_sample - fade_start;
+                1_f32 - fade as f32 / self.fade_length.max(1) as f32
+            }
+            _ => 1_f32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, PlaylistPlay};
+
+    const MAX_SAMPLE_SIZE: usize = 2048;
+
+    ///
+    /// VGM of the wait (samples) and the loop
+    ///
+    fn vgm(wait: u16, repeat: bool) -> Vec<u8> {
+        let mut vgm = vec![0; 0x40];
+        vgm[0..4].copy_from_slice(b"Vgm ");
+        vgm[0x08..0x0c].copy_from_slice(&0x150_u32.to_le_bytes());
+        vgm[0x34..0x38].copy_from_slice(&0x0c_u32.to_le_bytes());
+        if repeat {
+            vgm[0x1c..0x20].copy_from_slice(&(0x40 - 0x1c_u32).to_le_bytes());
+        }
+        vgm.push(0x61);
+        vgm.extend_from_slice(&wait.to_le_bytes());
+        vgm.push(0x66);
+        let length = vgm.len() as u32 - 4;
+        vgm[4..8].copy_from_slice(&length.to_le_bytes());
+        vgm
+    }
+
+    #[test]
+    fn playlist_1() {
+        let m3u = b"a.vgm\nmissing.vgm\nB.VGM::VGM,0,,,,0.5,2\n";
+        let mut playlistplay = PlaylistPlay::new(44100, MAX_SAMPLE_SIZE, m3u).unwrap();
+        assert_eq!(playlistplay.get_playlist().entries.len(), 3);
+        playlistplay.add_file("a.vgm", &vgm(3000, false));
+        playlistplay.add_file("b.vgm", &vgm(10000, true));
+        // 3000 samples, 2 loops of 10000 samples and 0.5 sec fade (22050 samples)
+        let mut chunk = 1;
+        while playlistplay.play() != usize::MAX {
+            chunk += 1;
+        }
+        assert_eq!(chunk, (3000 + 20480 + 22050) / MAX_SAMPLE_SIZE + 1);
+        assert_eq!(playlistplay.get_track_index(), 3);
+        assert_eq!(
+            playlistplay.get_error(),
+            Some(&Error::NotFound(String::from("missing.vgm")))
+        );
+    }
+}
@@ -0,0 +1,275 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use encoding_rs::SHIFT_JIS;
+use nom::bytes::complete::tag;
+use nom::number::complete::{le_u32, le_u8};
+use nom::IResult;
+
+use crate::driver::error::Error;
+use crate::driver::gd3meta::Gd3;
+use crate::driver::meta::Jsonlize;
+
+pub const S98_DEVICE_MAX: usize = 64;
+
+/// default device of the header without device info (YM2608 7.9872MHz)
+const S98_DEFAULT_DEVICE: S98Device = S98Device {
+    device_type: 4,
+    clock: 7987200,
+    pan: 0,
+};
+
+///
+/// http://www.purose.net/befis/download/kmp/s98spec3.txt
+///
+/// $0000 "S98" + version ('1' or '3')
+/// $0004 timer info (numerator, 0 is 10)
+/// $0008 timer info 2 (denominator, 0 is 1000)
+/// $000C compressing (always 0)
+/// $0010 tag offset (0 is no tag, v1: Shift-JIS title, v3: "[S98]" tag)
+/// $0014 dump data offset
+/// $0018 loop point offset (0 is no loop)
+/// $001C device count (v3, 0 is YM2608 only)
+/// $0020 device info (v3, 16 bytes: type, clock, pan, reserved)
+///
+#[derive(Deserialize, Serialize, Debug)]
+pub struct S98Header {
+    pub version: u32,
+    pub timer_numerator: u32,
+    pub timer_denominator: u32,
+    pub tag_offset: usize,
+    pub data_offset: usize,
+    pub loop_offset: usize,
+    pub device_count: u32,
+    pub device_info: Vec<S98Device>,
+}
+
+///
+/// S98 device info
+///
+/// type: 1 YM2149, 2 YM2203, 3 YM2612, 4 YM2608, 5 YM2151, 6 YM2413,
+///       7 YM3526, 8 YM3812, 9 YMF262, 15 AY8910, 16 SN76489
+///
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct S98Device {
+    pub device_type: u32,
+    pub clock: u32,
+    pub pan: u32,
+}
+
+///
+/// Parse S98 device info
+///
+fn parse_s98_device(i: &[u8]) -> IResult<&[u8], S98Device> {
+    let (i, device_type) = le_u32(i)?;
+    let (i, clock) = le_u32(i)?;
+    let (i, pan) = le_u32(i)?;
+    let (i, _) = le_u32(i)?;
+    Ok((
+        i,
+        S98Device {
+            device_type,
+            clock,
+            pan,
+        },
+    ))
+}
+
+///
+/// Parse S98 header
+///
+fn parse_s98_header(i: &[u8]) -> IResult<&[u8], S98Header> {
+    let (i, _) = tag("S98")(i)?;
+    let (i, version) = le_u8(i)?;
+    let (i, timer_numerator) = le_u32(i)?;
+    let (i, timer_denominator) = le_u32(i)?;
+    let (i, _) = le_u32(i)?;
+    let (i, tag_offset) = le_u32(i)?;
+    let (i, data_offset) = le_u32(i)?;
+    let (i, loop_offset) = le_u32(i)?;
+    let version = version.wrapping_sub(b'0') as u32;
+    let (i, device_count, device_info) = if version >= 3 {
+        let (mut i, device_count) = le_u32(i)?;
+        let mut device_info = Vec::new();
+        for _ in 0..(device_count as usize).min(S98_DEVICE_MAX) {
+            let (rest, device) = parse_s98_device(i)?;
+            i = rest;
+            device_info.push(device);
+        }
+        (i, device_count, device_info)
+    } else {
+        (i, 0, Vec::new())
+    };
+    let device_info = if device_info.is_empty() {
+        vec![S98_DEFAULT_DEVICE]
+    } else {
+        device_info
+    };
+
+    Ok((
+        i,
+        S98Header {
+            version,
+            timer_numerator: if timer_numerator == 0 {
+                10
+            } else {
+                timer_numerator
+            },
+            timer_denominator: if timer_denominator == 0 {
+                1000
+            } else {
+                timer_denominator
+            },
+            tag_offset: tag_offset as usize,
+            data_offset: data_offset as usize,
+            loop_offset: loop_offset as usize,
+            device_count,
+            device_info,
+        },
+    ))
+}
+
+///
+/// Decode null terminated string (UTF-8 with BOM or Shift-JIS)
+///
+fn decode_string(i: &[u8]) -> String {
+    let i = match i.iter().position(|c| *c == 0) {
+        Some(end) => &i[..end],
+        None => i,
+    };
+    match i.strip_prefix(b"\xef\xbb\xbf") {
+        Some(utf8) => String::from_utf8_lossy(utf8).into_owned(),
+        None => SHIFT_JIS.decode_without_bom_handling(i).0.into_owned(),
+    }
+}
+
+///
+/// Parse S98 tag
+///
+/// v1: title only
+/// v3: "[S98]" and "key=value" lines separated by 0x0a
+///
+fn parse_s98_tag(i: &[u8]) -> Gd3 {
+    let mut gd3 = Gd3::default();
+    let tags = match i.strip_prefix(b"[S98]") {
+        Some(tags) => decode_string(tags),
+        None => {
+            gd3.track_name = decode_string(i);
+            return gd3;
+        }
+    };
+    for line in tags.split('\n') {
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.to_ascii_lowercase(), value.trim_end_matches('\r')),
+            None => continue,
+        };
+        let value = String::from(value);
+        match key.as_str() {
+            "title" => gd3.track_name = value,
+            "artist" => gd3.track_author = value,
+            "game" => gd3.game_name = value,
+            "year" => gd3.date = value,
+            "system" => gd3.system_name = value,
+            "s98by" => gd3.converted = value,
+            "comment" => gd3.notes = value,
+            _ => { /* genre, copyright and others */ }
+        }
+    }
+    gd3
+}
+
+///
+/// Parse S98 meta
+///
+pub(crate) fn parse_s98_meta(s98data: &[u8]) -> Result<(S98Header, Gd3), Error> {
+    let header = match parse_s98_header(s98data) {
+        Ok((_, header)) => header,
+        Err(_) => return Err(Error::InvalidHeader("s98")),
+    };
+    if header.version != 1 && header.version != 3 {
+        return Err(Error::UnsupportedVersion(header.version));
+    }
+    if header.data_offset >= s98data.len() {
+        return Err(Error::BadOffset(header.data_offset));
+    }
+    if header.loop_offset != 0
+        && (header.loop_offset < header.data_offset || header.loop_offset >= s98data.len())
+    {
+        return Err(Error::BadOffset(header.loop_offset));
+    }
+    let gd3 = match s98data.get(header.tag_offset..) {
+        Some(tag) if header.tag_offset != 0 => parse_s98_tag(tag),
+        _ => Gd3::default(), // blank values
+    };
+
+    Ok((header, gd3))
+}
+
+impl Jsonlize for S98Header {}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_s98_meta, S98Device, S98_DEFAULT_DEVICE};
+    use crate::driver::error::Error;
+
+    #[test]
+    fn s98_v3() {
+        let mut s98 = b"S983".to_vec();
+        for value in [1_u32, 60, 0, 0x60, 0x40, 0x40, 2] {
+            s98.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [5_u32, 4000000, 0, 0, 16, 3993600, 0, 0] {
+            s98.extend_from_slice(&value.to_le_bytes());
+        }
+        s98.extend_from_slice(&[0xff; 0x20]);
+        s98.extend_from_slice(b"[S98]title=\x83e\x83X\x83g\nArtist=Composer\n");
+        s98.extend_from_slice(b"game=\xef\xbb\xbfignored\n\0");
+
+        let (header, gd3) = parse_s98_meta(&s98).unwrap();
+        assert_eq!(header.version, 3);
+        assert_eq!(header.timer_numerator, 1);
+        assert_eq!(header.timer_denominator, 60);
+        assert_eq!(
+            header.device_info[1],
+            S98Device {
+                device_type: 16,
+                clock: 3993600,
+                pan: 0
+            }
+        );
+        // Shift-JIS
+        assert_eq!(gd3.track_name, "テスト");
+        assert_eq!(gd3.track_author, "Composer");
+
+        // UTF-8 (BOM)
+        s98.truncate(0x60);
+        s98.extend_from_slice(b"[S98]\xef\xbb\xbftitle=\xe3\x83\x86\xe3\x82\xb9\xe3\x83\x88\n\0");
+        let (_, gd3) = parse_s98_meta(&s98).unwrap();
+        assert_eq!(gd3.track_name, "テスト");
+
+        // loop point outside of the dump data
+        s98[0x18] = 0x20;
+        assert_eq!(parse_s98_meta(&s98).unwrap_err(), Error::BadOffset(0x20));
+    }
+
+    #[test]
+    fn s98_v1() {
+        let mut s98 = b"S981".to_vec();
+        for value in [0_u32, 0, 0, 0x20, 0x1c, 0] {
+            s98.extend_from_slice(&value.to_le_bytes());
+        }
+        s98.extend_from_slice(&[0xfd, 0x00, 0x00, 0x00]);
+        s98.extend_from_slice(b"\x83^\x83C\x83g\x83\x8b\0");
+
+        let (header, gd3) = parse_s98_meta(&s98).unwrap();
+        assert_eq!(header.timer_numerator, 10);
+        assert_eq!(header.timer_denominator, 1000);
+        assert_eq!(header.device_info, vec![S98_DEFAULT_DEVICE]);
+        assert_eq!(gd3.track_name, "タイトル");
+
+        s98[3] = b'2';
+        assert_eq!(
+            parse_s98_meta(&s98).unwrap_err(),
+            Error::UnsupportedVersion(2)
+        );
+    }
+}
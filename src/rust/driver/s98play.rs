@@ -0,0 +1,409 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use crate::driver::error::Error;
+use crate::driver::gd3meta::Gd3;
+use crate::driver::meta::Jsonlize;
+use crate::driver::s98meta::{self, S98Header};
+use crate::sound::{RomIndex, SoundChipType, SoundSlot};
+
+pub const S98_TICK_RATE: u32 = 44100;
+
+///
+/// S98 Driver
+///
+pub struct S98Play {
+    sound_slot: SoundSlot,
+    s98_pos: usize,
+    s98_loop_count: usize,
+    s98_end: bool,
+    s98_data: Vec<u8>,
+    s98_header: Option<S98Header>,
+    s98_gd3: Option<Gd3>,
+    s98_error: Option<Error>,
+    s98_device: Vec<Option<(SoundChipType, usize)>>,
+    sync_remainder: u64,
+    remain_tick_count: usize,
+}
+
+#[allow(dead_code)]
+impl S98Play {
+    ///
+    /// Create sound driver.
+    ///
+    pub fn new(sound_slot: SoundSlot, s98_file: &[u8]) -> Result<Self, Error> {
+        let mut s98play = S98Play {
+            sound_slot,
+            s98_pos: 0,
+            s98_loop_count: 0,
+            s98_end: false,
+            s98_data: s98_file.to_vec(),
+            s98_header: None,
+            s98_gd3: None,
+            s98_error: None,
+            s98_device: Vec::new(),
+            sync_remainder: 0,
+            remain_tick_count: 0,
+        };
+        // parse s98 header and soundchip init
+        s98play.init()?;
+
+        Ok(s98play)
+    }
+
+    ///
+    /// Return sampling_l buffer referance.
+    ///
+    pub fn get_sampling_l_ref(&self) -> *const f32 {
+        self.sound_slot.get_output_sampling_l_ref()
+    }
+
+    ///
+    /// Return sampling buffer referance.
+    ///
+    pub fn get_sampling_r_ref(&self) -> *const f32 {
+        self.sound_slot.get_output_sampling_r_ref()
+    }
+
+    ///
+    /// Return s16le sampling buffer referance.
+    ///
+    pub fn get_output_sampling_s16le_ref(&mut self) -> *const i16 {
+        self.sound_slot.get_output_sampling_s16le_ref()
+    }
+
+    ///
+    /// Get S98 meta.
+    ///
+    pub fn get_s98_meta(&self) -> (&S98Header, &Gd3) {
+        (
+            self.s98_header.as_ref().unwrap(/* There always is */),
+            self.s98_gd3.as_ref().unwrap(/* There always is */),
+        )
+    }
+
+    ///
+    /// Get S98 header JSON.
+    ///
+    pub fn get_s98_header_json(&self) -> String {
+        self.s98_header.as_ref().unwrap(/* There always is */).get_json()
+    }
+
+    ///
+    /// Get S98 GD3 JSON (converted from the S98 tag).
+    ///
+    pub fn get_s98_gd3_json(&self) -> String {
+        self.s98_gd3.as_ref().unwrap(/* There always is */).get_json()
+    }
+
+    ///
+    /// Add YM2608 internal rhythm ROM (not included in S98 file).
+    ///
+    pub fn add_ym2608_rhythm_rom(&mut self, memory: &[u8]) {
+        if memory.is_empty() {
+            return;
+        }
+        let number_of = self
+            .s98_device
+            .iter()
+            .filter(|device| matches!(device, Some((SoundChipType::YM2608, _))))
+            .count();
+        for sound_chip_index in 0..number_of {
+            self.sound_slot.add_rom(
+                SoundChipType::YM2608,
+                sound_chip_index,
+                RomIndex::YM2608_RHYTHM,
+                memory,
+                0,
+                memory.len() - 1,
+            );
+        }
+    }
+
+    ///
+    /// Get the error that stopped playback (broken S98 data).
+    ///
+    pub fn get_error(&self) -> Option<&Error> {
+        self.s98_error.as_ref()
+    }
+
+    ///
+    /// Play Sound.
+    ///
+    pub fn play(&mut self, repeat: bool) -> usize {
+        while !self.sound_slot.is_stream_filled() && !self.s98_end {
+            for _ in 0..self.remain_tick_count {
+                self.sound_slot.update(1);
+                self.remain_tick_count -= 1;
+                if self.sound_slot.is_stream_filled() {
+                    break;
+                }
+            }
+            if self.remain_tick_count == 0 {
+                match self
+                    .parse_s98(repeat)
+                    .and_then(|sync| self.sync_to_tick(sync))
+                {
+                    Ok(tick) => self.remain_tick_count = tick,
+                    Err(error) => {
+                        // stop playback on broken data
+                        self.s98_error = Some(error);
+                        self.s98_end = true;
+                    }
+                }
+            };
+        }
+        self.sound_slot.stream();
+
+        if self.s98_loop_count == usize::MAX {
+            self.s98_loop_count = 0;
+        }
+        if self.s98_end {
+            usize::MAX
+        } else {
+            self.s98_loop_count
+        }
+    }
+
+    ///
+    /// Initialize sound driver.
+    ///
+    fn init(&mut self) -> Result<(), Error> {
+        // parse s98 header
+        let (header, gd3) = s98meta::parse_s98_meta(&self.s98_data)?;
+
+        // add sound chip (the command selects the device by index of the device info)
+        for device in header.device_info.iter() {
+            let sound_chip_type = match device.device_type {
+                1 => Some(SoundChipType::YM2149),
+                2 => Some(SoundChipType::YM2203),
+                3 => Some(SoundChipType::YM2612),
+                4 => Some(SoundChipType::YM2608),
+                5 => Some(SoundChipType::YM2151),
+                6 => Some(SoundChipType::YM2413),
+                7 => Some(SoundChipType::YM3526),
+                8 => Some(SoundChipType::YM3812),
+                9 => Some(SoundChipType::YMF262),
+                15 => Some(SoundChipType::AY8910),
+                16 => Some(SoundChipType::SN76489),
+                _ => None,
+            };
+            self.s98_device.push(sound_chip_type.map(|sound_chip_type| {
+                let sound_chip_index = self
+                    .s98_device
+                    .iter()
+                    .filter(|device| matches!(device, Some((t, _)) if *t == sound_chip_type))
+                    .count();
+                self.sound_slot
+                    .add_sound_device(sound_chip_type, 1, device.clock);
+                (sound_chip_type, sound_chip_index)
+            }));
+        }
+        self.s98_pos = header.data_offset;
+        self.s98_header = Some(header);
+        self.s98_gd3 = Some(gd3);
+
+        Ok(())
+    }
+
+    ///
+    /// Convert S98 sync count to tick count (keep the remainder of the timer).
+    ///
+    /// The wait that overflows is a broken file.
+    ///
+    fn sync_to_tick(&mut self, sync: u32) -> Result<usize, Error> {
+        let header = self.s98_header.as_ref().unwrap();
+        let tick = match (sync as u64)
+            .checked_mul(header.timer_numerator as u64)
+            .and_then(|tick| tick.checked_mul(S98_TICK_RATE as u64))
+            .and_then(|tick| tick.checked_add(self.sync_remainder))
+        {
+            Some(tick) => tick,
+            None => return Err(Error::InvalidDataBlock(self.s98_pos)),
+        };
+        let denominator = header.timer_denominator as u64;
+        self.sync_remainder = tick % denominator;
+        match usize::try_from(tick / denominator) {
+            Ok(tick) => Ok(tick),
+            Err(_) => Err(Error::InvalidDataBlock(self.s98_pos)),
+        }
+    }
+
+    fn get_s98_u8(&mut self) -> Result<u8, Error> {
+        let ret = match self.s98_data.get(self.s98_pos) {
+            Some(ret) => *ret,
+            None => return Err(Error::UnexpectedEof(self.s98_pos)),
+        };
+        self.s98_pos += 1;
+        Ok(ret)
+    }
+
+    fn parse_s98(&mut self, repeat: bool) -> Result<u32, Error> {
+        let mut sync: u32 = 0;
+
+        let command = self.get_s98_u8()?;
+        match command {
+            0x00..=0x7f => {
+                // device number * 2 + port, register, data
+                let reg = self.get_s98_u8()?;
+                let dat = self.get_s98_u8()?;
+                let port = (command & 1) as u32;
+                match self.s98_device.get((command >> 1) as usize) {
+                    Some(Some((SoundChipType::SN76489, sound_chip_index))) => {
+                        self.sound_slot.write(
+                            SoundChipType::SN76489,
+                            *sound_chip_index,
+                            0,
+                            dat.into(),
+                        );
+                    }
+                    Some(Some((sound_chip_type, sound_chip_index))) => {
+                        self.sound_slot.write(
+                            *sound_chip_type,
+                            *sound_chip_index,
+                            reg as u32 | port << 8,
+                            dat.into(),
+                        );
+                    }
+                    _ => { /* unsupported device */ }
+                }
+            }
+            0xfd => {
+                // end of data or loop
+                let loop_offset = self.s98_header.as_ref().unwrap().loop_offset;
+                if repeat && loop_offset != 0 {
+                    self.s98_pos = loop_offset;
+                    self.s98_loop_count += 1;
+                } else {
+                    self.s98_end = true;
+                }
+            }
+            0xfe => {
+                // n sync wait (variable length, n + 2)
+                let mut value: u64 = 0;
+                let mut shift = 0;
+                loop {
+                    let dat = self.get_s98_u8()?;
+                    value |= ((dat & 0x7f) as u64) << shift;
+                    shift += 7;
+                    if dat & 0x80 == 0 {
+                        break;
+                    }
+                    // up to 5 bytes (32-bit value)
+                    if shift >= 35 {
+                        return Err(Error::OutOfRange("s98 sync wait"));
+                    }
+                }
+                sync = u32::try_from(value)
+                    .map_err(|_| Error::OutOfRange("s98 sync wait"))?
+                    .saturating_add(2);
+            }
+            0xff => {
+                // 1 sync wait
+                sync = 1;
+            }
+            _ => return Err(Error::UnknownCommand(self.s98_pos - 1, command)),
+        }
+
+        Ok(sync)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sound::SoundSlot;
+
+    use super::{Error, S98Play, S98_TICK_RATE};
+
+    const MAX_SAMPLE_SIZE: usize = 2048;
+
+    #[test]
+    fn s98_1() {
+        // 1/100 sec sync, YM2203 and SN76489
+        let mut s98 = b"S983".to_vec();
+        for value in [1_u32, 100, 0, 0, 0x40, 0x48, 2] {
+            s98.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [2_u32, 3993600, 0, 0, 16, 3579545, 0, 0] {
+            s98.extend_from_slice(&value.to_le_bytes());
+        }
+        // 0x40: YM2203 write, SN76489 write, 3 sync
+        s98.extend_from_slice(&[0x00, 0x28, 0x01, 0x02, 0x00, 0x9f, 0xfe, 0x01]);
+        // 0x48: loop point, 2 sync
+        s98.extend_from_slice(&[0xff, 0xff, 0xfd]);
+        let mut s98play = S98Play::new(
+            SoundSlot::new(S98_TICK_RATE, 44100, MAX_SAMPLE_SIZE),
+            &s98,
+        )
+        .unwrap();
+        assert_eq!(s98play.get_s98_meta().0.device_count, 2);
+        // 3 sync (1323 samples) and then 2 sync (882 samples) loop
+        assert_eq!(s98play.play(true), 0);
+        assert_eq!(s98play.play(true), 3);
+        assert_eq!(s98play.get_error(), None);
+
+        // no loop (ends in the second chunk)
+        s98[0x18] = 0;
+        let mut s98play = S98Play::new(
+            SoundSlot::new(S98_TICK_RATE, 44100, MAX_SAMPLE_SIZE),
+            &s98,
+        )
+        .unwrap();
+        let mut chunk = 1;
+        while s98play.play(true) != usize::MAX {
+            chunk += 1;
+        }
+        assert_eq!(chunk, (1323 + 882) / MAX_SAMPLE_SIZE + 1);
+        assert_eq!(s98play.get_error(), None);
+    }
+
+    #[test]
+    fn broken_1() {
+        // v1 (YM2608) port 1 write without data
+        let mut s98 = b"S981".to_vec();
+        for value in [0_u32, 0, 0, 0, 0x1c, 0] {
+            s98.extend_from_slice(&value.to_le_bytes());
+        }
+        s98.extend_from_slice(&[0xff, 0x01, 0x10]);
+        let mut s98play = S98Play::new(
+            SoundSlot::new(S98_TICK_RATE, 44100, MAX_SAMPLE_SIZE),
+            &s98,
+        )
+        .unwrap();
+        assert_eq!(s98play.play(false), usize::MAX);
+        assert_eq!(s98play.get_error(), Some(&Error::UnexpectedEof(0x1f)));
+
+        // v1 (YM2608) wait overflows with the timer
+        let mut s98 = b"S981".to_vec();
+        for value in [u32::MAX, 0, 0, 0, 0x1c, 0] {
+            s98.extend_from_slice(&value.to_le_bytes());
+        }
+        s98.extend_from_slice(&[0xfe, 0xff, 0xff, 0xff, 0xff, 0x0f]);
+        let mut s98play = S98Play::new(
+            SoundSlot::new(S98_TICK_RATE, 44100, MAX_SAMPLE_SIZE),
+            &s98,
+        )
+        .unwrap();
+        assert_eq!(s98play.play(false), usize::MAX);
+        assert_eq!(s98play.get_error(), Some(&Error::InvalidDataBlock(0x22)));
+
+        // sync wait longer than 32-bit
+        for sync in [&[0xff, 0xff, 0xff, 0xff, 0x10][..], &[0x80, 0x80, 0x80, 0x80, 0x80, 0x00]] {
+            let mut s98 = b"S981".to_vec();
+            for value in [0_u32, 0, 0, 0, 0x1c, 0] {
+                s98.extend_from_slice(&value.to_le_bytes());
+            }
+            s98.push(0xfe);
+            s98.extend_from_slice(sync);
+            let mut s98play = S98Play::new(
+                SoundSlot::new(S98_TICK_RATE, 44100, MAX_SAMPLE_SIZE),
+                &s98,
+            )
+            .unwrap();
+            assert_eq!(s98play.play(false), usize::MAX);
+            assert_eq!(
+                s98play.get_error(),
+                Some(&Error::OutOfRange("s98 sync wait"))
+            );
+        }
+    }
+}
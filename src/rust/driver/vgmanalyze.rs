@@ -0,0 +1,388 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use flate2::read::GzDecoder;
+use std::collections::BTreeMap;
+use std::io::prelude::*;
+
+use crate::driver::error::Error;
+use crate::driver::meta::Jsonlize;
+use crate::driver::vgmmeta::{get_vgm_command_length, parse_vgm_meta, VgmHeader};
+
+///
+/// VGM analysis report
+///
+#[derive(Deserialize, Serialize, Default, Debug)]
+pub struct VgmAnalysis {
+    pub chip_write: Vec<ChipWrite>,
+    pub unsupported_command: Vec<UnsupportedCommand>,
+    pub unknown_command: Option<CommandOffset>,
+    pub data_block: Vec<DataBlockSize>,
+    pub total_samples: u64,
+    pub header_total_samples: u32,
+    pub loop_samples: u64,
+    pub header_loop_samples: u32,
+    pub loop_offset: Option<usize>,
+    pub loop_offset_on_command: bool,
+    pub end_of_data: bool,
+    pub error: Option<String>,
+}
+
+///
+/// Number of register writes per chip
+///
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct ChipWrite {
+    pub chip: String,
+    pub index: usize,
+    pub count: usize,
+    pub supported: bool,
+}
+
+///
+/// Command not supported by the driver (first offset and count)
+///
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct UnsupportedCommand {
+    pub command: u8,
+    pub chip: String,
+    pub offset: usize,
+    pub count: usize,
+}
+
+///
+/// Command offset
+///
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct CommandOffset {
+    pub command: u8,
+    pub offset: usize,
+}
+
+///
+/// Data block count and total size per data type
+///
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct DataBlockSize {
+    pub data_type: u8,
+    pub count: usize,
+    pub size: usize,
+}
+
+impl Jsonlize for VgmAnalysis {}
+
+///
+/// Chip written by the command (chip name, supported)
+///
+fn get_command_chip(command: u8) -> Option<(&'static str, bool)> {
+    let chip = match command {
+        0x30 | 0x50 => ("SN76489", true),
+        0x3f | 0x4f => ("SN76489", true), // Game Gear stereo
+        0x31 => ("AY8910", true), // stereo mask
+        0x32..=0x3e => ("reserved", false),
+        0x40..=0x4e => ("reserved", false),
+        0x51 | 0xa1 => ("YM2413", true),
+        0x52 | 0x53 | 0xa2 | 0xa3 => ("YM2612", true),
+        0x54 | 0xa4 => ("YM2151", true),
+        0x55 | 0xa5 => ("YM2203", true),
+        0x56 | 0x57 | 0xa6 | 0xa7 => ("YM2608", true),
+        0x58 | 0x59 | 0xa8 | 0xa9 => ("YM2610", true),
+        0x5a | 0xaa => ("YM3812", true),
+        0x5b | 0xab => ("YM3526", true),
+        0x5c | 0xac => ("Y8950", true),
+        0x5d | 0xad => ("YMZ280B", false),
+        0x5e | 0x5f | 0xae | 0xaf => ("YMF262", true),
+        0x68 => ("PCM RAM", false),
+        0x80..=0x8f => ("YM2612", true),
+        0xa0 => ("AY8910", true),
+        0xb0 => ("RF5C68", false),
+        0xb1 => ("RF5C164", false),
+        0xb2 => ("PWM", true),
+        0xb3 => ("GameBoy DMG", false),
+        0xb4 => ("NES APU", false),
+        0xb5 => ("MultiPCM", false),
+        0xb6 => ("uPD7759", false),
+        0xb7 => ("OKIM6258", true),
+        0xb8 => ("OKIM6295", true),
+        0xb9 => ("HuC6280", false),
+        0xba => ("K053260", false),
+        0xbb => ("Pokey", false),
+        0xbc => ("WonderSwan", false),
+        0xbd => ("SAA1099", false),
+        0xbe => ("ES5506", false),
+        0xbf => ("GA20", false),
+        0xc0 => ("SEGAPCM", true),
+        0xc1 => ("RF5C68", false),
+        0xc2 => ("RF5C164", false),
+        0xc3 => ("MultiPCM", false),
+        0xc4 => ("QSound", false),
+        0xc5 => ("SCSP", true),
+        0xc6 => ("WonderSwan", false),
+        0xc7 => ("VSU", true),
+        0xc8 => ("X1-010", false),
+        0xc9..=0xcf => ("reserved", false),
+        0xd0 => ("YMF278B", true),
+        0xd1 => ("YMF271", true),
+        0xd2 => ("K051649", true),
+        0xd3 => ("K054539", false),
+        0xd4 => ("C140", true),
+        0xd5 => ("ES5503", false),
+        0xd6 => ("ES5506", false),
+        0xd7..=0xdf => ("reserved", false),
+        0xe1 => ("C352", false),
+        0xe2..=0xff => ("reserved", false),
+        _ => return None,
+    };
+    Some(chip)
+}
+
+///
+/// Chip index selected by the command (second chip)
+///
+fn get_chip_index(command: u8, operand: &[u8]) -> usize {
+    match command {
+        0x30 | 0x3f | 0xa1..=0xaf => 1,
+        0x31 | 0xa0 | 0xb0..=0xb1 | 0xb3..=0xbf | 0xc5..=0xc8 | 0xd0..=0xd6 | 0xe1 => {
+            (operand[0] >> 7) as usize
+        }
+        _ => 0,
+    }
+}
+
+///
+/// Walk the whole command stream without rendering.
+///
+pub(crate) fn analyze_vgm(vgmdata: &[u8], header: &VgmHeader) -> VgmAnalysis {
+    let mut chip_write: BTreeMap<(&'static str, usize), (usize, bool)> = BTreeMap::new();
+    let mut unsupported: BTreeMap<u8, UnsupportedCommand> = BTreeMap::new();
+    let mut data_block: BTreeMap<u8, DataBlockSize> = BTreeMap::new();
+    let mut analysis = VgmAnalysis {
+        header_total_samples: header.total_samples,
+        header_loop_samples: header.loop_samples,
+        ..Default::default()
+    };
+
+    let loop_offset = if header.offset_loop != 0 {
+        Some((header.offset_loop as usize).saturating_add(0x1c))
+    } else {
+        None
+    };
+    let mut loop_start_samples: Option<u64> = None;
+    let mut pos = (header.vgm_data_offset as usize).saturating_add(0x34);
+
+    let error = loop {
+        if loop_offset == Some(pos) {
+            loop_start_samples = Some(analysis.total_samples);
+        }
+        let command = match vgmdata.get(pos) {
+            Some(command) => *command,
+            None => break Some(Error::UnexpectedEof(pos)),
+        };
+        if command == 0x66 {
+            analysis.end_of_data = true;
+            break None;
+        }
+        if command == 0x67 {
+            // 0x67 0x66 tt ss ss ss ss (data)
+            let block_header = match vgmdata.get(pos + 1..pos + 7) {
+                Some(block_header) => block_header,
+                None => break Some(Error::UnexpectedEof(pos)),
+            };
+            let data_type = block_header[1];
+            let data_length =
+                u32::from_le_bytes(block_header[2..6].try_into().unwrap()) as usize & 0x7fffffff;
+            let next_pos = (pos + 7).saturating_add(data_length);
+            if next_pos > vgmdata.len() {
+                break Some(Error::UnexpectedEof(pos + 7));
+            }
+            let size = data_block.entry(data_type).or_insert(DataBlockSize {
+                data_type,
+                count: 0,
+                size: 0,
+            });
+            size.count += 1;
+            size.size += data_length;
+            pos = next_pos;
+            continue;
+        }
+        let length = match get_vgm_command_length(command) {
+            Some(length) => length,
+            None => {
+                analysis.unknown_command = Some(CommandOffset {
+                    command,
+                    offset: pos,
+                });
+                break Some(Error::UnknownCommand(pos, command));
+            }
+        };
+        let operand = match vgmdata.get(pos + 1..pos + 1 + length) {
+            Some(operand) => operand,
+            None => break Some(Error::UnexpectedEof(pos + 1)),
+        };
+        // wait
+        analysis.total_samples += match command {
+            0x61 => u16::from_le_bytes([operand[0], operand[1]]) as u64,
+            0x62 => 735,
+            0x63 => 882,
+            0x70..=0x7f => ((command & 0x0f) + 1) as u64,
+            0x80..=0x8f => (command & 0x0f) as u64,
+            _ => 0,
+        };
+        // chip write
+        if let Some((chip, supported)) = get_command_chip(command) {
+            let index = get_chip_index(command, operand);
+            chip_write.entry((chip, index)).or_insert((0, supported)).0 += 1;
+            if !supported {
+                unsupported
+                    .entry(command)
+                    .or_insert(UnsupportedCommand {
+                        command,
+                        chip: String::from(chip),
+                        offset: pos,
+                        count: 0,
+                    })
+                    .count += 1;
+            }
+        }
+        pos += 1 + length;
+    };
+
+    if let Some(loop_start_samples) = loop_start_samples {
+        analysis.loop_samples = analysis.total_samples - loop_start_samples;
+    }
+    analysis.loop_offset = loop_offset;
+    analysis.loop_offset_on_command = loop_start_samples.is_some();
+    analysis.chip_write = chip_write
+        .into_iter()
+        .map(|((chip, index), (count, supported))| ChipWrite {
+            chip: String::from(chip),
+            index,
+            count,
+            supported,
+        })
+        .collect();
+    analysis.unsupported_command = unsupported.into_values().collect();
+    analysis.data_block = data_block.into_values().collect();
+    analysis.error = error.map(|error| error.to_string());
+
+    analysis
+}
+
+///
+/// Get analysis JSON of VGM/VGZ file without creating sound chips.
+///
+pub fn get_vgm_analysis_json(vgm_file: &[u8]) -> Result<String, Error> {
+    let mut vgmdata = Vec::new();
+    let vgmdata = if GzDecoder::new(vgm_file).read_to_end(&mut vgmdata).is_ok() {
+        vgmdata.as_slice()
+    } else {
+        vgm_file
+    };
+    let (header, _) = parse_vgm_meta(vgmdata)?;
+    Ok(analyze_vgm(vgmdata, &header).get_json())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::analyze_vgm;
+    use super::{ChipWrite, CommandOffset, DataBlockSize, UnsupportedCommand};
+    use crate::driver::vgmmeta::VgmHeader;
+
+    #[test]
+    fn analyze_1() {
+        let header = VgmHeader {
+            total_samples: 2000,
+            offset_loop: 0x100 + 9 - 0x1c,
+            loop_samples: 1000,
+            vgm_data_offset: 0x100 - 0x34,
+            ..Default::default()
+        };
+        let mut vgm = vec![0_u8; 0x100];
+        vgm.extend_from_slice(&[
+            0x67, 0x66, 0x00, 0x02, 0x00, 0x00, 0x00, 0xaa, 0xbb, // data block
+            0x52, 0x28, 0x00, // YM2612 (loop)
+            0xa2, 0x28, 0x00, // YM2612 #2
+            0xb3, 0x10, 0x00, // GameBoy DMG
+            0x31, 0x81, // AY8910 #2 stereo mask
+            0x62, // 735
+            0x8f, // YM2612 DAC, 15
+            0x61, 0xfa, 0x00, // 250
+            0x66,
+        ]);
+        let analysis = analyze_vgm(&vgm, &header);
+        assert!(analysis.end_of_data);
+        assert!(analysis.error.is_none());
+        assert_eq!(analysis.total_samples, 1000);
+        assert_eq!(analysis.loop_samples, 1000);
+        assert!(analysis.loop_offset_on_command);
+        assert_eq!(
+            analysis.chip_write,
+            vec![
+                ChipWrite {
+                    chip: String::from("AY8910"),
+                    index: 1,
+                    count: 1,
+                    supported: true
+                },
+                ChipWrite {
+                    chip: String::from("GameBoy DMG"),
+                    index: 0,
+                    count: 1,
+                    supported: false
+                },
+                ChipWrite {
+                    chip: String::from("YM2612"),
+                    index: 0,
+                    count: 2,
+                    supported: true
+                },
+                ChipWrite {
+                    chip: String::from("YM2612"),
+                    index: 1,
+                    count: 1,
+                    supported: true
+                },
+            ]
+        );
+        assert_eq!(
+            analysis.unsupported_command,
+            vec![UnsupportedCommand {
+                command: 0xb3,
+                chip: String::from("GameBoy DMG"),
+                offset: 0x10f,
+                count: 1
+            }]
+        );
+        assert_eq!(
+            analysis.data_block,
+            vec![DataBlockSize {
+                data_type: 0,
+                count: 1,
+                size: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn analyze_2() {
+        // loop offset inside the operand and unknown command
+        let header = VgmHeader {
+            offset_loop: 0x101 - 0x1c,
+            vgm_data_offset: 0x100 - 0x34,
+            ..Default::default()
+        };
+        let mut vgm = vec![0_u8; 0x100];
+        vgm.extend_from_slice(&[0x61, 0x10, 0x00, 0x00]);
+        let analysis = analyze_vgm(&vgm, &header);
+        assert!(!analysis.end_of_data);
+        assert!(!analysis.loop_offset_on_command);
+        assert_eq!(analysis.total_samples, 16);
+        assert_eq!(
+            analysis.unknown_command,
+            Some(CommandOffset {
+                command: 0x00,
+                offset: 0x103
+            })
+        );
+        assert!(analysis.error.is_some());
+    }
+}
@@ -0,0 +1,248 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+
+///
+/// Compression type
+///
+const COMPRESSION_BIT_PACKING: u8 = 0x00;
+const COMPRESSION_DPCM: u8 = 0x01;
+
+///
+/// Bit packing sub-type
+///
+const BIT_PACKING_COPY: u8 = 0x00;
+const BIT_PACKING_SHIFT_LEFT: u8 = 0x01;
+const BIT_PACKING_TABLE: u8 = 0x02;
+
+///
+/// VGM decompression table (data block type 0x7f)
+///
+pub struct DecompressTable {
+    compression_type: u8,
+    sub_type: u8,
+    bits_decompressed: u8,
+    bits_compressed: u8,
+    values: Vec<u16>,
+}
+
+impl DecompressTable {
+    ///
+    /// Parse data block type 0x7f.
+    ///
+    pub fn new(data_block: &[u8]) -> Option<Self> {
+        if data_block.len() < 6 {
+            return None;
+        }
+        let compression_type = data_block[0];
+        let sub_type = data_block[1];
+        let bits_decompressed = data_block[2];
+        let bits_compressed = data_block[3];
+        let value_count = u16::from_le_bytes([data_block[4], data_block[5]]) as usize;
+        let value_size = ((bits_decompressed as usize) + 7) / 8;
+        if value_size == 0 || value_size > 2 {
+            return None;
+        }
+        let values: Vec<u16> = data_block[6..]
+            .chunks_exact(value_size)
+            .take(value_count)
+            .map(|value| {
+                if value_size == 1 {
+                    value[0] as u16
+                } else {
+                    u16::from_le_bytes([value[0], value[1]])
+                }
+            })
+            .collect();
+        Some(DecompressTable {
+            compression_type,
+            sub_type,
+            bits_decompressed,
+            bits_compressed,
+            values,
+        })
+    }
+
+    fn is_match(
+        &self,
+        compression_type: u8,
+        sub_type: u8,
+        bits_decompressed: u8,
+        bits_compressed: u8,
+    ) -> bool {
+        self.compression_type == compression_type
+            && self.sub_type == sub_type
+            && self.bits_decompressed == bits_decompressed
+            && self.bits_compressed == bits_compressed
+    }
+}
+
+///
+/// MSB first bit reader
+///
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    shift: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader {
+            data,
+            pos: 0,
+            shift: 0,
+        }
+    }
+
+    ///
+    /// Read bits (values over 8 bits are read from the lower 8 bits)
+    ///
+    fn read(&mut self, bits: u8) -> Option<u32> {
+        let mut value: u32 = 0;
+        let mut out_bit = 0;
+        let mut bits_to_read = bits;
+        while bits_to_read > 0 {
+            let read_bits = bits_to_read.min(8);
+            bits_to_read -= read_bits;
+            let mut chunk: u32 = 0;
+            for _ in 0..read_bits {
+                let byte = *self.data.get(self.pos)?;
+                let bit = (byte >> (7 - self.shift)) & 1;
+                chunk = (chunk << 1) | bit as u32;
+                self.shift += 1;
+                if self.shift == 8 {
+                    self.shift = 0;
+                    self.pos += 1;
+                }
+            }
+            value |= chunk << out_bit;
+            out_bit += read_bits;
+        }
+        Some(value)
+    }
+}
+
+///
+/// Decompress data block type 0x40-0x7e.
+///
+/// Return None if the block is broken or requires an unknown decompression table.
+///
+pub fn decompress_data_block(
+    data_block: &[u8],
+    table: Option<&DecompressTable>,
+) -> Option<Vec<u8>> {
+    if data_block.len() < 10 {
+        return None;
+    }
+    let compression_type = data_block[0];
+    let uncompressed_size =
+        u32::from_le_bytes([data_block[1], data_block[2], data_block[3], data_block[4]]) as usize;
+    let bits_decompressed = data_block[5];
+    let bits_compressed = data_block[6];
+    let sub_type = data_block[7];
+    let add_value = u16::from_le_bytes([data_block[8], data_block[9]]);
+    let data = &data_block[10..];
+
+    let value_size = ((bits_decompressed as usize) + 7) / 8;
+    if value_size == 0 || value_size > 2 || bits_compressed == 0 || bits_compressed > 16 {
+        return None;
+    }
+
+    // decompression table
+    let table = match (compression_type, sub_type) {
+        (COMPRESSION_BIT_PACKING, BIT_PACKING_TABLE) | (COMPRESSION_DPCM, _) => {
+            let table = table?;
+            if !table.is_match(
+                compression_type,
+                sub_type,
+                bits_decompressed,
+                bits_compressed,
+            ) {
+                return None;
+            }
+            Some(table)
+        }
+        (COMPRESSION_BIT_PACKING, _) => None,
+        _ => return None,
+    };
+
+    let mut reader = BitReader::new(data);
+    let mut output: Vec<u8> = Vec::with_capacity(uncompressed_size);
+    let out_mask: u32 = (1 << bits_decompressed) - 1;
+    // DPCM start value
+    let mut dpcm_value: u32 = add_value as u32;
+
+    while output.len() + value_size <= uncompressed_size {
+        let value = match reader.read(bits_compressed) {
+            Some(value) => value,
+            None => break,
+        };
+        let value: u32 = match compression_type {
+            COMPRESSION_BIT_PACKING => match sub_type {
+                BIT_PACKING_COPY => value + add_value as u32,
+                BIT_PACKING_SHIFT_LEFT => {
+                    (value << (bits_decompressed.saturating_sub(bits_compressed)))
+                        + add_value as u32
+                }
+                BIT_PACKING_TABLE => *table?.values.get(value as usize)? as u32,
+                _ => return None,
+            },
+            _ => {
+                // DPCM
+                dpcm_value =
+                    dpcm_value.wrapping_add(*table?.values.get(value as usize)? as u32) & out_mask;
+                dpcm_value
+            }
+        };
+        if value_size == 1 {
+            output.push(value as u8);
+        } else {
+            output.extend_from_slice(&(value as u16).to_le_bytes());
+        }
+    }
+
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decompress_data_block, DecompressTable};
+
+    #[test]
+    fn bit_packing_copy() {
+        // 4 bit -> 8 bit, add 0x10
+        let block = [
+            0x00, 0x04, 0x00, 0x00, 0x00, 0x08, 0x04, 0x00, 0x10, 0x00, 0x12, 0x3f,
+        ];
+        assert_eq!(
+            decompress_data_block(&block, None).unwrap(),
+            vec![0x11, 0x12, 0x13, 0x1f]
+        );
+    }
+
+    #[test]
+    fn bit_packing_shift_left() {
+        // 4 bit -> 8 bit, shift left 4
+        let block = [
+            0x00, 0x02, 0x00, 0x00, 0x00, 0x08, 0x04, 0x01, 0x00, 0x00, 0x8f,
+        ];
+        assert_eq!(
+            decompress_data_block(&block, None).unwrap(),
+            vec![0x80, 0xf0]
+        );
+    }
+
+    #[test]
+    fn dpcm() {
+        // table: 0 -> +1, 1 -> -1 (0xff)
+        let table =
+            DecompressTable::new(&[0x01, 0x00, 0x08, 0x01, 0x02, 0x00, 0x01, 0xff]).unwrap();
+        let block = [
+            0x01, 0x04, 0x00, 0x00, 0x00, 0x08, 0x01, 0x00, 0x80, 0x00, 0x20,
+        ];
+        assert_eq!(
+            decompress_data_block(&block, Some(&table)).unwrap(),
+            vec![0x81, 0x82, 0x81, 0x82]
+        );
+    }
+}
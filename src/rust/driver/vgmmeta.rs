@@ -1,11 +1,17 @@
 // license:BSD-3-Clause
 // copyright-holders:Hiromasa Tanaka
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use nom::bytes::complete::{tag, take};
 use nom::number::complete::{le_u16, le_u32, le_u8};
 use nom::IResult;
+use std::io::prelude::*;
 
-use crate::driver::gd3meta::{parse_gd3, Gd3};
+use crate::driver::error::Error;
+use crate::driver::gd3meta::{parse_gd3, serialize_gd3, Gd3};
 use crate::driver::meta::Jsonlize;
+use crate::sound::SoundChipType;
 
 ///
 /// https://vgmrips.net/wiki/VGM_Specification
@@ -337,20 +343,20 @@ fn parse_extra_header(i: &[u8], mut header: VgmHeader) -> IResult<&[u8], VgmHead
 
     // Chip Clock Header
     if chip_clock_ofs != 0 {
-        extra_hdr.chip_clock =
-            match parse_extra_header_clock(&i[(0x04 + chip_clock_ofs as usize)..]) {
-                Ok((_, extra_header_clock)) => extra_header_clock,
-                Err(error) => return Err(error),
-            };
+        let (j, _) = take(chip_clock_ofs.saturating_add(0x04))(i)?;
+        extra_hdr.chip_clock = match parse_extra_header_clock(j) {
+            Ok((_, extra_header_clock)) => extra_header_clock,
+            Err(error) => return Err(error),
+        };
         extra_hdr.chip_clock_ofs = chip_clock_ofs;
     }
     // Chip Volume Header
     if chip_volume_ofs != 0 {
-        extra_hdr.chip_volume =
-            match parse_extra_header_volume(&i[(0x08 + chip_volume_ofs as usize)..]) {
-                Ok((_, extra_header_volume)) => extra_header_volume,
-                Err(error) => return Err(error),
-            };
+        let (j, _) = take(chip_volume_ofs.saturating_add(0x08))(i)?;
+        extra_hdr.chip_volume = match parse_extra_header_volume(j) {
+            Ok((_, extra_header_volume)) => extra_header_volume,
+            Err(error) => return Err(error),
+        };
         extra_hdr.chip_volume_ofs = chip_volume_ofs;
     }
 
@@ -404,46 +410,162 @@ fn parse_extra_header_volume(mut i: &[u8]) -> IResult<&[u8], Vec<ChipVolume>> {
 ///
 /// Parse VGM meta
 ///
-pub(crate) fn parse_vgm_meta(vgmdata: &[u8]) -> Result<(VgmHeader, Gd3), &'static str> {
+pub(crate) fn parse_vgm_meta(vgmdata: &[u8]) -> Result<(VgmHeader, Gd3), Error> {
     // clean header
-    let mut vgm_data_offset: usize =
-        (u32::from_le_bytes(vgmdata[0x34..=0x37].try_into().unwrap()) + 0x34) as usize;
-    if vgm_data_offset >= 0xff {
-        vgm_data_offset = 0xff;
-    }
+    let vgm_data_offset = match vgmdata.get(0x34..=0x37) {
+        Some(offset) => u32::from_le_bytes(offset.try_into().unwrap()),
+        None => return Err(Error::InvalidHeader("vgm")),
+    };
+    let vgm_data_offset = (vgm_data_offset.saturating_add(0x34) as usize)
+        .min(0xff)
+        .min(vgmdata.len());
     // The length of vgm_data_offset takes precedence over the length of the header.
     let mut clean_header = [0_u8; 0x100];
     clean_header[..vgm_data_offset].copy_from_slice(&vgmdata[..vgm_data_offset]);
 
     let mut header = match parse_vgm_header(&clean_header) {
         Ok((_, header)) => header,
-        Err(_) => return Err("vgm header parse error."),
+        Err(_) => return Err(Error::InvalidHeader("vgm")),
     };
+    if !(100..200).contains(&header.version) {
+        return Err(Error::UnsupportedVersion(header.version));
+    }
     // Parse extra header
     if header.version >= 170 && header.extra_hdr_ofs != 0 {
-        let extra_header = &vgmdata[(0xbc + header.extra_hdr_ofs as usize)..];
+        let extra_hdr_ofs = (header.extra_hdr_ofs as usize).saturating_add(0xbc);
+        let extra_header = match vgmdata.get(extra_hdr_ofs..) {
+            Some(extra_header) => extra_header,
+            None => return Err(Error::BadOffset(extra_hdr_ofs)),
+        };
         header = match parse_extra_header(extra_header, header) {
             Ok((_, header)) => header,
-            Err(_) => return Err("vgm extra header parse error."),
+            Err(_) => return Err(Error::InvalidHeader("vgm extra")),
         }
     }
     // Parse GD3
-    let gd3 = match parse_gd3(&vgmdata[(0x14 + header.offset_gd3 as usize)..]) {
-        Ok((_, gd3)) => gd3,
-        Err(_) => Gd3::default(), // blank values
+    let gd3 = match vgmdata
+        .get((header.offset_gd3 as usize).saturating_add(0x14)..)
+        .map(parse_gd3)
+    {
+        Some(Ok((_, gd3))) => gd3,
+        _ => Gd3::default(), // blank values
     };
 
     Ok((header, gd3))
 }
 
+///
+/// Operand length of VGM command (0x66 end of sound data and 0x67 data block are not included)
+///
+pub(crate) fn get_vgm_command_length(command: u8) -> Option<usize> {
+    let length = match command {
+        0x30..=0x3f | 0x4f | 0x50 | 0x94 => 1,
+        0x40..=0x4e | 0x51..=0x5f | 0x61 | 0xa0..=0xbf => 2,
+        0x62 | 0x63 | 0x70..=0x8f => 0,
+        0x68 => 11,
+        0x90 | 0x91 | 0x95 => 4,
+        0x92 => 5,
+        0x93 => 10,
+        0xc0..=0xdf => 3,
+        0xe0..=0xff => 4,
+        _ => return None,
+    };
+    Some(length)
+}
+
+///
+/// Data bank type (data block type 0x00-0x3f) played by the stream of the sound chip
+///
+pub(crate) fn get_data_bank_type(sound_chip_type: SoundChipType) -> u8 {
+    match sound_chip_type {
+        SoundChipType::PWM => 0x03,
+        SoundChipType::OKIM6258 => 0x04,
+        SoundChipType::SCSP => 0x06,
+        _ => 0x00, /* YM2612 PCM data */
+    }
+}
+
+///
+/// Rewrite GD3 tag of VGM/VGZ file (re-gzipped if the input is VGZ).
+///
+pub fn update_vgm_gd3(vgm_file: &[u8], gd3: &Gd3) -> Result<Vec<u8>, Error> {
+    let mut vgmdata = Vec::new();
+    if GzDecoder::new(vgm_file).read_to_end(&mut vgmdata).is_ok() {
+        replace_vgm_gd3(&vgmdata, gd3, true)
+    } else {
+        replace_vgm_gd3(vgm_file, gd3, false)
+    }
+}
+
+///
+/// Replace GD3 tag of uncompressed VGM data and fix up eof and GD3 offsets.
+///
+pub(crate) fn replace_vgm_gd3(vgmdata: &[u8], gd3: &Gd3, compress: bool) -> Result<Vec<u8>, Error> {
+    if vgmdata.len() < 0x40 || &vgmdata[0x00..0x04] != b"Vgm " {
+        return Err(Error::InvalidHeader("vgm"));
+    }
+    let offset_gd3 = u32::from_le_bytes(vgmdata[0x14..0x18].try_into().unwrap()) as usize;
+    let gd3_pos = offset_gd3.saturating_add(0x14);
+    let gd3_end = vgmdata
+        .get(gd3_pos..)
+        .filter(|gd3| offset_gd3 != 0 && gd3.len() >= 12 && &gd3[0..4] == b"Gd3 ")
+        .map(|gd3| {
+            let length = u32::from_le_bytes(gd3[8..12].try_into().unwrap()) as usize;
+            gd3_pos.saturating_add(12).saturating_add(length).min(vgmdata.len())
+        });
+    let mut vgm = vgmdata.to_vec();
+    if let Some(gd3_end) = gd3_end {
+        // cut off the current GD3 wherever it is and move the offsets pointing after it
+        let data_pos = match u32::from_le_bytes(vgm[0x34..0x38].try_into().unwrap()) as usize {
+            0 => 0x40,
+            offset => offset.saturating_add(0x34),
+        };
+        if gd3_pos < 0x40 || (gd3_pos < data_pos && data_pos < gd3_end) {
+            return Err(Error::BadOffset(gd3_pos));
+        }
+        let length = gd3_end - gd3_pos;
+        // loop offset, VGM data offset and extra header offset
+        for base in [0x1c, 0x34, 0xbc] {
+            if base + 4 > gd3_pos.min(data_pos) {
+                continue;
+            }
+            let offset = u32::from_le_bytes(vgm[base..base + 4].try_into().unwrap()) as usize;
+            if offset != 0 && base.saturating_add(offset) >= gd3_end {
+                vgm[base..base + 4].copy_from_slice(&((offset - length) as u32).to_le_bytes());
+            }
+        }
+        vgm.drain(gd3_pos..gd3_end);
+    }
+    let gd3_pos = vgm.len();
+    vgm.extend_from_slice(&serialize_gd3(gd3));
+    let eof = vgm.len();
+    vgm[0x04..0x08].copy_from_slice(&((eof - 0x04) as u32).to_le_bytes());
+    vgm[0x14..0x18].copy_from_slice(&((gd3_pos - 0x14) as u32).to_le_bytes());
+
+    if !compress {
+        return Ok(vgm);
+    }
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    match encoder.write_all(&vgm).and_then(|_| encoder.finish()) {
+        Ok(vgz) => Ok(vgz),
+        Err(error) => Err(Error::Compress(error.to_string())),
+    }
+}
+
 impl Jsonlize for VgmHeader {}
 
 #[cfg(test)]
 mod tests {
     use super::parse_vgm_meta;
+    use super::update_vgm_gd3;
     use super::Jsonlize;
+    use crate::driver::error::Error;
+    use crate::driver::gd3meta::{serialize_gd3, Gd3};
+    use flate2::read::GzDecoder;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
     use std::fs::File;
-    use std::io::Read;
+    use std::io::{Read, Write};
 
     #[test]
     fn test_1() {
@@ -465,6 +587,86 @@ mod tests {
         parse("./docs/vgm/c140-v170.vgm")
     }
 
+    #[test]
+    fn update_gd3() {
+        let mut vgm = vec![0_u8; 0x100];
+        vgm[0x00..0x04].copy_from_slice(b"Vgm ");
+        vgm[0x08..0x0c].copy_from_slice(&0x171_u32.to_le_bytes());
+        vgm[0x14..0x18].copy_from_slice(&(0x101_u32 - 0x14).to_le_bytes());
+        vgm[0x34..0x38].copy_from_slice(&0xcc_u32.to_le_bytes());
+        vgm.push(0x66);
+        vgm.extend_from_slice(&serialize_gd3(&Gd3 {
+            track_name: String::from("Before"),
+            ..Default::default()
+        }));
+        let eof = vgm.len() as u32 - 4;
+        vgm[0x04..0x08].copy_from_slice(&eof.to_le_bytes());
+
+        let gd3 = Gd3 {
+            track_name: String::from("After"),
+            game_name: String::from("Game"),
+            notes: String::from("Notes"),
+            ..Default::default()
+        };
+        // vgm
+        let updated = update_vgm_gd3(&vgm, &gd3).unwrap();
+        let (header, updated_gd3) = parse_vgm_meta(&updated).unwrap();
+        assert_eq!(header.offset_gd3, 0x101 - 0x14);
+        assert_eq!(header.eof as usize, updated.len() - 4);
+        assert_eq!(updated[0x100], 0x66);
+        assert_eq!(updated_gd3.track_name, "After");
+        assert_eq!(updated_gd3.game_name, "Game");
+        assert_eq!(updated_gd3.notes, "Notes");
+        // vgz
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&vgm).unwrap();
+        let vgz = encoder.finish().unwrap();
+        let updated_vgz = update_vgm_gd3(&vgz, &gd3).unwrap();
+        let mut extracted = Vec::new();
+        GzDecoder::new(updated_vgz.as_slice())
+            .read_to_end(&mut extracted)
+            .unwrap();
+        assert_eq!(extracted, updated);
+        // not vgm
+        assert!(update_vgm_gd3(&[0_u8; 0x100], &gd3).is_err());
+    }
+
+    #[test]
+    fn update_gd3_before_data() {
+        // GD3 between the header and the VGM data
+        let before = serialize_gd3(&Gd3 {
+            track_name: String::from("Before"),
+            ..Default::default()
+        });
+        let data_pos = 0x100 + before.len();
+        let mut vgm = vec![0_u8; 0x100];
+        vgm[0x00..0x04].copy_from_slice(b"Vgm ");
+        vgm[0x08..0x0c].copy_from_slice(&0x171_u32.to_le_bytes());
+        vgm[0x14..0x18].copy_from_slice(&(0x100_u32 - 0x14).to_le_bytes());
+        vgm[0x1c..0x20].copy_from_slice(&(data_pos as u32 + 1 - 0x1c).to_le_bytes());
+        vgm[0x34..0x38].copy_from_slice(&(data_pos as u32 - 0x34).to_le_bytes());
+        vgm.extend_from_slice(&before);
+        vgm.extend_from_slice(&[0x62, 0x63, 0x66]);
+        let eof = vgm.len() as u32 - 4;
+        vgm[0x04..0x08].copy_from_slice(&eof.to_le_bytes());
+
+        let gd3 = Gd3 {
+            track_name: String::from("After"),
+            ..Default::default()
+        };
+        let updated = update_vgm_gd3(&vgm, &gd3).unwrap();
+        let (header, updated_gd3) = parse_vgm_meta(&updated).unwrap();
+        assert_eq!(updated.len(), vgm.len() - before.len() + serialize_gd3(&gd3).len());
+        assert_eq!(header.vgm_data_offset, 0x100 - 0x34);
+        assert_eq!(header.offset_loop, 0x101 - 0x1c);
+        assert_eq!(header.offset_gd3, 0x103 - 0x14);
+        assert_eq!(updated[0x100..0x103], [0x62, 0x63, 0x66]);
+        assert_eq!(updated_gd3.track_name, "After");
+        // GD3 overlapping the VGM data start
+        vgm[0x34..0x38].copy_from_slice(&(0x104_u32 - 0x34).to_le_bytes());
+        assert_eq!(update_vgm_gd3(&vgm, &gd3).err(), Some(Error::BadOffset(0x100)));
+    }
+
     fn parse(filepath: &str) {
         // load sn76489 vgm file
         let mut file = File::open(filepath).unwrap();
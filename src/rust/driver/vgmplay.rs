@@ -3,9 +3,13 @@
 use flate2::read::GzDecoder;
 use std::collections::HashMap;
 use std::io::prelude::*;
+use std::ops::Range;
 
+use crate::driver::error::Error;
 use crate::driver::gd3meta::Gd3;
 use crate::driver::meta::Jsonlize;
+use crate::driver::vgmanalyze::{analyze_vgm, VgmAnalysis};
+use crate::driver::vgmdecompress::{decompress_data_block, DecompressTable};
 use crate::driver::vgmmeta;
 use crate::driver::vgmmeta::VgmHeader;
 use crate::driver::vgmmeta::ChipVolume;
@@ -24,10 +28,15 @@ pub struct VgmPlay {
     vgm_loop_count: usize,
     vgm_end: bool,
     vgm_data: Vec<u8>,
+    vgm_compressed: bool,
     vgm_header: Option<VgmHeader>,
     vgm_gd3: Option<Gd3>,
+    vgm_error: Option<Error>,
     data_block_id: usize,
+    data_bank: HashMap<u8, Vec<usize>>,
+    decompress_table: Option<DecompressTable>,
     data_stream: HashMap<usize, (SoundChipType, usize)>,
+    data_stream_bank: HashMap<usize, (u8, usize, usize)>,
     ym2612_pcm_pos: usize,
     ym2612_pcm_offset: usize,
     remain_tick_count: usize,
@@ -38,7 +47,7 @@ impl VgmPlay {
     ///
     /// Create sound driver.
     ///
-    pub fn new(sound_slot: SoundSlot, vgm_file: &[u8]) -> Result<Self, &'static str> {
+    pub fn new(sound_slot: SoundSlot, vgm_file: &[u8]) -> Result<Self, Error> {
         let mut vgmplay = VgmPlay {
             sound_slot,
             vgm_pos: 0,
@@ -47,10 +56,15 @@ impl VgmPlay {
             vgm_loop_count: 0,
             vgm_end: false,
             vgm_data: Vec::new(),
+            vgm_compressed: false,
             vgm_header: None,
             vgm_gd3: None,
+            vgm_error: None,
             data_block_id: 0,
+            data_bank: HashMap::new(),
+            decompress_table: None,
             data_stream: HashMap::new(),
+            data_stream_bank: HashMap::new(),
             ym2612_pcm_pos: 0,
             ym2612_pcm_offset: 0,
             remain_tick_count: 0,
@@ -83,6 +97,13 @@ impl VgmPlay {
         self.sound_slot.get_output_sampling_s16le_ref()
     }
 
+    ///
+    /// Return sound slot (for the playlist to read the last chunk).
+    ///
+    pub(crate) fn get_sound_slot(&self) -> &SoundSlot {
+        &self.sound_slot
+    }
+
     ///
     /// Get VGM meta.
     ///
@@ -107,6 +128,70 @@ impl VgmPlay {
         self.vgm_gd3.as_ref().unwrap(/* There always is */).get_json()
     }
 
+    ///
+    /// Walk the whole VGM command stream without rendering.
+    ///
+    pub fn analyze(&self) -> VgmAnalysis {
+        analyze_vgm(
+            &self.vgm_data,
+            self.vgm_header.as_ref().unwrap(/* There always is */),
+        )
+    }
+
+    ///
+    /// Get VGM analysis JSON.
+    ///
+    pub fn get_vgm_analysis_json(&self) -> String {
+        self.analyze().get_json()
+    }
+
+    ///
+    /// Create VGM file with the GD3 tag replaced (gzipped if the source is VGZ).
+    ///
+    pub fn update_gd3(&self, gd3: &Gd3) -> Result<Vec<u8>, Error> {
+        vgmmeta::replace_vgm_gd3(&self.vgm_data, gd3, self.vgm_compressed)
+    }
+
+    ///
+    /// Create VGM file with the GD3 tag replaced by JSON.
+    ///
+    pub fn update_gd3_json(&self, json: &str) -> Result<Vec<u8>, Error> {
+        match serde_json::from_str::<Gd3>(json) {
+            Ok(gd3) => self.update_gd3(&gd3),
+            Err(_) => Err(Error::InvalidHeader("gd3 json")),
+        }
+    }
+
+    ///
+    /// Add YM2608 internal rhythm ROM (not included in VGM file).
+    ///
+    pub fn add_ym2608_rhythm_rom(&mut self, memory: &[u8]) {
+        if memory.is_empty() {
+            return;
+        }
+        let number_of = match &self.vgm_header {
+            Some(header) if header.clock_ym2608 != 0 => self.number_of_chip(header.clock_ym2608),
+            _ => 0,
+        };
+        for sound_chip_index in 0..number_of {
+            self.sound_slot.add_rom(
+                SoundChipType::YM2608,
+                sound_chip_index,
+                RomIndex::YM2608_RHYTHM,
+                memory,
+                0,
+                memory.len() - 1,
+            );
+        }
+    }
+
+    ///
+    /// Get the error that stopped playback (broken VGM data).
+    ///
+    pub fn get_error(&self) -> Option<&Error> {
+        self.vgm_error.as_ref()
+    }
+
     ///
     /// Play Sound.
     ///
@@ -120,7 +205,14 @@ impl VgmPlay {
                 }
             }
             if self.remain_tick_count == 0 {
-                self.remain_tick_count = self.parse_vgm(repeat) as usize;
+                match self.parse_vgm(repeat) {
+                    Ok(wait) => self.remain_tick_count = wait as usize,
+                    Err(error) => {
+                        // stop playback on broken data
+                        self.vgm_error = Some(error);
+                        self.vgm_end = true;
+                    }
+                }
             };
         }
         self.sound_slot.stream();
@@ -138,7 +230,7 @@ impl VgmPlay {
     ///
     /// Extract vgz and initialize sound driver.
     ///
-    fn init(&mut self, vgm_file: &[u8]) -> Result<(), &'static str> {
+    fn init(&mut self, vgm_file: &[u8]) -> Result<(), Error> {
         // try vgz extract to vgm_data
         self.extract(vgm_file);
 
@@ -150,12 +242,19 @@ impl VgmPlay {
                 vgm_header = header;
                 vgm_gd3 = gd3;
             }
-            Err(message) => return Err(message),
+            Err(error) => return Err(error),
         };
 
         self.vgm_loop = vgm_header.offset_loop as usize;
-        self.vgm_loop_offset = (0x1c + vgm_header.offset_loop) as usize;
-        self.vgm_pos = (0x34 + vgm_header.vgm_data_offset) as usize;
+        self.vgm_loop_offset = (vgm_header.offset_loop as usize).saturating_add(0x1c);
+        self.vgm_pos = (vgm_header.vgm_data_offset as usize).saturating_add(0x34);
+        if self.vgm_pos >= self.vgm_data.len() {
+            return Err(Error::BadOffset(self.vgm_pos));
+        }
+        // loop offset outside of the file plays once
+        if self.vgm_loop != 0 && self.vgm_loop_offset >= self.vgm_data.len() {
+            self.vgm_loop = 0;
+        }
 
         self.add_sound_device(&vgm_header);
         self.set_sound_device_volume(&vgm_header.extra_hdr.chip_volume);
@@ -168,16 +267,28 @@ impl VgmPlay {
 
     fn add_sound_device(&mut self, header: &VgmHeader) {
         if header.clock_ym2612 != 0 {
+            // bit 31 is used to select YM3438 (OPN2C)
+            let sound_chip_type = if header.clock_ym2612 & 0x80000000 != 0 {
+                SoundChipType::YM3438
+            } else {
+                SoundChipType::YM2612
+            };
             self.sound_slot.add_sound_device(
-                SoundChipType::YM2612,
-                self.number_of_chip(header.clock_ym2612),
+                sound_chip_type,
+                self.number_of_variant_chip(header.clock_ym2612),
                 header.clock_ym2612 & 0x3fffffff,
             );
         }
         if header.clock_ym2151 != 0 {
+            // bit 31 is used to select YM2164 (OPP)
+            let sound_chip_type = if header.clock_ym2151 & 0x80000000 != 0 {
+                SoundChipType::YM2164
+            } else {
+                SoundChipType::YM2151
+            };
             self.sound_slot.add_sound_device(
-                SoundChipType::YM2151,
-                self.number_of_chip(header.clock_ym2151),
+                sound_chip_type,
+                self.number_of_variant_chip(header.clock_ym2151),
                 header.clock_ym2151 & 0x3fffffff,
             );
         }
@@ -196,21 +307,27 @@ impl VgmPlay {
             );
         }
         if header.clock_ay8910 != 0 {
-            let clock_ay8910: u32;
-            if header.clock_ym2151 != 0 {
-                // X1 Turbo sync YM2151
-                clock_ay8910 = header.clock_ym2151;
-            } else if header.clock_ym2413 != 0 {
-                // MSX sync YM2413
-                clock_ay8910 = header.clock_ym2413;
-            } else {
-                clock_ay8910 = header.clock_ay8910 * 2;
-            }
+            let number_of = self.number_of_chip(header.clock_ay8910);
             self.sound_slot.add_sound_device(
-                SoundChipType::YM2149,
-                self.number_of_chip(header.clock_ay8910),
-                clock_ay8910 & 0x3fffffff,
+                SoundChipType::AY8910,
+                number_of,
+                header.clock_ay8910 & 0x3fffffff,
             );
+            for sound_chip_index in 0..number_of {
+                // chip type (header 0x78) and flags (header 0x79)
+                self.sound_slot.write(
+                    SoundChipType::AY8910,
+                    sound_chip_index,
+                    0x100,
+                    header.ay8910_flag & 0xff,
+                );
+                self.sound_slot.write(
+                    SoundChipType::AY8910,
+                    sound_chip_index,
+                    0x101,
+                    (header.ay8910_flag >> 8) & 0xff,
+                );
+            }
         }
         if header.clock_ym2608 != 0 {
             self.sound_slot.add_sound_device(
@@ -220,9 +337,15 @@ impl VgmPlay {
             );
         }
         if header.clock_ym2610_b != 0 {
+            // bit 31 is used to select YM2610B
+            let sound_chip_type = if header.clock_ym2610_b & 0x80000000 != 0 {
+                SoundChipType::YM2610B
+            } else {
+                SoundChipType::YM2610
+            };
             self.sound_slot.add_sound_device(
-                SoundChipType::YM2610, // TODO:
-                self.number_of_chip(header.clock_ym2610_b),
+                sound_chip_type,
+                self.number_of_variant_chip(header.clock_ym2610_b),
                 header.clock_ym2610_b & 0x3fffffff,
             );
         }
@@ -262,8 +385,38 @@ impl VgmPlay {
             );
         }
         if header.clock_sn76489 != 0 {
-            self.sound_slot
-                .add_sound_device(SoundChipType::SEGAPSG, 1, header.clock_sn76489);
+            let number_of = self.number_of_chip(header.clock_sn76489);
+            self.sound_slot.add_sound_device(
+                SoundChipType::SEGAPSG,
+                number_of,
+                header.clock_sn76489 & 0x3fffffff,
+            );
+            for sound_chip_index in 0..number_of {
+                // feedback and shift register width (VGM 1.10 and later)
+                if header.sn76489_fb != 0 {
+                    self.sound_slot.write(
+                        SoundChipType::SEGAPSG,
+                        sound_chip_index,
+                        0x10,
+                        header.sn76489_fb.into(),
+                    );
+                    self.sound_slot.write(
+                        SoundChipType::SEGAPSG,
+                        sound_chip_index,
+                        0x11,
+                        header.sn76489_w.into(),
+                    );
+                }
+                // flags (VGM 1.51 and later)
+                if header.version >= 151 {
+                    self.sound_slot.write(
+                        SoundChipType::SEGAPSG,
+                        sound_chip_index,
+                        0x12,
+                        header.sn76489_f.into(),
+                    );
+                }
+            }
         }
         if header.clock_pwm != 0 {
             self.sound_slot
@@ -357,6 +510,40 @@ impl VgmPlay {
                 );
             }
         }
+        if header.clock_ym271 != 0 {
+            self.sound_slot.add_sound_device(
+                SoundChipType::YMF271,
+                self.number_of_chip(header.clock_ym271),
+                header.clock_ym271 & 0x3fffffff,
+            );
+        }
+        if header.clock_vsu != 0 {
+            self.sound_slot.add_sound_device(
+                SoundChipType::VSU,
+                self.number_of_chip(header.clock_vsu),
+                header.clock_vsu & 0x3fffffff,
+            );
+        }
+        if header.clock_scsp != 0 {
+            self.sound_slot.add_sound_device(
+                SoundChipType::SCSP,
+                self.number_of_chip(header.clock_scsp),
+                header.clock_scsp & 0x3fffffff,
+            );
+        }
+        if header.clock_k051649 != 0 {
+            // bit 31 is used to select K052539 (SCC+), so that dual chip is bit 30 only
+            let sound_chip_type = if header.clock_k051649 & 0x80000000 != 0 {
+                SoundChipType::K052539
+            } else {
+                SoundChipType::K051649
+            };
+            self.sound_slot.add_sound_device(
+                sound_chip_type,
+                self.number_of_variant_chip(header.clock_k051649),
+                header.clock_k051649 & 0x3fffffff,
+            );
+        }
     }
 
     fn set_sound_device_volume(&mut self, _volume: &[ChipVolume]) {
@@ -367,24 +554,43 @@ impl VgmPlay {
         let mut d = GzDecoder::new(vgm_file);
         if d.read_to_end(&mut self.vgm_data).is_err() {
             self.vgm_data = vgm_file.to_vec();
+        } else {
+            self.vgm_compressed = true;
         }
     }
 
-    fn get_vgm_u8(&mut self) -> u8 {
-        let ret = self.vgm_data[self.vgm_pos];
+    fn get_vgm_u8(&mut self) -> Result<u8, Error> {
+        let ret = match self.vgm_data.get(self.vgm_pos) {
+            Some(ret) => *ret,
+            None => return Err(Error::UnexpectedEof(self.vgm_pos)),
+        };
         self.vgm_pos += 1;
-        ret
+        Ok(ret)
+    }
+
+    fn get_vgm_u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from(self.get_vgm_u8()?) + (u16::from(self.get_vgm_u8()?) << 8))
     }
 
-    fn get_vgm_u16(&mut self) -> u16 {
-        u16::from(self.get_vgm_u8()) + (u16::from(self.get_vgm_u8()) << 8)
+    fn get_vgm_u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from(self.get_vgm_u8()?)
+            + (u32::from(self.get_vgm_u8()?) << 8)
+            + (u32::from(self.get_vgm_u8()?) << 16)
+            + (u32::from(self.get_vgm_u8()?) << 24))
     }
 
-    fn get_vgm_u32(&mut self) -> u32 {
-        u32::from(self.get_vgm_u8())
-            + (u32::from(self.get_vgm_u8()) << 8)
-            + (u32::from(self.get_vgm_u8()) << 16)
-            + (u32::from(self.get_vgm_u8()) << 24)
+    ///
+    /// Skip length bytes and return the range of them.
+    ///
+    fn get_vgm_range(&mut self, length: usize) -> Result<Range<usize>, Error> {
+        let start = self.vgm_pos;
+        match start.checked_add(length) {
+            Some(end) if end <= self.vgm_data.len() => {
+                self.vgm_pos = end;
+                Ok(start..end)
+            }
+            _ => Err(Error::UnexpectedEof(start)),
+        }
     }
 
     fn number_of_chip(&self, clock: u32) -> usize {
@@ -397,19 +603,50 @@ impl VgmPlay {
         }
     }
 
-    fn parse_vgm(&mut self, repeat: bool) -> u16 {
+    fn number_of_variant_chip(&self, clock: u32) -> usize {
+        // Bit 31 selects the chip variant, so that dual chip is bit 30 only.
+        if clock & 0x40000000 != 0 {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn parse_vgm(&mut self, repeat: bool) -> Result<u16, Error> {
         let mut wait: u16 = 0;
 
-        let command = self.get_vgm_u8();
+        let command = self.get_vgm_u8()?;
         match command {
-            0x50 => {
-                let dat = self.get_vgm_u8();
+            0x50 | 0x30 => {
+                // 0x30: dd: second SN76489
+                let dat = self.get_vgm_u8()?;
+                let sound_chip_index = if command == 0x30 { 1 } else { 0 };
+                self.sound_slot
+                    .write(SoundChipType::SEGAPSG, sound_chip_index, 0, dat.into());
+            }
+            0x4f | 0x3f => {
+                // Game Gear PSG stereo, write dd to port 0x06 (0x3f: second SN76489)
+                let dat = self.get_vgm_u8()?;
+                let sound_chip_index = if command == 0x3f { 1 } else { 0 };
                 self.sound_slot
-                    .write(SoundChipType::SEGAPSG, 0, 0, dat.into());
+                    .write(SoundChipType::SEGAPSG, sound_chip_index, 1, dat.into());
+            }
+            0x31 => {
+                // AY8910 stereo mask (bit 0-5: channel A-C left / right, bit 6: YM2203 SSG,
+                // bit 7: second chip)
+                let dat = self.get_vgm_u8()?;
+                if dat & 0x40 == 0 {
+                    self.sound_slot.write(
+                        SoundChipType::AY8910,
+                        (dat >> 7) as usize,
+                        0x102,
+                        (dat & 0x3f).into(),
+                    );
+                }
             }
             0x51 | 0xa1 => {
-                let reg = self.get_vgm_u8();
-                let dat = self.get_vgm_u8();
+                let reg = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
                 self.sound_slot.write(
                     SoundChipType::YM2413,
                     (command >> 7) as usize,
@@ -418,38 +655,38 @@ impl VgmPlay {
                 );
             }
             0x52 | 0xa2 => {
-                let reg = self.get_vgm_u8();
-                let dat = self.get_vgm_u8();
+                let reg = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
                 self.sound_slot.write(
-                    SoundChipType::YM2612,
+                    self.get_ym2612_chip_type(),
                     (command >> 7) as usize,
                     reg as u32,
                     dat.into(),
                 );
             }
             0x53 | 0xa3 => {
-                let reg = self.get_vgm_u8();
-                let dat = self.get_vgm_u8();
+                let reg = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
                 self.sound_slot.write(
-                    SoundChipType::YM2612,
+                    self.get_ym2612_chip_type(),
                     (command >> 7) as usize,
                     reg as u32 | 0x100,
                     dat.into(),
                 );
             }
             0x54 | 0xa4 => {
-                let reg = self.get_vgm_u8();
-                let dat = self.get_vgm_u8();
+                let reg = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
                 self.sound_slot.write(
-                    SoundChipType::YM2151,
+                    self.get_ym2151_chip_type(),
                     (command >> 7) as usize,
                     reg as u32,
                     dat.into(),
                 );
             }
             0x55 | 0xa5 => {
-                let reg = self.get_vgm_u8();
-                let dat = self.get_vgm_u8();
+                let reg = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
                 self.sound_slot.write(
                     SoundChipType::YM2203,
                     (command >> 7) as usize,
@@ -459,8 +696,8 @@ impl VgmPlay {
             }
             0x56 | 0xa6 => {
                 // YM2608 port 0 write
-                let reg = self.get_vgm_u8();
-                let dat = self.get_vgm_u8();
+                let reg = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
                 self.sound_slot.write(
                     SoundChipType::YM2608,
                     (command >> 7) as usize,
@@ -470,8 +707,8 @@ impl VgmPlay {
             }
             0x57 | 0xa7 => {
                 // YM2608 port 1 write
-                let reg = self.get_vgm_u8();
-                let dat = self.get_vgm_u8();
+                let reg = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
                 self.sound_slot.write(
                     SoundChipType::YM2608,
                     (command >> 7) as usize,
@@ -481,10 +718,10 @@ impl VgmPlay {
             }
             0x58 | 0xa8 => {
                 // YM2610 port 0 write
-                let reg = self.get_vgm_u8();
-                let dat = self.get_vgm_u8();
+                let reg = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
                 self.sound_slot.write(
-                    SoundChipType::YM2610,
+                    self.get_ym2610_chip_type(),
                     (command >> 7) as usize,
                     reg as u32,
                     dat.into(),
@@ -492,18 +729,18 @@ impl VgmPlay {
             }
             0x59 | 0xa9 => {
                 // YM2610 port 1 write
-                let reg = self.get_vgm_u8();
-                let dat = self.get_vgm_u8();
+                let reg = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
                 self.sound_slot.write(
-                    SoundChipType::YM2610,
+                    self.get_ym2610_chip_type(),
                     (command >> 7) as usize,
                     reg as u32 | 0x100,
                     dat.into(),
                 );
             }
             0x5a | 0xaa => {
-                let reg = self.get_vgm_u8();
-                let dat = self.get_vgm_u8();
+                let reg = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
                 self.sound_slot.write(
                     SoundChipType::YM3812,
                     (command >> 7) as usize,
@@ -512,8 +749,8 @@ impl VgmPlay {
                 );
             }
             0x5b | 0xab => {
-                let reg = self.get_vgm_u8();
-                let dat = self.get_vgm_u8();
+                let reg = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
                 self.sound_slot.write(
                     SoundChipType::YM3526,
                     (command >> 7) as usize,
@@ -522,8 +759,8 @@ impl VgmPlay {
                 );
             }
             0x5c | 0xac => {
-                let reg = self.get_vgm_u8();
-                let dat = self.get_vgm_u8();
+                let reg = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
                 self.sound_slot.write(
                     SoundChipType::Y8950,
                     (command >> 7) as usize,
@@ -533,8 +770,8 @@ impl VgmPlay {
             }
             0x5e | 0xae => {
                 // YMF262 port 0 write
-                let reg = self.get_vgm_u8();
-                let dat = self.get_vgm_u8();
+                let reg = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
                 self.sound_slot.write(
                     SoundChipType::YMF262,
                     (command >> 7) as usize,
@@ -544,8 +781,8 @@ impl VgmPlay {
             }
             0x5f | 0xaf => {
                 // YMF262 port 1 write
-                let reg = self.get_vgm_u8();
-                let dat = self.get_vgm_u8();
+                let reg = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
                 self.sound_slot.write(
                     SoundChipType::YMF262,
                     (command >> 7) as usize,
@@ -554,7 +791,7 @@ impl VgmPlay {
                 );
             }
             0x61 => {
-                wait = self.get_vgm_u16();
+                wait = self.get_vgm_u16()?;
             }
             0x62 => {
                 wait = 735;
@@ -574,51 +811,77 @@ impl VgmPlay {
             }
             0x67 => {
                 // 0x66 compatibility command to make older players stop parsing the stream
-                self.get_vgm_u8();
-                let data_type = self.get_vgm_u8();
-                let mut data_length = self.get_vgm_u32() as usize;
+                self.get_vgm_u8()?;
+                let data_type = self.get_vgm_u8()?;
+                let mut data_length = self.get_vgm_u32()? as usize;
                 // dual sound chip
                 let sound_chip_index = if data_length & 0x80000000 != 0 { 1 } else { 0 };
                 data_length &= 0x7fffffff;
                 // data position
                 let data_block_pos = self.vgm_pos;
-                self.vgm_pos += data_length;
+                let data_block = self.get_vgm_range(data_length)?;
+                let data_block = &self.vgm_data[data_block];
                 // handle data block
                 if (0x00..=0x3f).contains(&data_type) {
                     // add data block (support uncompressed)
-                    self.sound_slot.add_data_block(
-                        self.data_block_id,
-                        &self.vgm_data[data_block_pos..data_block_pos + data_length],
-                    );
-                    // data_block_id is a sequence id in vgm
-                    self.data_block_id += 1;
+                    self.sound_slot
+                        .add_data_block(self.data_block_id, data_block);
+                    self.add_data_bank(data_type);
+                } else if (0x40..=0x7e).contains(&data_type) {
+                    // add data block (compressed)
+                    let data_block =
+                        decompress_data_block(data_block, self.decompress_table.as_ref());
+                    if let Some(data_block) = data_block {
+                        self.sound_slot
+                            .add_data_block(self.data_block_id, &data_block);
+                    }
+                    self.add_data_bank(data_type - 0x40);
+                } else if data_type == 0x7f {
+                    // decompression table
+                    self.decompress_table = DecompressTable::new(data_block);
                 } else if (0x80..=0xbf).contains(&data_type) {
                     // ROM/RAM Image dumps
-                    let _real_rom_size = u32::from_le_bytes(
-                        self.vgm_data[data_block_pos..(data_block_pos + 4)]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let start_address = u32::from_le_bytes(
-                        self.vgm_data[(data_block_pos + 4)..(data_block_pos + 8)]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let mut data_size = data_length - 8;
-                    if data_size == 0 {
-                        data_size = 1;
+                    if data_block.len() < 8 {
+                        return Err(Error::InvalidDataBlock(data_block_pos));
                     }
+                    let _real_rom_size = u32::from_le_bytes(data_block[0..4].try_into().unwrap());
+                    let start_address = u32::from_le_bytes(data_block[4..8].try_into().unwrap());
+                    let data = &data_block[8..];
                     let start_address = start_address as usize;
                     let (rom_index, sound_chip_type): (RomIndex, Option<SoundChipType>) =
                         self.get_rom_index(data_type);
-                    if rom_index != RomIndex::NOT_SUPPOTED {
+                    if rom_index != RomIndex::NOT_SUPPOTED && !data.is_empty() {
                         self.sound_slot.add_rom(
                             sound_chip_type.unwrap(),
                             sound_chip_index,
                             rom_index,
-                            &self.vgm_data[(data_block_pos + 8)..(data_block_pos + 8) + data_size],
+                            data,
                             start_address,
-                            start_address + data_size - 1,
+                            start_address.saturating_add(data.len() - 1),
+                        );
+                    }
+                } else if (0xc0..=0xff).contains(&data_type) {
+                    // RAM writes (0xc0..=0xdf: 16-bit address, 0xe0..=0xff: 32-bit address)
+                    let header_size = if data_type < 0xe0 { 2 } else { 4 };
+                    if data_block.len() < header_size {
+                        return Err(Error::InvalidDataBlock(data_block_pos));
+                    }
+                    let start_address = if data_type < 0xe0 {
+                        u16::from_le_bytes(data_block[0..2].try_into().unwrap()) as usize
+                    } else {
+                        u32::from_le_bytes(data_block[0..4].try_into().unwrap()) as usize
+                    };
+                    let data = &data_block[header_size..];
+                    let (rom_index, sound_chip_type): (RomIndex, Option<SoundChipType>) =
+                        self.get_rom_index(data_type);
+                    if rom_index != RomIndex::NOT_SUPPOTED && !data.is_empty() {
+                        self.sound_slot.add_rom(
+                            sound_chip_type.unwrap(),
+                            sound_chip_index,
+                            rom_index,
+                            data,
+                            start_address,
+                            start_address.saturating_add(data.len() - 1),
                         );
                     }
                 }
@@ -630,23 +893,30 @@ impl VgmPlay {
                 // YM2612 port 0 address 2A write from the data bank, then wait n samples;
                 // n can range from 0 to 15. Note that the wait is n, NOT n+1.
                 // See also command 0xE0.
-                let ym2612_block = self
+                let data = self
                     .sound_slot
-                    .get_data_block(/* YM2612 data block 0 fixed */ 0);
-                let data = ym2612_block[self.ym2612_pcm_pos + self.ym2612_pcm_offset];
-                self.sound_slot
-                    .write(SoundChipType::YM2612, 0, 0x2a, data.into());
+                    .get_data_block(/* YM2612 data block 0 fixed */ 0)
+                    .and_then(|ym2612_block| {
+                        ym2612_block
+                            .get(self.ym2612_pcm_pos.saturating_add(self.ym2612_pcm_offset))
+                            .copied()
+                    });
+                // ignore reads outside of the data bank
+                if let Some(data) = data {
+                    self.sound_slot
+                        .write(self.get_ym2612_chip_type(), 0, 0x2a, data.into());
+                }
                 self.ym2612_pcm_offset += 1;
                 wait = (command & 0x0f).into();
             }
             0x90 => {
                 // Setup Stream Control
-                let data_stream_id = self.get_vgm_u8() as usize;
-                let chip_type = self.get_vgm_u8();
-                let write_port = self.get_vgm_u8() as u32;
-                let write_reg = self.get_vgm_u8() as u32;
+                let data_stream_id = self.get_vgm_u8()? as usize;
+                let chip_type = self.get_vgm_u8()?;
+                let write_port = self.get_vgm_u8()? as u32;
+                let write_reg = self.get_vgm_u8()? as u32;
                 // create new stream
-                let sound_chip_type = Self::get_chip_type(chip_type);
+                let sound_chip_type = self.get_chip_type(chip_type);
                 let sound_chip_index = (chip_type >> 7) as usize;
                 if let Some(sound_chip_type) = sound_chip_type {
                     self.sound_slot.add_data_stream(
@@ -662,26 +932,19 @@ impl VgmPlay {
             }
             0x91 => {
                 // Set Stream Data
-                let data_stream_id = self.get_vgm_u8() as usize;
-                let data_block_id = self.get_vgm_u8() as usize;
-                let /* TODO: */ _step_base = self.get_vgm_u8();
-                let /* TODO: */ _step_size = self.get_vgm_u8();
-                // assosiate data block to stream
-                if let Some((sound_chip_type, sound_chip_index)) =
-                    self.data_stream.get(&data_stream_id)
-                {
-                    self.sound_slot.attach_data_block_to_stream(
-                        *sound_chip_type,
-                        *sound_chip_index,
-                        data_stream_id,
-                        data_block_id,
-                    );
-                }
+                let data_stream_id = self.get_vgm_u8()? as usize;
+                let data_bank_type = self.get_vgm_u8()?;
+                let step_size = self.get_vgm_u8()? as usize;
+                let step_base = self.get_vgm_u8()? as usize;
+                // assosiate data bank to stream (from the first data block)
+                self.data_stream_bank
+                    .insert(data_stream_id, (data_bank_type, step_base, step_size));
+                self.attach_data_bank(data_stream_id, 0);
             }
             0x92 => {
                 // Set Stream Frequency
-                let data_stream_id = self.get_vgm_u8() as usize;
-                let frequency = self.get_vgm_u32();
+                let data_stream_id = self.get_vgm_u8()? as usize;
+                let frequency = self.get_vgm_u32()?;
                 if let Some((sound_chip_type, sound_chip_index)) =
                     self.data_stream.get(&data_stream_id)
                 {
@@ -695,11 +958,22 @@ impl VgmPlay {
             }
             0x93 => {
                 // Start Stream
-                let data_stream_id = self.get_vgm_u8() as usize;
-                let data_stream_start_offset = self.get_vgm_u32() as usize;
-                let /* TODO */ _length_mode = self.get_vgm_u8();
-                let pcm_stream_length = self.get_vgm_u32() as usize;
-                // initalize stream and start playback (set pcm_stream_length)
+                let data_stream_id = self.get_vgm_u8()? as usize;
+                let data_stream_start_offset = self.get_vgm_u32()?;
+                let length_mode = self.get_vgm_u8()?;
+                let pcm_stream_length = self.get_vgm_u32()? as usize;
+                // -1 means don't change the data start offset
+                let data_stream_start_offset = if data_stream_start_offset == 0xffffffff {
+                    None
+                } else {
+                    // offset in the data bank to the data block and the offset in the block
+                    let data_stream_start_offset = data_stream_start_offset as usize;
+                    Some(
+                        self.attach_data_bank(data_stream_id, data_stream_start_offset)
+                            .unwrap_or(data_stream_start_offset),
+                    )
+                };
+                // initalize stream and start playback
                 if let Some((sound_chip_type, sound_chip_index)) =
                     self.data_stream.get(&data_stream_id)
                 {
@@ -708,13 +982,14 @@ impl VgmPlay {
                         *sound_chip_index,
                         data_stream_id,
                         data_stream_start_offset,
+                        length_mode,
                         pcm_stream_length,
                     );
                 }
             }
             0x94 => {
                 // Stop Stream
-                let data_stream_id = self.get_vgm_u8() as usize;
+                let data_stream_id = self.get_vgm_u8()? as usize;
                 // stop stream (set pcm_stream_length to 0)
                 if let Some((sound_chip_type, sound_chip_index)) =
                     self.data_stream.get(&data_stream_id)
@@ -728,32 +1003,49 @@ impl VgmPlay {
             }
             0x95 => {
                 // Start Stream (fast call)
-                let data_stream_id = self.get_vgm_u8() as usize;
-                let data_block_id = self.get_vgm_u16() as usize;
-                let /* TODO */ _flags = self.get_vgm_u8();
+                let data_stream_id = self.get_vgm_u8()? as usize;
+                let bank_block_id = self.get_vgm_u16()? as usize;
+                let flags = self.get_vgm_u8()?;
                 // initalize stream and start playback (set pcm_stream_length to data block size)
                 if let Some((sound_chip_type, sound_chip_index)) =
-                    self.data_stream.get(&data_stream_id)
+                    self.data_stream.get(&data_stream_id).copied()
                 {
-                    self.sound_slot.start_data_stream_fast(
-                        *sound_chip_type,
-                        *sound_chip_index,
-                        data_stream_id,
-                        data_block_id,
-                    );
+                    // block id is the number of the data block in the data bank of the stream
+                    let data_bank_type = self
+                        .data_stream_bank
+                        .get(&data_stream_id)
+                        .map_or_else(|| vgmmeta::get_data_bank_type(sound_chip_type), |bank| bank.0);
+                    let data_block_id = self
+                        .data_bank
+                        .get(&data_bank_type)
+                        .and_then(|data_bank| data_bank.get(bank_block_id))
+                        .copied();
+                    if let Some(data_block_id) = data_block_id {
+                        self.sound_slot.start_data_stream_fast(
+                            sound_chip_type,
+                            sound_chip_index,
+                            data_stream_id,
+                            data_block_id,
+                            flags,
+                        );
+                    }
                 }
             }
             0xa0 => {
-                // TODO: YM2149 as AY8910, write
-                let reg = self.get_vgm_u8();
-                let dat = self.get_vgm_u8();
-                self.sound_slot
-                    .write(SoundChipType::YM2149, 0, reg as u32, dat.into());
+                // AY8910, write value dd to register aa (bit 7 selects the second chip)
+                let reg = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
+                self.sound_slot.write(
+                    SoundChipType::AY8910,
+                    (reg >> 7) as usize,
+                    (reg & 0x7f) as u32,
+                    dat.into(),
+                );
             }
             0xb2 => {
                 // PWM, write value ddd to register a (d is MSB, dd is LSB)
-                let offset = self.get_vgm_u8();
-                let data = self.get_vgm_u8();
+                let offset = self.get_vgm_u8()?;
+                let data = self.get_vgm_u8()?;
                 // hack (bad rip detected, enabling sega32x channels)
                 if self.hack_sega32x_channel >= 0 {
                     if offset & 0xf0 == 0 {
@@ -776,8 +1068,8 @@ impl VgmPlay {
             }
             0xb7 => {
                 // OKIM6258, write value dd to register aa
-                let offset = self.get_vgm_u8();
-                let dat = self.get_vgm_u8();
+                let offset = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
                 if offset & 0x80 != 0 {
                     self.sound_slot.write(
                         SoundChipType::OKIM6258,
@@ -796,8 +1088,8 @@ impl VgmPlay {
             }
             0xb8 => {
                 // OKIM6295, write value dd to register aa
-                let offset = self.get_vgm_u8();
-                let dat = self.get_vgm_u8();
+                let offset = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
                 if offset & 0x80 != 0 {
                     self.sound_slot.write(
                         SoundChipType::OKIM6295,
@@ -815,15 +1107,66 @@ impl VgmPlay {
                 }
             }
             0xc0 => {
-                let offset = self.get_vgm_u16();
-                let dat = self.get_vgm_u8();
+                let offset = self.get_vgm_u16()?;
+                let dat = self.get_vgm_u8()?;
                 self.sound_slot
                     .write(SoundChipType::SEGAPCM, 0, u32::from(offset), dat.into());
             }
+            0xc5 => {
+                // SCSP, write value dd to memory offset mmll (mm - offset MSB, ll - offset LSB)
+                let offset = u16::from(self.get_vgm_u8()?) << 8 | u16::from(self.get_vgm_u8()?);
+                let dat = self.get_vgm_u8()?;
+                self.sound_slot
+                    .write_offset16(SoundChipType::SCSP, offset, dat.into());
+            }
+            0xc7 => {
+                // VSU, write value dd to memory offset mmll (mm - offset MSB, ll - offset LSB)
+                let offset = u16::from(self.get_vgm_u8()?) << 8 | u16::from(self.get_vgm_u8()?);
+                let dat = self.get_vgm_u8()?;
+                self.sound_slot
+                    .write_offset16(SoundChipType::VSU, offset, dat.into());
+            }
+            0xd0 => {
+                // YMF278B, port pp, write value dd to register aa
+                let port = self.get_vgm_u8()?;
+                let offset = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
+                self.sound_slot.write(
+                    SoundChipType::YMF278B,
+                    (port >> 7) as usize,
+                    u32::from(port & 0x7f) << 8 | u32::from(offset),
+                    dat.into(),
+                );
+            }
+            0xd1 => {
+                // YMF271, port pp, write value dd to register aa
+                let port = self.get_vgm_u8()?;
+                let offset = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
+                self.sound_slot.write(
+                    SoundChipType::YMF271,
+                    (port >> 7) as usize,
+                    u32::from(port & 0x7f) << 8 | u32::from(offset),
+                    dat.into(),
+                );
+            }
+            0xd2 => {
+                // K051649 (SCC1), port pp, write value dd to register aa
+                let port = self.get_vgm_u8()?;
+                let offset = self.get_vgm_u8()?;
+                let dat = self.get_vgm_u8()?;
+                let sound_chip_type = self.get_k051649_chip_type();
+                self.sound_slot.write(
+                    sound_chip_type,
+                    (port >> 7) as usize,
+                    u32::from(port & 0x7f) << 8 | u32::from(offset),
+                    dat.into(),
+                );
+            }
             0xd4 => {
                 // C140, write value dd to register ppaa
-                let offset = u16::from(self.get_vgm_u8()) << 8 | u16::from(self.get_vgm_u8());
-                let dat = self.get_vgm_u8();
+                let offset = u16::from(self.get_vgm_u8()?) << 8 | u16::from(self.get_vgm_u8()?);
+                let dat = self.get_vgm_u8()?;
                 // select chip type
                 let sound_chip_type = self.get_c140_chip_type();
                 if offset & 0x8000 != 0 {
@@ -836,60 +1179,60 @@ impl VgmPlay {
             }
             0xe0 => {
                 // YM2612 data block 0
-                let pcm_pos = self.get_vgm_u32() as usize;
+                let pcm_pos = self.get_vgm_u32()? as usize;
                 self.ym2612_pcm_pos = pcm_pos;
                 self.ym2612_pcm_offset = 0;
             }
-            // unsupport
-            0x30..=0x3f | 0x4f => {
-                // 0x4f: dd: Game Gear PSG stereo, write dd to port 0x06
-                self.get_vgm_u8();
-            }
-            0x40..=0x4e | 0x5d | 0xb0..=0xb6 | 0xb9..=0xbf => {
-                // 0x5d: aa dd: YMZ280B, write value dd to register aa
-                // 0xb0: aa dd: RF5C68, write value dd to register aa
-                // 0xb1: aa dd: RF5C164, write value dd to register aa
-                // 0xb3: aa dd: GameBoy DMG, write value dd to register aa
-                // 0xb4: aa dd: NES APU, write value dd to register aa
-                // 0xb5: aa dd: MultiPCM, write value dd to register aa
-                // 0xb6: aa dd: uPD7759, write value dd to register aa
-                // 0xb9: aa dd: HuC6280, write value dd to register aa
-                // 0xba: aa dd: K053260, write value dd to register aa
-                // 0xbb: aa dd: Pokey, write value dd to register aa
-                // 0xbc: aa dd: WonderSwan, write value dd to register aa
-                // 0xbd: aa dd: SAA1099, write value dd to register aa
-                // 0xbe: aa dd: ES5506, write value dd to register aa
-                // 0xbf: aa dd: GA20, write value dd to register aa
-                self.get_vgm_u8();
-                self.get_vgm_u8();
-            }
-            0xc9..=0xcf | 0xd7..=0xdf | 0xc1..=0xc8 | 0xd1..=0xd3 | 0xd5..=0xd6 => {
-                // 0xc1: bbaa dd: RF5C68, write value dd to memory offset aabb
-                // 0xc2: bbaa dd: RF5C164, write value dd to memory offset aabb
-                // 0xc3: cc bbaa: MultiPCM, write set bank offset aabb to channel cc
-                // 0xc4: mmll rr: QSound, write value mmll to register rr (mm - data MSB, ll - data LSB)
-                // 0xc5: mmll dd: SCSP, write value dd to memory offset mmll (mm - offset MSB, ll - offset LSB)
-                // 0xc6: mmll dd: WonderSwan, write value dd to memory offset mmll (mm - offset MSB, ll - offset LSB)
-                // 0xc7: mmll dd: VSU, write value dd to memory offset mmll (mm - offset MSB, ll - offset LSB)
-                // 0xc8: mmll dd: X1-010, write value dd to memory offset mmll (mm - offset MSB, ll - offset LSB)
-                // 0xd1: pp aa dd: YMF271, port pp, write value dd to register aa
-                // 0xd2: pp aa dd: SCC1, port pp, write value dd to register aa
-                // 0xd3: pp aa dd: K054539, write value dd to register ppaa
-                // 0xd5: pp aa dd: ES5503, write value dd to register ppaa
-                // 0xd6: pp aa dd: ES5506, write value aadd to register pp
-                self.get_vgm_u16();
-                self.get_vgm_u8();
-            }
-            _ => {
-                #[cfg(not(target_arch = "wasm32"))]
-                println!(
-                    "unknown cmd at {:x}: {:x}",
-                    self.vgm_pos - 1,
-                    self.vgm_data[self.vgm_pos - 1]
-                );
+            // unsupport (skip the operands)
+            _ => match vgmmeta::get_vgm_command_length(command) {
+                Some(length) => {
+                    self.get_vgm_range(length)?;
+                }
+                None => return Err(Error::UnknownCommand(self.vgm_pos - 1, command)),
+            },
+        }
+        Ok(wait)
+    }
+
+    ///
+    /// Add the data block to the data bank of the type (data block id is a sequence id in vgm).
+    ///
+    fn add_data_bank(&mut self, data_type: u8) {
+        self.data_bank
+            .entry(data_type)
+            .or_default()
+            .push(self.data_block_id);
+        self.data_block_id += 1;
+    }
+
+    ///
+    /// Attach the data block at the data bank offset to the stream.
+    ///
+    /// Returns the offset in the attached data block.
+    ///
+    fn attach_data_bank(&mut self, data_stream_id: usize, bank_offset: usize) -> Option<usize> {
+        let (sound_chip_type, sound_chip_index) = *self.data_stream.get(&data_stream_id)?;
+        let (data_bank_type, step_base, step_size) = *self.data_stream_bank.get(&data_stream_id)?;
+        // data bank is the concatenation of the data blocks
+        let mut offset = bank_offset;
+        let mut data_block_id = None;
+        for id in self.data_bank.get(&data_bank_type)? {
+            let length = self.sound_slot.get_data_block(*id).map_or(0, |block| block.len());
+            if offset < length {
+                data_block_id = Some(*id);
+                break;
             }
+            offset -= length;
         }
-        wait
+        self.sound_slot.attach_data_block_to_stream(
+            sound_chip_type,
+            sound_chip_index,
+            data_stream_id,
+            data_block_id?,
+            step_base,
+            step_size,
+        );
+        Some(offset)
     }
 
     fn get_c140_chip_type(&self) -> SoundChipType {
@@ -900,58 +1243,92 @@ impl VgmPlay {
         }
     }
 
+    fn get_ym2612_chip_type(&self) -> SoundChipType {
+        if self.vgm_header.as_ref().unwrap().clock_ym2612 & 0x80000000 != 0 {
+            SoundChipType::YM3438
+        } else {
+            SoundChipType::YM2612
+        }
+    }
+
+    fn get_ym2151_chip_type(&self) -> SoundChipType {
+        if self.vgm_header.as_ref().unwrap().clock_ym2151 & 0x80000000 != 0 {
+            SoundChipType::YM2164
+        } else {
+            SoundChipType::YM2151
+        }
+    }
+
+    fn get_ym2610_chip_type(&self) -> SoundChipType {
+        if self.vgm_header.as_ref().unwrap().clock_ym2610_b & 0x80000000 != 0 {
+            SoundChipType::YM2610B
+        } else {
+            SoundChipType::YM2610
+        }
+    }
+
+    fn get_k051649_chip_type(&self) -> SoundChipType {
+        if self.vgm_header.as_ref().unwrap().clock_k051649 & 0x80000000 != 0 {
+            SoundChipType::K052539
+        } else {
+            SoundChipType::K051649
+        }
+    }
+
     fn get_rom_index(&self, data_type: u8) -> (RomIndex, Option<SoundChipType>) {
         match data_type {
             0x80 => (RomIndex::SEGAPCM_ROM, Some(SoundChipType::SEGAPCM)),
             0x81 => (RomIndex::YM2608_DELTA_T, Some(SoundChipType::YM2608)),
-            0x82 => (RomIndex::YM2610_ADPCM, Some(SoundChipType::YM2610)),
-            0x83 => (RomIndex::YM2610_DELTA_T, Some(SoundChipType::YM2610)),
+            0x82 => (RomIndex::YM2610_ADPCM, Some(self.get_ym2610_chip_type())),
+            0x83 => (RomIndex::YM2610_DELTA_T, Some(self.get_ym2610_chip_type())),
             0x84 => (RomIndex::YMF278B_ROM, Some(SoundChipType::YMF278B)),
+            0x85 => (RomIndex::YMF271_ROM, Some(SoundChipType::YMF271)),
             0x87 => (RomIndex::YMF278B_RAM, Some(SoundChipType::YMF278B)),
             0x88 => (RomIndex::Y8950_ROM, Some(SoundChipType::Y8950)),
             0x8b => (RomIndex::OKIM6295_ROM, Some(SoundChipType::OKIM6295)),
             0x8d => (RomIndex::C140_ROM, Some(self.get_c140_chip_type())),
+            0xe0 => (RomIndex::SCSP_RAM, Some(SoundChipType::SCSP)),
             _ => (RomIndex::NOT_SUPPOTED, None),
         }
     }
 
-    fn get_chip_type(chip_type: u8) -> Option<SoundChipType> {
+    fn get_chip_type(&self, chip_type: u8) -> Option<SoundChipType> {
         match chip_type & 0x7f {
             0 => Some(SoundChipType::SN76489),
             1 => Some(SoundChipType::YM2413),
-            2 => Some(SoundChipType::YM2612),
-            3 => Some(SoundChipType::YM2151),
+            2 => Some(self.get_ym2612_chip_type()),
+            3 => Some(self.get_ym2151_chip_type()),
             4 => Some(SoundChipType::SEGAPCM),
             5 => None, // rf5c68
             6 => Some(SoundChipType::YM2203),
             7 => Some(SoundChipType::YM2608),
-            8 => Some(SoundChipType::YM2610),
+            8 => Some(self.get_ym2610_chip_type()),
             9 => Some(SoundChipType::YM3812),
             10 => Some(SoundChipType::YM3526),
             11 => Some(SoundChipType::Y8950),
             12 => Some(SoundChipType::YMF262),
             13 => Some(SoundChipType::YMF278B),
-            14 => None, // ymf271
+            14 => Some(SoundChipType::YMF271),
             15 => None, // ymz280b
             16 => None, // rf5c16
             17 => Some(SoundChipType::PWM),
-            18 => Some(SoundChipType::YM2149),
+            18 => Some(SoundChipType::AY8910),
             19 => None, // gb_dmg
             20 => None, // nes_apu
             21 => None, // multi_pcm
             22 => None, // upd7759
             23 => Some(SoundChipType::OKIM6258),
             24 => Some(SoundChipType::OKIM6295),
-            25 => None, // k051649
+            25 => Some(self.get_k051649_chip_type()),
             26 => None, // k054539
             27 => None, // huc6280
             28 => Some(SoundChipType::C140),
             29 => None, // k053260
             30 => None, // pokey
             31 => None, // qsound
-            32 => None, // scsp
+            32 => Some(SoundChipType::SCSP),
             33 => None, // wonder_swan
-            34 => None, // vsu
+            34 => Some(SoundChipType::VSU),
             35 => None, // saa1099
             36 => None, // es5503
             37 => None, // es5506
@@ -968,9 +1345,10 @@ impl VgmPlay {
 ///
 #[cfg(test)]
 mod tests {
-    use crate::sound::SoundSlot;
+    use crate::sound::{SoundChipType, SoundEvent, SoundSlot};
 
-    use super::VgmPlay;
+    use super::{Error, VgmPlay};
+    use crate::driver::vgmanalyze::get_vgm_analysis_json;
     use std::fs::File;
     use std::io::{Read, Write};
 
@@ -1099,6 +1477,321 @@ mod tests {
         play("./docs/vgm/c219-1.vgz")
     }
 
+    #[test]
+    fn broken_1() {
+        // header only
+        assert_eq!(
+            VgmPlay::new(SoundSlot::new(44100, 44100, MAX_SAMPLE_SIZE), b"Vgm ").err(),
+            Some(Error::InvalidHeader("vgm"))
+        );
+        // data offset outside of the file
+        let mut vgm = create_vgm(&[0x66]);
+        vgm[0x34..0x38].copy_from_slice(&0x1000_u32.to_le_bytes());
+        assert_eq!(
+            VgmPlay::new(SoundSlot::new(44100, 44100, MAX_SAMPLE_SIZE), &vgm).err(),
+            Some(Error::BadOffset(0x1034))
+        );
+        // loop offset outside of the file (no loop)
+        let mut vgm = create_vgm(&[0x62, 0x66]);
+        vgm[0x1c..0x20].copy_from_slice(&0x1000_u32.to_le_bytes());
+        let mut vgmplay =
+            VgmPlay::new(SoundSlot::new(44100, 44100, MAX_SAMPLE_SIZE), &vgm).unwrap();
+        assert_eq!(vgmplay.play(true), usize::MAX);
+        assert_eq!(vgmplay.get_error(), None);
+    }
+
+    #[test]
+    fn broken_2() {
+        // wait command without operand
+        assert_eq!(
+            play_broken(&[0x62, 0x61, 0x10]),
+            Some(Error::UnexpectedEof(0x103))
+        );
+        // data block larger than the file
+        assert_eq!(
+            play_broken(&[0x67, 0x66, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00]),
+            Some(Error::UnexpectedEof(0x107))
+        );
+        // ROM dump without header
+        assert_eq!(
+            play_broken(&[0x67, 0x66, 0x80, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+            Some(Error::InvalidDataBlock(0x107))
+        );
+        // undefined command
+        assert_eq!(
+            play_broken(&[0x62, 0x00]),
+            Some(Error::UnknownCommand(0x101, 0x00))
+        );
+        // YM2612 data bank is not exist
+        assert_eq!(play_broken(&[0x81, 0x66]), None);
+    }
+
+    #[test]
+    fn broken_3() {
+        // OKIM6258 divider out of range
+        let mut vgm = create_vgm(&[0xb7, 0x0c, 0xff, 0x66]);
+        vgm[0x90..0x94].copy_from_slice(&4000000_u32.to_le_bytes());
+        let mut vgmplay =
+            VgmPlay::new(SoundSlot::new(44100, 44100, MAX_SAMPLE_SIZE), &vgm).unwrap();
+        assert_eq!(vgmplay.play(false), usize::MAX);
+        assert_eq!(vgmplay.get_error(), None);
+    }
+
+    #[test]
+    fn unsupported_command() {
+        // player and analyzer skip the same operand length
+        let vgm = create_vgm(&[
+            0xb3, 0x10, 0x00, // GameBoy DMG
+            0xc1, 0x00, 0x00, 0x00, // RF5C68
+            0x68, 0x66, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // PCM RAM
+            0xe1, 0x00, 0x00, 0x00, 0x00, // C352
+            0xff, 0x00, 0x00, 0x00, 0x00, // reserved
+            0x62, 0x66,
+        ]);
+        let mut vgmplay =
+            VgmPlay::new(SoundSlot::new(44100, 44100, MAX_SAMPLE_SIZE), &vgm).unwrap();
+        let analysis = vgmplay.analyze();
+        assert!(analysis.end_of_data);
+        assert!(analysis.error.is_none());
+        assert_eq!(analysis.total_samples, 735);
+        assert_eq!(
+            get_vgm_analysis_json(&vgm).unwrap(),
+            vgmplay.get_vgm_analysis_json()
+        );
+        assert_eq!(vgmplay.play(false), usize::MAX);
+        assert_eq!(vgmplay.get_error(), None);
+    }
+
+    #[test]
+    fn ym2608_rhythm_rom() {
+        let rom = [0_u8; 0x2000];
+        for (clock_ym2608, number_of) in [(0_u32, 0), (7987200, 1), (7987200 | 0x40000000, 2)] {
+            let mut vgm = create_vgm(&[0x66]);
+            vgm[0x48..0x4c].copy_from_slice(&clock_ym2608.to_le_bytes());
+            let mut vgmplay =
+                VgmPlay::new(SoundSlot::new(44100, 44100, MAX_SAMPLE_SIZE), &vgm).unwrap();
+            vgmplay.sound_slot.start_record();
+            vgmplay.add_ym2608_rhythm_rom(&rom);
+            let sound_log = vgmplay.sound_slot.stop_record().unwrap();
+            // only the YM2608 devices created from the header
+            assert_eq!(sound_log.event.len(), number_of);
+        }
+    }
+
+    #[test]
+    fn ymf271_header() {
+        assert_eq!(
+            sound_device(&[(0x64, 16934400)]),
+            vec![(SoundChipType::YMF271, 1, 16934400)]
+        );
+        assert_eq!(
+            sound_device(&[(0x64, 0x40000000 | 16934400)]),
+            vec![(SoundChipType::YMF271, 2, 16934400)]
+        );
+    }
+
+    #[test]
+    fn scsp_header() {
+        assert_eq!(
+            sound_device(&[(0xb8, 22579200)]),
+            vec![(SoundChipType::SCSP, 1, 22579200)]
+        );
+        assert_eq!(
+            sound_device(&[(0xb8, 0x40000000 | 22579200)]),
+            vec![(SoundChipType::SCSP, 2, 22579200)]
+        );
+    }
+
+    #[test]
+    fn vsu_header() {
+        assert_eq!(
+            sound_device(&[(0xc4, 5000000)]),
+            vec![(SoundChipType::VSU, 1, 5000000)]
+        );
+        // 0xc7 with offset bit 15 is the second chip (not in the header)
+        let mut vgm = create_vgm(&[0xc7, 0x81, 0x60, 0x01, 0x66]);
+        vgm[0xc4..0xc8].copy_from_slice(&5000000_u32.to_le_bytes());
+        let mut vgmplay =
+            VgmPlay::new(SoundSlot::new(44100, 44100, MAX_SAMPLE_SIZE), &vgm).unwrap();
+        assert_eq!(vgmplay.play(false), usize::MAX);
+        assert_eq!(vgmplay.get_error(), None);
+    }
+
+    #[test]
+    fn sn76489_header() {
+        // bit 30 is dual chip
+        assert_eq!(
+            sound_device(&[(0x0c, 3579545)]),
+            vec![(SoundChipType::SEGAPSG, 1, 3579545)]
+        );
+        assert_eq!(
+            sound_device(&[(0x0c, 0x40000000 | 3579545)]),
+            vec![(SoundChipType::SEGAPSG, 2, 3579545)]
+        );
+        // second SN76489 (0x30) and its Game Gear stereo (0x3f)
+        let mut vgm = create_vgm(&[0x30, 0x90, 0x3f, 0xf0, 0x66]);
+        vgm[0x0c..0x10].copy_from_slice(&(0x40000000 | 3579545_u32).to_le_bytes());
+        let mut vgmplay =
+            VgmPlay::new(SoundSlot::new(44100, 44100, MAX_SAMPLE_SIZE), &vgm).unwrap();
+        assert_eq!(vgmplay.play(false), usize::MAX);
+        assert_eq!(vgmplay.get_error(), None);
+    }
+
+    #[test]
+    fn ymfm_variant_header() {
+        // bit 31 selects YM3438, YM2164 and YM2610B, so that dual chip is bit 30 only
+        assert_eq!(
+            sound_device(&[
+                (0x2c, 0x80000000 | 7670453),
+                (0x30, 0xc0000000 | 3579545),
+                (0x4c, 0x80000000 | 8000000),
+            ]),
+            vec![
+                (SoundChipType::YM3438, 1, 7670453),
+                (SoundChipType::YM2164, 2, 3579545),
+                (SoundChipType::YM2610B, 1, 8000000),
+            ]
+        );
+        assert_eq!(
+            sound_device(&[(0x2c, 0x40000000 | 7670453)]),
+            vec![(SoundChipType::YM2612, 2, 7670453)]
+        );
+        // key on of the variants (YM2164 second chip and YM2610B port 1)
+        let mut vgm = create_vgm(&[
+            0x52, 0x28, 0xf0, 0x54, 0x08, 0x78, 0xa4, 0x08, 0x78, 0x59, 0xb4, 0xc0, 0x62, 0x66,
+        ]);
+        vgm[0x2c..0x30].copy_from_slice(&(0x80000000 | 7670453_u32).to_le_bytes());
+        vgm[0x30..0x34].copy_from_slice(&(0xc0000000 | 3579545_u32).to_le_bytes());
+        vgm[0x4c..0x50].copy_from_slice(&(0x80000000 | 8000000_u32).to_le_bytes());
+        let mut vgmplay =
+            VgmPlay::new(SoundSlot::new(44100, 44100, MAX_SAMPLE_SIZE), &vgm).unwrap();
+        assert_eq!(vgmplay.play(false), usize::MAX);
+        assert_eq!(vgmplay.get_error(), None);
+    }
+
+    #[test]
+    fn ay8910_header() {
+        // bit 30 is dual chip (chip type and flags are 0x78 and 0x79)
+        assert_eq!(
+            sound_device(&[(0x74, 1789772), (0x78, 0x0010)]),
+            vec![(SoundChipType::AY8910, 1, 1789772)]
+        );
+        assert_eq!(
+            sound_device(&[(0x74, 0x40000000 | 1789772), (0x78, 0x0103)]),
+            vec![(SoundChipType::AY8910, 2, 1789772)]
+        );
+        // 0xa0 register and 0x31 stereo mask bit 7 is the second chip
+        for clock in [1789772, 0x40000000 | 1789772_u32] {
+            let mut vgm = create_vgm(&[
+                0x31, 0x01, 0x31, 0x82, 0xa0, 0x08, 0x0f, 0xa0, 0x88, 0x0f, 0x62, 0x66,
+            ]);
+            vgm[0x74..0x78].copy_from_slice(&clock.to_le_bytes());
+            let mut vgmplay =
+                VgmPlay::new(SoundSlot::new(44100, 44100, MAX_SAMPLE_SIZE), &vgm).unwrap();
+            assert_eq!(vgmplay.play(false), usize::MAX);
+            assert_eq!(vgmplay.get_error(), None);
+        }
+    }
+
+    #[test]
+    fn k051649_header() {
+        // bit 31 selects K052539 (SCC+) and bit 30 is dual chip
+        assert_eq!(
+            sound_device(&[(0x9c, 1500000)]),
+            vec![(SoundChipType::K051649, 1, 1500000)]
+        );
+        assert_eq!(
+            sound_device(&[(0x9c, 0x80000000 | 1500000)]),
+            vec![(SoundChipType::K052539, 1, 1500000)]
+        );
+        assert_eq!(
+            sound_device(&[(0x9c, 0xc0000000 | 1500000)]),
+            vec![(SoundChipType::K052539, 2, 1500000)]
+        );
+    }
+
+    #[test]
+    fn data_bank() {
+        // YM2612 (type 0x00) and OKIM6258 (type 0x04) data banks
+        let mut vgm = create_vgm(&[
+            0x67, 0x66, 0x00, 0x02, 0x00, 0x00, 0x00, 0x80, 0x81,
+            0x67, 0x66, 0x04, 0x03, 0x00, 0x00, 0x00, 0x01, 0x02, 0x03,
+            0x67, 0x66, 0x04, 0x01, 0x00, 0x00, 0x00, 0x04,
+            0x90, 0x00, 0x02, 0x00, 0x2a, // YM2612 stream
+            0x90, 0x01, 0x17, 0x00, 0x01, // OKIM6258 stream
+            0x91, 0x01, 0x04, 0x01, 0x00, // OKIM6258 data bank
+            0x93, 0x01, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // bank offset 3
+            0x95, 0x01, 0x01, 0x00, 0x00, // second block of the OKIM6258 bank
+            0x95, 0x00, 0x00, 0x00, 0x00, // first block of the YM2612 bank (without 0x91)
+            0x62, 0x66,
+        ]);
+        vgm[0x2c..0x30].copy_from_slice(&7670453_u32.to_le_bytes());
+        vgm[0x90..0x94].copy_from_slice(&4000000_u32.to_le_bytes());
+        let mut vgmplay =
+            VgmPlay::new(SoundSlot::new(44100, 44100, MAX_SAMPLE_SIZE), &vgm).unwrap();
+        vgmplay.sound_slot.start_record();
+        assert_eq!(vgmplay.play(false), usize::MAX);
+        assert_eq!(vgmplay.get_error(), None);
+        let sound_log = vgmplay.sound_slot.stop_record().unwrap();
+        let data_block: Vec<&[u8]> = sound_log
+            .event
+            .iter()
+            .filter_map(|(_, sound_event)| match sound_event {
+                SoundEvent::AttachDataBlockToStream { data_block_id, .. }
+                | SoundEvent::StartDataStreamFast { data_block_id, .. } => {
+                    vgmplay.sound_slot.get_data_block(*data_block_id)
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            data_block,
+            vec![&[0x01, 0x02, 0x03][..], &[0x04], &[0x04], &[0x80, 0x81]]
+        );
+        // bank offset 3 is the start of the second block
+        assert!(sound_log.event.iter().any(|(_, sound_event)| matches!(
+            sound_event,
+            SoundEvent::StartDataStream {
+                data_block_start_offset: Some(0),
+                ..
+            }
+        )));
+    }
+
+    fn create_vgm(data: &[u8]) -> Vec<u8> {
+        // VGM 1.71 header (data start at 0x100, no sound chip)
+        let mut vgm = vec![0_u8; 0x100];
+        vgm[0x00..0x04].copy_from_slice(b"Vgm ");
+        vgm[0x08..0x0c].copy_from_slice(&0x171_u32.to_le_bytes());
+        vgm[0x34..0x38].copy_from_slice(&0xcc_u32.to_le_bytes());
+        vgm.extend_from_slice(data);
+        let eof = vgm.len() as u32 - 4;
+        vgm[0x04..0x08].copy_from_slice(&eof.to_le_bytes());
+        vgm
+    }
+
+    fn sound_device(header: &[(usize, u32)]) -> Vec<(SoundChipType, usize, u32)> {
+        // sound chip type, number of chips and clock from the header
+        let mut vgm = create_vgm(&[0x66]);
+        for (offset, clock) in header {
+            vgm[*offset..*offset + 4].copy_from_slice(&clock.to_le_bytes());
+        }
+        let mut vgmplay =
+            VgmPlay::new(SoundSlot::new(44100, 44100, MAX_SAMPLE_SIZE), &vgm).unwrap();
+        vgmplay.sound_slot.start_record();
+        vgmplay.sound_slot.stop_record().unwrap().sound_device
+    }
+
+    fn play_broken(data: &[u8]) -> Option<Error> {
+        let mut vgmplay = VgmPlay::new(
+            SoundSlot::new(44100, 44100, MAX_SAMPLE_SIZE),
+            &create_vgm(data),
+        )
+        .unwrap();
+        assert_eq!(vgmplay.play(false), usize::MAX);
+        vgmplay.get_error().cloned()
+    }
+
     fn play(filepath: &str) {
         println!("Play start! {filepath}");
 
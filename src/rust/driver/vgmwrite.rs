@@ -0,0 +1,695 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use std::collections::{BTreeMap, HashMap};
+
+use crate::driver::error::Error;
+use crate::driver::gd3meta::{serialize_gd3, Gd3};
+use crate::driver::vgmmeta::get_data_bank_type;
+use crate::sound::{RomBusType, RomIndex, SoundChipType, SoundEvent, SoundLog};
+
+///
+/// VGM 1.71 header size
+///
+const VGM_HEADER_SIZE: usize = 0x100;
+
+///
+/// Create VGM 1.71 file from the sound slot record.
+///
+/// Sound chips and events that cannot be represented in VGM are skipped.
+/// Data stream values that do not fit in the VGM command are an error.
+///
+pub fn create_vgm(sound_log: &SoundLog, gd3: &Gd3) -> Result<Vec<u8>, Error> {
+    let mut vgm: Vec<u8> = vec![0; VGM_HEADER_SIZE];
+    vgm[0x00..0x04].copy_from_slice(b"Vgm ");
+    write_u32(&mut vgm, 0x08, 0x171);
+    write_u32(&mut vgm, 0x34, (VGM_HEADER_SIZE - 0x34) as u32);
+    write_header_clock(&mut vgm, sound_log);
+    write_header_flag(&mut vgm, sound_log);
+
+    let rom_size = get_rom_size(sound_log);
+    let data_bank_type = get_data_bank_types(sound_log);
+    // data bank type -> (number of data blocks, bank size)
+    let mut data_bank: HashMap<u8, (usize, usize)> = HashMap::new();
+    // data block id -> (data bank type, block id in the bank, bank offset)
+    let mut data_block_id: HashMap<usize, (u8, usize, usize)> = HashMap::new();
+    let mut data_stream_id: HashMap<(SoundChipType, usize, usize), u8> = HashMap::new();
+    // stream id -> (data bank type, bank offset of the attached block, step size, step base)
+    let mut data_stream_bank: HashMap<u8, (u8, usize, u8, u8)> = HashMap::new();
+    let mut data_stream_count: usize = 0;
+    let mut position: u64 = 0;
+    let mut loop_offset: Option<usize> = None;
+    for (index, (event_position, sound_event)) in sound_log.event.iter().enumerate() {
+        if let Some((loop_index, loop_position)) = sound_log.loop_point {
+            if loop_index == index {
+                write_wait(&mut vgm, loop_position - position);
+                position = loop_position;
+                loop_offset = Some(vgm.len());
+            }
+        }
+        write_wait(&mut vgm, event_position - position);
+        position = *event_position;
+        match sound_event {
+            SoundEvent::Write {
+                sound_chip_type,
+                sound_chip_index,
+                port,
+                data,
+            } => {
+                if let Some(command) =
+                    get_write_command(*sound_chip_type, *sound_chip_index, *port, *data)
+                {
+                    vgm.extend_from_slice(&command);
+                }
+            }
+            SoundEvent::AddRom {
+                sound_chip_type: _,
+                sound_chip_index,
+                rom_index,
+                memory,
+                start_address,
+            } => {
+                if let Some(data_type) = get_data_type(*rom_index) {
+                    let mut data_block: Vec<u8> = Vec::with_capacity(memory.len() + 8);
+                    if data_type < 0xc0 {
+                        // ROM image (total ROM size, start address)
+                        let size = rom_size[&(*rom_index, *sound_chip_index)];
+                        data_block.extend_from_slice(&(size as u32).to_le_bytes());
+                        data_block.extend_from_slice(&(*start_address as u32).to_le_bytes());
+                    } else {
+                        // RAM write (32-bit start address)
+                        data_block.extend_from_slice(&(*start_address as u32).to_le_bytes());
+                    }
+                    data_block.extend_from_slice(memory);
+                    write_data_block(&mut vgm, data_type, *sound_chip_index, &data_block);
+                }
+            }
+            SoundEvent::AddDataBlock {
+                data_block_id: id,
+                data_block,
+            } => {
+                // data blocks are concatenated into the data bank of the type
+                let data_type = data_bank_type.get(id).copied().unwrap_or(0x00);
+                let (bank_count, bank_size) = data_bank.entry(data_type).or_insert((0, 0));
+                data_block_id.insert(*id, (data_type, *bank_count, *bank_size));
+                *bank_count += 1;
+                *bank_size += data_block.len();
+                write_data_block(&mut vgm, data_type, 0, data_block);
+            }
+            SoundEvent::AddDataStream {
+                sound_chip_type,
+                sound_chip_index,
+                data_stream_id: id,
+                write_port,
+                write_reg,
+            } => {
+                if let (Some(chip_id), Ok(stream_id)) = (
+                    get_chip_id(*sound_chip_type),
+                    u8::try_from(data_stream_count),
+                ) {
+                    data_stream_count += 1;
+                    data_stream_id.insert((*sound_chip_type, *sound_chip_index, *id), stream_id);
+                    vgm.extend_from_slice(&[
+                        0x90,
+                        stream_id,
+                        chip_id | (*sound_chip_index as u8) << 7,
+                        *write_port as u8,
+                        *write_reg as u8,
+                    ]);
+                }
+            }
+            SoundEvent::SetDataStreamFrequency {
+                sound_chip_type,
+                sound_chip_index,
+                data_stream_id: id,
+                frequency,
+            } => {
+                if let Some(stream_id) =
+                    data_stream_id.get(&(*sound_chip_type, *sound_chip_index, *id))
+                {
+                    vgm.extend_from_slice(&[0x92, *stream_id]);
+                    vgm.extend_from_slice(&frequency.to_le_bytes());
+                }
+            }
+            SoundEvent::AttachDataBlockToStream {
+                sound_chip_type,
+                sound_chip_index,
+                data_stream_id: id,
+                data_block_id: block_id,
+                step_base,
+                step_size,
+            } => {
+                if let (Some(stream_id), Some((data_type, _, bank_offset))) = (
+                    data_stream_id.get(&(*sound_chip_type, *sound_chip_index, *id)),
+                    data_block_id.get(block_id),
+                ) {
+                    let step_size =
+                        u8::try_from(*step_size).map_err(|_| Error::OutOfRange("step size"))?;
+                    let step_base =
+                        u8::try_from(*step_base).map_err(|_| Error::OutOfRange("step base"))?;
+                    data_stream_bank
+                        .insert(*stream_id, (*data_type, *bank_offset, step_size, step_base));
+                    vgm.extend_from_slice(&[0x91, *stream_id, *data_type, step_size, step_base]);
+                }
+            }
+            SoundEvent::StartDataStream {
+                sound_chip_type,
+                sound_chip_index,
+                data_stream_id: id,
+                data_block_start_offset,
+                length_mode,
+                length,
+            } => {
+                if let Some(stream_id) =
+                    data_stream_id.get(&(*sound_chip_type, *sound_chip_index, *id))
+                {
+                    // -1 means don't change the data start offset
+                    let offset = match data_block_start_offset {
+                        Some(offset) => {
+                            // offset in the data bank of the attached block
+                            let bank_offset =
+                                data_stream_bank.get(stream_id).map_or(0, |bank| bank.1);
+                            bank_offset
+                                .checked_add(*offset)
+                                .and_then(|offset| u32::try_from(offset).ok())
+                                .filter(|offset| *offset != 0xffffffff)
+                                .ok_or(Error::OutOfRange("data start offset"))?
+                        }
+                        None => 0xffffffff,
+                    };
+                    let length =
+                        u32::try_from(*length).map_err(|_| Error::OutOfRange("data length"))?;
+                    vgm.extend_from_slice(&[0x93, *stream_id]);
+                    vgm.extend_from_slice(&offset.to_le_bytes());
+                    vgm.push(*length_mode);
+                    vgm.extend_from_slice(&length.to_le_bytes());
+                }
+            }
+            SoundEvent::StartDataStreamFast {
+                sound_chip_type,
+                sound_chip_index,
+                data_stream_id: id,
+                data_block_id: block_id,
+                flags,
+            } => {
+                if let (Some(stream_id), Some((data_type, bank_id, bank_offset))) = (
+                    data_stream_id.get(&(*sound_chip_type, *sound_chip_index, *id)),
+                    data_block_id.get(block_id),
+                ) {
+                    let bank_id =
+                        u16::try_from(*bank_id).map_err(|_| Error::OutOfRange("data block id"))?;
+                    // block id is relative to the data bank set by 0x91
+                    let bank = data_stream_bank.get(stream_id).copied();
+                    if bank.map(|bank| bank.0) != Some(*data_type) {
+                        let (_, _, step_size, step_base) = bank.unwrap_or((0, 0, 1, 0));
+                        data_stream_bank
+                            .insert(*stream_id, (*data_type, *bank_offset, step_size, step_base));
+                        vgm.extend_from_slice(&[0x91, *stream_id, *data_type, step_size, step_base]);
+                    }
+                    vgm.extend_from_slice(&[0x95, *stream_id]);
+                    vgm.extend_from_slice(&bank_id.to_le_bytes());
+                    vgm.push(*flags);
+                }
+            }
+            SoundEvent::StopDataStream {
+                sound_chip_type,
+                sound_chip_index,
+                data_stream_id: id,
+            } => {
+                if let Some(stream_id) =
+                    data_stream_id.get(&(*sound_chip_type, *sound_chip_index, *id))
+                {
+                    vgm.extend_from_slice(&[0x94, *stream_id]);
+                }
+            }
+            SoundEvent::SetRomBusType { .. } => { /* header only */ }
+        }
+    }
+    // loop point after the last event
+    if let Some((loop_index, loop_position)) = sound_log.loop_point {
+        if loop_index >= sound_log.event.len() && loop_position < sound_log.total_samples {
+            write_wait(&mut vgm, loop_position - position);
+            position = loop_position;
+            loop_offset = Some(vgm.len());
+        }
+    }
+    write_wait(&mut vgm, sound_log.total_samples.saturating_sub(position));
+    vgm.push(0x66);
+
+    // total samples and loop
+    write_u32(&mut vgm, 0x18, sound_log.total_samples as u32);
+    if let (Some(loop_offset), Some((_, loop_position))) = (loop_offset, sound_log.loop_point) {
+        let loop_samples = sound_log.total_samples.saturating_sub(loop_position);
+        if loop_samples > 0 {
+            write_u32(&mut vgm, 0x1c, (loop_offset - 0x1c) as u32);
+            write_u32(&mut vgm, 0x20, loop_samples as u32);
+        }
+    }
+    // GD3 and end of file
+    let gd3_offset = vgm.len();
+    vgm.extend_from_slice(&serialize_gd3(gd3));
+    write_u32(&mut vgm, 0x14, (gd3_offset - 0x14) as u32);
+    let eof = vgm.len();
+    write_u32(&mut vgm, 0x04, (eof - 0x04) as u32);
+
+    Ok(vgm)
+}
+
+fn write_u32(vgm: &mut [u8], offset: usize, value: u32) {
+    vgm[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+///
+/// Write sound chip clocks (bit 30: dual chip, bit 31: chip variant)
+///
+fn write_header_clock(vgm: &mut [u8], sound_log: &SoundLog) {
+    let mut clock: BTreeMap<usize, (u32, usize)> = BTreeMap::new();
+    for (sound_chip_type, number_of, chip_clock) in sound_log.sound_device.iter() {
+        if let Some((offset, variant)) = get_header_clock_offset(*sound_chip_type) {
+            let flag = if variant { 0x80000000 } else { 0 };
+            let entry = clock.entry(offset).or_insert((0, 0));
+            *entry = (chip_clock & 0x3fffffff | flag, entry.1 + number_of);
+        }
+    }
+    for (offset, (chip_clock, number_of)) in clock {
+        let dual = if number_of >= 2 { 0x40000000 } else { 0 };
+        write_u32(vgm, offset, chip_clock | dual);
+    }
+}
+
+///
+/// Write sound chip settings from the configuration writes
+///
+fn write_header_flag(vgm: &mut [u8], sound_log: &SoundLog) {
+    let mut okim6258_flag: u8 = 0;
+    for sound_chip_type in sound_log.sound_device.iter().map(|device| device.0) {
+        match sound_chip_type {
+            // Sega PCM interface register
+            SoundChipType::SEGAPCM => write_u32(vgm, 0x3c, 0x000f8000),
+            SoundChipType::C219 => vgm[0x96] = 0x02,
+            _ => {}
+        }
+    }
+    for (_, sound_event) in sound_log.event.iter() {
+        match sound_event {
+            SoundEvent::Write {
+                sound_chip_type: SoundChipType::SEGAPSG | SoundChipType::SN76489,
+                sound_chip_index: _,
+                port,
+                data,
+            } => match port {
+                0x10 => vgm[0x28..0x2a].copy_from_slice(&(*data as u16).to_le_bytes()),
+                0x11 => vgm[0x2a] = *data as u8,
+                0x12 => vgm[0x2b] = *data as u8,
+                _ => {}
+            },
+            SoundEvent::Write {
+                sound_chip_type: SoundChipType::AY8910,
+                sound_chip_index: _,
+                port,
+                data,
+            } => match port {
+                0x100 => vgm[0x78] = *data as u8,
+                0x101 => vgm[0x79] = *data as u8,
+                _ => {}
+            },
+            SoundEvent::Write {
+                sound_chip_type: SoundChipType::OKIM6258,
+                sound_chip_index: _,
+                port,
+                data,
+            } => match port {
+                0x10 => okim6258_flag = okim6258_flag & !0x03 | (*data as u8 & 0x03),
+                0x11 => okim6258_flag = okim6258_flag & !0x04 | if *data == 10 { 0x04 } else { 0 },
+                _ => {}
+            },
+            SoundEvent::SetRomBusType {
+                sound_chip_type: SoundChipType::C140 | SoundChipType::C219,
+                sound_chip_index: _,
+                rom_bus_type,
+            } => {
+                vgm[0x96] = match rom_bus_type {
+                    Some(RomBusType::C140_TYPE_SYSTEM21) => 0x01,
+                    Some(RomBusType::C219_TYPE_ASIC219) => 0x02,
+                    _ => 0x00,
+                };
+            }
+            _ => {}
+        }
+    }
+    vgm[0x94] = okim6258_flag;
+}
+
+///
+/// Total ROM size for the ROM image data block header
+///
+fn get_rom_size(sound_log: &SoundLog) -> HashMap<(RomIndex, usize), usize> {
+    let mut rom_size: HashMap<(RomIndex, usize), usize> = HashMap::new();
+    for (_, sound_event) in sound_log.event.iter() {
+        if let SoundEvent::AddRom {
+            sound_chip_type: _,
+            sound_chip_index,
+            rom_index,
+            memory,
+            start_address,
+        } = sound_event
+        {
+            let size = rom_size.entry((*rom_index, *sound_chip_index)).or_insert(0);
+            *size = (*size).max(start_address + memory.len());
+        }
+    }
+    rom_size
+}
+
+///
+/// Data bank type of the data block from the stream chip that plays it first
+///
+fn get_data_bank_types(sound_log: &SoundLog) -> HashMap<usize, u8> {
+    let mut data_bank_type: HashMap<usize, u8> = HashMap::new();
+    for (_, sound_event) in sound_log.event.iter() {
+        match sound_event {
+            SoundEvent::AttachDataBlockToStream {
+                sound_chip_type,
+                data_block_id,
+                ..
+            }
+            | SoundEvent::StartDataStreamFast {
+                sound_chip_type,
+                data_block_id,
+                ..
+            } => {
+                data_bank_type
+                    .entry(*data_block_id)
+                    .or_insert_with(|| get_data_bank_type(*sound_chip_type));
+            }
+            _ => {}
+        }
+    }
+    data_bank_type
+}
+
+fn write_wait(vgm: &mut Vec<u8>, samples: u64) {
+    let mut samples = samples;
+    while samples > 0 {
+        match samples {
+            1..=16 => {
+                vgm.push(0x70 | (samples - 1) as u8);
+                samples = 0;
+            }
+            735 => {
+                vgm.push(0x62);
+                samples = 0;
+            }
+            882 => {
+                vgm.push(0x63);
+                samples = 0;
+            }
+            _ => {
+                let wait = samples.min(0xffff);
+                vgm.push(0x61);
+                vgm.extend_from_slice(&(wait as u16).to_le_bytes());
+                samples -= wait;
+            }
+        }
+    }
+}
+
+fn write_data_block(vgm: &mut Vec<u8>, data_type: u8, sound_chip_index: usize, data_block: &[u8]) {
+    // bit 31 of the size selects the second chip
+    let size = data_block.len() as u32 | if sound_chip_index != 0 { 0x80000000 } else { 0 };
+    vgm.extend_from_slice(&[0x67, 0x66, data_type]);
+    vgm.extend_from_slice(&size.to_le_bytes());
+    vgm.extend_from_slice(data_block);
+}
+
+///
+/// Header clock offset and chip variant bit
+///
+fn get_header_clock_offset(sound_chip_type: SoundChipType) -> Option<(usize, bool)> {
+    match sound_chip_type {
+        SoundChipType::SEGAPSG | SoundChipType::SN76489 => Some((0x0c, false)),
+        SoundChipType::YM2413 => Some((0x10, false)),
+        SoundChipType::YM2612 => Some((0x2c, false)),
+        SoundChipType::YM3438 => Some((0x2c, true)),
+        SoundChipType::YM2151 => Some((0x30, false)),
+        SoundChipType::YM2164 => Some((0x30, true)),
+        SoundChipType::SEGAPCM => Some((0x38, false)),
+        SoundChipType::YM2203 => Some((0x44, false)),
+        SoundChipType::YM2608 => Some((0x48, false)),
+        SoundChipType::YM2610 => Some((0x4c, false)),
+        SoundChipType::YM2610B => Some((0x4c, true)),
+        SoundChipType::YM3812 => Some((0x50, false)),
+        SoundChipType::YM3526 => Some((0x54, false)),
+        SoundChipType::Y8950 => Some((0x58, false)),
+        SoundChipType::YMF262 => Some((0x5c, false)),
+        SoundChipType::YMF278B => Some((0x60, false)),
+        SoundChipType::YMF271 => Some((0x64, false)),
+        SoundChipType::PWM => Some((0x70, false)),
+        SoundChipType::AY8910 => Some((0x74, false)),
+        SoundChipType::OKIM6258 => Some((0x90, false)),
+        SoundChipType::OKIM6295 => Some((0x98, false)),
+        SoundChipType::K051649 => Some((0x9c, false)),
+        SoundChipType::K052539 => Some((0x9c, true)),
+        SoundChipType::C140 | SoundChipType::C219 => Some((0xa8, false)),
+        SoundChipType::SCSP => Some((0xb8, false)),
+        SoundChipType::VSU => Some((0xc4, false)),
+        SoundChipType::YM2149 | SoundChipType::YMF288 | SoundChipType::YMF289B => None,
+    }
+}
+
+///
+/// Chip type of the data stream control
+///
+fn get_chip_id(sound_chip_type: SoundChipType) -> Option<u8> {
+    match sound_chip_type {
+        SoundChipType::SEGAPSG | SoundChipType::SN76489 => Some(0),
+        SoundChipType::YM2413 => Some(1),
+        SoundChipType::YM2612 | SoundChipType::YM3438 => Some(2),
+        SoundChipType::YM2151 | SoundChipType::YM2164 => Some(3),
+        SoundChipType::SEGAPCM => Some(4),
+        SoundChipType::YM2203 => Some(6),
+        SoundChipType::YM2608 => Some(7),
+        SoundChipType::YM2610 | SoundChipType::YM2610B => Some(8),
+        SoundChipType::YM3812 => Some(9),
+        SoundChipType::YM3526 => Some(10),
+        SoundChipType::Y8950 => Some(11),
+        SoundChipType::YMF262 => Some(12),
+        SoundChipType::YMF278B => Some(13),
+        SoundChipType::YMF271 => Some(14),
+        SoundChipType::PWM => Some(17),
+        SoundChipType::AY8910 => Some(18),
+        SoundChipType::OKIM6258 => Some(23),
+        SoundChipType::OKIM6295 => Some(24),
+        SoundChipType::K051649 | SoundChipType::K052539 => Some(25),
+        SoundChipType::C140 | SoundChipType::C219 => Some(28),
+        SoundChipType::SCSP => Some(32),
+        SoundChipType::VSU => Some(34),
+        SoundChipType::YM2149 | SoundChipType::YMF288 | SoundChipType::YMF289B => None,
+    }
+}
+
+///
+/// Data block type of the ROM/RAM image
+///
+fn get_data_type(rom_index: RomIndex) -> Option<u8> {
+    match rom_index {
+        RomIndex::YM2608_RHYTHM | RomIndex::NOT_SUPPOTED => None,
+        _ => Some(rom_index as u8),
+    }
+}
+
+///
+/// VGM command of the sound chip write
+///
+fn get_write_command(
+    sound_chip_type: SoundChipType,
+    sound_chip_index: usize,
+    port: u32,
+    data: u32,
+) -> Option<Vec<u8>> {
+    let index = (sound_chip_index & 1) as u8;
+    let reg = port as u8;
+    let dat = data as u8;
+    // YM family command (0x5n, second chip 0xan)
+    let ym = |command: u8| Some(vec![command + index * 0x50, reg, dat]);
+    // port 1 (address bit 8)
+    let ym_port = |command: u8| ym(command + ((port >> 8) & 1) as u8);
+    // 15-bit offset (bit 15 selects the second chip)
+    let offset16 = |command: u8| {
+        Some(vec![
+            command,
+            (port >> 8) as u8 & 0x7f | index << 7,
+            reg,
+            dat,
+        ])
+    };
+    match sound_chip_type {
+        SoundChipType::SEGAPSG | SoundChipType::SN76489 => match port {
+            0 => Some(vec![if index == 0 { 0x50 } else { 0x30 }, dat]),
+            1 => Some(vec![if index == 0 { 0x4f } else { 0x3f }, dat]),
+            _ => None, /* header settings */
+        },
+        SoundChipType::YM2413 => ym(0x51),
+        SoundChipType::YM2612 | SoundChipType::YM3438 => ym_port(0x52),
+        SoundChipType::YM2151 | SoundChipType::YM2164 => ym(0x54),
+        SoundChipType::YM2203 => ym(0x55),
+        SoundChipType::YM2608 => ym_port(0x56),
+        SoundChipType::YM2610 | SoundChipType::YM2610B => ym_port(0x58),
+        SoundChipType::YM3812 => ym(0x5a),
+        SoundChipType::YM3526 => ym(0x5b),
+        SoundChipType::Y8950 => ym(0x5c),
+        SoundChipType::YMF262 => ym_port(0x5e),
+        SoundChipType::AY8910 => match port {
+            0x00..=0x7f => Some(vec![0xa0, reg | index << 7, dat]),
+            0x102 => Some(vec![0x31, dat & 0x3f | index << 7]),
+            _ => None, /* header settings */
+        },
+        SoundChipType::PWM => Some(vec![
+            0xb2,
+            (port as u8 & 0x0f) << 4 | (data >> 8) as u8 & 0x0f,
+            dat,
+        ]),
+        SoundChipType::OKIM6258 => match port {
+            0x00..=0x0f => Some(vec![0xb7, reg | index << 7, dat]),
+            _ => None, /* header settings */
+        },
+        SoundChipType::OKIM6295 => Some(vec![0xb8, reg & 0x7f | index << 7, dat]),
+        SoundChipType::SEGAPCM => Some(vec![0xc0, reg, (port >> 8) as u8, dat]),
+        SoundChipType::SCSP => offset16(0xc5),
+        SoundChipType::VSU => offset16(0xc7),
+        SoundChipType::YMF278B => offset16(0xd0),
+        SoundChipType::YMF271 => offset16(0xd1),
+        SoundChipType::K051649 | SoundChipType::K052539 => offset16(0xd2),
+        SoundChipType::C140 | SoundChipType::C219 => offset16(0xd4),
+        SoundChipType::YM2149 | SoundChipType::YMF288 | SoundChipType::YMF289B => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::create_vgm;
+    use crate::driver::error::Error;
+    use crate::driver::gd3meta::{parse_gd3, Gd3};
+    use crate::driver::vgmanalyze::{analyze_vgm, ChipWrite, DataBlockSize};
+    use crate::driver::vgmmeta::parse_vgm_meta;
+    use crate::sound::{SoundChipType, SoundSlot};
+
+    #[test]
+    fn record_1() {
+        let mut sound_slot = SoundSlot::new(60, 44100, 128);
+        sound_slot.add_sound_device(SoundChipType::YM2612, 1, 7670453);
+        sound_slot.add_sound_device(SoundChipType::SEGAPSG, 2, 3579545);
+        sound_slot.add_data_block(3, &[0x80, 0x90, 0xa0]);
+        sound_slot.start_record();
+        sound_slot.write(SoundChipType::SEGAPSG, 0, 0x10, 0x0009);
+        sound_slot.write(SoundChipType::YM2612, 0, 0x28, 0xf0);
+        sound_slot.write(SoundChipType::YM2612, 0, 0x1b4, 0xc0);
+        sound_slot.update(1);
+        sound_slot.set_record_loop_point();
+        sound_slot.write(SoundChipType::SEGAPSG, 1, 0, 0x9f);
+        sound_slot.add_data_stream(SoundChipType::YM2612, 0, 5, 0, 0x2a);
+        sound_slot.attach_data_block_to_stream(SoundChipType::YM2612, 0, 5, 3, 0, 1);
+        sound_slot.start_data_stream_fast(SoundChipType::YM2612, 0, 5, 3, 0);
+        sound_slot.update(2);
+        let sound_log = sound_slot.stop_record().unwrap();
+        let gd3 = Gd3 {
+            track_name: String::from("Record"),
+            ..Default::default()
+        };
+        let vgm = create_vgm(&sound_log, &gd3).unwrap();
+
+        let (header, _) = parse_vgm_meta(&vgm).unwrap();
+        assert_eq!(header.version, 171);
+        assert_eq!(header.eof as usize, vgm.len() - 4);
+        assert_eq!(header.clock_ym2612, 7670453);
+        assert_eq!(header.clock_sn76489, 3579545 | 0x40000000);
+        assert_eq!(header.sn76489_fb, 0x0009);
+        assert_eq!(header.total_samples, 735 * 3);
+        assert_eq!(header.loop_samples, 735 * 2);
+        let (_, gd3) = parse_gd3(&vgm[header.offset_gd3 as usize + 0x14..]).unwrap();
+        assert_eq!(gd3.track_name, "Record");
+
+        let analysis = analyze_vgm(&vgm, &header);
+        assert!(analysis.end_of_data);
+        assert!(analysis.error.is_none());
+        assert!(analysis.loop_offset_on_command);
+        assert_eq!(analysis.total_samples, 735 * 3);
+        assert_eq!(analysis.data_block.len(), 1);
+        assert_eq!(
+            analysis.chip_write,
+            vec![
+                ChipWrite {
+                    chip: String::from("SN76489"),
+                    index: 1,
+                    count: 1,
+                    supported: true
+                },
+                ChipWrite {
+                    chip: String::from("YM2612"),
+                    index: 0,
+                    count: 2,
+                    supported: true
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn data_bank() {
+        let mut sound_slot = SoundSlot::new(60, 44100, 128);
+        sound_slot.add_sound_device(SoundChipType::YM2612, 1, 7670453);
+        sound_slot.add_sound_device(SoundChipType::OKIM6258, 1, 4000000);
+        sound_slot.start_record();
+        sound_slot.add_data_block(0, &[0x80, 0x81]);
+        sound_slot.add_data_block(1, &[0x01, 0x02, 0x03]);
+        sound_slot.add_data_block(2, &[0x04]);
+        sound_slot.add_data_stream(SoundChipType::YM2612, 0, 0, 0, 0x2a);
+        sound_slot.add_data_stream(SoundChipType::OKIM6258, 0, 1, 0, 0x01);
+        sound_slot.start_data_stream_fast(SoundChipType::YM2612, 0, 0, 0, 0);
+        sound_slot.attach_data_block_to_stream(SoundChipType::OKIM6258, 0, 1, 2, 0, 1);
+        sound_slot.start_data_stream(SoundChipType::OKIM6258, 0, 1, Some(0), 0, 1);
+        sound_slot.start_data_stream_fast(SoundChipType::OKIM6258, 0, 1, 1, 0);
+        sound_slot.update(1);
+        let vgm = create_vgm(&sound_slot.stop_record().unwrap(), &Gd3::default()).unwrap();
+
+        // YM2612 and OKIM6258 data banks
+        let (header, _) = parse_vgm_meta(&vgm).unwrap();
+        let analysis = analyze_vgm(&vgm, &header);
+        assert!(analysis.error.is_none());
+        assert_eq!(
+            analysis.data_block,
+            vec![
+                DataBlockSize {
+                    data_type: 0x00,
+                    count: 1,
+                    size: 2
+                },
+                DataBlockSize {
+                    data_type: 0x04,
+                    count: 2,
+                    size: 4
+                },
+            ]
+        );
+        // stream commands refer the data bank of the stream chip
+        let find = |command: &[u8]| vgm.windows(command.len()).any(|w| w == command);
+        assert!(find(&[0x91, 0x00, 0x00, 0x01, 0x00, 0x95, 0x00, 0x00, 0x00, 0x00]));
+        assert!(find(&[0x91, 0x01, 0x04, 0x01, 0x00]));
+        assert!(find(&[0x93, 0x01, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]));
+        assert!(find(&[0x95, 0x01, 0x00, 0x00, 0x00]));
+    }
+
+    #[test]
+    fn data_bank_out_of_range() {
+        let mut sound_slot = SoundSlot::new(60, 44100, 128);
+        sound_slot.add_sound_device(SoundChipType::YM2612, 1, 7670453);
+        sound_slot.start_record();
+        sound_slot.add_data_block(0, &[0x80]);
+        sound_slot.add_data_stream(SoundChipType::YM2612, 0, 0, 0, 0x2a);
+        sound_slot.attach_data_block_to_stream(SoundChipType::YM2612, 0, 0, 0, 0, 0x100);
+        let sound_log = sound_slot.stop_record().unwrap();
+        assert_eq!(
+            create_vgm(&sound_log, &Gd3::default()).err(),
+            Some(Error::OutOfRange("step size"))
+        );
+    }
+}
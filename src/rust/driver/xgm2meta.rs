@@ -0,0 +1,219 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use nom::bytes::complete::{tag, take};
+use nom::number::complete::{le_u16, le_u8};
+use nom::IResult;
+
+use crate::driver::error::Error;
+use crate::driver::gd3meta::{parse_gd3, Gd3};
+use crate::driver::meta::Jsonlize;
+use crate::driver::xgmmeta::VDPMode;
+
+pub const XGM2_SAMPLE_ID_MAX: usize = 124;
+pub const XGM2_TRACK_MAX: usize = 128;
+
+///
+/// https://github.com/Stephane-D/SGDK/blob/master/bin/xgm2.txt
+///
+/// $0000 "XGM2"
+/// $0004 version ($1x)
+/// $0005 flags (bit 0: PAL, bit 1: multi tracks, bit 2: GD3, bit 3: packed)
+/// $0006 sample data bloc size / 256
+/// $0008 FM music data bloc size / 256
+/// $000A PSG music data bloc size / 256
+/// $000C single track: sample id table (124 entries, address / 256, $FFFF is empty)
+///       multi tracks: FM track table (128 entries, offset / 256, $FFFF is empty),
+///                     PSG track table (128 entries), sample id table (124 entries)
+/// then sample data bloc, FM music data bloc, PSG music data bloc and GD3
+///
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Xgm2Header {
+    pub version: u8,
+    pub vdp_mode: VDPMode,
+    pub multi_track_file: bool,
+    pub gd3_tag: bool,
+    pub sample_data_bloc_size: u16,
+    pub fm_data_bloc_size: u16,
+    pub psg_data_bloc_size: u16,
+    pub sample_id_table: Vec<Option<(usize, usize)>>,
+    pub fm_track_offset: Vec<usize>,
+    pub psg_track_offset: Vec<usize>,
+    pub sample_data_bloc_address: usize,
+    pub fm_data_bloc_address: usize,
+    pub psg_data_bloc_address: usize,
+    pub tick_rate: u32,
+}
+
+///
+/// Parse address table (address / 256, $FFFF is empty)
+///
+fn parse_address_table(i: &[u8], count: usize) -> IResult<&[u8], Vec<Option<usize>>> {
+    let mut table = Vec::with_capacity(count);
+    let mut i = i;
+    for _ in 0..count {
+        let (rest, address) = le_u16(i)?;
+        i = rest;
+        table.push(if address == 0xffff {
+            None
+        } else {
+            Some(address as usize * 256)
+        });
+    }
+    Ok((i, table))
+}
+
+///
+/// Parse XGM2 header
+///
+fn parse_xgm2_header(i: &[u8]) -> IResult<&[u8], Xgm2Header> {
+    let (i, _) = tag("XGM2")(i)?;
+    let (i, version) = le_u8(i)?;
+    let (i, flags) = le_u8(i)?;
+    let vdp_mode = if flags & 0b00000001 == 0 {
+        VDPMode::NTSC
+    } else {
+        VDPMode::PAL
+    };
+    let multi_track_file = flags & 0b00000010 != 0;
+    let gd3_tag = flags & 0b00000100 != 0;
+    let (i, sample_data_bloc_size) = le_u16(i)?;
+    let (i, fm_data_bloc_size) = le_u16(i)?;
+    let (i, psg_data_bloc_size) = le_u16(i)?;
+    let (i, fm_track_offset, psg_track_offset) = if multi_track_file {
+        let (i, fm_track) = parse_address_table(i, XGM2_TRACK_MAX)?;
+        let (i, psg_track) = parse_address_table(i, XGM2_TRACK_MAX)?;
+        // a track needs both of FM and PSG offset
+        let (fm_track, psg_track) = fm_track
+            .iter()
+            .zip(psg_track.iter())
+            .map_while(|(fm, psg)| fm.zip(*psg))
+            .unzip();
+        (i, fm_track, psg_track)
+    } else {
+        (i, vec![0], vec![0])
+    };
+    let (i, sample_address) = parse_address_table(i, XGM2_SAMPLE_ID_MAX)?;
+    let track_table_length = if multi_track_file {
+        XGM2_TRACK_MAX * 4
+    } else {
+        0
+    };
+    let sample_data_bloc_address = 0x0c + track_table_length + XGM2_SAMPLE_ID_MAX * 2;
+    let sample_data_bloc_length = sample_data_bloc_size as usize * 256;
+    let (i, _) = take(sample_data_bloc_length)(i)?;
+
+    // the sample size is up to the next sample address
+    let sample_id_table = sample_address
+        .iter()
+        .map(|address| {
+            address.map(|address| {
+                let end = sample_address
+                    .iter()
+                    .flatten()
+                    .filter(|next| **next > address)
+                    .min()
+                    .copied()
+                    .unwrap_or(sample_data_bloc_length);
+                (address, end.max(address) - address)
+            })
+        })
+        .collect();
+    let fm_data_bloc_address = sample_data_bloc_address + sample_data_bloc_length;
+    let psg_data_bloc_address = fm_data_bloc_address + fm_data_bloc_size as usize * 256;
+
+    Ok((
+        i,
+        Xgm2Header {
+            version,
+            vdp_mode,
+            multi_track_file,
+            gd3_tag,
+            sample_data_bloc_size,
+            fm_data_bloc_size,
+            psg_data_bloc_size,
+            sample_id_table,
+            fm_track_offset,
+            psg_track_offset,
+            sample_data_bloc_address,
+            fm_data_bloc_address,
+            psg_data_bloc_address,
+            tick_rate: vdp_mode as u32,
+        },
+    ))
+}
+
+///
+/// Parse XGM2 meta
+///
+pub(crate) fn parse_xgm2_meta(xgm2data: &[u8]) -> Result<(Xgm2Header, Gd3), Error> {
+    let header = match parse_xgm2_header(xgm2data) {
+        Ok((_, header)) => header,
+        Err(_) => return Err(Error::InvalidHeader("xgm2")),
+    };
+    if header.version >> 4 != 1 {
+        return Err(Error::UnsupportedVersion(header.version as u32));
+    }
+    // packed FM / PSG data blocs (XGC) are not supported
+    if xgm2data[5] & 0b00001000 != 0 {
+        return Err(Error::InvalidHeader("xgm2 packed"));
+    }
+    if header.fm_track_offset.is_empty() {
+        return Err(Error::InvalidHeader("xgm2 track"));
+    }
+    let gd3 = if header.gd3_tag {
+        let gd3_offset = header
+            .psg_data_bloc_address
+            .saturating_add(header.psg_data_bloc_size as usize * 256);
+        match xgm2data.get(gd3_offset..).map(parse_gd3) {
+            Some(Ok((_, gd3))) => gd3,
+            _ => Gd3::default(), // blank values
+        }
+    } else {
+        Gd3::default() // blank values
+    };
+
+    Ok((header, gd3))
+}
+
+impl Jsonlize for Xgm2Header {}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_xgm2_meta;
+    use crate::driver::error::Error;
+    use crate::driver::xgmmeta::VDPMode;
+
+    #[test]
+    fn multi_track() {
+        let mut xgm2 = b"XGM2".to_vec();
+        xgm2.extend_from_slice(&[0x10, 0b00000011]);
+        xgm2.extend_from_slice(&[0x02, 0x00, 0x02, 0x00, 0x01, 0x00]);
+        // FM / PSG track table
+        xgm2.extend_from_slice(&[0x00, 0x00, 0x01, 0x00]);
+        xgm2.extend_from_slice(&[0xff; 126 * 2]);
+        xgm2.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+        xgm2.extend_from_slice(&[0xff; 126 * 2]);
+        // sample id table
+        xgm2.extend_from_slice(&[0x01, 0x00, 0x00, 0x00]);
+        xgm2.extend_from_slice(&[0xff; 122 * 2]);
+        xgm2.extend_from_slice(&[0x00; (2 + 2 + 1) * 256]);
+
+        let (header, _) = parse_xgm2_meta(&xgm2).unwrap();
+        assert_eq!(header.vdp_mode, VDPMode::PAL);
+        assert_eq!(header.fm_track_offset, vec![0x000, 0x100]);
+        assert_eq!(header.psg_track_offset, vec![0x000, 0x000]);
+        assert_eq!(header.sample_id_table[0], Some((0x100, 0x100)));
+        assert_eq!(header.sample_id_table[1], Some((0x000, 0x100)));
+        assert_eq!(header.sample_id_table[2], None);
+        assert_eq!(header.sample_data_bloc_address, 0x304);
+        assert_eq!(header.fm_data_bloc_address, 0x504);
+        assert_eq!(header.psg_data_bloc_address, 0x704);
+
+        // packed
+        xgm2[5] |= 0b00001000;
+        assert_eq!(
+            parse_xgm2_meta(&xgm2).unwrap_err(),
+            Error::InvalidHeader("xgm2 packed")
+        );
+    }
+}
@@ -0,0 +1,752 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use crate::driver::error::Error;
+use crate::driver::gd3meta::Gd3;
+use crate::driver::meta::Jsonlize;
+use crate::driver::xgm2meta::{self, Xgm2Header};
+use crate::driver::xgmmeta::VDPMode;
+use crate::driver::xgmplay::get_vdp_timing;
+use crate::sound::{DataStreamMode, SoundChipType, SoundSlot};
+use flate2::read::GzDecoder;
+use std::io::Read;
+
+const XGM2_PCM_SAMPLING_RATE: u32 = 13300;
+const XGM2_PCM_MAX_CHANNEL: usize = 3;
+
+///
+/// XGM2 music stream (FM or PSG)
+///
+#[derive(Default)]
+struct Xgm2Stream {
+    pos: usize,
+    bloc_address: usize,
+    wait: usize,
+    end: bool,
+}
+
+///
+/// XGM2 Driver
+///
+pub struct Xgm2Play {
+    sound_slot: SoundSlot,
+    xgm2_fm: Xgm2Stream,
+    xgm2_psg: Xgm2Stream,
+    xgm2_track: usize,
+    xgm2_loop_count: usize,
+    xgm2_end: bool,
+    xgm2_data: Vec<u8>,
+    xgm2_header: Option<Xgm2Header>,
+    xgm2_gd3: Option<Gd3>,
+    xgm2_pcm_priority: [u8; XGM2_PCM_MAX_CHANNEL],
+    xgm2_pcm_now_play: [bool; XGM2_PCM_MAX_CHANNEL],
+    xgm2_sample_id_max: usize,
+    xgm2_ym2612_reg: [[u8; 256]; 2],
+    xgm2_psg_latch: (usize, bool),
+    xgm2_psg_attenuation: [u8; 4],
+    xgm2_psg_period: [u16; 4],
+    xgm2_error: Option<Error>,
+    remain_tick_count: usize,
+}
+
+#[allow(dead_code)]
+impl Xgm2Play {
+    ///
+    /// Create sound driver.
+    ///
+    pub fn new(sound_slot: SoundSlot, xgm2_file: &[u8]) -> Result<Self, Error> {
+        Self::new_with_vdp_mode(sound_slot, xgm2_file, None)
+    }
+
+    ///
+    /// Create sound driver with the VDP mode (NTSC / PAL) forced regardless of header flags.
+    ///
+    pub fn new_with_vdp_mode(
+        sound_slot: SoundSlot,
+        xgm2_file: &[u8],
+        vdp_mode: Option<VDPMode>,
+    ) -> Result<Self, Error> {
+        let mut xgm2play = Xgm2Play {
+            sound_slot,
+            xgm2_fm: Xgm2Stream::default(),
+            xgm2_psg: Xgm2Stream::default(),
+            xgm2_track: 0,
+            xgm2_loop_count: 0,
+            xgm2_end: false,
+            xgm2_data: Vec::new(),
+            xgm2_header: None,
+            xgm2_gd3: None,
+            xgm2_pcm_priority: [0; XGM2_PCM_MAX_CHANNEL],
+            xgm2_pcm_now_play: [false; XGM2_PCM_MAX_CHANNEL],
+            xgm2_sample_id_max: 0,
+            xgm2_ym2612_reg: [[0; 256]; 2],
+            xgm2_psg_latch: (0, false),
+            xgm2_psg_attenuation: [0xf; 4],
+            xgm2_psg_period: [0; 4],
+            xgm2_error: None,
+            remain_tick_count: 0,
+        };
+        // clone xgm2_file and soundchip init
+        xgm2play.init(xgm2_file, vdp_mode)?;
+
+        Ok(xgm2play)
+    }
+
+    ///
+    /// Return sampling_l buffer referance.
+    ///
+    pub fn get_sampling_l_ref(&self) -> *const f32 {
+        self.sound_slot.get_output_sampling_l_ref()
+    }
+
+    ///
+    /// Return sampling buffer referance.
+    ///
+    pub fn get_sampling_r_ref(&self) -> *const f32 {
+        self.sound_slot.get_output_sampling_r_ref()
+    }
+
+    ///
+    /// Return s16le sampling buffer referance.
+    ///
+    pub fn get_output_sampling_s16le_ref(&mut self) -> *const i16 {
+        self.sound_slot.get_output_sampling_s16le_ref()
+    }
+
+    ///
+    /// Return sound slot (for the playlist to read the last chunk).
+    ///
+    pub(crate) fn get_sound_slot(&self) -> &SoundSlot {
+        &self.sound_slot
+    }
+
+    ///
+    /// Get XGM2 meta.
+    ///
+    pub fn get_xgm2_meta(&self) -> (&Xgm2Header, &Gd3) {
+        (
+            self.xgm2_header.as_ref().unwrap(/* There always is */),
+            self.xgm2_gd3.as_ref().unwrap(/* There always is */),
+        )
+    }
+
+    ///
+    /// Get XGM2 header JSON.
+    ///
+    pub fn get_xgm2_header_json(&self) -> String {
+        self.xgm2_header.as_ref().unwrap(/* There always is */).get_json()
+    }
+
+    ///
+    /// Get XGM2 header GD3 JSON.
+    ///
+    pub fn get_xgm2_gd3_json(&self) -> String {
+        self.xgm2_gd3.as_ref().unwrap(/* There always is */).get_json()
+    }
+
+    ///
+    /// Get the error that stopped playback (broken XGM2 data).
+    ///
+    pub fn get_error(&self) -> Option<&Error> {
+        self.xgm2_error.as_ref()
+    }
+
+    ///
+    /// Return number of tracks.
+    ///
+    pub fn track_count(&self) -> usize {
+        self.xgm2_header.as_ref().unwrap(/* There always is */).fm_track_offset.len()
+    }
+
+    ///
+    /// Select track and play it from the beginning.
+    ///
+    pub fn select_track(&mut self, track: usize) -> Result<(), Error> {
+        if track >= self.track_count() {
+            return Err(Error::BadOffset(track));
+        }
+        self.xgm2_track = track;
+        self.silence();
+        self.set_track_position();
+
+        Ok(())
+    }
+
+    ///
+    /// Play Sound.
+    ///
+    pub fn play(&mut self, repeat: bool) -> usize {
+        while !self.sound_slot.is_stream_filled() && !self.xgm2_end {
+            for _ in 0..self.remain_tick_count {
+                self.sound_slot.update(1);
+                self.remain_tick_count -= 1;
+                if self.sound_slot.is_stream_filled() {
+                    break;
+                }
+            }
+            if self.remain_tick_count == 0 {
+                match self.parse_xgm2(repeat) {
+                    Ok(wait) => self.remain_tick_count = wait,
+                    Err(error) => {
+                        // stop playback on broken data
+                        self.xgm2_error = Some(error);
+                        self.xgm2_end = true;
+                    }
+                }
+            };
+        }
+        self.sound_slot.stream();
+
+        if self.xgm2_loop_count == usize::MAX {
+            self.xgm2_loop_count = 0;
+        }
+        if self.xgm2_end {
+            usize::MAX
+        } else {
+            self.xgm2_loop_count
+        }
+    }
+
+    ///
+    /// Extract xgz and initialize sound driver.
+    ///
+    fn init(&mut self, xgm2_file: &[u8], vdp_mode: Option<VDPMode>) -> Result<(), Error> {
+        // try xgz extract to xgm2_data
+        self.extract(xgm2_file);
+
+        // parse xgm2 header
+        let (mut header, gd3) = xgm2meta::parse_xgm2_meta(&self.xgm2_data)?;
+
+        // set sound chip clock and external tick rate (frame rate)
+        let (clock_ym2612, clock_sn76489, tick_rate) =
+            get_vdp_timing(vdp_mode.unwrap_or(header.vdp_mode));
+        self.sound_slot.change_external_tick_rate(tick_rate);
+        header.tick_rate = tick_rate;
+
+        // add sound chip
+        self.sound_slot
+            .add_sound_device(SoundChipType::YM2612, 1, clock_ym2612);
+        self.sound_slot
+            .add_sound_device(SoundChipType::SEGAPSG, 1, clock_sn76489);
+
+        // set up YM2612 data stream
+        // 3 PCM channels (8 bits signed at 13.3 Khz or 6.65 Khz)
+        self.sound_slot
+            .set_data_stream_mode(SoundChipType::YM2612, 0, DataStreamMode::MergeS8le);
+        for channel in 0..XGM2_PCM_MAX_CHANNEL {
+            self.sound_slot
+                .add_data_stream(SoundChipType::YM2612, 0, channel, 0, 0x2a);
+            self.sound_slot.set_data_stream_frequency(
+                SoundChipType::YM2612,
+                0,
+                channel,
+                XGM2_PCM_SAMPLING_RATE,
+            );
+        }
+
+        // parse sample table
+        for (xgm2_sample_id, sample) in header.sample_id_table.iter().enumerate() {
+            // sapmle id starts with 1 (sample id 0 is stop stream)
+            let data_stream_id = xgm2_sample_id + 1;
+            if let Some((address, size)) = sample {
+                let start_address = header.sample_data_bloc_address + address;
+                let sample = match self.xgm2_data.get(start_address..start_address + size) {
+                    Some(sample) => sample,
+                    None => return Err(Error::BadOffset(start_address)),
+                };
+                // create data stream into sound device
+                self.sound_slot.add_data_block(data_stream_id, sample);
+                self.xgm2_sample_id_max = data_stream_id;
+            }
+        }
+
+        self.xgm2_header = Some(header);
+        self.xgm2_gd3 = Some(gd3);
+
+        // set sequence offset
+        self.set_track_position();
+
+        Ok(())
+    }
+
+    fn extract(&mut self, xgm2_file: &[u8]) {
+        let mut d = GzDecoder::new(xgm2_file);
+        if d.read_to_end(&mut self.xgm2_data).is_err() {
+            self.xgm2_data = xgm2_file.to_vec();
+        }
+    }
+
+    fn set_track_position(&mut self) {
+        let header = self.xgm2_header.as_ref().unwrap(/* There always is */);
+        let fm_bloc_address = header.fm_data_bloc_address + header.fm_track_offset[self.xgm2_track];
+        let psg_bloc_address =
+            header.psg_data_bloc_address + header.psg_track_offset[self.xgm2_track];
+        self.xgm2_fm = Xgm2Stream {
+            pos: fm_bloc_address,
+            bloc_address: fm_bloc_address,
+            ..Default::default()
+        };
+        self.xgm2_psg = Xgm2Stream {
+            pos: psg_bloc_address,
+            bloc_address: psg_bloc_address,
+            ..Default::default()
+        };
+        self.xgm2_loop_count = 0;
+        self.xgm2_end = false;
+        self.xgm2_error = None;
+        self.remain_tick_count = 0;
+    }
+
+    fn silence(&mut self) {
+        // stop PCM
+        for channel in 0..XGM2_PCM_MAX_CHANNEL {
+            self.sound_slot
+                .stop_data_stream(SoundChipType::YM2612, 0, channel);
+            self.xgm2_pcm_priority[channel] = 0;
+            self.xgm2_pcm_now_play[channel] = false;
+        }
+        // YM2612 key off
+        for channel in [0, 1, 2, 4, 5, 6] {
+            self.sound_slot
+                .write(SoundChipType::YM2612, 0, 0x28, channel);
+        }
+        // PSG attenuation off
+        for channel in 0..4 {
+            self.write_psg_attenuation(channel, 0xf);
+        }
+    }
+
+    fn get_xgm2_u8(&mut self, psg: bool) -> Result<u8, Error> {
+        let stream = if psg {
+            &mut self.xgm2_psg
+        } else {
+            &mut self.xgm2_fm
+        };
+        let ret = match self.xgm2_data.get(stream.pos) {
+            Some(ret) => *ret,
+            None => return Err(Error::UnexpectedEof(stream.pos)),
+        };
+        stream.pos += 1;
+        Ok(ret)
+    }
+
+    fn get_xgm2_u16(&mut self, psg: bool) -> Result<u16, Error> {
+        Ok(u16::from(self.get_xgm2_u8(psg)?) + (u16::from(self.get_xgm2_u8(psg)?) << 8))
+    }
+
+    fn get_xgm2_u16_be(&mut self, psg: bool) -> Result<u16, Error> {
+        Ok((u16::from(self.get_xgm2_u8(psg)?) << 8) + u16::from(self.get_xgm2_u8(psg)?))
+    }
+
+    fn get_xgm2_u24(&mut self, psg: bool) -> Result<u32, Error> {
+        Ok(u32::from(self.get_xgm2_u8(psg)?)
+            + (u32::from(self.get_xgm2_u8(psg)?) << 8)
+            + (u32::from(self.get_xgm2_u8(psg)?) << 16))
+    }
+
+    fn get_run_length(command: u8) -> u8 {
+        (command & 0xf) + 1
+    }
+
+    fn get_fm_channel(&self, command: u8) -> Result<(usize, u8), Error> {
+        // %xxxxxPCC (port, channel 0-2)
+        match (((command >> 2) & 1) as usize, command & 0x3) {
+            (_, 0x3) => Err(Error::UnknownCommand(self.xgm2_fm.pos - 1, command)),
+            channel => Ok(channel),
+        }
+    }
+
+    fn parse_xgm2(&mut self, repeat: bool) -> Result<usize, Error> {
+        // run each stream up to its next wait
+        while self.xgm2_fm.wait == 0 && !self.xgm2_fm.end {
+            self.xgm2_fm.wait = self.parse_fm(repeat)?;
+        }
+        while self.xgm2_psg.wait == 0 && !self.xgm2_psg.end {
+            self.xgm2_psg.wait = self.parse_psg(repeat)?;
+        }
+        // wait until the nearest stream event
+        let wait = [&self.xgm2_fm, &self.xgm2_psg]
+            .iter()
+            .filter(|stream| !stream.end)
+            .map(|stream| stream.wait)
+            .min();
+        match wait {
+            Some(wait) => {
+                for stream in [&mut self.xgm2_fm, &mut self.xgm2_psg] {
+                    stream.wait = stream.wait.saturating_sub(wait);
+                }
+                Ok(wait)
+            }
+            None => {
+                self.xgm2_end = true;
+                Ok(0)
+            }
+        }
+    }
+
+    fn parse_loop(&mut self, repeat: bool, psg: bool) -> Result<(), Error> {
+        // loop offset in the music data bloc ($FFFFFF is the end of the track)
+        let loop_offset = self.get_xgm2_u24(psg)? as usize;
+        let stream = if psg {
+            &mut self.xgm2_psg
+        } else {
+            &mut self.xgm2_fm
+        };
+        if repeat && loop_offset != 0xffffff {
+            stream.pos = stream.bloc_address + loop_offset;
+            if !psg {
+                self.xgm2_loop_count += 1;
+            }
+        } else {
+            stream.end = true;
+        }
+        Ok(())
+    }
+
+    ///
+    /// FM music data
+    ///
+    /// $00-$0E wait (X + 1 frames), $0F xx long wait (xx + 16 frames)
+    /// $1X     PCM (X bit 0-1: channel, bit 2: half rate, bit 3: priority) + sample id
+    /// $2X     load instrument (%0010xPCC) + 30 bytes ($30-$90 x 4 slots, $B0, $B4)
+    /// $3X     set frequency (%0011WPCC, W: wait a frame) + block/fnum (hh, ll)
+    /// $4X     key on/off (%0100KPCC, K: key on)
+    /// $5X     key sequence (%0101SPCC, S = 0: off then on, S = 1: on then off)
+    /// $6X/$7X port 0 / port 1 register write (X + 1 pairs of reg, data)
+    /// $8X     set TL (%1000xPCC) + slot + TL
+    /// $9X     set panning (%1001xPCC) + panning (bit 6-7)
+    /// $AX     frequency delta (%1010WPCC, W: wait a frame) + signed 16-bit delta
+    /// $BX     TL delta (%1011xPCC) + slot + signed delta
+    /// $F0     set LFO + LFO register ($22)
+    /// $F1/$F2 channel 3 special mode on / off
+    /// $FF     loop + 24-bit offset ($FFFFFF: end)
+    ///
+    fn parse_fm(&mut self, repeat: bool) -> Result<usize, Error> {
+        let mut wait: usize = 0;
+
+        let command = self.get_xgm2_u8(false)?;
+        match command {
+            0x00..=0x0e => {
+                // frame wait (1/60 of second in NTSC, 1/50 of second in PAL)
+                wait = command as usize + 1;
+            }
+            0x0f => {
+                wait = self.get_xgm2_u8(false)? as usize + 16;
+            }
+            0x10..=0x1f => {
+                // PCM play command
+                let channel = (command & 0x3) as usize;
+                let half_rate = command & 0x4 != 0;
+                let priority = (command & 0x8) >> 3;
+                let sample_id = self.get_xgm2_u8(false)? as usize;
+                if channel >= XGM2_PCM_MAX_CHANNEL {
+                    return Err(Error::UnknownCommand(self.xgm2_fm.pos - 2, command));
+                }
+                self.play_pcm(channel, sample_id, priority, half_rate);
+            }
+            0x20..=0x2f => {
+                // load instrument (slot registers, algorithm / feedback, panning / ams / pms)
+                let (port, channel) = self.get_fm_channel(command)?;
+                for reg in (0x30..=0x90).step_by(0x10) {
+                    for slot in 0..4 {
+                        let dat = self.get_xgm2_u8(false)?;
+                        self.write_ym2612(port, reg + (slot << 2) + channel, dat);
+                    }
+                }
+                for reg in [0xb0, 0xb4] {
+                    let dat = self.get_xgm2_u8(false)?;
+                    self.write_ym2612(port, reg + channel, dat);
+                }
+            }
+            0x30..=0x3f => {
+                let (port, channel) = self.get_fm_channel(command)?;
+                let freq = self.get_xgm2_u16_be(false)?;
+                self.write_ym2612_freq(port, channel, freq);
+                wait = ((command >> 3) & 1) as usize;
+            }
+            0x40..=0x4f => {
+                let (port, channel) = self.get_fm_channel(command)?;
+                let key = if command & 0x8 != 0 { 0xf0 } else { 0x00 };
+                self.write_ym2612(0, 0x28, key | (port << 2) as u8 | channel);
+            }
+            0x50..=0x5f => {
+                let (port, channel) = self.get_fm_channel(command)?;
+                let key = if command & 0x8 != 0 {
+                    [0xf0, 0x00]
+                } else {
+                    [0x00, 0xf0]
+                };
+                for key in key {
+                    self.write_ym2612(0, 0x28, key | (port << 2) as u8 | channel);
+                }
+            }
+            0x60..=0x7f => {
+                // YM2612 port 0 / port 1 register write
+                let port = ((command >> 4) & 1) as usize;
+                for _ in 0..Self::get_run_length(command) {
+                    let reg = self.get_xgm2_u8(false)?;
+                    let dat = self.get_xgm2_u8(false)?;
+                    self.write_ym2612(port, reg, dat);
+                }
+            }
+            0x80..=0x8f => {
+                let (port, channel) = self.get_fm_channel(command)?;
+                let slot = self.get_xgm2_u8(false)? & 0x3;
+                let tl = self.get_xgm2_u8(false)? & 0x7f;
+                self.write_ym2612(port, 0x40 + (slot << 2) + channel, tl);
+            }
+            0x90..=0x9f => {
+                let (port, channel) = self.get_fm_channel(command)?;
+                let pan = self.get_xgm2_u8(false)? & 0xc0;
+                let reg = 0xb4 + channel;
+                let dat = self.xgm2_ym2612_reg[port][reg as usize] & 0x3f | pan;
+                self.write_ym2612(port, reg, dat);
+            }
+            0xa0..=0xaf => {
+                // frequency relative change (signed delta of block / fnum)
+                let (port, channel) = self.get_fm_channel(command)?;
+                let delta = self.get_xgm2_u16(false)? as i16;
+                let freq = ((self.xgm2_ym2612_reg[port][0xa4 + channel as usize] as u16 & 0x3f)
+                    << 8)
+                    | self.xgm2_ym2612_reg[port][0xa0 + channel as usize] as u16;
+                let freq = (freq as i32 + delta as i32).clamp(0, 0x3fff) as u16;
+                self.write_ym2612_freq(port, channel, freq);
+                wait = ((command >> 3) & 1) as usize;
+            }
+            0xb0..=0xbf => {
+                // TL relative change (slot, signed delta)
+                let (port, channel) = self.get_fm_channel(command)?;
+                let slot = self.get_xgm2_u8(false)? & 0x3;
+                let delta = self.get_xgm2_u8(false)? as i8;
+                let reg = 0x40 + (slot << 2) + channel;
+                let tl = self.xgm2_ym2612_reg[port][reg as usize] & 0x7f;
+                let tl = (tl as i16 + delta as i16).clamp(0, 0x7f) as u8;
+                self.write_ym2612(port, reg, tl);
+            }
+            0xf0 => {
+                let lfo = self.get_xgm2_u8(false)?;
+                self.write_ym2612(0, 0x22, lfo);
+            }
+            0xf1 | 0xf2 => {
+                // channel 3 special mode (keep timer settings)
+                let mode = if command == 0xf1 { 0x40 } else { 0x00 };
+                let dat = self.xgm2_ym2612_reg[0][0x27] & 0x3f | mode;
+                self.write_ym2612(0, 0x27, dat);
+            }
+            0xff => {
+                self.parse_loop(repeat, false)?;
+            }
+            _ => return Err(Error::UnknownCommand(self.xgm2_fm.pos - 1, command)),
+        }
+
+        Ok(wait)
+    }
+
+    ///
+    /// PSG music data
+    ///
+    /// $00-$0D wait (X + 1 frames), $0E xx long wait (xx + 15 frames)
+    /// $0F     loop + 24-bit offset ($FFFFFF: end)
+    /// $2X/$3X set tone (%001WCCHH, W: wait a frame, HH: period bit 8-9) + period bit 0-7
+    /// $4X     tone delta (%0100WxCC, W: wait a frame) + signed delta
+    /// $8X-$BX set attenuation (%10CCVVVV)
+    /// $CX-$FX attenuation delta (%11CCDDDD, signed delta)
+    ///
+    fn parse_psg(&mut self, repeat: bool) -> Result<usize, Error> {
+        let mut wait: usize = 0;
+
+        let command = self.get_xgm2_u8(true)?;
+        match command {
+            0x00..=0x0d => {
+                wait = command as usize + 1;
+            }
+            0x0e => {
+                wait = self.get_xgm2_u8(true)? as usize + 15;
+            }
+            0x0f => {
+                self.parse_loop(repeat, true)?;
+            }
+            0x20..=0x3f => {
+                let channel = ((command >> 2) & 0x3) as usize;
+                let period = ((command & 0x3) as u16) << 8 | self.get_xgm2_u8(true)? as u16;
+                self.write_psg_period(channel, period);
+                wait = ((command >> 4) & 1) as usize;
+            }
+            0x40..=0x4f => {
+                let channel = (command & 0x3) as usize;
+                let delta = self.get_xgm2_u8(true)? as i8;
+                let period =
+                    (self.xgm2_psg_period[channel] as i16 + delta as i16).clamp(0, 0x3ff) as u16;
+                self.write_psg_period(channel, period);
+                wait = ((command >> 3) & 1) as usize;
+            }
+            0x80..=0xbf => {
+                let channel = ((command >> 4) & 0x3) as usize;
+                self.write_psg_attenuation(channel, command & 0xf);
+            }
+            0xc0..=0xff => {
+                // signed 4-bit delta
+                let channel = ((command >> 4) & 0x3) as usize;
+                let delta = ((command << 4) as i8) >> 4;
+                let attenuation =
+                    (self.xgm2_psg_attenuation[channel] as i8 + delta).clamp(0, 0xf) as u8;
+                self.write_psg_attenuation(channel, attenuation);
+            }
+            _ => return Err(Error::UnknownCommand(self.xgm2_psg.pos - 1, command)),
+        }
+
+        Ok(wait)
+    }
+
+    fn play_pcm(&mut self, channel: usize, sample_id: usize, priority: u8, half_rate: bool) {
+        // update now play state
+        if self.xgm2_pcm_now_play[channel]
+            && self
+                .sound_slot
+                .is_stop_data_stream(SoundChipType::YM2612, 0, channel)
+        {
+            self.xgm2_pcm_now_play[channel] = false;
+        }
+        if self.xgm2_pcm_now_play[channel] && self.xgm2_pcm_priority[channel] > priority {
+            return;
+        }
+        if sample_id != 0
+            && sample_id <= self.xgm2_sample_id_max
+            && self.sound_slot.get_data_block(sample_id).is_some()
+        {
+            // sample rate is selected on each play
+            let frequency = if half_rate {
+                XGM2_PCM_SAMPLING_RATE / 2
+            } else {
+                XGM2_PCM_SAMPLING_RATE
+            };
+            self.sound_slot
+                .set_data_stream_frequency(SoundChipType::YM2612, 0, channel, frequency);
+            self.sound_slot
+                .start_data_stream_fast(SoundChipType::YM2612, 0, channel, sample_id, 0);
+            self.xgm2_pcm_priority[channel] = priority;
+            self.xgm2_pcm_now_play[channel] = true;
+        } else {
+            self.sound_slot
+                .stop_data_stream(SoundChipType::YM2612, 0, channel);
+            self.xgm2_pcm_priority[channel] = 0;
+            self.xgm2_pcm_now_play[channel] = false;
+        }
+    }
+
+    fn write_ym2612(&mut self, port: usize, reg: u8, dat: u8) {
+        // keep registers for relative change commands
+        self.xgm2_ym2612_reg[port][reg as usize] = dat;
+        self.sound_slot.write(
+            SoundChipType::YM2612,
+            0,
+            reg as u32 | (port as u32) << 8,
+            dat.into(),
+        );
+    }
+
+    fn write_ym2612_freq(&mut self, port: usize, channel: u8, freq: u16) {
+        // block / fnum high first
+        self.write_ym2612(port, 0xa4 + channel, (freq >> 8) as u8 & 0x3f);
+        self.write_ym2612(port, 0xa0 + channel, (freq & 0xff) as u8);
+    }
+
+    fn write_psg(&mut self, dat: u8) {
+        // keep attenuation and tone period for relative change commands
+        if dat & 0x80 != 0 {
+            let channel = ((dat >> 5) & 0x3) as usize;
+            let attenuation = dat & 0x10 != 0;
+            self.xgm2_psg_latch = (channel, attenuation);
+            if attenuation {
+                self.xgm2_psg_attenuation[channel] = dat & 0xf;
+            } else if channel < 3 {
+                self.xgm2_psg_period[channel] =
+                    (self.xgm2_psg_period[channel] & 0x3f0) | (dat & 0xf) as u16;
+            }
+        } else {
+            let (channel, attenuation) = self.xgm2_psg_latch;
+            if attenuation {
+                self.xgm2_psg_attenuation[channel] = dat & 0xf;
+            } else if channel < 3 {
+                self.xgm2_psg_period[channel] =
+                    (self.xgm2_psg_period[channel] & 0xf) | ((dat & 0x3f) as u16) << 4;
+            }
+        }
+        self.sound_slot
+            .write(SoundChipType::SEGAPSG, 0, 0, dat.into());
+    }
+
+    fn write_psg_period(&mut self, channel: usize, period: u16) {
+        if channel < 3 {
+            self.write_psg(0x80 | (channel << 5) as u8 | (period & 0xf) as u8);
+            self.write_psg(((period >> 4) & 0x3f) as u8);
+        } else {
+            // noise control
+            self.xgm2_psg_period[channel] = period & 0x7;
+            self.write_psg(0xe0 | (period & 0x7) as u8);
+        }
+    }
+
+    fn write_psg_attenuation(&mut self, channel: usize, attenuation: u8) {
+        self.write_psg(0x90 | (channel << 5) as u8 | attenuation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sound::SoundSlot;
+
+    use super::Xgm2Play;
+    use std::fs::File;
+    use std::io::{Read, Write};
+
+    const MAX_SAMPLE_SIZE: usize = 2048;
+
+    #[test]
+    fn xgm2_1() {
+        // xgm2tool output (single track)
+        play("./docs/vgm/xgm2-1.xgm", 0)
+    }
+
+    #[test]
+    fn xgm2_2() {
+        // xgm2tool output (multi tracks)
+        play("./docs/vgm/xgm2-2.xgm", 0);
+        play("./docs/vgm/xgm2-2.xgm", 1);
+    }
+
+    fn play(filepath: &str, track: usize) {
+        println!("Play start! {filepath} track {track}");
+
+        let mut file = File::open(filepath).unwrap();
+        let mut buffer = Vec::new();
+        let _ = file.read_to_end(&mut buffer).unwrap();
+
+        // read xgm2
+        let mut xgm2play = Xgm2Play::new(
+            SoundSlot::new(/* XGM NTSC */ 60, 44100, MAX_SAMPLE_SIZE),
+            &buffer,
+        )
+        .unwrap();
+        xgm2play.select_track(track).unwrap();
+
+        let mut pcm = File::create("output.pcm").expect("file open error.");
+        // play
+        // ffplay -f f32le -ar 44100 -ac 2 output.pcm
+        #[allow(clippy::absurd_extreme_comparisons)]
+        while xgm2play.play(false) <= 0 {
+            for i in 0..MAX_SAMPLE_SIZE {
+                let sampling_l = xgm2play.get_sampling_l_ref();
+                let sampling_r = xgm2play.get_sampling_r_ref();
+                unsafe {
+                    let slice_l = std::slice::from_raw_parts(sampling_l.add(i) as *const u8, 4);
+                    let slice_r = std::slice::from_raw_parts(sampling_r.add(i) as *const u8, 4);
+                    pcm.write_all(slice_l).expect("stdout error");
+                    pcm.write_all(slice_r).expect("stdout error");
+                }
+            }
+        }
+        // xgm2tool output has no unknown command
+        assert_eq!(xgm2play.get_error(), None);
+        println!("Play end! {filepath} (xgm2 instance drop)");
+    }
+}
@@ -1,36 +1,58 @@
 // license:BSD-3-Clause
 // copyright-holders:Hiromasa Tanaka
 use nom::bytes::complete::{tag, take};
+use nom::multi::count;
 use nom::number::complete::{le_u16, le_u32, le_u8};
 use nom::IResult;
 
+use crate::driver::error::Error;
 use crate::driver::meta::Jsonlize;
 use crate::driver::gd3meta::{parse_gd3, Gd3};
 
 ///
 /// VDP Mode
 ///
-#[derive(Deserialize, Serialize, Debug, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone, Copy)]
 #[allow(clippy::upper_case_acronyms)]
 pub enum VDPMode {
     NTSC = 60,
     PAL = 50,
 }
 
+impl VDPMode {
+    ///
+    /// Get VDP mode from the frame rate (60 or 50).
+    ///
+    pub fn from_frame_rate(frame_rate: u32) -> Option<VDPMode> {
+        match frame_rate {
+            60 => Some(VDPMode::NTSC),
+            50 => Some(VDPMode::PAL),
+            _ => None,
+        }
+    }
+}
+
 pub const XGM_SAMPLE_DATA_BLOC_ADDRESS: usize = 0x104;
+pub const XGM_TRACK_MAX: usize = 128;
 
 ///
 /// https://github.com/Stephane-D/SGDK/blob/master/bin/xgm.txt
 ///
+/// Multi tracks file has the track table between the music data bloc size and
+/// the music data bloc. (128 entries, offset from the music data bloc, $FFFFFFFF is empty)
+///
 #[derive(Deserialize, Serialize, Debug)]
 pub struct XgmHeader {
-    pub sample_id_table: Vec<(u16, u16)>,
+    pub sample_id_table: Vec<Option<(u16, u16)>>,
     pub sample_data_bloc_size: u16,
     pub version: u8,
     pub vdp_mode: VDPMode,
     pub gd3_tag: bool,
     pub multi_track_file: bool,
     pub music_data_bloc_size: u32,
+    pub music_track_offset: Vec<usize>,
+    pub music_data_bloc_address: usize,
+    pub tick_rate: u32,
 }
 
 ///
@@ -47,6 +69,19 @@ fn parse_xgm_header(i: &[u8]) -> IResult<&[u8], XgmHeader> {
     let multi_track_file = flags & 0b00000100 != 0;
     let (i, /* sample data bloc */ _) = take(sample_data_bloc_size as u32 * 256)(i)?;
     let (i, music_data_bloc_size) = le_u32(i)?;
+    let mut music_data_bloc_address =
+        XGM_SAMPLE_DATA_BLOC_ADDRESS + sample_data_bloc_size as usize * 256 + 4;
+    let (i, music_track_offset) = if multi_track_file {
+        let (i, track_table) = count(le_u32, XGM_TRACK_MAX)(i)?;
+        music_data_bloc_address += XGM_TRACK_MAX * 4;
+        let music_track_offset = track_table
+            .iter()
+            .map_while(|offset| (*offset != 0xffffffff).then_some(*offset as usize))
+            .collect();
+        (i, music_track_offset)
+    } else {
+        (i, vec![0])
+    };
 
     // extract sampling id table
     let mut sample_id_table = Vec::new();
@@ -60,6 +95,7 @@ fn parse_xgm_header(i: &[u8]) -> IResult<&[u8], XgmHeader> {
         // An empty entry should have its address set to $FFFF and size set to $0001.
         // There are cases where the size is 0, so it is not handled.
         if address == 0xffff {
+            sample_id_table.push(None);
             continue;
         }
         let size = u16::from_le_bytes(
@@ -67,7 +103,7 @@ fn parse_xgm_header(i: &[u8]) -> IResult<&[u8], XgmHeader> {
                 .try_into()
                 .unwrap(),
         );
-        sample_id_table.insert(index, (address, size));
+        sample_id_table.push(Some((address, size)));
     }
 
     Ok((
@@ -80,6 +116,9 @@ fn parse_xgm_header(i: &[u8]) -> IResult<&[u8], XgmHeader> {
             gd3_tag,
             multi_track_file,
             music_data_bloc_size,
+            music_track_offset,
+            music_data_bloc_address,
+            tick_rate: vdp_mode as u32,
         },
     ))
 }
@@ -87,18 +126,24 @@ fn parse_xgm_header(i: &[u8]) -> IResult<&[u8], XgmHeader> {
 ///
 /// Parse XGM meta
 ///
-pub(crate) fn parse_xgm_meta(xgmdata: &[u8]) -> Result<(XgmHeader, Gd3), &'static str> {
+pub(crate) fn parse_xgm_meta(xgmdata: &[u8]) -> Result<(XgmHeader, Gd3), Error> {
     let header = match parse_xgm_header(xgmdata) {
         Ok((_, header)) => header,
-        Err(_) => return Err("xgm header parse error."),
+        Err(_) => return Err(Error::InvalidHeader("xgm")),
     };
+    if header.version > 1 {
+        return Err(Error::UnsupportedVersion(header.version as u32));
+    }
+    if header.music_track_offset.is_empty() {
+        return Err(Error::InvalidHeader("xgm track"));
+    }
     let gd3 = if header.gd3_tag {
-        match parse_gd3(
-            &xgmdata[(0x108 + (header.sample_data_bloc_size as u32 * 256) + header.music_data_bloc_size)
-                as usize..],
-        ) {
-            Ok((_, gd3)) => gd3,
-            Err(_) => Gd3::default(), // blank values
+        let gd3_offset = header
+            .music_data_bloc_address
+            .saturating_add(header.music_data_bloc_size as usize);
+        match xgmdata.get(gd3_offset..).map(parse_gd3) {
+            Some(Ok((_, gd3))) => gd3,
+            _ => Gd3::default(), // blank values
         }
     } else {
         Gd3::default() // blank values
@@ -113,6 +158,7 @@ impl Jsonlize for XgmHeader {}
 mod tests {
     use super::parse_xgm_meta;
     use super::Jsonlize;
+    use crate::driver::error::Error;
     use std::fs::File;
     use std::io::Read;
 
@@ -126,6 +172,53 @@ mod tests {
         parse("./docs/vgm/sor3.xgm")
     }
 
+    #[test]
+    fn multi_track() {
+        // XGM header (empty sample table, NTSC, multi tracks)
+        let mut xgm = b"XGM ".to_vec();
+        for _ in 0..63 {
+            xgm.extend_from_slice(&[0xff, 0xff, 0x01, 0x00]);
+        }
+        xgm.extend_from_slice(&[0x00, 0x00, 0x01, 0b00000100]);
+        xgm.extend_from_slice(&4_u32.to_le_bytes());
+        // track table
+        xgm.extend_from_slice(&0_u32.to_le_bytes());
+        xgm.extend_from_slice(&2_u32.to_le_bytes());
+        xgm.extend_from_slice(&[0xff; 126 * 4]);
+        xgm.extend_from_slice(&[0x00, 0x7f, 0x00, 0x7f]);
+
+        let (header, _) = parse_xgm_meta(&xgm).unwrap();
+        assert_eq!(header.music_track_offset, vec![0, 2]);
+        assert_eq!(header.music_data_bloc_address, 0x308);
+
+        // no track
+        xgm[0x108..0x10c].copy_from_slice(&[0xff; 4]);
+        assert_eq!(
+            parse_xgm_meta(&xgm).unwrap_err(),
+            Error::InvalidHeader("xgm track")
+        );
+    }
+
+    #[test]
+    fn broken_sample_table() {
+        // empty entry is followed by sample id 2
+        let mut xgm = b"XGM ".to_vec();
+        xgm.extend_from_slice(&[0xff, 0xff, 0x01, 0x00]);
+        xgm.extend_from_slice(&[0x00, 0x00, 0x01, 0x00]);
+        for _ in 0..61 {
+            xgm.extend_from_slice(&[0xff, 0xff, 0x01, 0x00]);
+        }
+        xgm.extend_from_slice(&[0x01, 0x00, 0x01, 0x00]);
+        xgm.extend_from_slice(&[0x00; 256]);
+        xgm.extend_from_slice(&1_u32.to_le_bytes());
+        xgm.push(0x7f);
+
+        let (header, _) = parse_xgm_meta(&xgm).unwrap();
+        assert_eq!(header.sample_id_table.len(), 62);
+        assert_eq!(header.sample_id_table[0], None);
+        assert_eq!(header.sample_id_table[1], Some((0x0000, 0x0001)));
+    }
+
     fn parse(filepath: &str) {
         // load sn76489 vgm file
         let mut file = File::open(filepath).unwrap();
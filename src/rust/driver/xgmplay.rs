@@ -1,15 +1,15 @@
 // license:BSD-3-Clause
 // copyright-holders:Hiromasa Tanaka
+use crate::driver::error::Error;
 use crate::driver::gd3meta::Gd3;
 use crate::driver::meta::Jsonlize;
-use crate::driver::xgmmeta::{self, VDPMode, XgmHeader, XGM_SAMPLE_DATA_BLOC_ADDRESS};
-use crate::sound::{DataStreamMode, SoundChipType, SoundSlot};
+use crate::driver::vgmwrite;
+use crate::driver::xgmmeta::{self, VDPMode, XgmHeader};
+use crate::sound::{DataStreamMode, SoundChipType, SoundEvent, SoundSlot};
 use flate2::read::GzDecoder;
 use std::{collections::HashMap, io::Read};
 
-#[allow(dead_code)]
 pub const XGM_NTSC_TICK_RATE: u32 = 60;
-#[allow(dead_code)]
 pub const XGM_PAL_TICK_RATE: u32 = 50;
 
 const XGM_PCM_SAMPLING_RATE: u32 = 14000;
@@ -17,6 +17,24 @@ const XGM_PCM_MAX_CHANNEL: u32 = 4;
 const MASTER_CLOCK_NTSC: u32 = 53693175;
 const MASTER_CLOCK_PAL: u32 = 53203424;
 
+///
+/// Get YM2612 clock, SN76489 clock and tick rate of the VDP mode.
+///
+pub(crate) fn get_vdp_timing(vdp_mode: VDPMode) -> (u32, u32, u32) {
+    match vdp_mode {
+        VDPMode::NTSC => (
+            MASTER_CLOCK_NTSC / 7,
+            MASTER_CLOCK_NTSC / 15,
+            XGM_NTSC_TICK_RATE,
+        ),
+        VDPMode::PAL => (
+            MASTER_CLOCK_PAL / 7,
+            MASTER_CLOCK_PAL / 15,
+            XGM_PAL_TICK_RATE,
+        ),
+    }
+}
+
 ///
 /// XGM Driver
 ///
@@ -25,6 +43,7 @@ pub struct XgmPlay {
     xgm_pos: usize,
     xgm_loop_offset: usize,
     xgm_loop_count: usize,
+    xgm_track: usize,
     xgm_end: bool,
     xgm_data: Vec<u8>,
     xgm_header: Option<XgmHeader>,
@@ -32,6 +51,7 @@ pub struct XgmPlay {
     xgm_pcm_priority: HashMap<usize, u8>,
     xgm_pcm_now_play: HashMap<usize, bool>,
     xgm_sample_id_max: usize,
+    xgm_error: Option<Error>,
     remain_tick_count: usize,
 }
 
@@ -40,12 +60,24 @@ impl XgmPlay {
     ///
     /// Create sound driver.
     ///
-    pub fn new(sound_slot: SoundSlot, xgm_file: &[u8]) -> Result<Self, &'static str> {
+    pub fn new(sound_slot: SoundSlot, xgm_file: &[u8]) -> Result<Self, Error> {
+        Self::new_with_vdp_mode(sound_slot, xgm_file, None)
+    }
+
+    ///
+    /// Create sound driver with the VDP mode (NTSC / PAL) forced regardless of header flags.
+    ///
+    pub fn new_with_vdp_mode(
+        sound_slot: SoundSlot,
+        xgm_file: &[u8],
+        vdp_mode: Option<VDPMode>,
+    ) -> Result<Self, Error> {
         let mut xgmplay = XgmPlay {
             sound_slot,
             xgm_pos: 0,
             xgm_loop_offset: 0,
             xgm_loop_count: 0,
+            xgm_track: 0,
             xgm_end: false,
             xgm_data: Vec::new(),
             xgm_header: None,
@@ -53,10 +85,11 @@ impl XgmPlay {
             xgm_pcm_priority: HashMap::new(),
             xgm_pcm_now_play: HashMap::new(),
             xgm_sample_id_max: 0,
+            xgm_error: None,
             remain_tick_count: 0,
         };
         // clone xgm_file and soundchip init
-        xgmplay.init(xgm_file)?;
+        xgmplay.init(xgm_file, vdp_mode)?;
 
         Ok(xgmplay)
     }
@@ -82,6 +115,13 @@ impl XgmPlay {
         self.sound_slot.get_output_sampling_s16le_ref()
     }
 
+    ///
+    /// Return sound slot (for the playlist to read the last chunk).
+    ///
+    pub(crate) fn get_sound_slot(&self) -> &SoundSlot {
+        &self.sound_slot
+    }
+
     ///
     /// Get XGM meta.
     ///
@@ -106,6 +146,109 @@ impl XgmPlay {
         self.xgm_gd3.as_ref().unwrap(/* There always is */).get_json()
     }
 
+    ///
+    /// Get the error that stopped playback (broken XGM data).
+    ///
+    pub fn get_error(&self) -> Option<&Error> {
+        self.xgm_error.as_ref()
+    }
+
+    ///
+    /// Return number of tracks.
+    ///
+    pub fn track_count(&self) -> usize {
+        self.xgm_header.as_ref().unwrap(/* There always is */).music_track_offset.len()
+    }
+
+    ///
+    /// Select track and play it from the beginning.
+    ///
+    pub fn select_track(&mut self, track: usize) -> Result<(), Error> {
+        if track >= self.track_count() {
+            return Err(Error::BadOffset(track));
+        }
+        self.xgm_track = track;
+        self.silence();
+        self.set_track_position();
+
+        Ok(())
+    }
+
+    ///
+    /// Convert the selected track to VGM from the beginning.
+    ///
+    /// Call this before playing. The track is recorded up to the end or the first loop.
+    /// PCM channels become separate YM2612 DAC streams, so overlapped samples are not
+    /// mixed as the XGM driver does.
+    ///
+    pub fn convert_vgm(&mut self) -> Result<Vec<u8>, Error> {
+        self.sound_slot.start_record();
+        // Sega PSG noise feedback and shift register width
+        self.sound_slot
+            .write(SoundChipType::SEGAPSG, 0, 0x10, 0x0009);
+        self.sound_slot.write(SoundChipType::SEGAPSG, 0, 0x11, 16);
+        // record data streams again with the data bank type (YM2612 PCM)
+        self.setup_data_stream();
+        if self.xgm_sample_id_max > 0 {
+            for channel in 0..XGM_PCM_MAX_CHANNEL as usize {
+                self.sound_slot.attach_data_block_to_stream(
+                    SoundChipType::YM2612,
+                    0,
+                    channel,
+                    1,
+                    0,
+                    1,
+                );
+            }
+        }
+
+        // run the command stream and keep the record point of each command
+        self.set_track_position();
+        let mut record_point: HashMap<usize, (usize, u64)> = HashMap::new();
+        let mut result = Ok(());
+        while !self.xgm_end {
+            if let Some(point) = self.sound_slot.get_record_point() {
+                record_point.insert(self.xgm_pos, point);
+            }
+            match self.parse_xgm(true) {
+                Ok(_) if self.xgm_loop_count > 0 => break,
+                Ok(wait) => {
+                    for _ in 0..wait {
+                        self.sound_slot.update(1);
+                        if self.sound_slot.is_stream_filled() {
+                            self.sound_slot.stream();
+                        }
+                    }
+                }
+                Err(error) => {
+                    result = Err(error);
+                    break;
+                }
+            }
+        }
+        let mut sound_log = self.sound_slot.stop_record().unwrap(/* started above */);
+        if self.xgm_loop_count > 0 {
+            sound_log.loop_point = record_point.get(&self.xgm_pos).copied();
+        }
+
+        // rewind
+        self.silence();
+        self.set_track_position();
+        result?;
+
+        // XGM sample is signed, DAC stream is unsigned
+        for (_, sound_event) in sound_log.event.iter_mut() {
+            if let SoundEvent::AddDataBlock { data_block, .. } = sound_event {
+                data_block.iter_mut().for_each(|data| *data ^= 0x80);
+            }
+        }
+
+        vgmwrite::create_vgm(
+            &sound_log,
+            self.xgm_gd3.as_ref().unwrap(/* There always is */),
+        )
+    }
+
     ///
     /// Play Sound.
     ///
@@ -119,7 +262,14 @@ impl XgmPlay {
                 }
             }
             if self.remain_tick_count == 0 {
-                self.remain_tick_count = self.parse_xgm(repeat) as usize;
+                match self.parse_xgm(repeat) {
+                    Ok(wait) => self.remain_tick_count = wait as usize,
+                    Err(error) => {
+                        // stop playback on broken data
+                        self.xgm_error = Some(error);
+                        self.xgm_end = true;
+                    }
+                }
             };
         }
         self.sound_slot.stream();
@@ -137,7 +287,7 @@ impl XgmPlay {
     ///
     /// Extract xgz and initialize sound driver.
     ///
-    fn init(&mut self, xgm_file: &[u8]) -> Result<(), &'static str> {
+    fn init(&mut self, xgm_file: &[u8], vdp_mode: Option<VDPMode>) -> Result<(), Error> {
         // try xgz extract to xgm_data
         self.extract(xgm_file);
 
@@ -147,25 +297,19 @@ impl XgmPlay {
                 self.xgm_header = Some(header);
                 self.xgm_gd3 = Some(gd3);
             }
-            Err(message) => return Err(message),
+            Err(error) => return Err(error),
         };
 
         // set sequence offset
+        self.set_track_position();
+        let header = self.xgm_header.as_mut().unwrap();
+
+        // set sound chip clock and external tick rate (frame rate)
+        let (clock_ym2612, clock_sn76489, tick_rate) =
+            get_vdp_timing(vdp_mode.unwrap_or(header.vdp_mode));
+        self.sound_slot.change_external_tick_rate(tick_rate);
+        header.tick_rate = tick_rate;
         let header = self.xgm_header.as_ref().unwrap();
-        self.xgm_pos =
-            XGM_SAMPLE_DATA_BLOC_ADDRESS + header.sample_data_bloc_size as usize * 256 + 4;
-        self.xgm_loop_offset = self.xgm_pos;
-
-        // set sound chip clock
-        let (clock_ym2612, clock_sn76489) = match header.vdp_mode {
-            VDPMode::NTSC => (MASTER_CLOCK_NTSC / 7, MASTER_CLOCK_NTSC / 15),
-            VDPMode::PAL => (MASTER_CLOCK_PAL / 7, MASTER_CLOCK_NTSC / 15),
-        };
-        // change external tick rate
-        if header.vdp_mode == VDPMode::PAL {
-            self.sound_slot
-                .change_external_tick_rate(VDPMode::PAL as u32);
-        }
 
         // add sound chip
         self.sound_slot
@@ -173,7 +317,31 @@ impl XgmPlay {
         self.sound_slot
             .add_sound_device(SoundChipType::SEGAPSG, 1, clock_sn76489);
 
+        // parse sample table
+        for (xgm_sample_id, sample) in header.sample_id_table.iter().enumerate() {
+            // sapmle id starts with 1 (sample id 0 is stop stream)
+            let data_stream_id = xgm_sample_id + 1;
+            if let Some((address, size)) = sample {
+                let start_address: usize =
+                    *address as usize * 256 + xgmmeta::XGM_SAMPLE_DATA_BLOC_ADDRESS;
+                let end_address: usize = *size as usize * 256 + start_address;
+                let sample = match self.xgm_data.get(start_address..end_address) {
+                    Some(sample) => sample,
+                    None => return Err(Error::BadOffset(start_address)),
+                };
+                // create data stream into sound device
+                self.sound_slot.add_data_block(data_stream_id, sample);
+                self.xgm_sample_id_max = data_stream_id;
+            }
+        }
+
         // set up YM2612 data stream
+        self.setup_data_stream();
+
+        Ok(())
+    }
+
+    fn setup_data_stream(&mut self) {
         // 4 PCM channels (8 bits signed at 14 Khz)
         self.sound_slot
             .set_data_stream_mode(SoundChipType::YM2612, 0, DataStreamMode::MergeS8le);
@@ -189,21 +357,36 @@ impl XgmPlay {
             self.xgm_pcm_priority.insert(channel, 0);
             self.xgm_pcm_now_play.insert(channel, false);
         }
+    }
 
-        // parse sample table
-        for (xgm_sample_id, (address, size)) in header.sample_id_table.iter().enumerate() {
-            // sapmle id starts with 1 (sample id 0 is stop stream)
-            let data_stream_id = xgm_sample_id + 1;
-            let start_address: usize =
-                *address as usize * 256 + xgmmeta::XGM_SAMPLE_DATA_BLOC_ADDRESS;
-            let end_address: usize = *size as usize * 256 + start_address;
-            // create data stream into sound device
+    fn set_track_position(&mut self) {
+        let header = self.xgm_header.as_ref().unwrap(/* There always is */);
+        // loop offset is relative to the music data bloc shared by tracks
+        self.xgm_loop_offset = header.music_data_bloc_address;
+        self.xgm_pos = self.xgm_loop_offset + header.music_track_offset[self.xgm_track];
+        self.xgm_loop_count = 0;
+        self.xgm_end = false;
+        self.xgm_error = None;
+        self.remain_tick_count = 0;
+    }
+
+    fn silence(&mut self) {
+        // stop PCM
+        for channel in 0..XGM_PCM_MAX_CHANNEL as usize {
             self.sound_slot
-                .add_data_block(data_stream_id, &self.xgm_data[start_address..end_address]);
-            self.xgm_sample_id_max = data_stream_id;
+                .stop_data_stream(SoundChipType::YM2612, 0, channel);
+            self.xgm_pcm_priority.insert(channel, 0);
+            self.xgm_pcm_now_play.insert(channel, false);
+        }
+        // YM2612 key off
+        for channel in [0, 1, 2, 4, 5, 6] {
+            self.sound_slot
+                .write(SoundChipType::YM2612, 0, 0x28, channel);
+        }
+        // PSG attenuation off
+        for dat in [0x9f, 0xbf, 0xdf, 0xff] {
+            self.sound_slot.write(SoundChipType::SEGAPSG, 0, 0, dat);
         }
-
-        Ok(())
     }
 
     fn extract(&mut self, xgm_file: &[u8]) {
@@ -213,37 +396,40 @@ impl XgmPlay {
         }
     }
 
-    fn get_xgm_u8(&mut self) -> u8 {
-        let ret = self.xgm_data[self.xgm_pos];
+    fn get_xgm_u8(&mut self) -> Result<u8, Error> {
+        let ret = match self.xgm_data.get(self.xgm_pos) {
+            Some(ret) => *ret,
+            None => return Err(Error::UnexpectedEof(self.xgm_pos)),
+        };
         self.xgm_pos += 1;
-        ret
+        Ok(ret)
     }
 
-    fn get_xgm_u16(&mut self) -> u16 {
-        u16::from(self.get_xgm_u8()) + (u16::from(self.get_xgm_u8()) << 8)
+    fn get_xgm_u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from(self.get_xgm_u8()?) + (u16::from(self.get_xgm_u8()?) << 8))
     }
 
-    fn get_xgm_u24(&mut self) -> u32 {
-        u32::from(self.get_xgm_u8())
-            + (u32::from(self.get_xgm_u8()) << 8)
-            + (u32::from(self.get_xgm_u8()) << 16)
+    fn get_xgm_u24(&mut self) -> Result<u32, Error> {
+        Ok(u32::from(self.get_xgm_u8()?)
+            + (u32::from(self.get_xgm_u8()?) << 8)
+            + (u32::from(self.get_xgm_u8()?) << 16))
     }
 
-    fn get_xgm_u32(&mut self) -> u32 {
-        u32::from(self.get_xgm_u8())
-            + (u32::from(self.get_xgm_u8()) << 8)
-            + (u32::from(self.get_xgm_u8()) << 16)
-            + (u32::from(self.get_xgm_u8()) << 24)
+    fn get_xgm_u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from(self.get_xgm_u8()?)
+            + (u32::from(self.get_xgm_u8()?) << 8)
+            + (u32::from(self.get_xgm_u8()?) << 16)
+            + (u32::from(self.get_xgm_u8()?) << 24))
     }
 
     fn get_run_length(command: u8) -> u8 {
         (command & 0xf) + 1
     }
 
-    fn parse_xgm(&mut self, repeat: bool) -> u16 {
+    fn parse_xgm(&mut self, repeat: bool) -> Result<u16, Error> {
         let mut wait: u16 = 0;
 
-        let command = self.get_xgm_u8();
+        let command = self.get_xgm_u8()?;
         match command {
             0x00 => {
                 // frame wait (1/60 of second in NTSC, 1/50 of second in PAL)
@@ -252,7 +438,7 @@ impl XgmPlay {
             0x10..=0x1f => {
                 // PSG register write
                 for _ in 0..Self::get_run_length(command) {
-                    let dat = self.get_xgm_u8();
+                    let dat = self.get_xgm_u8()?;
                     self.sound_slot
                         .write(SoundChipType::SEGAPSG, 0, 0, dat.into());
                 }
@@ -260,8 +446,8 @@ impl XgmPlay {
             0x20..=0x2f => {
                 // YM2612 port 0 register write
                 for _ in 0..Self::get_run_length(command) {
-                    let reg = self.get_xgm_u8();
-                    let dat = self.get_xgm_u8();
+                    let reg = self.get_xgm_u8()?;
+                    let dat = self.get_xgm_u8()?;
                     self.sound_slot
                         .write(SoundChipType::YM2612, 0, reg as u32, dat.into());
                 }
@@ -269,8 +455,8 @@ impl XgmPlay {
             0x30..=0x3f => {
                 // YM2612 port 1 register write
                 for _ in 0..Self::get_run_length(command) {
-                    let reg = self.get_xgm_u8();
-                    let dat = self.get_xgm_u8();
+                    let reg = self.get_xgm_u8()?;
+                    let dat = self.get_xgm_u8()?;
                     self.sound_slot
                         .write(SoundChipType::YM2612, 0, reg as u32 | 0x100, dat.into());
                 }
@@ -278,7 +464,7 @@ impl XgmPlay {
             0x40..=0x4f => {
                 // YM2612 key off/on ($28) command write
                 for _ in 0..Self::get_run_length(command) {
-                    let dat = self.get_xgm_u8();
+                    let dat = self.get_xgm_u8()?;
                     self.sound_slot
                         .write(SoundChipType::YM2612, 0, 0x28, dat.into());
                 }
@@ -287,7 +473,7 @@ impl XgmPlay {
                 // PCM play command
                 let priority = command & 0xc;
                 let channel = (command & 0x3) as usize;
-                let sample_id = self.get_xgm_u8() as usize;
+                let sample_id = self.get_xgm_u8()? as usize;
                 let channel_priority =
                     self.xgm_pcm_priority.get_mut(&channel).unwrap(/* support 4ch(0x3) */);
                 let channel_now_play =
@@ -307,6 +493,7 @@ impl XgmPlay {
                             0,
                             channel,
                             sample_id,
+                            0,
                         );
                         *channel_priority = priority;
                         *channel_now_play = true;
@@ -319,7 +506,7 @@ impl XgmPlay {
                 }
             }
             0x7e => {
-                let loop_offset = self.get_xgm_u24();
+                let loop_offset = self.get_xgm_u24()?;
                 if repeat {
                     self.xgm_pos = self.xgm_loop_offset + loop_offset as usize;
                     self.xgm_loop_count += 1;
@@ -330,10 +517,10 @@ impl XgmPlay {
             0x7f => {
                 self.xgm_end = true;
             }
-            _ => panic!("xgm parse error"),
+            _ => return Err(Error::UnknownCommand(self.xgm_pos - 1, command)),
         }
 
-        wait
+        Ok(wait)
     }
 }
 
@@ -341,7 +528,10 @@ impl XgmPlay {
 mod tests {
     use crate::sound::SoundSlot;
 
-    use super::XgmPlay;
+    use super::{get_vdp_timing, Error, XgmPlay, XGM_NTSC_TICK_RATE, XGM_PAL_TICK_RATE};
+    use crate::driver::vgmanalyze::analyze_vgm;
+    use crate::driver::vgmmeta::parse_vgm_meta;
+    use crate::driver::xgmmeta::VDPMode;
     use std::fs::File;
     use std::io::{Read, Write};
 
@@ -367,6 +557,169 @@ mod tests {
         play("./docs/vgm/xgm-1-ng.xgm")
     }
 
+    #[test]
+    fn broken_1() {
+        // YM2612 port 0 write without data
+        assert_eq!(
+            play_broken(&[0x00, 0x20, 0x2b]),
+            Some(Error::UnexpectedEof(0x10b))
+        );
+        // undefined command
+        assert_eq!(
+            play_broken(&[0x00, 0x60]),
+            Some(Error::UnknownCommand(0x109, 0x60))
+        );
+    }
+
+    #[test]
+    fn broken_2() {
+        // empty sample id 1 is followed by sample id 2
+        let mut xgm = b"XGM ".to_vec();
+        xgm.extend_from_slice(&[0xff, 0xff, 0x01, 0x00]);
+        xgm.extend_from_slice(&[0x00, 0x00, 0x01, 0x00]);
+        for _ in 0..61 {
+            xgm.extend_from_slice(&[0xff, 0xff, 0x01, 0x00]);
+        }
+        xgm.extend_from_slice(&[0x01, 0x00, 0x01, 0x00]);
+        xgm.extend_from_slice(&[0x00; 256]);
+        // PCM play sample id 2, frame wait and end
+        let music = [0x50, 0x02, 0x00, 0x7f];
+        xgm.extend_from_slice(&(music.len() as u32).to_le_bytes());
+        xgm.extend_from_slice(&music);
+
+        let mut xgmplay = XgmPlay::new(
+            SoundSlot::new(/* XGM NTSC */ 60, 44100, MAX_SAMPLE_SIZE),
+            &xgm,
+        )
+        .unwrap();
+        assert_eq!(xgmplay.xgm_sample_id_max, 2);
+        assert_eq!(xgmplay.play(false), usize::MAX);
+        assert_eq!(xgmplay.get_error(), None);
+    }
+
+    #[test]
+    fn select_track() {
+        // XGM header (empty sample table, NTSC, multi tracks)
+        let mut xgm = b"XGM ".to_vec();
+        for _ in 0..63 {
+            xgm.extend_from_slice(&[0xff, 0xff, 0x01, 0x00]);
+        }
+        xgm.extend_from_slice(&[0x00, 0x00, 0x01, 0b00000100]);
+        xgm.extend_from_slice(&4_u32.to_le_bytes());
+        // track 0: end, track 1: broken
+        xgm.extend_from_slice(&0_u32.to_le_bytes());
+        xgm.extend_from_slice(&2_u32.to_le_bytes());
+        xgm.extend_from_slice(&[0xff; 126 * 4]);
+        xgm.extend_from_slice(&[0x00, 0x7f, 0x00, 0x60]);
+
+        let mut xgmplay = XgmPlay::new(
+            SoundSlot::new(/* XGM NTSC */ 60, 44100, MAX_SAMPLE_SIZE),
+            &xgm,
+        )
+        .unwrap();
+        assert_eq!(xgmplay.track_count(), 2);
+        assert_eq!(xgmplay.play(false), usize::MAX);
+        assert_eq!(xgmplay.get_error(), None);
+        assert_eq!(xgmplay.select_track(1), Ok(()));
+        assert_eq!(xgmplay.play(false), usize::MAX);
+        assert_eq!(
+            xgmplay.get_error(),
+            Some(&Error::UnknownCommand(0x30b, 0x60))
+        );
+        assert_eq!(xgmplay.select_track(2), Err(Error::BadOffset(2)));
+    }
+
+    #[test]
+    fn vdp_mode() {
+        // XGM header (empty sample table, PAL)
+        let mut xgm = b"XGM ".to_vec();
+        for _ in 0..63 {
+            xgm.extend_from_slice(&[0xff, 0xff, 0x01, 0x00]);
+        }
+        xgm.extend_from_slice(&[0x00, 0x00, 0x01, 0b00000001]);
+        xgm.extend_from_slice(&1_u32.to_le_bytes());
+        xgm.extend_from_slice(&[0x7f]);
+
+        let sound_slot = || SoundSlot::new(/* XGM NTSC */ 60, 44100, MAX_SAMPLE_SIZE);
+        let xgmplay = XgmPlay::new(sound_slot(), &xgm).unwrap();
+        assert_eq!(xgmplay.get_xgm_meta().0.vdp_mode, VDPMode::PAL);
+        assert_eq!(xgmplay.get_xgm_meta().0.tick_rate, XGM_PAL_TICK_RATE);
+        // force NTSC
+        let xgmplay = XgmPlay::new_with_vdp_mode(sound_slot(), &xgm, Some(VDPMode::NTSC)).unwrap();
+        assert_eq!(xgmplay.get_xgm_meta().0.vdp_mode, VDPMode::PAL);
+        assert_eq!(xgmplay.get_xgm_meta().0.tick_rate, XGM_NTSC_TICK_RATE);
+        // SN76489 clock from PAL master clock
+        assert_eq!(get_vdp_timing(VDPMode::PAL), (7600489, 3546894, 50));
+    }
+
+    #[test]
+    fn convert_vgm() {
+        // XGM header (1 sample, NTSC)
+        let mut xgm = b"XGM ".to_vec();
+        xgm.extend_from_slice(&[0x00, 0x00, 0x01, 0x00]);
+        for _ in 0..62 {
+            xgm.extend_from_slice(&[0xff, 0xff, 0x01, 0x00]);
+        }
+        xgm.extend_from_slice(&[0x01, 0x00, 0x01, 0x00]);
+        xgm.extend_from_slice(&[0x00; 256]);
+        // DAC on, PCM play, wait, (loop) PSG write, wait, loop
+        let music = [
+            0x20, 0x2b, 0x80, 0x50, 0x01, 0x00, 0x10, 0x9f, 0x00, 0x7e, 0x06, 0x00, 0x00,
+        ];
+        xgm.extend_from_slice(&(music.len() as u32).to_le_bytes());
+        xgm.extend_from_slice(&music);
+
+        let mut xgmplay = XgmPlay::new(
+            SoundSlot::new(/* XGM NTSC */ 60, 44100, MAX_SAMPLE_SIZE),
+            &xgm,
+        )
+        .unwrap();
+        let vgm = xgmplay.convert_vgm().unwrap();
+
+        let (header, _) = parse_vgm_meta(&vgm).unwrap();
+        assert_eq!(header.clock_ym2612, 53693175 / 7);
+        assert_eq!(header.clock_sn76489, 53693175 / 15);
+        assert_eq!(header.sn76489_fb, 0x0009);
+        assert_eq!(header.sn76489_w, 16);
+        assert_eq!(header.total_samples, 735 * 2);
+        assert_eq!(header.loop_samples, 735);
+        // unsigned sample data block
+        assert_eq!(vgm[0x100..0x103], [0x67, 0x66, 0x00]);
+        assert_eq!(vgm[0x107], 0x80);
+        // data bank type of the stream and fast call
+        let find = |command: &[u8]| vgm.windows(command.len()).any(|window| window == command);
+        assert!(find(&[0x91, 0x00, 0x00, 0x01, 0x00]));
+        assert!(find(&[0x95, 0x00, 0x00, 0x00, 0x00]));
+
+        let analysis = analyze_vgm(&vgm, &header);
+        assert!(analysis.end_of_data);
+        assert!(analysis.error.is_none());
+        assert!(analysis.loop_offset_on_command);
+        assert_eq!(analysis.total_samples, 735 * 2);
+
+        // playable after the conversion
+        assert_eq!(xgmplay.play(true), 0);
+    }
+
+    fn play_broken(music: &[u8]) -> Option<Error> {
+        // XGM header (empty sample table, NTSC)
+        let mut xgm = b"XGM ".to_vec();
+        for _ in 0..63 {
+            xgm.extend_from_slice(&[0xff, 0xff, 0x01, 0x00]);
+        }
+        xgm.extend_from_slice(&[0x00, 0x00, 0x01, 0x00]);
+        xgm.extend_from_slice(&(music.len() as u32).to_le_bytes());
+        xgm.extend_from_slice(music);
+
+        let mut xgmplay = XgmPlay::new(
+            SoundSlot::new(/* XGM NTSC */ 60, 44100, MAX_SAMPLE_SIZE),
+            &xgm,
+        )
+        .unwrap();
+        assert_eq!(xgmplay.play(false), usize::MAX);
+        xgmplay.get_error().cloned()
+    }
+
     fn play(filepath: &str) {
         println!("Play start! {filepath}");
 
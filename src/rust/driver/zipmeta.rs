@@ -0,0 +1,304 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use flate2::read::DeflateDecoder;
+use flate2::Crc;
+use nom::bytes::complete::{tag, take};
+use nom::number::complete::{le_u16, le_u32};
+use nom::IResult;
+use std::io::prelude::*;
+
+use crate::driver::error::Error;
+
+const ZIP_END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+const ZIP_LOCAL_FILE_HEADER_SIZE: usize = 30;
+const ZIP_METHOD_STORED: u16 = 0;
+const ZIP_METHOD_DEFLATED: u16 = 8;
+const ZIP_RESERVE_MAX: usize = 0x100000;
+
+///
+/// https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT
+///
+/// Central directory file header (ZIP64 is not supported)
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZipEntry {
+    pub file_name: String,
+    pub method: u16,
+    pub crc32: u32,
+    pub compressed_size: u32,
+    pub uncompressed_size: u32,
+    pub local_header_offset: u32,
+}
+
+///
+/// Parse end of central directory record
+///
+fn parse_end_of_central_directory(i: &[u8]) -> IResult<&[u8], (u16, u32)> {
+    let (i, _) = tag(b"PK\x05\x06")(i)?;
+    let (i, /* disk number */ _) = le_u16(i)?;
+    let (i, /* start disk number */ _) = le_u16(i)?;
+    let (i, /* entries on this disk */ _) = le_u16(i)?;
+    let (i, entries) = le_u16(i)?;
+    let (i, /* central directory size */ _) = le_u32(i)?;
+    let (i, central_directory_offset) = le_u32(i)?;
+    Ok((i, (entries, central_directory_offset)))
+}
+
+///
+/// Parse central directory file header
+///
+fn parse_central_directory(i: &[u8]) -> IResult<&[u8], ZipEntry> {
+    let (i, _) = tag(b"PK\x01\x02")(i)?;
+    let (i, /* version made by */ _) = le_u16(i)?;
+    let (i, /* version needed */ _) = le_u16(i)?;
+    let (i, flags) = le_u16(i)?;
+    let (i, method) = le_u16(i)?;
+    let (i, /* modification time */ _) = le_u16(i)?;
+    let (i, /* modification date */ _) = le_u16(i)?;
+    let (i, crc32) = le_u32(i)?;
+    let (i, compressed_size) = le_u32(i)?;
+    let (i, uncompressed_size) = le_u32(i)?;
+    let (i, file_name_length) = le_u16(i)?;
+    let (i, extra_field_length) = le_u16(i)?;
+    let (i, file_comment_length) = le_u16(i)?;
+    let (i, /* disk number */ _) = le_u16(i)?;
+    let (i, /* internal attributes */ _) = le_u16(i)?;
+    let (i, /* external attributes */ _) = le_u32(i)?;
+    let (i, local_header_offset) = le_u32(i)?;
+    let (i, file_name) = take(file_name_length)(i)?;
+    let (i, _) = take(extra_field_length)(i)?;
+    let (i, _) = take(file_comment_length)(i)?;
+
+    // bit 11 is UTF-8, otherwise the file name is CP437 (ASCII is enough for the packs)
+    let file_name = if flags & 0x0800 != 0 {
+        String::from_utf8_lossy(file_name).into_owned()
+    } else {
+        file_name
+            .iter()
+            .map(|c| if c.is_ascii() { *c as char } else { '_' })
+            .collect()
+    };
+
+    Ok((
+        i,
+        ZipEntry {
+            file_name,
+            method,
+            crc32,
+            compressed_size,
+            uncompressed_size,
+            local_header_offset,
+        },
+    ))
+}
+
+///
+/// Parse ZIP meta (central directory)
+///
+pub(crate) fn parse_zip_meta(zipdata: &[u8]) -> Result<Vec<ZipEntry>, Error> {
+    // end of central directory is followed by the comment (max 65535 bytes)
+    let end_of_central_directory = (0..=zipdata
+        .len()
+        .saturating_sub(ZIP_END_OF_CENTRAL_DIRECTORY_SIZE))
+        .rev()
+        .take(u16::MAX as usize + 1)
+        .find(|pos| zipdata[*pos..].starts_with(b"PK\x05\x06"));
+    let (entries, central_directory_offset) =
+        match end_of_central_directory.map(|pos| parse_end_of_central_directory(&zipdata[pos..])) {
+            Some(Ok((_, end))) => end,
+            _ => return Err(Error::InvalidHeader("zip")),
+        };
+
+    let mut i = match zipdata.get(central_directory_offset as usize..) {
+        Some(i) => i,
+        None => return Err(Error::BadOffset(central_directory_offset as usize)),
+    };
+    let mut zip_entries = Vec::with_capacity(entries as usize);
+    for _ in 0..entries {
+        let (next, zip_entry) = match parse_central_directory(i) {
+            Ok(entry) => entry,
+            Err(_) => return Err(Error::InvalidHeader("zip central directory")),
+        };
+        // skip directory
+        if !zip_entry.file_name.ends_with('/') {
+            zip_entries.push(zip_entry);
+        }
+        i = next;
+    }
+
+    Ok(zip_entries)
+}
+
+///
+/// Open the reader of ZIP entry (stored or deflated)
+///
+pub(crate) fn open_zip_entry<'a>(
+    zipdata: &'a [u8],
+    zip_entry: &ZipEntry,
+) -> Result<Box<dyn Read + 'a>, Error> {
+    let offset = zip_entry.local_header_offset as usize;
+    let local_header = match offset
+        .checked_add(ZIP_LOCAL_FILE_HEADER_SIZE)
+        .and_then(|end| zipdata.get(offset..end))
+    {
+        Some(local_header) if local_header.starts_with(b"PK\x03\x04") => local_header,
+        _ => return Err(Error::BadOffset(offset)),
+    };
+    // the lengths of the local header can be different from the central directory
+    let file_name_length = u16::from_le_bytes(local_header[26..28].try_into().unwrap()) as usize;
+    let extra_field_length = u16::from_le_bytes(local_header[28..30].try_into().unwrap()) as usize;
+    let data_offset = match (offset + ZIP_LOCAL_FILE_HEADER_SIZE)
+        .checked_add(file_name_length + extra_field_length)
+    {
+        Some(data_offset) => data_offset,
+        None => return Err(Error::BadOffset(offset)),
+    };
+    let data = match data_offset
+        .checked_add(zip_entry.compressed_size as usize)
+        .and_then(|end| zipdata.get(data_offset..end))
+    {
+        Some(data) => data,
+        None => return Err(Error::UnexpectedEof(data_offset)),
+    };
+
+    match zip_entry.method {
+        ZIP_METHOD_STORED => Ok(Box::new(data)),
+        ZIP_METHOD_DEFLATED => Ok(Box::new(DeflateDecoder::new(data))),
+        _ => Err(Error::InvalidHeader("zip compression method")),
+    }
+}
+
+///
+/// Read the data of ZIP entry (stored or deflated)
+///
+pub(crate) fn read_zip_entry(zipdata: &[u8], zip_entry: &ZipEntry) -> Result<Vec<u8>, Error> {
+    let uncompressed_size = zip_entry.uncompressed_size as usize;
+    // the size is from the file, the reader stops just after it
+    let mut file = Vec::with_capacity(uncompressed_size.min(ZIP_RESERVE_MAX));
+    let reader = open_zip_entry(zipdata, zip_entry)?;
+    if reader
+        .take(zip_entry.uncompressed_size as u64 + 1)
+        .read_to_end(&mut file)
+        .is_err()
+    {
+        return Err(Error::InvalidDataBlock(zip_entry.local_header_offset as usize));
+    }
+    let mut crc = Crc::new();
+    crc.update(&file);
+    if file.len() != uncompressed_size || crc.sum() != zip_entry.crc32 {
+        return Err(Error::InvalidDataBlock(zip_entry.local_header_offset as usize));
+    }
+
+    Ok(file)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{parse_zip_meta, read_zip_entry};
+    use crate::driver::error::Error;
+    use flate2::write::DeflateEncoder;
+    use flate2::{Compression, Crc};
+    use std::io::prelude::*;
+
+    ///
+    /// ZIP of the files (deflated, the last one is stored)
+    ///
+    pub(crate) fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = Vec::new();
+        let mut central_directory = Vec::new();
+        for (index, (file_name, file)) in files.iter().enumerate() {
+            let stored = index == files.len() - 1;
+            let data = if stored {
+                file.to_vec()
+            } else {
+                let mut e = DeflateEncoder::new(Vec::new(), Compression::default());
+                e.write_all(file).unwrap();
+                e.finish().unwrap()
+            };
+            let mut crc = Crc::new();
+            crc.update(file);
+            let mut header = Vec::new();
+            header.extend_from_slice(&[20, 0, 0, 0]);
+            header.extend_from_slice(&(if stored { 0_u16 } else { 8 }).to_le_bytes());
+            header.extend_from_slice(&[0; 4]);
+            header.extend_from_slice(&crc.sum().to_le_bytes());
+            header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            header.extend_from_slice(&(file.len() as u32).to_le_bytes());
+            header.extend_from_slice(&(file_name.len() as u16).to_le_bytes());
+            header.extend_from_slice(&[0; 2]);
+
+            central_directory.extend_from_slice(b"PK\x01\x02\x14\x00");
+            central_directory.extend_from_slice(&header);
+            central_directory.extend_from_slice(&[0; 10]);
+            central_directory.extend_from_slice(&(zip.len() as u32).to_le_bytes());
+            central_directory.extend_from_slice(file_name.as_bytes());
+
+            zip.extend_from_slice(b"PK\x03\x04");
+            zip.extend_from_slice(&header);
+            zip.extend_from_slice(file_name.as_bytes());
+            zip.extend_from_slice(&data);
+        }
+        let central_directory_offset = zip.len() as u32;
+        zip.extend_from_slice(&central_directory);
+        zip.extend_from_slice(b"PK\x05\x06\x00\x00\x00\x00");
+        zip.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        zip.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        zip.extend_from_slice(&(central_directory.len() as u32).to_le_bytes());
+        zip.extend_from_slice(&central_directory_offset.to_le_bytes());
+        zip.extend_from_slice(&[0; 2]);
+        zip
+    }
+
+    #[test]
+    fn zip_1() {
+        let zip = zip(&[
+            ("01 Title.vgz", b"deflated deflated deflated"),
+            ("Pack.txt", b"stored"),
+        ]);
+        let entries = parse_zip_meta(&zip).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].file_name, "01 Title.vgz");
+        assert_eq!(entries[0].method, 8);
+        assert_eq!(
+            read_zip_entry(&zip, &entries[0]).unwrap(),
+            b"deflated deflated deflated"
+        );
+        assert_eq!(entries[1].method, 0);
+        assert_eq!(read_zip_entry(&zip, &entries[1]).unwrap(), b"stored");
+
+        // broken crc
+        let mut broken = entries[1].clone();
+        broken.crc32 ^= 1;
+        assert!(matches!(
+            read_zip_entry(&zip, &broken),
+            Err(Error::InvalidDataBlock(_))
+        ));
+        // sizes and offsets from the file
+        let mut broken = entries[0].clone();
+        broken.uncompressed_size = u32::MAX;
+        assert!(matches!(
+            read_zip_entry(&zip, &broken),
+            Err(Error::InvalidDataBlock(_))
+        ));
+        broken.uncompressed_size = 4;
+        assert!(matches!(
+            read_zip_entry(&zip, &broken),
+            Err(Error::InvalidDataBlock(_))
+        ));
+        broken.local_header_offset = u32::MAX;
+        assert_eq!(
+            read_zip_entry(&zip, &broken),
+            Err(Error::BadOffset(u32::MAX as usize))
+        );
+        let mut broken = entries[1].clone();
+        broken.compressed_size = u32::MAX;
+        assert!(matches!(
+            read_zip_entry(&zip, &broken),
+            Err(Error::UnexpectedEof(_))
+        ));
+        assert_eq!(
+            parse_zip_meta(b"not zip").unwrap_err(),
+            Error::InvalidHeader("zip")
+        );
+    }
+}
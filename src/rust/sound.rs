@@ -5,6 +5,9 @@ mod device;
 mod sound_chip;
 mod stream;
 mod rom;
+mod recorder;
+#[cfg(feature = "ym2608-rhythm-fallback")]
+mod rom_ym2608_rhythm;
 mod data_stream;
 
 mod chip_ymfm;
@@ -14,9 +17,17 @@ mod chip_segapcm;
 mod chip_okim6258;
 mod chip_c140;
 mod chip_okim6295;
+mod chip_k051649;
+mod chip_ymf271;
+mod chip_scsp;
+mod chip_vsu;
+mod chip_ay8910;
 
 pub use crate::sound::sound_chip::SoundChipType as SoundChipType;
 pub use crate::sound::slot::SoundSlot as SoundSlot;
 pub use crate::sound::rom::RomIndex as RomIndex;
 pub use crate::sound::rom::RomBusType as RomBusType;
 pub use crate::sound::device::DataStreamMode as DataStreamMode;
+pub(crate) use crate::sound::stream::convert_sample_f2i as convert_sample_f2i;
+pub use crate::sound::recorder::SoundEvent as SoundEvent;
+pub use crate::sound::recorder::SoundLog as SoundLog;
@@ -0,0 +1,904 @@
+// license:BSD-3-Clause
+/*
+ * Rust AY8910 ports by
+ *  Hiromasa Tanaka <h1romas4@gmail.com>
+ *  https://github.com/h1romas4/libymfm.wasm
+ *
+ * Porting from:
+ *  AY-3-8910 emulation by Couriersud
+ *  https://github.com/mamedev/mame/blob/master/src/devices/sound/ay8910.cpp
+ */
+
+/*
+ * Original AY8910 emulation Copyright
+ */
+// license:BSD-3-Clause
+// copyright-holders:Couriersud
+/***************************************************************************
+
+    ay8910.cpp
+
+    Emulation of the AY-3-8910 / YM2149 sound chip.
+
+    Based on various code snippets by Ville Hallik, Michael Cuddy,
+    Tatsuyuki Satoh, Fabrice Frances, Nicola Salmoria.
+
+    Mostly rewritten by couriersud in 2008
+
+    Public documentation:
+
+    - http://privatfrickler.de/blick-auf-den-chip-soundchip-general-instruments-ay-3-8910/
+      Die pictures of the AY8910
+
+    - US Patent 4933980
+
+    Games using ADSR: gyruss
+
+    A list with more games using ADSR can be found here:
+        http://mametesters.org/view.php?id=3043
+
+***************************************************************************/
+use super::{
+    rom::RomBank,
+    sound_chip::SoundChip,
+    stream::SoundStream,
+    RomBusType, RomIndex, SoundChipType,
+};
+
+const NUM_CHANNELS: usize = 3;
+
+///
+/// Sound chip write port
+///
+/// 0x00-0x0f: register write (AY8930 expanded mode bank is selected by register 0x0d)
+///
+const PORT_CONFIG_CHIP_TYPE: u32 = 0x100;
+const PORT_CONFIG_FLAGS: u32 = 0x101;
+const PORT_CONFIG_STEREO_MASK: u32 = 0x102;
+
+///
+/// Stereo mask (VGM command 0x31, bit 0-5: channel A-C left / right)
+///
+const AY8910_STEREO_MASK_ALL: u8 = 0x3f;
+
+///
+/// Chip type (VGM header 0x78)
+///
+const AY_TYPE_AY8910: u32 = 0x00;
+const AY_TYPE_AY8912: u32 = 0x01;
+const AY_TYPE_AY8913: u32 = 0x02;
+const AY_TYPE_AY8930: u32 = 0x03;
+const AY_TYPE_YM2149: u32 = 0x10;
+const AY_TYPE_YM3439: u32 = 0x11;
+const AY_TYPE_YMZ284: u32 = 0x12;
+const AY_TYPE_YMZ294: u32 = 0x13;
+
+///
+/// Chip flags (VGM header 0x79)
+///
+const AY8910_LEGACY_OUTPUT: u32 = 0x01;
+const AY8910_SINGLE_OUTPUT: u32 = 0x02;
+const AY8910_DISCRETE_OUTPUT: u32 = 0x04;
+const YM2149_PIN26_LOW: u32 = 0x10;
+
+///
+/// Registers
+///
+const AY_AFINE: usize = 0x00;
+const AY_ACOARSE: usize = 0x01;
+const AY_NOISEPER: usize = 0x06;
+const AY_ENABLE: usize = 0x07;
+const AY_AVOL: usize = 0x08;
+const AY_EAFINE: usize = 0x0b;
+const AY_EACOARSE: usize = 0x0c;
+const AY_EASHAPE: usize = 0x0d;
+const AY_EBFINE: usize = 0x10;
+const AY_EBCOARSE: usize = 0x11;
+const AY_ECFINE: usize = 0x12;
+const AY_ECCOARSE: usize = 0x13;
+const AY_EBSHAPE: usize = 0x14;
+const AY_ECSHAPE: usize = 0x15;
+const AY_ADUTY: usize = 0x16;
+const AY_NOISEAND: usize = 0x19;
+const AY_NOISEOR: usize = 0x1a;
+
+///
+/// AY8930 duty cycle (32 steps)
+///
+const DUTY_CYCLE: [u32; 9] = [
+    0x80000000, // 3.125 %
+    0xc0000000, // 6.25 %
+    0xf0000000, // 12.50 %
+    0xff000000, // 25.00 %
+    0xffff0000, // 50.00 %
+    0xffffff00, // 75.00 %
+    0xfffffff0, // 87.50 %
+    0xfffffffc, // 93.75 %
+    0xfffffffe, // 96.875 %
+];
+
+///
+/// Output resistor parameter
+///
+struct AyYmParam {
+    r_up: f64,
+    r_down: f64,
+    res: &'static [f64],
+}
+
+/*************************************
+ *
+ *  Type definitions
+ *
+ *************************************/
+
+const YM2149_PARAM: AyYmParam = AyYmParam {
+    r_up: 630.0,
+    r_down: 801.0,
+    res: &[
+        73770.0, 37586.0, 27458.0, 21451.0, 15864.0, 12371.0, 8922.0, 6796.0, 4763.0, 3521.0,
+        2403.0, 1737.0, 1123.0, 762.0, 438.0, 251.0,
+    ],
+};
+
+const YM2149_PARAM_ENV: AyYmParam = AyYmParam {
+    r_up: 630.0,
+    r_down: 801.0,
+    res: &[
+        103350.0, 73770.0, 52657.0, 37586.0, 32125.0, 27458.0, 24269.0, 21451.0, 18447.0,
+        15864.0, 14009.0, 12371.0, 10506.0, 8922.0, 7787.0, 6796.0, 5689.0, 4763.0, 4095.0,
+        3521.0, 2909.0, 2403.0, 2043.0, 1737.0, 1397.0, 1123.0, 925.0, 762.0, 578.0, 438.0,
+        332.0, 251.0,
+    ],
+};
+
+/*
+ * 'Pin 26 voltage divider' output
+ */
+const AY8910_PARAM: AyYmParam = AyYmParam {
+    r_up: 800000.0,
+    r_down: 8000000.0,
+    res: &[
+        15950.0, 15350.0, 15090.0, 14760.0, 14275.0, 13620.0, 12890.0, 11370.0, 10600.0,
+        8590.0, 7190.0, 5985.0, 4820.0, 3945.0, 3017.0, 2345.0,
+    ],
+};
+
+/*
+ * AY8930 has 32 volume steps in expanded mode.
+ * Its resistor values are not measured, so that YM2149 envelope values are used.
+ */
+const AY8930_PARAM_EXPANDED: AyYmParam = YM2149_PARAM_ENV;
+
+///
+/// Output load resistor (ohm)
+///
+const RES_LOAD: f64 = 1000.0;
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum PsgType {
+    AY,
+    YM,
+}
+
+#[derive(Default, Clone, Copy)]
+struct Tone {
+    count: u32,
+    duty_cycle: u8,
+    output: u8,
+}
+
+#[derive(Default, Clone, Copy)]
+struct Envelope {
+    count: u32,
+    step: i8,
+    volume: u32,
+    hold: bool,
+    alternate: bool,
+    attack: u8,
+    holding: bool,
+}
+
+impl Envelope {
+    fn set_shape(&mut self, shape: u8, mask: u8) {
+        self.attack = if shape & 0x04 != 0 { mask } else { 0x00 };
+        if shape & 0x08 == 0 {
+            // if Continue = 0, map the shape to the equivalent one which has Continue = 1
+            self.hold = true;
+            self.alternate = self.attack != 0;
+        } else {
+            self.hold = shape & 0x01 != 0;
+            self.alternate = shape & 0x02 != 0;
+        }
+        self.step = mask as i8;
+        self.holding = false;
+        self.volume = (self.step as u8 ^ self.attack) as u32;
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+pub struct AY8910 {
+    chip_type: u32,
+    flags: u32,
+    stereo_mask: u8,
+    psg_type: PsgType,
+    has_expanded_mode: bool,
+    single_output: bool,
+    zero_is_off: bool,
+    env_step_mask: u8,
+    step: u32,
+    regs: [u8; 0x20],
+    mode: u8,
+    prescale_clock: bool,
+    tone: [Tone; NUM_CHANNELS],
+    envelope: [Envelope; NUM_CHANNELS],
+    count_noise: u32,
+    prescale_noise: bool,
+    noise_value: u32,
+    noise_out: u8,
+    rng: u32,
+    vol_enabled: [bool; NUM_CHANNELS],
+    vol_table: [f32; 32],
+    env_table: [f32; 32],
+    vol3d_table: Vec<f32>,
+}
+
+impl AY8910 {
+    fn new() -> Self {
+        let mut ay8910 = AY8910 {
+            chip_type: AY_TYPE_AY8910,
+            flags: AY8910_LEGACY_OUTPUT,
+            stereo_mask: AY8910_STEREO_MASK_ALL,
+            psg_type: PsgType::AY,
+            has_expanded_mode: false,
+            single_output: false,
+            zero_is_off: true,
+            env_step_mask: 0x0f,
+            step: 2,
+            regs: [0; 0x20],
+            mode: 0,
+            prescale_clock: false,
+            tone: [Tone::default(); NUM_CHANNELS],
+            envelope: [Envelope::default(); NUM_CHANNELS],
+            count_noise: 0,
+            prescale_noise: false,
+            noise_value: 0,
+            noise_out: 0,
+            rng: 1,
+            vol_enabled: [false; NUM_CHANNELS],
+            vol_table: [0_f32; 32],
+            env_table: [0_f32; 32],
+            vol3d_table: Vec::new(),
+        };
+        ay8910.set_type(AY_TYPE_AY8910);
+        ay8910
+    }
+
+    fn device_start(&mut self, clock: u32) -> u32 {
+        self.device_reset();
+        clock / 8
+    }
+
+    fn device_reset(&mut self) {
+        self.rng = 1;
+        self.mode = 0; // AY-3-8910 compatible mode
+        self.count_noise = 0;
+        self.prescale_noise = false;
+        self.noise_value = 0;
+        self.noise_out = 0;
+        self.prescale_clock = false;
+        for tone in self.tone.iter_mut() {
+            *tone = Tone::default();
+        }
+        for envelope in self.envelope.iter_mut() {
+            *envelope = Envelope::default();
+        }
+        self.set_step_mask();
+        for i in 0..AY_EASHAPE {
+            self.write_reg(i, 0);
+        }
+        for i in AY_EBFINE..=AY_NOISEOR {
+            self.write_reg(i, 0);
+        }
+        self.build_mixer_table();
+    }
+
+    ///
+    /// Set chip type (VGM header 0x78)
+    ///
+    fn set_type(&mut self, chip_type: u32) {
+        self.chip_type = chip_type;
+        match chip_type {
+            AY_TYPE_YM2149 | AY_TYPE_YM3439 | AY_TYPE_YMZ284 | AY_TYPE_YMZ294 => {
+                self.psg_type = PsgType::YM;
+                self.zero_is_off = false;
+            }
+            AY_TYPE_AY8910 | AY_TYPE_AY8912 | AY_TYPE_AY8913 | AY_TYPE_AY8930 => {
+                self.psg_type = PsgType::AY;
+                self.zero_is_off = true;
+            }
+            _ => {
+                // unknown chip types act as AY-3-8910
+                self.psg_type = PsgType::AY;
+                self.zero_is_off = true;
+            }
+        }
+        self.has_expanded_mode = chip_type == AY_TYPE_AY8930;
+        self.set_step_mask();
+        self.update_output_mode();
+    }
+
+    ///
+    /// Set chip flags (VGM header 0x79)
+    ///
+    fn set_flags(&mut self, flags: u32) {
+        self.flags = flags;
+        self.update_output_mode();
+    }
+
+    fn update_output_mode(&mut self) {
+        // YMZ284 and YMZ294 have only one mixed output pin
+        self.single_output = self.flags & AY8910_SINGLE_OUTPUT != 0
+            || matches!(self.chip_type, AY_TYPE_YMZ284 | AY_TYPE_YMZ294);
+        self.build_mixer_table();
+    }
+
+    fn set_step_mask(&mut self) {
+        if self.psg_type == PsgType::AY && !self.has_expanded_mode {
+            self.env_step_mask = 0x0f;
+            self.step = 2;
+        } else {
+            // YM2149 and AY8930 have 32 steps envelope
+            self.env_step_mask = 0x1f;
+            self.step = 1;
+        }
+    }
+
+    #[inline]
+    fn is_expanded_mode(&self) -> bool {
+        self.has_expanded_mode && (self.mode & 0x0e) == 0x0a
+    }
+
+    #[inline]
+    fn tone_period(&self, chan: usize) -> u32 {
+        let period = self.regs[AY_AFINE + chan * 2] as u32
+            | (self.regs[AY_ACOARSE + chan * 2] as u32) << 8;
+        if self.is_expanded_mode() {
+            period
+        } else {
+            period & 0x0fff
+        }
+    }
+
+    #[inline]
+    fn tone_volume(&self, chan: usize) -> u8 {
+        let volume = self.regs[AY_AVOL + chan];
+        if self.is_expanded_mode() {
+            volume & 0x1f
+        } else {
+            volume & 0x0f
+        }
+    }
+
+    #[inline]
+    fn tone_envelope(&self, chan: usize) -> bool {
+        let volume = self.regs[AY_AVOL + chan];
+        if self.is_expanded_mode() {
+            volume & 0x20 != 0
+        } else {
+            volume & 0x10 != 0
+        }
+    }
+
+    #[inline]
+    fn envelope_period(&self, chan: usize) -> u32 {
+        let (fine, coarse) = match chan {
+            0 => (AY_EAFINE, AY_EACOARSE),
+            1 => (AY_EBFINE, AY_EBCOARSE),
+            _ => (AY_ECFINE, AY_ECCOARSE),
+        };
+        self.regs[fine] as u32 | (self.regs[coarse] as u32) << 8
+    }
+
+    #[inline]
+    fn noise_period(&self) -> u32 {
+        if self.is_expanded_mode() {
+            self.regs[AY_NOISEPER] as u32
+        } else {
+            self.regs[AY_NOISEPER] as u32 & 0x1f
+        }
+    }
+
+    #[inline]
+    fn noise_output(&self) -> u8 {
+        if self.is_expanded_mode() {
+            self.noise_out & 1
+        } else {
+            (self.rng & 1) as u8
+        }
+    }
+
+    #[inline]
+    fn noise_rng_tick(&mut self) {
+        // The Random Number Generator of the 8910 is a 17-bit shift
+        // register. The input to the shift register is bit0 XOR bit3
+        // (bit0 is the output). This was verified on AY-3-8910 and YM2149 chips.
+        self.rng ^= ((self.rng & 1) ^ ((self.rng >> 3) & 1)) << 17;
+        self.rng >>= 1;
+    }
+
+    fn write_reg(&mut self, r: usize, v: u8) {
+        // register 0x0d is shared with both banks
+        let r = if r & 0x0f == AY_EASHAPE { AY_EASHAPE } else { r };
+        self.regs[r] = v;
+
+        match r {
+            AY_EASHAPE => {
+                if self.has_expanded_mode {
+                    let old_mode = self.mode;
+                    self.mode = (v >> 4) & 0x0f;
+                    if ((old_mode & 0x0e) == 0x0a) != ((self.mode & 0x0e) == 0x0a) {
+                        // AY8930 expanded mode changed
+                        for i in 0..AY_EASHAPE {
+                            self.write_reg(i, 0);
+                            self.write_reg(i + 0x10, 0);
+                        }
+                        self.build_mixer_table();
+                    }
+                }
+                self.envelope[0].set_shape(self.regs[AY_EASHAPE], self.env_step_mask);
+            }
+            AY_EBSHAPE => {
+                self.envelope[1].set_shape(self.regs[AY_EBSHAPE], self.env_step_mask);
+            }
+            AY_ECSHAPE => {
+                self.envelope[2].set_shape(self.regs[AY_ECSHAPE], self.env_step_mask);
+            }
+            _ => { /* nothing to do */ }
+        }
+    }
+
+    ///
+    /// Write register (AY8930 expanded mode bank is selected by register 0x0d bit 4)
+    ///
+    fn write(&mut self, reg: u32, data: u8) {
+        let mut r = (reg & 0x0f) as usize;
+        if self.is_expanded_mode() {
+            r |= ((self.mode & 0x01) as usize) << 4;
+        }
+        self.write_reg(r, data);
+    }
+
+    fn sound_stream_update(&mut self, buffer_l: &mut [f32], buffer_r: &mut [f32]) {
+        // YM2149 and AY8930 /SEL pin low halves clock input
+        let clock_enabled = if self.flags & YM2149_PIN26_LOW != 0
+            && (self.psg_type == PsgType::YM || self.has_expanded_mode)
+        {
+            self.prescale_clock = !self.prescale_clock;
+            self.prescale_clock
+        } else {
+            true
+        };
+        if clock_enabled {
+            self.update_generator();
+        }
+
+        // mixer
+        let enable = self.regs[AY_ENABLE];
+        let noise_output = self.noise_output();
+        for chan in 0..NUM_CHANNELS {
+            self.vol_enabled[chan] = (self.tone[chan].output | ((enable >> chan) & 1))
+                & (noise_output | ((enable >> (3 + chan)) & 1))
+                != 0;
+        }
+
+        let mut out_l: f32 = 0_f32;
+        let mut out_r: f32 = 0_f32;
+        if self.single_output {
+            // the output is mixed through the resistor network of all channels
+            let mut indx: usize = 0;
+            for chan in 0..NUM_CHANNELS {
+                let (use_env, level) = self.channel_level(chan);
+                if use_env {
+                    indx |= 1 << (15 + chan);
+                }
+                indx |= (level as usize) << (chan * 5);
+            }
+            out_l = self.vol3d_table[indx];
+            out_r = out_l;
+        } else {
+            for chan in 0..NUM_CHANNELS {
+                let (use_env, level) = self.channel_level(chan);
+                let out = if use_env {
+                    self.env_table[level as usize]
+                } else {
+                    self.vol_table[level as usize]
+                };
+                if self.stereo_mask & (1 << (chan * 2)) != 0 {
+                    out_l += out;
+                }
+                if self.stereo_mask & (2 << (chan * 2)) != 0 {
+                    out_r += out;
+                }
+            }
+        }
+        buffer_l[0] += out_l * 0.5;
+        buffer_r[0] += out_r * 0.5;
+    }
+
+    fn update_generator(&mut self) {
+        let expanded = self.is_expanded_mode();
+
+        // tone
+        for chan in 0..NUM_CHANNELS {
+            let period = self.tone_period(chan).max(1);
+            let duty = self.regs[AY_ADUTY + chan].min(8) as usize;
+            let tone = &mut self.tone[chan];
+            if expanded {
+                // 32 steps duty cycle in one tone period
+                tone.count += 16;
+                while tone.count >= period {
+                    tone.duty_cycle = tone.duty_cycle.wrapping_sub(1) & 0x1f;
+                    tone.output = ((DUTY_CYCLE[duty] >> tone.duty_cycle) & 1) as u8;
+                    tone.count -= period;
+                }
+            } else {
+                tone.count += 1;
+                if tone.count >= period {
+                    tone.duty_cycle = tone.duty_cycle.wrapping_sub(1) & 0x1f;
+                    tone.output = tone.duty_cycle & 1;
+                    tone.count = 0;
+                }
+            }
+        }
+
+        // noise
+        self.count_noise += 1;
+        if self.count_noise >= self.noise_period() {
+            // toggle the prescaler output. Noise is no different to channels.
+            self.count_noise = 0;
+            self.prescale_noise = !self.prescale_noise;
+            if expanded {
+                // The period of the noise is determined by the least significant byte
+                // of the LFSR, which is ANDed with the AND mask and ORed with the OR mask.
+                // (clock is also not divided)
+                self.noise_value += 1;
+                let compare = ((self.rng & 0xff) & self.regs[AY_NOISEAND] as u32)
+                    | self.regs[AY_NOISEOR] as u32;
+                if self.noise_value >= compare {
+                    self.noise_value = 0;
+                    self.noise_out ^= 1;
+                    self.noise_rng_tick();
+                }
+            } else if !self.prescale_noise {
+                self.noise_rng_tick();
+            }
+        }
+
+        // envelope (AY-3-8910 compatible mode uses envelope A only)
+        let num_envelope = if expanded { NUM_CHANNELS } else { 1 };
+        for chan in 0..num_envelope {
+            let period = self.envelope_period(chan) * self.step;
+            let env_step_mask = self.env_step_mask;
+            let envelope = &mut self.envelope[chan];
+            if !envelope.holding {
+                envelope.count += 1;
+                if envelope.count >= period {
+                    envelope.count = 0;
+                    envelope.step -= 1;
+
+                    // check envelope current position
+                    if envelope.step < 0 {
+                        if envelope.hold {
+                            if envelope.alternate {
+                                envelope.attack ^= env_step_mask;
+                            }
+                            envelope.holding = true;
+                            envelope.step = 0;
+                        } else {
+                            // if CountEnv has looped an odd number of times (usually 1),
+                            // invert the output.
+                            if envelope.alternate
+                                && (envelope.step as u8 & (env_step_mask + 1)) != 0
+                            {
+                                envelope.attack ^= env_step_mask;
+                            }
+                            envelope.step &= env_step_mask as i8;
+                        }
+                    }
+                }
+            }
+            envelope.volume = (envelope.step as u8 ^ envelope.attack) as u32;
+        }
+    }
+
+    ///
+    /// Return (envelope mode, table index) of channel
+    ///
+    #[inline]
+    fn channel_level(&self, chan: usize) -> (bool, u32) {
+        let expanded = self.is_expanded_mode();
+        if self.tone_envelope(chan) {
+            let envelope = &self.envelope[if expanded { chan } else { 0 }];
+            let mut env_volume = envelope.volume;
+            if self.has_expanded_mode && !expanded {
+                // AY8930 compatible mode uses 16 steps volume table
+                env_volume >>= 1;
+            }
+            (true, if self.vol_enabled[chan] { env_volume } else { 0 })
+        } else {
+            let volume = if self.vol_enabled[chan] {
+                self.tone_volume(chan) as u32
+            } else {
+                0
+            };
+            // AY8930 expanded mode volume uses 32 steps table
+            (expanded, volume)
+        }
+    }
+
+    fn build_mixer_table(&mut self) {
+        let (par, par_env): (&AyYmParam, &AyYmParam) = match self.psg_type {
+            PsgType::YM => (&YM2149_PARAM, &YM2149_PARAM_ENV),
+            PsgType::AY if self.is_expanded_mode() => {
+                (&AY8930_PARAM_EXPANDED, &AY8930_PARAM_EXPANDED)
+            }
+            PsgType::AY => (&AY8910_PARAM, &AY8910_PARAM),
+        };
+        // discrete output has no normalize
+        let normalize = self.flags & AY8910_LEGACY_OUTPUT != 0
+            && self.flags & AY8910_DISCRETE_OUTPUT == 0;
+
+        if self.single_output {
+            self.vol3d_table = build_3d_table(
+                RES_LOAD,
+                par,
+                par_env,
+                normalize,
+                NUM_CHANNELS as f64,
+                self.zero_is_off,
+            );
+        } else {
+            build_single_table(
+                RES_LOAD,
+                par,
+                normalize,
+                &mut self.vol_table,
+                self.zero_is_off,
+            );
+            build_single_table(RES_LOAD, par_env, normalize, &mut self.env_table, false);
+        }
+    }
+}
+
+///
+/// Build per channel output table
+///
+/// Normalized output starts at zero level so that silence has no DC offset.
+/// Output without normalize keeps the resistor network offset and is scaled
+/// so that the maximum matches the normalized output.
+///
+fn build_single_table(
+    rl: f64,
+    par: &AyYmParam,
+    normalize: bool,
+    tab: &mut [f32; 32],
+    zero_is_off: bool,
+) {
+    let mut temp: [f64; 32] = [0_f64; 32];
+    let mut min: f64 = 10.0;
+    let mut max: f64 = 0.0;
+
+    for (j, res) in par.res.iter().enumerate() {
+        let mut rt = 1.0 / par.r_down + 1.0 / rl;
+        let mut rw = 1.0 / res;
+        rt += 1.0 / res;
+
+        if !(zero_is_off && j == 0) {
+            rw += 1.0 / par.r_up;
+            rt += 1.0 / par.r_up;
+        }
+
+        temp[j] = rw / rt;
+        min = min.min(temp[j]);
+        max = max.max(temp[j]);
+    }
+    tab.fill(0_f32);
+    for j in 0..par.res.len() {
+        let value = if normalize {
+            ((temp[j] - min) / (max - min)) * 0.5
+        } else {
+            temp[j] / max * 0.5
+        };
+        tab[j] = value as f32;
+    }
+}
+
+///
+/// Build output table of 3 channels mixed through the resistor network
+///
+/// index: envelope mode (3bit) << 15 | channel C << 10 | channel B << 5 | channel A
+///
+fn build_3d_table(
+    rl: f64,
+    par: &AyYmParam,
+    par_env: &AyYmParam,
+    normalize: bool,
+    factor: f64,
+    zero_is_off: bool,
+) -> Vec<f32> {
+    let mut min: f64 = 10.0;
+    let mut max: f64 = 0.0;
+
+    let mut temp: Vec<f64> = vec![0_f64; 8 * 32 * 32 * 32];
+
+    for e in 0..8 {
+        let par_ch1 = if e & 0x01 != 0 { par_env } else { par };
+        let par_ch2 = if e & 0x02 != 0 { par_env } else { par };
+        let par_ch3 = if e & 0x04 != 0 { par_env } else { par };
+
+        for (j1, res1) in par_ch1.res.iter().enumerate() {
+            for (j2, res2) in par_ch2.res.iter().enumerate() {
+                for (j3, res3) in par_ch3.res.iter().enumerate() {
+                    let n: f64 = if zero_is_off {
+                        let mut n = 0.0;
+                        if j1 != 0 || (e & 0x01) != 0 {
+                            n += 1.0;
+                        }
+                        if j2 != 0 || (e & 0x02) != 0 {
+                            n += 1.0;
+                        }
+                        if j3 != 0 || (e & 0x04) != 0 {
+                            n += 1.0;
+                        }
+                        n
+                    } else {
+                        3.0
+                    };
+
+                    let mut rt = n / par.r_up + 3.0 / par.r_down + 1.0 / rl;
+                    let mut rw = n / par.r_up;
+
+                    rw += 1.0 / res1;
+                    rt += 1.0 / res1;
+                    rw += 1.0 / res2;
+                    rt += 1.0 / res2;
+                    rw += 1.0 / res3;
+                    rt += 1.0 / res3;
+
+                    let indx = (e << 15) | (j3 << 10) | (j2 << 5) | j1;
+                    temp[indx] = rw / rt;
+                    min = min.min(temp[indx]);
+                    max = max.max(temp[indx]);
+                }
+            }
+        }
+    }
+
+    // the previous implementation added all three channels up instead of averaging them.
+    // the factor of 3 will force the same levels if normalizing is used.
+    temp.iter()
+        .map(|temp| {
+            let value = if normalize {
+                ((temp - min) / (max - min)) * 0.5 * factor
+            } else {
+                temp / max * 0.5 * factor
+            };
+            value as f32
+        })
+        .collect()
+}
+
+impl SoundChip for AY8910 {
+    fn create(sound_device_name: SoundChipType) -> Self {
+        match sound_device_name {
+            SoundChipType::AY8910 => AY8910::new(),
+            _ => {
+                panic!("not supported sound chip type");
+            }
+        }
+    }
+
+    fn init(&mut self, clock: u32) -> u32 {
+        self.device_start(clock)
+    }
+
+    fn reset(&mut self) {
+        self.device_reset();
+    }
+
+    fn write(&mut self, _: usize, port: u32, data: u32, _: &mut dyn SoundStream) {
+        match port {
+            PORT_CONFIG_CHIP_TYPE => self.set_type(data),
+            PORT_CONFIG_FLAGS => self.set_flags(data),
+            PORT_CONFIG_STEREO_MASK => self.stereo_mask = data as u8 & AY8910_STEREO_MASK_ALL,
+            _ => self.write(port, data as u8),
+        }
+    }
+
+    fn tick(&mut self, _: usize, sound_stream: &mut dyn SoundStream) {
+        let mut l: [f32; 1] = [0_f32];
+        let mut r: [f32; 1] = [0_f32];
+        self.sound_stream_update(&mut l, &mut r);
+        sound_stream.push(l[0], r[0]);
+    }
+
+    fn set_rom_bank(&mut self, _: RomIndex, _: RomBank) {
+        /* nothing to do */
+    }
+
+    fn notify_add_rom(&mut self, _: RomIndex, _: usize) {
+        /* nothing to do */
+    }
+
+    fn set_rom_bus(&mut self, _: Option<RomBusType>) {
+        /* nothing to do */
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AY8910_SINGLE_OUTPUT, AY_TYPE_AY8910, AY_TYPE_AY8930, AY_TYPE_YM2149};
+    use super::{PORT_CONFIG_CHIP_TYPE, PORT_CONFIG_FLAGS, PORT_CONFIG_STEREO_MASK};
+    use crate::sound::slot::tests::{assert_frequency, frequency, generate, sampling, sound_slot};
+    use crate::sound::{SoundChipType, SoundSlot};
+
+    #[test]
+    fn ay8910_1() {
+        for (chip_type, flags) in [
+            (AY_TYPE_AY8910, 0),
+            (AY_TYPE_YM2149, AY8910_SINGLE_OUTPUT),
+            (AY_TYPE_AY8930, 0),
+        ] {
+            let mut sound_slot = sound_slot(SoundChipType::AY8910, 1789772);
+            sound_slot.write(SoundChipType::AY8910, 0, PORT_CONFIG_CHIP_TYPE, chip_type);
+            sound_slot.write(SoundChipType::AY8910, 0, PORT_CONFIG_FLAGS, flags);
+            if chip_type == AY_TYPE_AY8930 {
+                // expanded mode bank B tone A duty cycle 50% and bank A
+                sound_slot.write(SoundChipType::AY8910, 0, 0x0d, 0xb0);
+                sound_slot.write(SoundChipType::AY8910, 0, 0x06, 0x04);
+                sound_slot.write(SoundChipType::AY8910, 0, 0x0d, 0xa0);
+            }
+            // tone A period 0x100 and tone A only
+            sound_slot.write(SoundChipType::AY8910, 0, 0x00, 0x00);
+            sound_slot.write(SoundChipType::AY8910, 0, 0x01, 0x01);
+            sound_slot.write(SoundChipType::AY8910, 0, 0x07, 0x3e);
+            // the output starts from zero to the offset
+            generate(&mut sound_slot);
+            let swing_off = swing(&mut sound_slot);
+            // tone A volume (AY8930 expanded mode has 32 steps)
+            let volume = if chip_type == AY_TYPE_AY8930 { 0x1f } else { 0x0f };
+            sound_slot.write(SoundChipType::AY8910, 0, 0x08, volume);
+            let swing_on = swing(&mut sound_slot);
+            assert!(swing_on > swing_off * 2_f32);
+            // clock / (16 * period)
+            let (l, _) = sampling(&sound_slot);
+            assert_frequency(l, 1789772_f32 / (16_f32 * 256_f32));
+        }
+    }
+
+    #[test]
+    fn ay8910_stereo_mask() {
+        let mut sound_slot = sound_slot(SoundChipType::AY8910, 1789772);
+        // tone A (left only) and tone B (right only) of volume 15
+        sound_slot.write(SoundChipType::AY8910, 0, PORT_CONFIG_STEREO_MASK, 0x09);
+        sound_slot.write(SoundChipType::AY8910, 0, 0x01, 0x01);
+        sound_slot.write(SoundChipType::AY8910, 0, 0x03, 0x02);
+        sound_slot.write(SoundChipType::AY8910, 0, 0x07, 0x3c);
+        sound_slot.write(SoundChipType::AY8910, 0, 0x08, 0x0f);
+        sound_slot.write(SoundChipType::AY8910, 0, 0x09, 0x0f);
+        generate(&mut sound_slot);
+        let (l, r) = sampling(&sound_slot);
+        // tone A is 0x100 period and tone B is 0x200 period
+        assert_eq!(frequency(l), 2 * frequency(r));
+    }
+
+    fn swing(sound_slot: &mut SoundSlot) -> f32 {
+        // peak to peak (the output without the legacy flag has the offset of the resistor network)
+        generate(sound_slot);
+        let (l, _) = sampling(sound_slot);
+        let max = l.iter().fold(f32::MIN, |max, sample| max.max(*sample));
+        let min = l.iter().fold(f32::MAX, |min, sample| min.min(*sample));
+        max - min
+    }
+}
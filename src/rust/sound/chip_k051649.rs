@@ -0,0 +1,279 @@
+// license:BSD-3-Clause
+/*
+ * Rust K051649 ports by
+ *  Hiromasa Tanaka <h1romas4@gmail.com>
+ *  https://github.com/h1romas4/libymfm.wasm
+ *
+ * Porting from:
+ *  K051649 emulation by Bryan McPhail
+ *  https://github.com/mamedev/mame/blob/master/src/devices/sound/k051649.cpp
+ */
+
+/*
+ * Original K051649 emulation Copyright
+ */
+// license:BSD-3-Clause
+// copyright-holders:Bryan McPhail
+/***************************************************************************
+
+    Konami 051649 - SCC1 sound as used in Haunted Castle, City Bomber
+
+    This file is pieced together by Bryan McPhail from a combination of
+    Namco Sound, Amuse by Cab, Haunted Castle schematics and whoever first
+    figured out SCC!
+
+    The 051649 is a 5 channel sound generator, each channel gets its
+    waveform from RAM (32 bytes per waveform, 8 bit signed data).
+
+    This sound chip is the same as the sound chip in some Konami
+    megaROM cartridges for the MSX. It is actually well researched
+    and documented:
+
+        http://bifi.msxnet.org/msxnet/tech/scc.html
+
+    Thanks to Sean Young (sean@mess.org) for some bugfixes.
+
+    K052539 is more or less equivalent to this chip except channel 5
+    does not share waveram with channel 4.
+
+    TODO:
+    - make K052539 a subdevice
+    - test register bits 0-4, not used in any software
+
+***************************************************************************/
+use super::{
+    rom::RomBank,
+    sound_chip::SoundChip,
+    stream::{convert_int, SoundStream},
+    RomBusType, RomIndex, SoundChipType,
+};
+
+const MAX_CHANNEL: usize = 5;
+
+///
+/// VGM 0xd2 port
+///
+const PORT_WAVEFORM: u32 = 0x00;
+const PORT_FREQUENCY: u32 = 0x01;
+const PORT_VOLUME: u32 = 0x02;
+const PORT_KEYONOFF: u32 = 0x03;
+const PORT_WAVEFORM_K052539: u32 = 0x04;
+const PORT_TEST: u32 = 0x05;
+
+pub struct K051649 {
+    channel_list: [K051649Channel; MAX_CHANNEL],
+    test: u8,
+    sccplus: bool,
+}
+
+#[derive(Default, Copy, Clone)]
+struct K051649Channel {
+    clock: i32,
+    frequency: i32,
+    volume: i32,
+    counter: usize,
+    key: bool,
+    waveram: [i8; 32],
+}
+
+impl K051649 {
+    fn new(sccplus: bool) -> Self {
+        K051649 {
+            channel_list: [K051649Channel::default(); MAX_CHANNEL],
+            test: 0,
+            sccplus,
+        }
+    }
+
+    fn device_start(&mut self, clock: u32) -> u32 {
+        self.device_reset();
+        clock
+    }
+
+    fn device_reset(&mut self) {
+        // reset all the voices
+        for voice in self.channel_list.iter_mut() {
+            voice.frequency = 0;
+            voice.volume = 0;
+            voice.counter = 0;
+            voice.key = false;
+        }
+        // other parameters
+        self.test = 0;
+    }
+
+    fn sound_stream_update(&mut self, buffer_l: &mut [f32], buffer_r: &mut [f32]) {
+        let mut out: i32 = 0;
+        for voice in self.channel_list.iter_mut() {
+            // channel is halted for freq < 9
+            if voice.frequency > 8 {
+                voice.clock -= 1;
+                if voice.clock < 0 {
+                    voice.counter = (voice.counter + 1) & 0x1f;
+                    voice.clock = voice.frequency;
+                }
+                // scale to 11 bit digital output on chip
+                if voice.key {
+                    out += (voice.waveram[voice.counter] as i32 * voice.volume) >> 4;
+                }
+            }
+        }
+        let out = convert_int(out, 1024);
+        buffer_l[0] += out;
+        buffer_r[0] += out;
+    }
+
+    fn k051649_waveform_w(&mut self, offset: usize, data: u8) {
+        // waveram is read-only?
+        if self.test & 0x40 != 0 || (self.test & 0x80 != 0 && offset >= 0x60) {
+            return;
+        }
+        if offset >= 0x60 {
+            // channel 5 shares waveram with channel 4
+            self.channel_list[3].waveram[offset & 0x1f] = data as i8;
+            self.channel_list[4].waveram[offset & 0x1f] = data as i8;
+        } else {
+            self.channel_list[offset >> 5].waveram[offset & 0x1f] = data as i8;
+        }
+    }
+
+    fn k052539_waveform_w(&mut self, offset: usize, data: u8) {
+        // waveram is read-only?
+        if self.test & 0x40 != 0 {
+            return;
+        }
+        self.channel_list[(offset >> 5) % MAX_CHANNEL].waveram[offset & 0x1f] = data as i8;
+    }
+
+    fn k051649_volume_w(&mut self, offset: usize, data: u8) {
+        self.channel_list[offset % MAX_CHANNEL].volume = (data & 0xf) as i32;
+    }
+
+    fn k051649_frequency_w(&mut self, offset: usize, data: u8) {
+        let freq_hi = offset & 1 != 0;
+        let voice = &mut self.channel_list[(offset >> 1) % MAX_CHANNEL];
+
+        // test-register bit 5 resets the internal counter
+        if self.test & 0x20 != 0 {
+            voice.counter = 0;
+            voice.clock = 0;
+        } else if voice.frequency < 9 {
+            // channel counter is reset when frequency changes from halted state
+            voice.clock = 0;
+        }
+
+        // update frequency
+        if freq_hi {
+            voice.frequency = (voice.frequency & 0x0ff) | ((data as i32) << 8 & 0xf00);
+        } else {
+            voice.frequency = (voice.frequency & 0xf00) | data as i32;
+        }
+    }
+
+    fn k051649_keyonoff_w(&mut self, data: u8) {
+        for (i, voice) in self.channel_list.iter_mut().enumerate() {
+            voice.key = (data >> i) & 1 != 0;
+        }
+    }
+
+    fn k051649_test_w(&mut self, data: u8) {
+        self.test = data;
+    }
+
+    ///
+    /// Write VGM 0xd2 command (port << 8 | offset)
+    ///
+    fn write(&mut self, port: u32, data: u8) {
+        let offset = (port & 0xff) as usize;
+        match port >> 8 {
+            PORT_WAVEFORM => {
+                if self.sccplus {
+                    self.k052539_waveform_w(offset, data);
+                } else {
+                    self.k051649_waveform_w(offset, data);
+                }
+            }
+            PORT_FREQUENCY => self.k051649_frequency_w(offset, data),
+            PORT_VOLUME => self.k051649_volume_w(offset, data),
+            PORT_KEYONOFF => self.k051649_keyonoff_w(data),
+            PORT_WAVEFORM_K052539 => self.k052539_waveform_w(offset, data),
+            PORT_TEST => self.k051649_test_w(data),
+            _ => { /* nothing to do */ }
+        }
+    }
+}
+
+impl SoundChip for K051649 {
+    fn create(sound_device_name: SoundChipType) -> Self {
+        match sound_device_name {
+            SoundChipType::K051649 => K051649::new(false),
+            SoundChipType::K052539 => K051649::new(true),
+            _ => {
+                panic!("not supported sound chip type");
+            }
+        }
+    }
+
+    fn init(&mut self, clock: u32) -> u32 {
+        self.device_start(clock)
+    }
+
+    fn reset(&mut self) {
+        self.device_reset();
+    }
+
+    fn write(&mut self, _: usize, port: u32, data: u32, _: &mut dyn SoundStream) {
+        self.write(port, data as u8);
+    }
+
+    fn tick(&mut self, _: usize, sound_stream: &mut dyn SoundStream) {
+        let mut l: [f32; 1] = [0_f32];
+        let mut r: [f32; 1] = [0_f32];
+        self.sound_stream_update(&mut l, &mut r);
+        sound_stream.push(l[0], r[0]);
+    }
+
+    fn set_rom_bank(&mut self, _: RomIndex, _: RomBank) {
+        /* nothing to do */
+    }
+
+    fn notify_add_rom(&mut self, _: RomIndex, _: usize) {
+        /* nothing to do */
+    }
+
+    fn set_rom_bus(&mut self, _: Option<RomBusType>) {
+        /* nothing to do */
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sound::slot::tests::{assert_frequency, generate, sampling, sound_slot};
+    use crate::sound::SoundChipType;
+
+    #[test]
+    fn k051649_1() {
+        for sound_chip_type in [SoundChipType::K051649, SoundChipType::K052539] {
+            let mut sound_slot = sound_slot(sound_chip_type, 1789772);
+            // square wave of channel 1 (VGM 0xd2 port 0)
+            for offset in 0..32 {
+                let data = if offset < 16 { 0x7f } else { 0x80 };
+                sound_slot.write(sound_chip_type, 0, offset, data);
+            }
+            // frequency 0x100 and volume 15
+            sound_slot.write(sound_chip_type, 0, 0x100, 0x00);
+            sound_slot.write(sound_chip_type, 0, 0x101, 0x01);
+            sound_slot.write(sound_chip_type, 0, 0x200, 0x0f);
+            assert_eq!(generate(&mut sound_slot), 0_f32);
+            // key on (clock / (32 * (frequency + 1)))
+            sound_slot.write(sound_chip_type, 0, 0x300, 0x01);
+            assert!(generate(&mut sound_slot) > 0_f32);
+            let (l, r) = sampling(&sound_slot);
+            assert_eq!(l, r);
+            assert_frequency(l, 1789772_f32 / (32_f32 * 257_f32));
+            // key off
+            sound_slot.write(sound_chip_type, 0, 0x300, 0x00);
+            assert_eq!(generate(&mut sound_slot), 0_f32);
+        }
+    }
+}
@@ -173,7 +173,14 @@ impl OKIM6258 {
     }
 
     pub fn set_divider(&mut self, val: u32) -> u32 {
-        self.divider = DIVIDERS[val as usize];
+        // divider is 2 bits (the value is from the file)
+        self.divider = DIVIDERS[(val & 3) as usize];
+        // return sampling rate
+        self.clock / self.divider
+    }
+
+    pub fn set_clock(&mut self, clock: u32) -> u32 {
+        self.clock = clock;
         // return sampling rate
         self.clock / self.divider
     }
@@ -280,6 +287,7 @@ impl SoundChip for OKIM6258 {
             0x10 => sound_stream.change_sampling_rate(self.set_divider(data)),
             0x11 => self.set_outbits((data & 0xff) as u8),
             0x12 => self.set_type((data & 0xff) as u8),
+            0x13 => sound_stream.change_sampling_rate(self.set_clock(data)),
             _ => {
                 /* panic!("chip_okim6258 unknown offset") */
             }
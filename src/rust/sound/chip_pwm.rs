@@ -296,3 +296,24 @@ impl SoundChip for PWM {
         /* nothing to do */
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::sound::slot::tests::{generate, sound_slot};
+    use crate::sound::SoundChipType;
+
+    #[test]
+    fn pwm_data_stream() {
+        let mut sound_slot = sound_slot(SoundChipType::PWM, 23011361);
+        // left / right channel on and 22kHz cycle
+        sound_slot.write(SoundChipType::PWM, 0, 0x00, 0x05);
+        sound_slot.write(SoundChipType::PWM, 0, 0x01, 1045);
+        // 12-bit samples to the mono register (command 0x14 is register 0x04)
+        let samples: Vec<u8> = (0..2205).flat_map(|i| [(i & 0xff) as u8, 0x08]).collect();
+        sound_slot.add_data_block(0, &samples);
+        sound_slot.add_data_stream(SoundChipType::PWM, 0, 0, 0x01, 0x14);
+        sound_slot.set_data_stream_frequency(SoundChipType::PWM, 0, 0, 22050);
+        sound_slot.start_data_stream_fast(SoundChipType::PWM, 0, 0, 0, 0);
+        assert!(generate(&mut sound_slot) > 0_f32);
+    }
+}
@@ -0,0 +1,1332 @@
+// license:BSD-3-Clause
+/*
+ * Rust SCSP ports by
+ *  Hiromasa Tanaka <h1romas4@gmail.com>
+ *  https://github.com/h1romas4/libymfm.wasm
+ *
+ * Porting from:
+ *  SCSP (YMF292-F) emulation by ElSemi, R. Belmont
+ *  https://github.com/mamedev/mame/blob/master/src/devices/sound/scsp.cpp
+ *  https://github.com/mamedev/mame/blob/master/src/devices/sound/scspdsp.cpp
+ */
+
+/*
+ * Original SCSP emulation Copyright
+ */
+// license:BSD-3-Clause
+// copyright-holders:ElSemi, R. Belmont
+/*
+    Sega/Yamaha YMF292-F (SCSP = 'Saturn Custom Sound Processor') emulation
+    By ElSemi, kingshriek, Deunan Knute, and R. Belmont
+    Many thanks to Guru, Tourniquet, and TyRaNiD
+
+    SCSP features 32 programmable slots that can generate FM and PCM (from ROM/RAM) sound
+    * Sound ram (Saturn)
+    * 32 slots (FM and PCM)
+    * DSP
+
+    Changelog:
+    - PCM8B fix (use SA&0x7fffe in 16-bit mode), LPCTL=2 and LPCTL=3 fixes
+    - Implemented Phase LFO, amplitude LFO, DSP
+    - Ring buffer for FM (modulation from slots)
+
+    TODO:
+    - noise generator (SSCTL != 0)
+    - timers, interrupts, MIDI, DMA (not needed by VGM playback)
+*/
+use super::{
+    rom::{get_rom_ref, RomBank},
+    sound_chip::SoundChip,
+    stream::{convert_sample_i2f, SoundStream},
+    RomBusType, RomIndex, SoundChipType,
+};
+
+const SHIFT: i32 = 12;
+const LFO_SHIFT: i32 = 8;
+const EG_SHIFT: i32 = 16;
+
+const SCSP_RAM_LENGTH: usize = 512 * 1024;
+
+#[rustfmt::skip]
+const AR_TIMES: [f64; 64] = [
+    100000.0 /* infinity */, 100000.0 /* infinity */, 8100.0, 6900.0, 6000.0, 4800.0, 4000.0, 3400.0,
+    3000.0, 2400.0, 2000.0, 1700.0, 1500.0, 1200.0, 1000.0, 860.0,
+    760.0, 600.0, 500.0, 430.0, 380.0, 300.0, 250.0, 220.0,
+    190.0, 150.0, 130.0, 110.0, 95.0, 76.0, 63.0, 55.0,
+    47.0, 38.0, 31.0, 27.0, 24.0, 19.0, 15.0, 13.0,
+    12.0, 9.4, 7.9, 6.8, 6.0, 4.7, 3.8, 3.4,
+    3.0, 2.4, 2.0, 1.8, 1.6, 1.3, 1.1, 0.93,
+    0.85, 0.65, 0.53, 0.44, 0.40, 0.35, 0.0, 0.0,
+];
+
+#[rustfmt::skip]
+const DR_TIMES: [f64; 64] = [
+    100000.0 /* infinity */, 100000.0 /* infinity */, 118200.0, 101300.0, 88600.0, 70900.0, 59100.0, 50700.0,
+    44300.0, 35500.0, 29600.0, 25300.0, 22200.0, 17700.0, 14800.0, 12700.0,
+    11100.0, 8900.0, 7400.0, 6300.0, 5500.0, 4400.0, 3700.0, 3200.0,
+    2800.0, 2200.0, 1800.0, 1600.0, 1400.0, 1100.0, 920.0, 790.0,
+    690.0, 550.0, 460.0, 390.0, 340.0, 270.0, 230.0, 200.0,
+    170.0, 140.0, 110.0, 98.0, 85.0, 68.0, 57.0, 49.0,
+    43.0, 34.0, 28.0, 25.0, 22.0, 18.0, 14.0, 12.0,
+    11.0, 8.5, 7.1, 6.1, 5.4, 4.3, 3.6, 3.1,
+];
+
+const SDLT: [f64; 8] = [-1000000.0, -36.0, -30.0, -24.0, -18.0, -12.0, -6.0, 0.0];
+
+#[rustfmt::skip]
+const LFO_FREQ: [f32; 32] = [
+    0.17, 0.19, 0.23, 0.27, 0.34, 0.39, 0.45, 0.55, 0.68, 0.78, 0.92, 1.10, 1.39, 1.60, 1.87, 2.27,
+    2.87, 3.31, 3.92, 4.79, 6.15, 7.18, 8.60, 10.8, 14.4, 17.2, 21.5, 28.7, 43.1, 57.4, 86.1, 172.3,
+];
+const ASCALE: [f32; 8] = [0.0, 0.4, 0.8, 1.5, 3.0, 6.0, 12.0, 24.0];
+const PSCALE: [f32; 8] = [0.0, 7.0, 13.5, 27.0, 55.0, 112.0, 230.0, 494.0];
+
+#[inline]
+fn fix(v: f64) -> i32 {
+    ((1 << SHIFT) as f64 * v) as u32 as i32
+}
+
+#[inline]
+fn lfix(v: f64) -> i32 {
+    ((1 << LFO_SHIFT) as f64 * v) as u32 as i32
+}
+
+// Convert DB to multiply amplitude
+#[inline]
+fn db(v: f64) -> i32 {
+    lfix(10.0_f64.powf(v / 20.0))
+}
+
+// Convert cents to step increment
+#[inline]
+fn cents(v: f64) -> i32 {
+    lfix(2.0_f64.powf(v / 1200.0))
+}
+
+#[derive(Default, Copy, Clone, PartialEq, Eq)]
+enum ScspEgState {
+    Attack,
+    Decay1,
+    Decay2,
+    #[default]
+    Release,
+}
+
+#[derive(Default, Copy, Clone)]
+struct ScspEg {
+    volume: i32,
+    state: ScspEgState,
+    ar: i32, // Attack rate
+    d1r: i32, // Decay1 rate
+    d2r: i32, // Decay2 rate
+    rr: i32, // Release rate
+    dl: i32, // Decay level
+    eghold: bool,
+}
+
+#[derive(Default, Copy, Clone)]
+struct ScspLfo {
+    phase: u16,
+    phase_step: u32,
+    table: usize,
+    scale: usize,
+}
+
+#[derive(Default, Copy, Clone)]
+struct ScspSlot {
+    data: [u16; 0x10],
+    backwards: bool, // the wave is playing backwards
+    active: bool,    // this slot is currently playing
+    cur_addr: u32,   // current play address (24.8)
+    nxt_addr: u32,   // next play address
+    step: u32,       // pitch step (24.8)
+    eg: ScspEg,      // Envelope
+    plfo: ScspLfo,   // Phase LFO
+    alfo: ScspLfo,   // Amplitude LFO
+}
+
+#[allow(non_snake_case)]
+impl ScspSlot {
+    fn KEYONEX(&self) -> bool {
+        self.data[0x0] & 0x1000 != 0
+    }
+    fn KEYONB(&self) -> bool {
+        self.data[0x0] & 0x0800 != 0
+    }
+    fn SBCTL(&self) -> u16 {
+        (self.data[0x0] >> 0x9) & 0x0003
+    }
+    fn SSCTL(&self) -> u16 {
+        (self.data[0x0] >> 0x7) & 0x0003
+    }
+    fn LPCTL(&self) -> u16 {
+        (self.data[0x0] >> 0x5) & 0x0003
+    }
+    fn PCM8B(&self) -> bool {
+        self.data[0x0] & 0x0010 != 0
+    }
+    fn SA(&self) -> u32 {
+        ((self.data[0x0] as u32 & 0xf) << 16) | self.data[0x1] as u32
+    }
+    fn LSA(&self) -> u32 {
+        self.data[0x2] as u32
+    }
+    fn LEA(&self) -> u32 {
+        self.data[0x3] as u32
+    }
+    fn D2R(&self) -> i32 {
+        ((self.data[0x4] >> 0xb) & 0x001f) as i32
+    }
+    fn D1R(&self) -> i32 {
+        ((self.data[0x4] >> 0x6) & 0x001f) as i32
+    }
+    fn EGHOLD(&self) -> bool {
+        self.data[0x4] & 0x0020 != 0
+    }
+    fn AR(&self) -> i32 {
+        (self.data[0x4] & 0x001f) as i32
+    }
+    fn LPSLNK(&self) -> bool {
+        self.data[0x5] & 0x4000 != 0
+    }
+    fn KRS(&self) -> i32 {
+        ((self.data[0x5] >> 0xa) & 0x000f) as i32
+    }
+    fn DL(&self) -> i32 {
+        ((self.data[0x5] >> 0x5) & 0x001f) as i32
+    }
+    fn RR(&self) -> i32 {
+        (self.data[0x5] & 0x001f) as i32
+    }
+    fn STWINH(&self) -> bool {
+        self.data[0x6] & 0x0200 != 0
+    }
+    fn SDIR(&self) -> bool {
+        self.data[0x6] & 0x0100 != 0
+    }
+    fn TL(&self) -> usize {
+        (self.data[0x6] & 0x00ff) as usize
+    }
+    fn MDL(&self) -> i32 {
+        ((self.data[0x7] >> 0xc) & 0x000f) as i32
+    }
+    fn MDXSL(&self) -> usize {
+        ((self.data[0x7] >> 0x6) & 0x003f) as usize
+    }
+    fn MDYSL(&self) -> usize {
+        (self.data[0x7] & 0x003f) as usize
+    }
+    fn OCT(&self) -> i32 {
+        ((self.data[0x8] >> 0xb) & 0x000f) as i32
+    }
+    fn FNS(&self) -> u32 {
+        (self.data[0x8] & 0x03ff) as u32
+    }
+    fn LFOF(&self) -> usize {
+        ((self.data[0x9] >> 0xa) & 0x001f) as usize
+    }
+    fn PLFOWS(&self) -> usize {
+        ((self.data[0x9] >> 0x8) & 0x0003) as usize
+    }
+    fn PLFOS(&self) -> usize {
+        ((self.data[0x9] >> 0x5) & 0x0007) as usize
+    }
+    fn ALFOWS(&self) -> usize {
+        ((self.data[0x9] >> 0x3) & 0x0003) as usize
+    }
+    fn ALFOS(&self) -> usize {
+        (self.data[0x9] & 0x0007) as usize
+    }
+    fn ISEL(&self) -> usize {
+        ((self.data[0xa] >> 0x3) & 0x000f) as usize
+    }
+    fn IMXL(&self) -> usize {
+        (self.data[0xa] & 0x0007) as usize
+    }
+    fn DISDL(&self) -> usize {
+        ((self.data[0xb] >> 0xd) & 0x0007) as usize
+    }
+    fn DIPAN(&self) -> usize {
+        ((self.data[0xb] >> 0x8) & 0x001f) as usize
+    }
+    fn EFSDL(&self) -> usize {
+        ((self.data[0xb] >> 0x5) & 0x0007) as usize
+    }
+    fn EFPAN(&self) -> usize {
+        (self.data[0xb] & 0x001f) as usize
+    }
+
+    fn step(&self) -> u32 {
+        let octave = (self.OCT() ^ 8) - 8 + SHIFT - 10;
+        let fnum = self.FNS() + (1 << 10);
+        if octave >= 0 {
+            fnum << octave
+        } else {
+            fnum >> -octave
+        }
+    }
+
+    fn stop_slot(&mut self, keyoff: bool) {
+        if keyoff {
+            self.eg.state = ScspEgState::Release;
+        } else {
+            self.active = false;
+        }
+        self.data[0] &= !0x800;
+    }
+
+    fn eg_update(&mut self) -> i32 {
+        match self.eg.state {
+            ScspEgState::Attack => {
+                self.eg.volume += self.eg.ar;
+                if self.eg.volume >= (0x3ff << EG_SHIFT) {
+                    if !self.LPSLNK() {
+                        self.eg.state = ScspEgState::Decay1;
+                        if self.eg.d1r >= (1024 << EG_SHIFT) {
+                            // Skip DECAY1, go directly to DECAY2
+                            self.eg.state = ScspEgState::Decay2;
+                        }
+                    }
+                    self.eg.volume = 0x3ff << EG_SHIFT;
+                }
+                if self.eg.eghold {
+                    return 0x3ff << (SHIFT - 10);
+                }
+            }
+            ScspEgState::Decay1 => {
+                self.eg.volume -= self.eg.d1r;
+                if self.eg.volume <= 0 {
+                    self.eg.volume = 0;
+                }
+                if self.eg.volume >> (EG_SHIFT + 5) <= self.eg.dl {
+                    self.eg.state = ScspEgState::Decay2;
+                }
+            }
+            ScspEgState::Decay2 => {
+                if self.D2R() == 0 {
+                    return (self.eg.volume >> EG_SHIFT) << (SHIFT - 10);
+                }
+                self.eg.volume -= self.eg.d2r;
+                if self.eg.volume <= 0 {
+                    self.eg.volume = 0;
+                }
+            }
+            ScspEgState::Release => {
+                self.eg.volume -= self.eg.rr;
+                if self.eg.volume <= 0 {
+                    self.eg.volume = 0;
+                    self.stop_slot(false);
+                }
+            }
+        }
+        (self.eg.volume >> EG_SHIFT) << (SHIFT - 10)
+    }
+}
+
+///
+/// SCSP DSP
+///
+struct ScspDsp {
+    rbp: u32, // Ring buf pointer
+    rbl: u32, // Delay ram (Ring buffer) size in words
+
+    coef: [i16; 64],   // 16 bit signed
+    madrs: [u16; 32],  // offsets (in words), 16 bit
+    mpro: [u16; 512],  // 128 steps 64 bit
+    temp: [i32; 128],  // TEMP regs,24 bit signed
+    mems: [i32; 32],   // MEMS regs,24 bit signed
+    dec: u32,
+
+    mixs: [i32; 16], // MIXS, 24 bit signed
+
+    efreg: [i16; 16], // EFREG, 16 bit signed
+
+    stopped: bool,
+    last_step: usize,
+}
+
+fn pack(val: i32) -> u16 {
+    let sign = (val >> 23) & 0x1;
+    let mut temp = ((val ^ (val << 1)) & 0xffffff) as u32;
+    let mut exponent = 0;
+    for _ in 0..12 {
+        if temp & 0x800000 != 0 {
+            break;
+        }
+        temp <<= 1;
+        exponent += 1;
+    }
+    let mut val = if exponent < 12 {
+        (val << exponent) & 0x3fffff
+    } else {
+        val << 11
+    };
+    val >>= 11;
+    val |= sign << 15;
+    val |= exponent << 11;
+
+    val as u16
+}
+
+fn unpack(val: u16) -> i32 {
+    let sign = ((val >> 15) & 0x1) as i32;
+    let mut exponent = ((val >> 11) & 0xf) as i32;
+    let mantissa = (val & 0x7ff) as i32;
+    let mut uval = mantissa << 11;
+    if exponent > 11 {
+        exponent = 11;
+        uval |= sign << 22;
+    } else {
+        uval |= (sign ^ 1) << 22;
+    }
+    uval |= sign << 23;
+    uval <<= 8;
+    uval >>= 8;
+    uval >>= exponent;
+
+    uval
+}
+
+#[inline]
+fn read_ram_word(ram: &[u8], address: usize) -> u16 {
+    let address = (address * 2) & (SCSP_RAM_LENGTH - 1);
+    (ram[address] as u16) << 8 | ram[address + 1] as u16
+}
+
+#[inline]
+fn write_ram_word(ram: &mut [u8], address: usize, data: u16) {
+    let address = (address * 2) & (SCSP_RAM_LENGTH - 1);
+    ram[address] = (data >> 8) as u8;
+    ram[address + 1] = data as u8;
+}
+
+impl ScspDsp {
+    fn new() -> Self {
+        ScspDsp {
+            rbp: 0,
+            rbl: 0x8000,
+            coef: [0; 64],
+            madrs: [0; 32],
+            mpro: [0; 512],
+            temp: [0; 128],
+            mems: [0; 32],
+            dec: 0,
+            mixs: [0; 16],
+            efreg: [0; 16],
+            stopped: true,
+            last_step: 0,
+        }
+    }
+
+    fn set_sample(&mut self, sample: i32, sel: usize, _mxl: usize) {
+        self.mixs[sel] = self.mixs[sel].wrapping_add(sample);
+    }
+
+    fn start(&mut self) {
+        self.stopped = false;
+        let mut last_step = 0;
+        for i in (0..128).rev() {
+            let iptr = &self.mpro[i * 4..i * 4 + 4];
+            if iptr.iter().any(|&i| i != 0) {
+                last_step = i + 1;
+                break;
+            }
+        }
+        self.last_step = last_step;
+    }
+
+    fn step(&mut self, ram: &mut [u8]) {
+        let mut acc: i32 = 0; // 26 bit
+        let mut shifted: i32; // 24 bit
+        let mut x: i32; // 24 bit
+        let mut y: i32 = 0; // 13 bit
+        let mut b: i32; // 26 bit
+        let mut inputs: i32; // 24 bit
+        let mut memval: i32 = 0;
+        let mut frc_reg: i32 = 0; // 13 bit
+        let mut y_reg: i32 = 0; // 24 bit
+        let mut adrs_reg: u32 = 0; // 13 bit
+
+        if self.stopped {
+            return;
+        }
+
+        self.efreg = [0; 16];
+        for step in 0..self.last_step {
+            let iptr = &self.mpro[step * 4..step * 4 + 4];
+
+            let tra = ((iptr[0] >> 8) & 0x7f) as u32;
+            let twt = (iptr[0] >> 7) & 0x01 != 0;
+            let twa = (iptr[0] & 0x7f) as u32;
+
+            let xsel = (iptr[1] >> 15) & 0x01 != 0;
+            let ysel = (iptr[1] >> 13) & 0x03;
+            let ira = ((iptr[1] >> 6) & 0x3f) as usize;
+            let iwt = (iptr[1] >> 5) & 0x01 != 0;
+            let iwa = (iptr[1] & 0x1f) as usize;
+
+            let table = (iptr[2] >> 15) & 0x01 != 0;
+            let mwt = (iptr[2] >> 14) & 0x01 != 0;
+            let mrd = (iptr[2] >> 13) & 0x01 != 0;
+            let ewt = (iptr[2] >> 12) & 0x01 != 0;
+            let ewa = ((iptr[2] >> 8) & 0x0f) as usize;
+            let adrl = (iptr[2] >> 7) & 0x01 != 0;
+            let frcl = (iptr[2] >> 6) & 0x01 != 0;
+            let shift = (iptr[2] >> 4) & 0x03;
+            let yrl = (iptr[2] >> 3) & 0x01 != 0;
+            let negb = (iptr[2] >> 2) & 0x01 != 0;
+            let zero = (iptr[2] >> 1) & 0x01 != 0;
+            let bsel = iptr[2] & 0x01 != 0;
+
+            let nofl = (iptr[3] >> 15) & 1 != 0;
+            let coef = ((iptr[3] >> 9) & 0x3f) as usize;
+
+            let masa = ((iptr[3] >> 2) & 0x1f) as usize;
+            let adreb = (iptr[3] >> 1) & 0x1 != 0;
+            let nxadr = iptr[3] & 0x1 != 0;
+
+            // operations are done at 24 bit precision
+
+            // INPUTS RW
+            if ira <= 0x1f {
+                inputs = self.mems[ira];
+            } else if ira <= 0x2f {
+                inputs = self.mixs[ira - 0x20] << 4; // MIXS is 20 bit
+            } else {
+                inputs = 0;
+            }
+
+            inputs <<= 8;
+            inputs >>= 8;
+
+            if iwt {
+                self.mems[iwa] = memval; // MEMVAL was selected in previous MRD
+                if ira == iwa {
+                    inputs = memval;
+                }
+            }
+
+            // Operand sel
+            // B
+            if !zero {
+                if bsel {
+                    b = acc;
+                } else {
+                    b = self.temp[(tra.wrapping_add(self.dec) & 0x7f) as usize];
+                    b <<= 8;
+                    b >>= 8;
+                }
+                if negb {
+                    b = b.wrapping_neg();
+                }
+            } else {
+                b = 0;
+            }
+
+            // X
+            if xsel {
+                x = inputs;
+            } else {
+                x = self.temp[(tra.wrapping_add(self.dec) & 0x7f) as usize];
+                x <<= 8;
+                x >>= 8;
+            }
+
+            // Y
+            match ysel {
+                0 => y = frc_reg,
+                1 => y = (self.coef[coef << 1] >> 3) as i32, // COEF is 16 bits
+                2 => y = (y_reg >> 11) & 0x1fff,
+                3 => y = (y_reg >> 4) & 0x0fff,
+                _ => {}
+            }
+
+            if yrl {
+                y_reg = inputs;
+            }
+
+            // Shifter
+            match shift {
+                0 => {
+                    shifted = acc.clamp(-0x00800000, 0x007fffff);
+                }
+                1 => {
+                    shifted = acc.wrapping_mul(2).clamp(-0x00800000, 0x007fffff);
+                }
+                2 => {
+                    shifted = acc.wrapping_mul(2);
+                    shifted <<= 8;
+                    shifted >>= 8;
+                }
+                _ => {
+                    shifted = acc;
+                    shifted <<= 8;
+                    shifted >>= 8;
+                }
+            }
+
+            // ACCUM
+            y <<= 19;
+            y >>= 19;
+
+            let v = (x as i64 * y as i64) >> 12;
+            acc = (v as i32).wrapping_add(b);
+
+            if twt {
+                self.temp[(twa.wrapping_add(self.dec) & 0x7f) as usize] = shifted;
+            }
+
+            if frcl {
+                if shift == 3 {
+                    frc_reg = shifted & 0x0fff;
+                } else {
+                    frc_reg = (shifted >> 11) & 0x1fff;
+                }
+            }
+
+            if mrd || mwt {
+                let mut addr = self.madrs[masa] as u32;
+                if !table {
+                    addr = addr.wrapping_add(self.dec);
+                }
+                if adreb {
+                    addr = addr.wrapping_add(adrs_reg & 0x0fff);
+                }
+                if nxadr {
+                    addr = addr.wrapping_add(1);
+                }
+                if !table {
+                    addr &= self.rbl - 1;
+                } else {
+                    addr &= 0xffff;
+                }
+                addr += self.rbp << 12;
+                // memory only allowed on odd? DoA inserts NOPs on even
+                if mrd && (step & 1) != 0 {
+                    if nofl {
+                        memval = (read_ram_word(ram, addr as usize) as i32) << 8;
+                    } else {
+                        memval = unpack(read_ram_word(ram, addr as usize));
+                    }
+                }
+                if mwt && (step & 1) != 0 {
+                    if nofl {
+                        write_ram_word(ram, addr as usize, (shifted >> 8) as u16);
+                    } else {
+                        write_ram_word(ram, addr as usize, pack(shifted));
+                    }
+                }
+            }
+
+            if adrl {
+                if shift == 3 {
+                    adrs_reg = ((shifted >> 12) & 0xfff) as u32;
+                } else {
+                    adrs_reg = (inputs >> 16) as u32;
+                }
+            }
+
+            if ewt {
+                self.efreg[ewa] = self.efreg[ewa].wrapping_add((shifted >> 8) as i16);
+            }
+        }
+        self.dec = self.dec.wrapping_sub(1);
+        self.mixs = [0; 16];
+    }
+}
+
+///
+/// SCSP
+///
+#[allow(clippy::upper_case_acronyms)]
+pub struct SCSP {
+    common: [u16; 0x18],
+    slots: [ScspSlot; 32],
+    ringbuf: [i16; 64],
+    bufptr: usize,
+    ram: Vec<u8>,
+    rom_bank: RomBank,
+    dsp: ScspDsp,
+
+    lpantable: Vec<i32>,
+    rpantable: Vec<i32>,
+    eg_table: Vec<i32>,
+    artable: [i32; 64],
+    drtable: [i32; 64],
+
+    plfo_table: [[i32; 256]; 4],
+    alfo_table: [[i32; 256]; 4],
+    pscales: [[i32; 256]; 8],
+    ascales: [[i32; 256]; 8],
+}
+
+impl SCSP {
+    fn new() -> Self {
+        SCSP {
+            common: [0; 0x18],
+            slots: [ScspSlot::default(); 32],
+            ringbuf: [0; 64],
+            bufptr: 0,
+            ram: vec![0; SCSP_RAM_LENGTH],
+            rom_bank: None,
+            dsp: ScspDsp::new(),
+            lpantable: vec![0; 0x10000],
+            rpantable: vec![0; 0x10000],
+            eg_table: vec![0; 0x400],
+            artable: [0; 64],
+            drtable: [0; 64],
+            plfo_table: [[0; 256]; 4],
+            alfo_table: [[0; 256]; 4],
+            pscales: [[0; 256]; 8],
+            ascales: [[0; 256]; 8],
+        }
+    }
+
+    fn device_start(&mut self, clock: u32) -> u32 {
+        for i in 0..0x400 {
+            let env_db = (3 * (i - 0x3ff)) as f64 / 32.0;
+            let scale = (1 << SHIFT) as f64;
+            self.eg_table[i as usize] = (10.0_f64.powf(env_db / 20.0) * scale) as i32;
+        }
+
+        for i in 0..0x10000 {
+            let itl = i & 0xff;
+            let ipan = (i >> 0x8) & 0x1f;
+            let isdl = (i >> 0xd) & 0x07;
+
+            let mut sega_db: f64 = 0.0;
+            if itl & 0x01 != 0 {
+                sega_db -= 0.4;
+            }
+            if itl & 0x02 != 0 {
+                sega_db -= 0.8;
+            }
+            if itl & 0x04 != 0 {
+                sega_db -= 1.5;
+            }
+            if itl & 0x08 != 0 {
+                sega_db -= 3.0;
+            }
+            if itl & 0x10 != 0 {
+                sega_db -= 6.0;
+            }
+            if itl & 0x20 != 0 {
+                sega_db -= 12.0;
+            }
+            if itl & 0x40 != 0 {
+                sega_db -= 24.0;
+            }
+            if itl & 0x80 != 0 {
+                sega_db -= 48.0;
+            }
+
+            let tl = 10.0_f64.powf(sega_db / 20.0);
+
+            sega_db = 0.0;
+            if ipan & 0x1 != 0 {
+                sega_db -= 3.0;
+            }
+            if ipan & 0x2 != 0 {
+                sega_db -= 6.0;
+            }
+            if ipan & 0x4 != 0 {
+                sega_db -= 12.0;
+            }
+            if ipan & 0x8 != 0 {
+                sega_db -= 24.0;
+            }
+
+            let pan = if (ipan & 0xf) == 0xf {
+                0.0
+            } else {
+                10.0_f64.powf(sega_db / 20.0)
+            };
+
+            let (lpan, rpan) = if ipan < 0x10 { (pan, 1.0) } else { (1.0, pan) };
+
+            let fsdl = if isdl != 0 {
+                10.0_f64.powf(SDLT[isdl] / 20.0)
+            } else {
+                0.0
+            };
+
+            self.lpantable[i] = fix(4.0 * lpan * tl * fsdl);
+            self.rpantable[i] = fix(4.0 * rpan * tl * fsdl);
+        }
+
+        // Infinite time
+        self.artable[0] = 0;
+        self.drtable[0] = 0;
+        self.artable[1] = 0;
+        self.drtable[1] = 0;
+        for i in 2..64 {
+            let scale = (1 << EG_SHIFT) as f64;
+            // In ms
+            let t = AR_TIMES[i];
+            if t != 0.0 {
+                let step = (1023.0 * 1000.0) / (44100.0 * t);
+                self.artable[i] = (step * scale) as i32;
+            } else {
+                self.artable[i] = 1024 << EG_SHIFT;
+            }
+            let t = DR_TIMES[i];
+            let step = (1023.0 * 1000.0) / (44100.0 * t);
+            self.drtable[i] = (step * scale) as i32;
+        }
+
+        self.lfo_init();
+        self.device_reset();
+
+        clock / 512
+    }
+
+    fn device_reset(&mut self) {
+        // make sure all the slots are off
+        for slot in self.slots.iter_mut() {
+            slot.active = false;
+            slot.eg.state = ScspEgState::Release;
+        }
+    }
+
+    fn lfo_init(&mut self) {
+        // noise (deterministic pseudo random)
+        let mut seed: u32 = 0x12345678;
+        for i in 0..256 {
+            // Saw
+            let a = 255 - i;
+            let p = if i < 128 { i } else { i - 256 };
+            self.alfo_table[0][i as usize] = a;
+            self.plfo_table[0][i as usize] = p;
+
+            // Square
+            let (a, p) = if i < 128 { (255, 127) } else { (0, -128) };
+            self.alfo_table[1][i as usize] = a;
+            self.plfo_table[1][i as usize] = p;
+
+            // Tri
+            let a = if i < 128 { 255 - (i * 2) } else { (i * 2) - 256 };
+            let p = if i < 64 {
+                i * 2
+            } else if i < 128 {
+                255 - i * 2
+            } else if i < 192 {
+                256 - i * 2
+            } else {
+                i * 2 - 511
+            };
+            self.alfo_table[2][i as usize] = a;
+            self.plfo_table[2][i as usize] = p;
+
+            // noise
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let a = ((seed >> 16) & 0xff) as i32;
+            let p = 128 - a;
+            self.alfo_table[3][i as usize] = a;
+            self.plfo_table[3][i as usize] = p.min(127);
+        }
+
+        for s in 0..8 {
+            let limit = PSCALE[s];
+            for i in -128..128 {
+                self.pscales[s][(i + 128) as usize] = cents((limit * i as f32 / 128.0) as f64);
+            }
+            let limit = -ASCALE[s];
+            for i in 0..256 {
+                self.ascales[s][i] = db((limit * i as f32 / 256.0) as f64);
+            }
+        }
+    }
+
+    fn lfo_compute_step(lfo: &mut ScspLfo, lfof: usize, lfows: usize, lfos: usize) {
+        let step = LFO_FREQ[lfof] * 256.0 / 44100.0;
+        lfo.phase_step = ((1 << LFO_SHIFT) as f32 * step) as u32;
+        lfo.table = lfows;
+        lfo.scale = lfos;
+    }
+
+    fn plfo_step(&self, lfo: &mut ScspLfo) -> i32 {
+        lfo.phase = lfo.phase.wrapping_add(lfo.phase_step as u16);
+        let p = self.plfo_table[lfo.table][(lfo.phase >> LFO_SHIFT) as usize];
+        let p = self.pscales[lfo.scale][(p + 128) as usize];
+        p << (SHIFT - LFO_SHIFT)
+    }
+
+    fn alfo_step(&self, lfo: &mut ScspLfo) -> i32 {
+        lfo.phase = lfo.phase.wrapping_add(lfo.phase_step as u16);
+        let p = self.alfo_table[lfo.table][(lfo.phase >> LFO_SHIFT) as usize];
+        let p = self.ascales[lfo.scale][p as usize];
+        p << (SHIFT - LFO_SHIFT)
+    }
+
+    fn compute_lfo(slot: &mut ScspSlot) {
+        let lfof = slot.LFOF();
+        if slot.PLFOS() != 0 {
+            let (lfows, lfos) = (slot.PLFOWS(), slot.PLFOS());
+            Self::lfo_compute_step(&mut slot.plfo, lfof, lfows, lfos);
+        }
+        if slot.ALFOS() != 0 {
+            let (lfows, lfos) = (slot.ALFOWS(), slot.ALFOS());
+            Self::lfo_compute_step(&mut slot.alfo, lfof, lfows, lfos);
+        }
+    }
+
+    fn get_ar(&self, base: i32, r: i32) -> i32 {
+        let rate = (base + (r << 1)).clamp(0, 63);
+        self.artable[rate as usize]
+    }
+
+    fn get_dr(&self, base: i32, r: i32) -> i32 {
+        let rate = (base + (r << 1)).clamp(0, 63);
+        self.drtable[rate as usize]
+    }
+
+    fn compute_eg(&self, slot: &mut ScspSlot) {
+        let octave = (slot.OCT() ^ 8) - 8;
+        let rate = if slot.KRS() != 0xf {
+            octave + 2 * slot.KRS() + ((slot.FNS() >> 9) & 1) as i32
+        } else {
+            0
+        };
+
+        slot.eg.volume = 0x17f << EG_SHIFT;
+        slot.eg.ar = self.get_ar(rate, slot.AR());
+        slot.eg.d1r = self.get_dr(rate, slot.D1R());
+        slot.eg.d2r = self.get_dr(rate, slot.D2R());
+        slot.eg.rr = self.get_dr(rate, slot.RR());
+        slot.eg.dl = 0x1f - slot.DL();
+        slot.eg.eghold = slot.EGHOLD();
+    }
+
+    fn start_slot(&self, slot: &mut ScspSlot) {
+        slot.active = true;
+        slot.cur_addr = 0;
+        slot.nxt_addr = 1 << SHIFT;
+        slot.step = slot.step();
+        self.compute_eg(slot);
+        slot.eg.state = ScspEgState::Attack;
+        slot.eg.volume = 0x17f << EG_SHIFT;
+        slot.backwards = false;
+
+        Self::compute_lfo(slot);
+    }
+
+    fn update_slot_reg(&mut self, s: usize, r: usize) {
+        match r & 0x3f {
+            0x0 | 0x1 if self.slots[s].KEYONEX() => {
+                for sl in 0..32 {
+                    let mut s2 = self.slots[sl];
+                    if s2.KEYONB() && s2.eg.state == ScspEgState::Release {
+                        self.start_slot(&mut s2);
+                    }
+                    if !s2.KEYONB() {
+                        s2.stop_slot(true);
+                    }
+                    self.slots[sl] = s2;
+                }
+                self.slots[s].data[0] &= !0x1000;
+            }
+            0x10 | 0x11 => {
+                let slot = &mut self.slots[s];
+                slot.step = slot.step();
+            }
+            0xa | 0xb => {
+                let rr = self.get_dr(0, self.slots[s].RR());
+                let slot = &mut self.slots[s];
+                slot.eg.rr = rr;
+                slot.eg.dl = 0x1f - slot.DL();
+            }
+            0x12 | 0x13 => {
+                Self::compute_lfo(&mut self.slots[s]);
+            }
+            _ => { /* nothing to do */ }
+        }
+    }
+
+    fn update_reg(&mut self, r: usize) {
+        if let 0x2 | 0x3 = r & 0x3f {
+            // RBL, RBP
+            let rbl = (self.common[1] >> 0x7) & 0x0003;
+            self.dsp.rbp = (self.common[1] & 0x003f) as u32;
+            self.dsp.rbl = match rbl {
+                0 => 8 * 1024,
+                1 => 16 * 1024,
+                2 => 32 * 1024,
+                _ => 64 * 1024,
+            };
+        }
+        // timers, interrupts, MIDI and DMA are not supported
+    }
+
+    fn r16(&self, addr: usize) -> u16 {
+        let addr = addr & 0xffff;
+        if addr < 0x400 {
+            let slot = addr / 0x20;
+            self.slots[slot].data[(addr & 0x1f) >> 1]
+        } else if addr < 0x600 {
+            if addr < 0x430 {
+                self.common[(addr & 0x3f) >> 1]
+            } else {
+                0
+            }
+        } else if addr < 0x700 {
+            self.ringbuf[(addr - 0x600) / 2] as u16
+        } else if addr < 0x780 {
+            self.dsp.coef[(addr - 0x700) / 2] as u16
+        } else if addr < 0x7c0 {
+            self.dsp.madrs[(addr - 0x780) / 2]
+        } else if addr < 0x800 {
+            self.dsp.madrs[(addr - 0x7c0) / 2]
+        } else if addr < 0xc00 {
+            self.dsp.mpro[(addr - 0x800) / 2]
+        } else {
+            0
+        }
+    }
+
+    fn w16(&mut self, addr: usize, val: u16) {
+        let addr = addr & 0xffff;
+        if addr < 0x400 {
+            let slot = addr / 0x20;
+            let addr = addr & 0x1f;
+            self.slots[slot].data[addr >> 1] = val;
+            self.update_slot_reg(slot, addr & 0x1f);
+        } else if addr < 0x600 {
+            if addr < 0x430 {
+                self.common[(addr & 0x3f) >> 1] = val;
+                self.update_reg(addr & 0x3f);
+            }
+        } else if addr < 0x700 {
+            self.ringbuf[(addr - 0x600) / 2] = val as i16;
+        } else {
+            // DSP
+            if addr < 0x780 {
+                // COEF
+                self.dsp.coef[(addr - 0x700) / 2] = val as i16;
+            } else if addr < 0x7c0 {
+                self.dsp.madrs[(addr - 0x780) / 2] = val;
+            } else if addr < 0x800 {
+                // MADRS is mirrored twice
+                self.dsp.madrs[(addr - 0x7c0) / 2] = val;
+            } else if addr < 0xc00 {
+                self.dsp.mpro[(addr - 0x800) / 2] = val;
+                if addr == 0xbf0 {
+                    self.dsp.start();
+                }
+            }
+        }
+    }
+
+    ///
+    /// Write VGM 0xc5 command (byte write to register offset)
+    ///
+    fn write(&mut self, offset: u32, data: u8) {
+        let addr = (offset & 0xfffe) as usize;
+        let tmp = self.r16(addr);
+        let tmp = if offset & 1 != 0 {
+            (tmp & 0xff00) | data as u16
+        } else {
+            (tmp & 0x00ff) | (data as u16) << 8
+        };
+        self.w16(addr, tmp);
+    }
+
+    fn update_slot(&mut self, sl: usize) -> i32 {
+        let mut slot = self.slots[sl];
+        let mut sample: i32;
+        let mut step = slot.step as i64;
+
+        if slot.SSCTL() != 0 {
+            // no FM or noise yet
+            return 0;
+        }
+
+        if slot.PLFOS() != 0 {
+            let mut plfo = slot.plfo;
+            step = (step * self.plfo_step(&mut plfo) as i64) >> SHIFT;
+            slot.plfo = plfo;
+        }
+        let step = step as u32;
+
+        let (mut addr1, mut addr2) = if slot.PCM8B() {
+            (slot.cur_addr >> SHIFT, slot.nxt_addr >> SHIFT)
+        } else {
+            (
+                (slot.cur_addr >> (SHIFT - 1)) & 0x7fffe,
+                (slot.nxt_addr >> (SHIFT - 1)) & 0x7fffe,
+            )
+        };
+
+        if slot.MDL() != 0 || slot.MDXSL() != 0 || slot.MDYSL() != 0 {
+            let mut smp: i32 = (self.ringbuf[(self.bufptr + slot.MDXSL()) & 63] as i32
+                + self.ringbuf[(self.bufptr + slot.MDYSL()) & 63] as i32)
+                / 2;
+
+            smp <<= 0xa; // associate cycle with 1024
+            smp >>= 0x1a - slot.MDL(); // ex. for MDL=0xF, sample range corresponds to +/- 64 pi (32=2^5 cycles) so shift by 11 (16-5 == 0x1A-0xF)
+            if !slot.PCM8B() {
+                smp <<= 1;
+            }
+
+            addr1 = addr1.wrapping_add(smp as u32);
+            addr2 = addr2.wrapping_add(smp as u32);
+        }
+
+        let fpart = (slot.cur_addr & ((1 << SHIFT) - 1)) as i32;
+        if slot.PCM8B() {
+            // 8 bit signed
+            let p1 = self.ram[(slot.SA().wrapping_add(addr1) & 0x7ffff) as usize] as i8 as i32;
+            let p2 = self.ram[(slot.SA().wrapping_add(addr2) & 0x7ffff) as usize] as i8 as i32;
+            let s = (p1 << 8) * ((1 << SHIFT) - fpart) + (p2 << 8) * fpart;
+            sample = s >> SHIFT;
+        } else {
+            // 16 bit signed (big endian)
+            let p1 = read_ram_word(&self.ram, (slot.SA().wrapping_add(addr1) & 0x7fffe) as usize / 2)
+                as i16 as i32;
+            let p2 = read_ram_word(&self.ram, (slot.SA().wrapping_add(addr2) & 0x7fffe) as usize / 2)
+                as i16 as i32;
+            let s = p1 * ((1 << SHIFT) - fpart) + p2 * fpart;
+            sample = s >> SHIFT;
+        }
+
+        if slot.SBCTL() & 0x1 != 0 {
+            sample ^= 0x7fff;
+        }
+        if slot.SBCTL() & 0x2 != 0 {
+            sample = (sample ^ 0x8000) as i16 as i32;
+        }
+
+        if slot.backwards {
+            slot.cur_addr = slot.cur_addr.wrapping_sub(step);
+        } else {
+            slot.cur_addr = slot.cur_addr.wrapping_add(step);
+        }
+        slot.nxt_addr = slot.cur_addr.wrapping_add(1 << SHIFT);
+
+        let addr = [slot.cur_addr >> SHIFT, slot.nxt_addr >> SHIFT];
+
+        if addr[0] >= slot.LSA()
+            && !slot.backwards
+            && slot.LPSLNK()
+            && slot.eg.state == ScspEgState::Attack
+        {
+            slot.eg.state = ScspEgState::Decay1;
+        }
+
+        let lsa = slot.LSA();
+        let lea = slot.LEA();
+        let mut slot_addr = [slot.cur_addr, slot.nxt_addr];
+        for addr_select in 0..2 {
+            let rem_addr: u32;
+            match slot.LPCTL() {
+                0 => {
+                    // no loop
+                    if addr[addr_select] >= lsa && addr[addr_select] >= lea {
+                        slot.stop_slot(false);
+                    }
+                }
+                1 => {
+                    // normal loop
+                    if addr[addr_select] >= lea {
+                        rem_addr = slot_addr[addr_select].wrapping_sub(lea << SHIFT);
+                        slot_addr[addr_select] = (lsa << SHIFT).wrapping_add(rem_addr);
+                    }
+                }
+                2 => {
+                    // reverse loop
+                    if addr[addr_select] >= lsa && !slot.backwards {
+                        rem_addr = slot_addr[addr_select].wrapping_sub(lsa << SHIFT);
+                        slot_addr[addr_select] = (lea << SHIFT).wrapping_sub(rem_addr);
+                        slot.backwards = true;
+                    } else if (addr[addr_select] < lsa || (slot_addr[addr_select] & 0x80000000) != 0)
+                        && slot.backwards
+                    {
+                        rem_addr = (lsa << SHIFT).wrapping_sub(slot_addr[addr_select]);
+                        slot_addr[addr_select] = (lea << SHIFT).wrapping_sub(rem_addr);
+                    }
+                }
+                _ => {
+                    // ping-pong
+                    if addr[addr_select] >= lea {
+                        // reached end, reverse until start
+                        rem_addr = slot_addr[addr_select].wrapping_sub(lea << SHIFT);
+                        slot_addr[addr_select] = (lea << SHIFT).wrapping_sub(rem_addr);
+                        slot.backwards = true;
+                    } else if (addr[addr_select] < lsa || (slot_addr[addr_select] & 0x80000000) != 0)
+                        && slot.backwards
+                    {
+                        // reached start or negative
+                        rem_addr = (lsa << SHIFT).wrapping_sub(slot_addr[addr_select]);
+                        slot_addr[addr_select] = (lsa << SHIFT).wrapping_add(rem_addr);
+                        slot.backwards = false;
+                    }
+                }
+            }
+        }
+        slot.cur_addr = slot_addr[0];
+        slot.nxt_addr = slot_addr[1];
+
+        if !slot.SDIR() {
+            if slot.ALFOS() != 0 {
+                let mut alfo = slot.alfo;
+                sample = (sample * self.alfo_step(&mut alfo)) >> SHIFT;
+                slot.alfo = alfo;
+            }
+
+            if slot.eg.state == ScspEgState::Attack {
+                sample = (sample * slot.eg_update()) >> SHIFT;
+            } else {
+                let eg = (slot.eg_update() >> (SHIFT - 10)) as usize;
+                sample = (sample * self.eg_table[eg & 0x3ff]) >> SHIFT;
+            }
+        }
+
+        if !slot.STWINH() {
+            let enc = slot.TL() | (0x7 << 0xd);
+            self.ringbuf[self.bufptr] = ((sample * self.lpantable[enc]) >> (SHIFT + 1)) as i16;
+        }
+
+        self.slots[sl] = slot;
+
+        sample
+    }
+
+    fn sound_stream_update(&mut self, buffer_l: &mut [f32], buffer_r: &mut [f32]) {
+        let mut smpl: i32 = 0;
+        let mut smpr: i32 = 0;
+
+        for sl in 0..32 {
+            if self.slots[sl].active {
+                let sample = self.update_slot(sl);
+                let slot = &self.slots[sl];
+
+                let enc = slot.TL() | (slot.IMXL() << 0xd);
+                self.dsp.set_sample(
+                    (sample * self.lpantable[enc]) >> (SHIFT - 2),
+                    slot.ISEL(),
+                    slot.IMXL(),
+                );
+                let enc = slot.TL() | (slot.DIPAN() << 0x8) | (slot.DISDL() << 0xd);
+                smpl += (sample * self.lpantable[enc]) >> SHIFT;
+                smpr += (sample * self.rpantable[enc]) >> SHIFT;
+            }
+            self.bufptr = (self.bufptr + 1) & 63;
+        }
+
+        self.dsp.step(&mut self.ram);
+
+        for i in 0..16 {
+            let slot = &self.slots[i];
+            if slot.EFSDL() != 0 {
+                let enc = (slot.EFPAN() << 0x8) | (slot.EFSDL() << 0xd);
+                smpl += (self.dsp.efreg[i] as i32 * self.lpantable[enc]) >> SHIFT;
+                smpr += (self.dsp.efreg[i] as i32 * self.rpantable[enc]) >> SHIFT;
+            }
+        }
+
+        // DAC18B
+        if self.common[0] & 0x0100 != 0 {
+            smpl = smpl.clamp(-131072, 131071) >> 2;
+            smpr = smpr.clamp(-131072, 131071) >> 2;
+        } else {
+            smpl = (smpl >> 2).clamp(-32768, 32767);
+            smpr = (smpr >> 2).clamp(-32768, 32767);
+        }
+
+        buffer_l[0] += convert_sample_i2f(smpl);
+        buffer_r[0] += convert_sample_i2f(smpr);
+    }
+
+    ///
+    /// Write sound RAM from RAM write data block
+    ///
+    fn write_ram(&mut self, start_address: usize, memory: &[u8]) {
+        for (i, &data) in memory.iter().enumerate() {
+            if start_address + i >= SCSP_RAM_LENGTH {
+                break;
+            }
+            self.ram[start_address + i] = data;
+        }
+    }
+}
+
+impl SoundChip for SCSP {
+    fn create(_sound_device_name: SoundChipType) -> Self {
+        SCSP::new()
+    }
+
+    fn init(&mut self, clock: u32) -> u32 {
+        self.device_start(clock)
+    }
+
+    fn reset(&mut self) {
+        self.device_reset();
+    }
+
+    fn write(&mut self, _: usize, offset: u32, data: u32, _: &mut dyn SoundStream) {
+        self.write(offset, data as u8);
+    }
+
+    fn tick(&mut self, _: usize, sound_stream: &mut dyn SoundStream) {
+        let mut l: [f32; 1] = [0_f32];
+        let mut r: [f32; 1] = [0_f32];
+        self.sound_stream_update(&mut l, &mut r);
+        sound_stream.push(l[0], r[0]);
+    }
+
+    fn set_rom_bank(&mut self, _ /* SCSP has only one RomBank */: RomIndex, rombank: RomBank) {
+        self.rom_bank = rombank;
+    }
+
+    fn notify_add_rom(&mut self, _: RomIndex, index_no: usize) {
+        // transfer RAM image to sound RAM (DSP also writes the sound RAM)
+        let (memory, start_address, length) = get_rom_ref(&self.rom_bank, index_no);
+        let memory = unsafe { std::slice::from_raw_parts(memory, length) };
+        self.write_ram(start_address, memory);
+    }
+
+    fn set_rom_bus(&mut self, _: Option<RomBusType>) {
+        /* nothing to do */
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sound::slot::tests::{assert_frequency, generate, sampling, sound_slot};
+    use crate::sound::{RomIndex, SoundChipType, SoundSlot};
+
+    #[test]
+    fn scsp_1() {
+        let mut sound_slot = sound_slot(SoundChipType::SCSP, 22579200);
+        // 8bit square wave in sound RAM
+        let memory: Vec<u8> = (0..0x100).map(|i| if i < 0x80 { 0x7f } else { 0x80 }).collect();
+        sound_slot.add_rom(SoundChipType::SCSP, 0, RomIndex::SCSP_RAM, &memory, 0, 0xff);
+        // VGM 0xc5 is byte write (big endian), slot 0 register and master volume
+        let write = |sound_slot: &mut SoundSlot, offset: u32, data: u32| {
+            sound_slot.write(SoundChipType::SCSP, 0, offset, data >> 8);
+            sound_slot.write(SoundChipType::SCSP, 0, offset + 1, data & 0xff);
+        };
+        // KYONB, forward loop, 8bit PCM
+        write(&mut sound_slot, 0x000, 0x0830);
+        // loop end address, attack rate 31, release rate 31, direct send level 7
+        write(&mut sound_slot, 0x006, 0x00ff);
+        write(&mut sound_slot, 0x008, 0x001f);
+        write(&mut sound_slot, 0x00a, 0x001f);
+        write(&mut sound_slot, 0x016, 0xe000);
+        write(&mut sound_slot, 0x400, 0x000f);
+        assert_eq!(generate(&mut sound_slot), 0_f32);
+        // key on (KYONEX), 0x100 samples loop at 44100Hz (OCT 0, FNS 0)
+        write(&mut sound_slot, 0x000, 0x1830);
+        assert!(generate(&mut sound_slot) > 0_f32);
+        let (l, _) = sampling(&sound_slot);
+        assert_frequency(l, 44100_f32 / 256_f32);
+        // OCT 1 is one octave up
+        write(&mut sound_slot, 0x010, 0x0800);
+        generate(&mut sound_slot);
+        let (l, _) = sampling(&sound_slot);
+        assert_frequency(l, 44100_f32 / 128_f32);
+        // key off (KYONEX without KYONB)
+        write(&mut sound_slot, 0x000, 0x1030);
+        generate(&mut sound_slot);
+        assert_eq!(generate(&mut sound_slot), 0_f32);
+    }
+}
@@ -163,6 +163,15 @@ use super::{
 
 const MAX_OUTPUT: i32 = 0x7fff;
 
+///
+/// Sound chip write port
+///
+const PORT_WRITE: u32 = 0x00;
+const PORT_STEREO: u32 = 0x01;
+const PORT_CONFIG_FEEDBACK: u32 = 0x10;
+const PORT_CONFIG_SHIFT_WIDTH: u32 = 0x11;
+const PORT_CONFIG_FLAGS: u32 = 0x12;
+
 #[allow(non_snake_case)]
 pub struct SN76496 {
     clock: u32,
@@ -485,6 +494,63 @@ impl SN76496 {
         }
     }
 
+    ///
+    /// Game Gear stereo mask (bit 0-3: right, bit 4-7: left)
+    ///
+    pub fn stereo_w(&mut self, data: u8) {
+        // Game Gear stereo write implies stereo PSG (also older VGM without flags)
+        self.stereo = true;
+        self.stereo_mask = data as i32;
+    }
+
+    ///
+    /// Configure white noise taps from VGM feedback pattern (e.g. 0x0009, 0x0003, 0x0006)
+    ///
+    fn set_feedback_pattern(&mut self, feedback: u32) {
+        if feedback == 0 {
+            return;
+        }
+        // lower set bit is tap 1, next set bit is tap 2
+        self.whitenoise_tap1 = feedback & feedback.wrapping_neg();
+        let feedback = feedback & !self.whitenoise_tap1;
+        self.whitenoise_tap2 = if feedback != 0 {
+            feedback & feedback.wrapping_neg()
+        } else {
+            self.whitenoise_tap1
+        };
+    }
+
+    ///
+    /// Configure LFSR width from VGM shift register width
+    ///
+    fn set_shift_width(&mut self, width: u32) {
+        if width == 0 || width > 32 {
+            return;
+        }
+        self.feedback_mask = 1 << (width - 1);
+        self.RNG = self.feedback_mask;
+        self.output[3] = self.RNG & 1;
+    }
+
+    ///
+    /// Configure VGM SN76489 flags
+    ///
+    ///  bit 0: frequency 0 is 0x400
+    ///  bit 1: output negate flag
+    ///  bit 2: stereo on/off (on when bit clear)
+    ///  bit 3: /8 clock divider on/off (on when bit clear)
+    ///  bit 4: XNOR noise mode (for NCR8496/PSSJ-3)
+    ///
+    fn set_flags(&mut self, flags: u32) {
+        self.sega_style_psg = flags & 0x01 != 0;
+        self.negate = flags & 0x02 != 0;
+        self.stereo = flags & 0x04 == 0;
+        self.clock_divider = if flags & 0x08 == 0 { 8 } else { 1 };
+        self.ncr_style_psg = flags & 0x10 != 0;
+        // restart with the flags (the divider and the Sega VDP PSG register are set on start)
+        self.device_start(self.clock);
+    }
+
     #[inline]
     fn in_noise_mode(&self) -> bool {
         self.register[6] & 4 != 0
@@ -512,8 +578,15 @@ impl SoundChip for SN76496 {
         todo!("WIP");
     }
 
-    fn write(&mut self, _: usize, _: u32, data: u32, _: &mut dyn SoundStream) {
-        self.write(data as u8);
+    fn write(&mut self, _: usize, port: u32, data: u32, _: &mut dyn SoundStream) {
+        match port {
+            PORT_WRITE => self.write(data as u8),
+            PORT_STEREO => self.stereo_w(data as u8),
+            PORT_CONFIG_FEEDBACK => self.set_feedback_pattern(data),
+            PORT_CONFIG_SHIFT_WIDTH => self.set_shift_width(data),
+            PORT_CONFIG_FLAGS => self.set_flags(data),
+            _ => { /* nothing to do */ }
+        }
     }
 
     fn tick(&mut self, _: usize, sound_stream: &mut dyn SoundStream) {
@@ -535,3 +608,50 @@ impl SoundChip for SN76496 {
         /* nothing to do */
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::SN76496;
+    use crate::sound::slot::tests::{assert_frequency, generate, sampling, sound_slot};
+    use crate::sound::{sound_chip::SoundChip, SoundChipType};
+
+    #[test]
+    fn sn76496_1() {
+        let mut sound_slot = sound_slot(SoundChipType::SEGAPSG, 3579545);
+        // tone 1 period 0x100 and volume 0 attenuation (clock / (32 * period))
+        sound_slot.write(SoundChipType::SEGAPSG, 0, 0, 0x80);
+        sound_slot.write(SoundChipType::SEGAPSG, 0, 0, 0x10);
+        sound_slot.write(SoundChipType::SEGAPSG, 0, 0, 0x90);
+        assert!(generate(&mut sound_slot) > 0_f32);
+        let (l, r) = sampling(&sound_slot);
+        assert_eq!(l, r);
+        assert_frequency(l, 3579545_f32 / (32_f32 * 256_f32));
+        // Game Gear stereo (VGM 0x4f), all channels to the left and to the right
+        for (stereo, left, right) in [(0xf0, true, false), (0x0f, false, true)] {
+            sound_slot.write(SoundChipType::SEGAPSG, 0, 1, stereo);
+            generate(&mut sound_slot);
+            generate(&mut sound_slot);
+            let (l, r) = sampling(&sound_slot);
+            assert_eq!(l.iter().any(|sample| *sample != 0_f32), left);
+            assert_eq!(r.iter().any(|sample| *sample != 0_f32), right);
+        }
+        // volume off
+        sound_slot.write(SoundChipType::SEGAPSG, 0, 0, 0x9f);
+        generate(&mut sound_slot);
+        assert_eq!(generate(&mut sound_slot), 0_f32);
+    }
+
+    #[test]
+    fn set_flags() {
+        let mut sn76496 = SN76496::create(SoundChipType::SEGAPSG);
+        assert_eq!(sn76496.init(3579545), 3579545 / 2);
+        assert_eq!(sn76496.current_clock, 7);
+        assert_eq!(sn76496.last_register, 0);
+        // frequency 0 is 0x400, /8 clock divider off
+        sn76496.set_flags(0x01 | 0x08);
+        assert_eq!(sn76496.clock_divider, 1);
+        assert_eq!(sn76496.current_clock, 0);
+        assert_eq!(sn76496.last_register, 3);
+        assert!(sn76496.stereo);
+    }
+}
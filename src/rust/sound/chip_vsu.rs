@@ -0,0 +1,521 @@
+// license:BSD-3-Clause
+/*
+ * Rust VSU implementation by
+ *  Hiromasa Tanaka <h1romas4@gmail.com>
+ *  https://github.com/h1romas4/libymfm.wasm
+ *
+ * Based on:
+ *  Virtual Boy Sound Unit (VSU) hardware documentation
+ *  Virtual Boy Sacred Tech Scroll by Guy Perfect
+ *  https://www.planetvb.com/content/downloads/documents/stsvb.html
+ */
+
+/*
+ * Virtual Boy VSU
+ *
+ *  - 5MHz master clock
+ *  - channel 1-4: wavetable (32 samples x 6 bit, 5 shared tables)
+ *  - channel 5: wavetable with frequency sweep / modulation
+ *  - channel 6: noise (15 bit LFSR with selectable tap)
+ *  - 4 bit stereo level and 4 bit envelope per channel
+ *
+ * Memory map (VGM 0xc7 offset is the VSU address divided by 4):
+ *  0x000-0x27f: wave table RAM
+ *  0x280-0x2ff: modulation table RAM
+ *  0x400-0x57f: channel registers (0x40 bytes per channel)
+ *  0x580      : SSTOP (stop all channels)
+ */
+use super::{
+    rom::RomBank,
+    sound_chip::SoundChip,
+    stream::{convert_int, SoundStream},
+    RomBusType, RomIndex, SoundChipType,
+};
+
+const MAX_CHANNEL: usize = 6;
+const SWEEP_CHANNEL: usize = 4;
+const NOISE_CHANNEL: usize = 5;
+
+///
+/// VSU clocks per output sample (5MHz / 120 = 41.666kHz)
+///
+const CLOCK_DIVIDER: u32 = 120;
+
+///
+/// Frequency modification clock (0.96ms)
+///
+const EFFECT_CLOCK: i32 = 4800;
+
+///
+/// Noise tap position selected by EV1 bit 4-6
+///
+const NOISE_TAP: [u32; 8] = [14, 10, 13, 4, 8, 6, 9, 11];
+
+#[derive(Default, Copy, Clone)]
+struct VsuChannel {
+    enable: bool,
+    interval_enable: bool,
+    interval: u8,
+    interval_counter: u8,
+    level_left: i32,
+    level_right: i32,
+    frequency: i32,
+    effective_frequency: i32,
+    frequency_counter: i32,
+    envelope: i32,
+    envelope_init: i32,
+    envelope_grow: bool,
+    envelope_interval: u8,
+    envelope_counter: u8,
+    envelope_enable: bool,
+    envelope_repeat: bool,
+    ram_address: usize,
+    wave_pos: usize,
+    effect_clock: i32,
+    interval_clock: u8,
+    envelope_clock: u8,
+}
+
+impl VsuChannel {
+    fn frequency_period(&self, noise: bool) -> i32 {
+        let period = 2048 - self.effective_frequency;
+        if noise {
+            period * 10
+        } else {
+            period
+        }
+    }
+
+    fn volume(&self, level: i32) -> i32 {
+        let volume = self.envelope * level;
+        if volume != 0 {
+            (volume >> 3) + 1
+        } else {
+            0
+        }
+    }
+
+    fn tick_envelope(&mut self) {
+        if !self.envelope_enable {
+            return;
+        }
+        self.envelope_counter -= 1;
+        if self.envelope_counter != 0 {
+            return;
+        }
+        self.envelope_counter = self.envelope_interval + 1;
+        if self.envelope_grow {
+            if self.envelope < 0xf {
+                self.envelope += 1;
+            } else if self.envelope_repeat {
+                self.envelope = self.envelope_init;
+            }
+        } else if self.envelope > 0 {
+            self.envelope -= 1;
+        } else if self.envelope_repeat {
+            self.envelope = self.envelope_init;
+        }
+    }
+
+    fn tick_interval(&mut self) {
+        if !self.interval_enable {
+            return;
+        }
+        self.interval_counter -= 1;
+        if self.interval_counter == 0 {
+            self.enable = false;
+        }
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+pub struct VSU {
+    channel: [VsuChannel; MAX_CHANNEL],
+    wave_ram: [[u8; 32]; 5],
+    modulation_ram: [i8; 32],
+    // channel 5 sweep / modulation
+    sweep_modulation_enable: bool,
+    modulation_repeat: bool,
+    modulation_function: bool,
+    sweep_clock: bool,
+    sweep_interval: u8,
+    sweep_direction: bool,
+    sweep_shift: u8,
+    sweep_counter: u8,
+    sweep_clock_divider: u8,
+    modulation_pos: usize,
+    // channel 6 noise
+    noise_tap: u32,
+    lfsr: u32,
+}
+
+impl VSU {
+    fn new() -> Self {
+        VSU {
+            channel: [VsuChannel::default(); MAX_CHANNEL],
+            wave_ram: [[0; 32]; 5],
+            modulation_ram: [0; 32],
+            sweep_modulation_enable: false,
+            modulation_repeat: false,
+            modulation_function: false,
+            sweep_clock: false,
+            sweep_interval: 0,
+            sweep_direction: false,
+            sweep_shift: 0,
+            sweep_counter: 0,
+            sweep_clock_divider: 1,
+            modulation_pos: 0,
+            noise_tap: NOISE_TAP[0],
+            lfsr: 1,
+        }
+    }
+
+    fn device_start(&mut self, clock: u32) -> u32 {
+        self.device_reset();
+        clock / CLOCK_DIVIDER
+    }
+
+    fn device_reset(&mut self) {
+        for channel in self.channel.iter_mut() {
+            *channel = VsuChannel::default();
+            channel.frequency_counter = 2048;
+            channel.effect_clock = EFFECT_CLOCK;
+            channel.interval_clock = 4;
+            channel.envelope_clock = 4;
+        }
+        self.wave_ram = [[0; 32]; 5];
+        self.modulation_ram = [0; 32];
+        self.sweep_modulation_enable = false;
+        self.modulation_repeat = false;
+        self.modulation_function = false;
+        self.sweep_clock = false;
+        self.sweep_interval = 0;
+        self.sweep_direction = false;
+        self.sweep_shift = 0;
+        self.sweep_counter = 0;
+        self.sweep_clock_divider = 1;
+        self.modulation_pos = 0;
+        self.noise_tap = NOISE_TAP[0];
+        self.lfsr = 1;
+    }
+
+    fn sample(&self, ch: usize) -> i32 {
+        let channel = &self.channel[ch];
+        if ch == NOISE_CHANNEL {
+            if self.lfsr & 1 != 0 {
+                0x3f
+            } else {
+                0
+            }
+        } else if channel.ram_address < 5 {
+            self.wave_ram[channel.ram_address][channel.wave_pos] as i32
+        } else {
+            0
+        }
+    }
+
+    fn step_frequency(&mut self, ch: usize) {
+        if ch == NOISE_CHANNEL {
+            let feedback = ((self.lfsr >> 7) ^ (self.lfsr >> self.noise_tap) ^ 1) & 1;
+            self.lfsr = ((self.lfsr << 1) & 0x7fff) | feedback;
+        } else {
+            let channel = &mut self.channel[ch];
+            channel.wave_pos = (channel.wave_pos + 1) & 0x1f;
+        }
+    }
+
+    fn tick_sweep_modulation(&mut self) {
+        self.sweep_clock_divider -= 1;
+        if self.sweep_clock_divider != 0 {
+            return;
+        }
+        self.sweep_clock_divider = if self.sweep_clock { 8 } else { 1 };
+
+        if self.sweep_interval == 0 || !self.sweep_modulation_enable {
+            return;
+        }
+        if self.sweep_counter > 0 {
+            self.sweep_counter -= 1;
+        }
+        if self.sweep_counter != 0 {
+            return;
+        }
+        self.sweep_counter = self.sweep_interval;
+
+        let channel = &mut self.channel[SWEEP_CHANNEL];
+        if self.modulation_function {
+            // modulation
+            if self.modulation_pos < 32 || self.modulation_repeat {
+                self.modulation_pos &= 0x1f;
+                channel.effective_frequency = (channel.effective_frequency
+                    + self.modulation_ram[self.modulation_pos] as i32)
+                    .clamp(0, 0x7ff);
+                self.modulation_pos += 1;
+            }
+        } else {
+            // sweep
+            let delta = channel.effective_frequency >> self.sweep_shift;
+            let frequency = if self.sweep_direction {
+                channel.effective_frequency + delta
+            } else {
+                channel.effective_frequency - delta
+            };
+            if frequency < 0 {
+                channel.effective_frequency = 0;
+            } else if frequency > 0x7ff {
+                channel.enable = false;
+            } else {
+                channel.effective_frequency = frequency;
+            }
+        }
+    }
+
+    fn tick_effect(&mut self, ch: usize) {
+        let channel = &mut self.channel[ch];
+        channel.interval_clock -= 1;
+        if channel.interval_clock == 0 {
+            // 3.84ms
+            channel.interval_clock = 4;
+            channel.tick_interval();
+            channel.envelope_clock -= 1;
+            if channel.envelope_clock == 0 {
+                // 15.36ms
+                channel.envelope_clock = 4;
+                channel.tick_envelope();
+            }
+        }
+        if ch == SWEEP_CHANNEL {
+            self.tick_sweep_modulation();
+        }
+    }
+
+    ///
+    /// Run channel for VSU clocks and return the average output level
+    ///
+    fn update_channel(&mut self, ch: usize, clocks: u32) -> (i32, i32) {
+        let mut clocks = clocks as i32;
+        let mut sum_l = 0;
+        let mut sum_r = 0;
+        while clocks > 0 && self.channel[ch].enable {
+            let channel = &self.channel[ch];
+            let chunk = clocks
+                .min(channel.frequency_counter)
+                .min(channel.effect_clock);
+            let sample = self.sample(ch);
+            sum_l += sample * channel.volume(channel.level_left) * chunk;
+            sum_r += sample * channel.volume(channel.level_right) * chunk;
+
+            self.channel[ch].frequency_counter -= chunk;
+            if self.channel[ch].frequency_counter <= 0 {
+                self.step_frequency(ch);
+                let channel = &mut self.channel[ch];
+                channel.frequency_counter += channel.frequency_period(ch == NOISE_CHANNEL);
+            }
+            self.channel[ch].effect_clock -= chunk;
+            if self.channel[ch].effect_clock <= 0 {
+                self.channel[ch].effect_clock += EFFECT_CLOCK;
+                self.tick_effect(ch);
+            }
+            clocks -= chunk;
+        }
+        (
+            sum_l / CLOCK_DIVIDER as i32,
+            sum_r / CLOCK_DIVIDER as i32,
+        )
+    }
+
+    fn sound_stream_update(&mut self, buffer_l: &mut [f32], buffer_r: &mut [f32]) {
+        let mut out_l = 0;
+        let mut out_r = 0;
+        for ch in 0..MAX_CHANNEL {
+            let (l, r) = self.update_channel(ch, CLOCK_DIVIDER);
+            out_l += l;
+            out_r += r;
+        }
+        // 6 bit sample * volume is 1827 at maximum per channel
+        buffer_l[0] += convert_int(out_l, 8192);
+        buffer_r[0] += convert_int(out_r, 8192);
+    }
+
+    fn key_on(&mut self, ch: usize) {
+        let channel = &mut self.channel[ch];
+        channel.effective_frequency = channel.frequency;
+        channel.frequency_counter = channel.frequency_period(ch == NOISE_CHANNEL);
+        channel.interval_counter = channel.interval + 1;
+        channel.envelope_counter = channel.envelope_interval + 1;
+        channel.wave_pos = 0;
+        channel.effect_clock = EFFECT_CLOCK;
+        channel.interval_clock = 4;
+        channel.envelope_clock = 4;
+        if ch == SWEEP_CHANNEL {
+            self.sweep_counter = self.sweep_interval;
+            self.sweep_clock_divider = if self.sweep_clock { 8 } else { 1 };
+            self.modulation_pos = 0;
+        }
+        if ch == NOISE_CHANNEL {
+            self.lfsr = 1;
+        }
+    }
+
+    fn write_channel(&mut self, ch: usize, reg: usize, data: u8) {
+        let channel = &mut self.channel[ch];
+        match reg {
+            0x0 => {
+                // S*INT
+                channel.enable = data & 0x80 != 0;
+                channel.interval_enable = data & 0x20 != 0;
+                channel.interval = data & 0x1f;
+                if channel.enable {
+                    self.key_on(ch);
+                }
+            }
+            0x1 => {
+                // S*LRV
+                channel.level_left = (data >> 4) as i32;
+                channel.level_right = (data & 0xf) as i32;
+            }
+            0x2 => {
+                // S*FQL
+                channel.frequency = (channel.frequency & 0x700) | data as i32;
+                channel.effective_frequency = (channel.effective_frequency & 0x700) | data as i32;
+            }
+            0x3 => {
+                // S*FQH
+                channel.frequency = (channel.frequency & 0xff) | ((data as i32 & 0x7) << 8);
+                channel.effective_frequency =
+                    (channel.effective_frequency & 0xff) | ((data as i32 & 0x7) << 8);
+            }
+            0x4 => {
+                // S*EV0
+                channel.envelope_init = (data >> 4) as i32;
+                channel.envelope = channel.envelope_init;
+                channel.envelope_grow = data & 0x08 != 0;
+                channel.envelope_interval = data & 0x7;
+            }
+            0x5 => {
+                // S*EV1
+                channel.envelope_enable = data & 0x01 != 0;
+                channel.envelope_repeat = data & 0x02 != 0;
+                if ch == SWEEP_CHANNEL {
+                    self.modulation_function = data & 0x10 != 0;
+                    self.modulation_repeat = data & 0x20 != 0;
+                    self.sweep_modulation_enable = data & 0x40 != 0;
+                } else if ch == NOISE_CHANNEL {
+                    self.noise_tap = NOISE_TAP[((data >> 4) & 0x7) as usize];
+                    self.lfsr = 1;
+                }
+            }
+            0x6 => {
+                // S*RAM
+                channel.ram_address = (data & 0xf) as usize;
+            }
+            0x7 if ch == SWEEP_CHANNEL => {
+                // S5SWP
+                self.sweep_clock = data & 0x80 != 0;
+                self.sweep_interval = (data >> 4) & 0x7;
+                self.sweep_direction = data & 0x08 != 0;
+                self.sweep_shift = data & 0x7;
+            }
+            _ => { /* nothing to do */ }
+        }
+    }
+
+    ///
+    /// Write VGM 0xc7 command (VSU address >> 2)
+    ///
+    fn write(&mut self, offset: u32, data: u8) {
+        let address = ((offset << 2) & 0x7ff) as usize;
+        if address < 0x280 {
+            self.wave_ram[address >> 7][(address >> 2) & 0x1f] = data & 0x3f;
+        } else if address < 0x400 {
+            self.modulation_ram[(address >> 2) & 0x1f] = data as i8;
+        } else if address < 0x580 {
+            self.write_channel((address >> 6) & 0xf, (address >> 2) & 0xf, data);
+        } else if address == 0x580 && data & 0x1 != 0 {
+            // SSTOP
+            for channel in self.channel.iter_mut() {
+                channel.enable = false;
+            }
+        }
+    }
+}
+
+impl SoundChip for VSU {
+    fn create(_sound_device_name: SoundChipType) -> Self {
+        VSU::new()
+    }
+
+    fn init(&mut self, clock: u32) -> u32 {
+        self.device_start(clock)
+    }
+
+    fn reset(&mut self) {
+        self.device_reset();
+    }
+
+    fn write(&mut self, _: usize, offset: u32, data: u32, _: &mut dyn SoundStream) {
+        self.write(offset, data as u8);
+    }
+
+    fn tick(&mut self, _: usize, sound_stream: &mut dyn SoundStream) {
+        let mut l: [f32; 1] = [0_f32];
+        let mut r: [f32; 1] = [0_f32];
+        self.sound_stream_update(&mut l, &mut r);
+        sound_stream.push(l[0], r[0]);
+    }
+
+    fn set_rom_bank(&mut self, _: RomIndex, _: RomBank) {
+        /* nothing to do */
+    }
+
+    fn notify_add_rom(&mut self, _: RomIndex, _: usize) {
+        /* nothing to do */
+    }
+
+    fn set_rom_bus(&mut self, _: Option<RomBusType>) {
+        /* nothing to do */
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sound::slot::tests::{assert_frequency, generate, sampling, sound_slot};
+    use crate::sound::SoundChipType;
+
+    #[test]
+    fn vsu_1() {
+        let mut sound_slot = sound_slot(SoundChipType::VSU, 5000000);
+        // square wave of wave table 0 (VGM 0xc7 offset is VSU address >> 2)
+        for offset in 0..32 {
+            let data = if offset < 16 { 0x3f } else { 0x00 };
+            sound_slot.write_offset16(SoundChipType::VSU, offset, data);
+        }
+        // channel 1 level, frequency, envelope and wave table 0
+        sound_slot.write_offset16(SoundChipType::VSU, 0x101, 0xff);
+        sound_slot.write_offset16(SoundChipType::VSU, 0x102, 0x00);
+        sound_slot.write_offset16(SoundChipType::VSU, 0x103, 0x04);
+        sound_slot.write_offset16(SoundChipType::VSU, 0x104, 0xf0);
+        sound_slot.write_offset16(SoundChipType::VSU, 0x106, 0x00);
+        assert_eq!(generate(&mut sound_slot), 0_f32);
+        // channel 1 enable (5MHz / (32 * (2048 - frequency)))
+        sound_slot.write_offset16(SoundChipType::VSU, 0x100, 0x80);
+        assert!(generate(&mut sound_slot) > 0_f32);
+        let (l, r) = sampling(&sound_slot);
+        assert_eq!(l, r);
+        assert_frequency(l, 5000000_f32 / (32_f32 * 1024_f32));
+        // left level only
+        sound_slot.write_offset16(SoundChipType::VSU, 0x101, 0xf0);
+        generate(&mut sound_slot);
+        generate(&mut sound_slot);
+        let (l, r) = sampling(&sound_slot);
+        assert!(l.iter().any(|sample| *sample != 0_f32));
+        assert!(r.iter().all(|sample| *sample == 0_f32));
+        // bit 15 is the second chip (not added)
+        sound_slot.write_offset16(SoundChipType::VSU, 0x8000 | 0x160, 0x01);
+        assert!(generate(&mut sound_slot) > 0_f32);
+        // SSTOP
+        sound_slot.write_offset16(SoundChipType::VSU, 0x160, 0x01);
+        generate(&mut sound_slot);
+        assert_eq!(generate(&mut sound_slot), 0_f32);
+    }
+}
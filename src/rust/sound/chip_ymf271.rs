@@ -0,0 +1,1377 @@
+// license:BSD-3-Clause
+/*
+ * Rust YMF271 ports by
+ *  Hiromasa Tanaka <h1romas4@gmail.com>
+ *  https://github.com/h1romas4/libymfm.wasm
+ *
+ * Porting from:
+ *  YMF271 emulation by R. Belmont, O. Galibert, hap
+ *  https://github.com/mamedev/mame/blob/master/src/devices/sound/ymf271.cpp
+ */
+
+/*
+ * Original YMF271 emulation Copyright
+ */
+// license:BSD-3-Clause
+// copyright-holders:R. Belmont, Olivier Galibert, hap
+/*
+    Yamaha YMF271-F "OPX" emulator v0.1
+    By R. Belmont.
+    Based in part on YMF278B emulator by R. Belmont and O. Galibert.
+    12June04 update by Toshiaki Nijiura
+    Copyright R. Belmont.
+
+    This software is dual-licensed: it may be used in MAME and properly licensed
+    MAME derivatives under the terms of the MAME license.  For use outside of
+    MAME and properly licensed derivatives, it is available under the
+    terms of the GNU Lesser General Public License (LGPL), version 2.1.
+    You may read the LGPL at http://www.gnu.org/licenses/lgpl.html
+
+    TODO:
+    - A/L bit (alternate loop)
+    - statusreg Busy flag
+    - PFM (FM using external PCM waveform)
+    - detune (should be same as on other Yamaha chips)
+    - Acc On bit (some sort of accumulation mode, where output is held?)
+    - Is memory handling 100% correct? At the moment, seibuspi.c is the only
+      hardware currently emulated that uses external handlers.
+*/
+use super::{
+    rom::{read_byte, RomBank},
+    sound_chip::SoundChip,
+    stream::{convert_int, SoundStream},
+    RomBusType, RomIndex, SoundChipType,
+};
+
+const MAXOUT: f64 = 32767.0;
+const MINOUT: f64 = -32768.0;
+
+const SIN_BITS: i64 = 10;
+const SIN_LEN: usize = 1 << SIN_BITS;
+const SIN_MASK: i64 = SIN_LEN as i64 - 1;
+
+const LFO_LENGTH: usize = 256;
+const LFO_SHIFT: i32 = 8;
+const PLFO_MAX: f64 = 1.0;
+const PLFO_MIN: f64 = -1.0;
+const ALFO_MAX: i32 = 65536;
+const ALFO_MIN: i32 = 0;
+
+const ENV_ATTACK: u8 = 0;
+const ENV_DECAY1: u8 = 1;
+const ENV_DECAY2: u8 = 2;
+const ENV_RELEASE: u8 = 3;
+
+const ENV_VOLUME_SHIFT: i32 = 16;
+
+const STD_CLOCK: u32 = 16934400;
+
+const INF: f64 = -1.0;
+
+#[rustfmt::skip]
+const AR_TIME: [f64; 64] = [
+    INF,        INF,        INF,        INF,        6188.12,    4980.68,    4144.76,    3541.04,
+    3094.06,    2490.34,    2072.38,    1770.52,    1547.03,    1245.17,    1036.19,    885.26,
+    773.51,     622.59,     518.10,     441.63,     386.76,     311.29,     259.05,     221.31,
+    193.38,     155.65,     129.52,     110.66,     96.69,      77.82,      64.76,      55.33,
+    48.34,      38.91,      32.38,      27.66,      24.17,      19.46,      16.19,      13.83,
+    12.09,      9.73,       8.10,       6.92,       6.04,       4.86,       4.05,       3.46,
+    3.02,       2.47,       2.14,       1.88,       1.70,       1.38,       1.16,       1.02,
+    0.88,       0.70,       0.57,       0.48,       0.43,       0.43,       0.43,       0.07,
+];
+
+#[rustfmt::skip]
+const DC_TIME: [f64; 64] = [
+    INF,        INF,        INF,        INF,        93599.64,   74837.91,   62392.02,   53475.56,
+    46799.82,   37418.96,   31196.01,   26737.78,   23399.91,   18709.48,   15598.00,   13368.89,
+    11699.95,   9354.74,    7799.00,    6684.44,    5849.98,    4677.37,    3899.50,    3342.22,
+    2924.99,    2338.68,    1949.75,    1671.11,    1462.49,    1169.34,    974.88,     835.56,
+    731.25,     584.67,     487.44,     417.78,     365.62,     292.34,     243.72,     208.89,
+    182.81,     146.17,     121.86,     104.44,     91.41,      73.08,      60.93,      52.22,
+    45.69,      36.55,      33.85,      26.09,      22.83,      18.28,      15.22,      13.03,
+    11.41,      9.12,       7.60,       6.51,       5.69,       5.69,       5.69,       5.69,
+];
+
+#[rustfmt::skip]
+const RKS_TABLE: [[i32; 8]; 32] = [
+    [  0,  0,  0,  0,  0,  2,  4,  8 ],
+    [  0,  0,  0,  0,  1,  3,  5,  9 ],
+    [  0,  0,  0,  1,  2,  4,  6, 10 ],
+    [  0,  0,  0,  1,  3,  5,  7, 11 ],
+    [  0,  0,  1,  2,  4,  6,  8, 12 ],
+    [  0,  0,  1,  2,  5,  7,  9, 13 ],
+    [  0,  0,  1,  3,  6,  8, 10, 14 ],
+    [  0,  0,  1,  3,  7,  9, 11, 15 ],
+    [  0,  1,  2,  4,  8, 10, 12, 16 ],
+    [  0,  1,  2,  4,  9, 11, 13, 17 ],
+    [  0,  1,  2,  5, 10, 12, 14, 18 ],
+    [  0,  1,  2,  5, 11, 13, 15, 19 ],
+    [  0,  1,  3,  6, 12, 14, 16, 20 ],
+    [  0,  1,  3,  6, 13, 15, 17, 21 ],
+    [  0,  1,  3,  7, 14, 16, 18, 22 ],
+    [  0,  1,  3,  7, 15, 17, 19, 23 ],
+    [  0,  2,  4,  8, 16, 18, 20, 24 ],
+    [  0,  2,  4,  8, 17, 19, 21, 25 ],
+    [  0,  2,  4,  9, 18, 20, 22, 26 ],
+    [  0,  2,  4,  9, 19, 21, 23, 27 ],
+    [  0,  2,  5, 10, 20, 22, 24, 28 ],
+    [  0,  2,  5, 10, 21, 23, 25, 29 ],
+    [  0,  2,  5, 11, 22, 24, 26, 30 ],
+    [  0,  2,  5, 11, 23, 25, 27, 31 ],
+    [  0,  3,  6, 12, 24, 26, 28, 31 ],
+    [  0,  3,  6, 12, 25, 27, 29, 31 ],
+    [  0,  3,  6, 13, 26, 28, 30, 31 ],
+    [  0,  3,  6, 13, 27, 29, 31, 31 ],
+    [  0,  3,  7, 14, 28, 30, 31, 31 ],
+    [  0,  3,  7, 14, 29, 31, 31, 31 ],
+    [  0,  3,  7, 15, 30, 31, 31, 31 ],
+    [  0,  3,  7, 15, 31, 31, 31, 31 ],
+];
+
+const MULTIPLE_TABLE: [f64; 16] = [
+    0.5, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 13.0, 14.0, 15.0,
+];
+
+const POW_TABLE: [f64; 16] = [
+    128.0, 256.0, 512.0, 1024.0, 2048.0, 4096.0, 8192.0, 16384.0, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0,
+    32.0, 64.0,
+];
+
+const FS_FREQUENCY: [f64; 4] = [1.0, 1.0 / 2.0, 1.0 / 4.0, 1.0 / 8.0];
+
+const CHANNEL_ATTENUATION_TABLE: [f64; 16] = [
+    0.0, 2.5, 6.0, 8.5, 12.0, 14.5, 18.1, 20.6, 24.1, 26.6, 30.1, 32.6, 36.1, 96.1, 96.1, 96.1,
+];
+
+const MODULATION_LEVEL: [i64; 8] = [16, 8, 4, 2, 1, 32, 64, 128];
+
+// feedback_level * 16
+const FEEDBACK_LEVEL: [i64; 8] = [0, 1, 2, 4, 8, 16, 32, 64];
+
+// slot mapping assists
+const FM_TAB: [i32; 16] = [0, 1, 2, -1, 3, 4, 5, -1, 6, 7, 8, -1, 9, 10, 11, -1];
+const PCM_TAB: [i32; 16] = [0, 4, 8, -1, 12, 16, 20, -1, 24, 28, 32, -1, 36, 40, 44, -1];
+
+///
+/// VGM 0xd1 port
+///
+const PORT_FM_BANK0: u32 = 0x00;
+const PORT_FM_BANK3: u32 = 0x03;
+const PORT_PCM: u32 = 0x04;
+const PORT_TIMER: u32 = 0x06;
+
+///
+/// Operator input
+///
+#[derive(Clone, Copy)]
+enum OpInput {
+    Feedback,
+    None,
+    Slot(i64),
+}
+
+#[derive(Default, Copy, Clone)]
+struct YMF271Slot {
+    lfo_freq: u8,
+    lfowave: u8,
+    pms: u8,
+    ams: u8,
+    multiple: u8,
+    tl: u8,
+    keyscale: u8,
+    ar: u8,
+    decay1rate: u8,
+    decay2rate: u8,
+    decay1lvl: u8,
+    relrate: u8,
+    block: u8,
+    fns_hi: u8,
+    fns: u32,
+    feedback: u8,
+    waveform: u8,
+    algorithm: u8,
+    ch0_level: u8,
+    ch1_level: u8,
+    ch2_level: u8,
+    ch3_level: u8,
+
+    startaddr: u32,
+    loopaddr: u32,
+    endaddr: u32,
+    fs: u8,
+    bits: u8,
+
+    step: u32,
+    stepptr: u64,
+
+    active: bool,
+
+    volume: i32,
+    env_state: u8,
+    env_attack_step: i32,  // volume increase step in attack state
+    env_decay1_step: i32,  // volume decrease step in decay1 state
+    env_decay2_step: i32,  // volume decrease step in decay2 state
+    env_release_step: i32, // volume decrease step in release state
+
+    feedback_modulation0: i64,
+    feedback_modulation1: i64,
+
+    lfo_phase: i32,
+    lfo_step: i32,
+    lfo_amplitude: i32,
+    lfo_phasemod: f64,
+}
+
+#[derive(Default, Copy, Clone)]
+struct YMF271Group {
+    sync: u8,
+}
+
+///
+/// Lookup tables
+///
+struct YMF271Lut {
+    waves: Vec<[i16; SIN_LEN]>,
+    plfo: Vec<[[f64; LFO_LENGTH]; 8]>,
+    alfo: Vec<[i32; LFO_LENGTH]>,
+    ar: [f64; 64],
+    dc: [f64; 64],
+    lfo: [f64; 256],
+    attenuation: [i64; 16],
+    total_level: [i64; 128],
+    env_volume: [i64; 256],
+}
+
+pub struct YMF271 {
+    slots: [YMF271Slot; 48],
+    groups: [YMF271Group; 12],
+    lut: YMF271Lut,
+    rom_bank: RomBank,
+}
+
+#[inline]
+fn get_keyscaled_rate(rate: i32, keycode: usize, keyscale: u8) -> usize {
+    let newrate = rate + RKS_TABLE[keycode][keyscale as usize];
+    newrate.clamp(0, 63) as usize
+}
+
+#[inline]
+fn get_internal_keycode(block: u8, fns: u32) -> usize {
+    let n43 = if fns < 0x780 {
+        0
+    } else if fns < 0x900 {
+        1
+    } else if fns < 0xa80 {
+        2
+    } else {
+        3
+    };
+    ((block as usize & 7) * 4) + n43
+}
+
+#[inline]
+fn get_external_keycode(block: u8, fns: u32) -> usize {
+    let n43 = if fns < 0x100 {
+        0
+    } else if fns < 0x300 {
+        1
+    } else if fns < 0x500 {
+        2
+    } else {
+        3
+    };
+    ((block as usize & 7) * 4) + n43
+}
+
+impl YMF271Slot {
+    fn calculate_step(&mut self) {
+        let mut st: f64;
+        if self.waveform == 7 {
+            // external waveform (PCM)
+            st = (2 * (self.fns | 2048)) as f64
+                * POW_TABLE[self.block as usize]
+                * FS_FREQUENCY[self.fs as usize];
+            st *= MULTIPLE_TABLE[self.multiple as usize];
+            // LFO phase modulation
+            st *= self.lfo_phasemod;
+            st /= (524288 / 65536) as f64; // pre-multiply with 65536
+        } else {
+            // internal waveform (FM)
+            st = (2 * self.fns) as f64 * POW_TABLE[self.block as usize];
+            st = st * MULTIPLE_TABLE[self.multiple as usize] * SIN_LEN as f64;
+            // LFO phase modulation
+            st *= self.lfo_phasemod;
+            st /= (536870912 / 65536) as f64; // pre-multiply with 65536
+        }
+        self.step = st as u32;
+    }
+
+    fn check_envelope_end(&mut self) -> bool {
+        if self.volume <= 0 {
+            self.active = false;
+            self.volume = 0;
+            return true;
+        }
+        false
+    }
+
+    // calculate status output
+    fn update_envelope(&mut self) {
+        match self.env_state {
+            ENV_ATTACK => {
+                self.volume += self.env_attack_step;
+                if self.volume >= (255 << ENV_VOLUME_SHIFT) {
+                    self.volume = 255 << ENV_VOLUME_SHIFT;
+                    self.env_state = ENV_DECAY1;
+                }
+            }
+            ENV_DECAY1 => {
+                let decay_level = 255 - ((self.decay1lvl as i32) << 4);
+                self.volume -= self.env_decay1_step;
+                if !self.check_envelope_end() && (self.volume >> ENV_VOLUME_SHIFT) <= decay_level {
+                    self.env_state = ENV_DECAY2;
+                }
+            }
+            ENV_DECAY2 => {
+                self.volume -= self.env_decay2_step;
+                self.check_envelope_end();
+            }
+            ENV_RELEASE => {
+                self.volume -= self.env_release_step;
+                self.check_envelope_end();
+            }
+            _ => { /* nothing to do */ }
+        }
+    }
+
+    fn init_envelope(&mut self, lut: &YMF271Lut) {
+        let decay_level = 255 - ((self.decay1lvl as i32) << 4);
+
+        let keycode = if self.waveform != 7 {
+            get_internal_keycode(self.block, self.fns)
+        } else {
+            get_external_keycode(self.block, self.fns & 0x7ff)
+            /* keycode = (keycode + slot->srcb * 4 + slot->srcnote) / 2; */ // not sure
+        };
+
+        // init attack state
+        let rate = get_keyscaled_rate(self.ar as i32 * 2, keycode, self.keyscale);
+        self.env_attack_step = if rate < 4 {
+            0
+        } else {
+            ((255.0 / lut.ar[rate]) * 65536.0) as i32
+        };
+
+        // init decay1 state
+        let rate = get_keyscaled_rate(self.decay1rate as i32 * 2, keycode, self.keyscale);
+        self.env_decay1_step = if rate < 4 {
+            0
+        } else {
+            (((255 - decay_level) as f64 / lut.dc[rate]) * 65536.0) as i32
+        };
+
+        // init decay2 state
+        let rate = get_keyscaled_rate(self.decay2rate as i32 * 2, keycode, self.keyscale);
+        self.env_decay2_step = if rate < 4 {
+            0
+        } else {
+            ((255.0 / lut.dc[rate]) * 65536.0) as i32
+        };
+
+        // init release state
+        let rate = get_keyscaled_rate(self.relrate as i32 * 4, keycode, self.keyscale);
+        self.env_release_step = if rate < 4 {
+            0
+        } else {
+            ((255.0 / lut.ar[rate]) * 65536.0) as i32
+        };
+
+        self.volume = (255 - 160) << ENV_VOLUME_SHIFT; // -60db
+        self.env_state = ENV_ATTACK;
+    }
+
+    fn init_lfo(&mut self, lut: &YMF271Lut) {
+        self.lfo_phase = 0;
+        self.lfo_amplitude = 0;
+        self.lfo_phasemod = 0.0;
+
+        self.lfo_step =
+            (((LFO_LENGTH as f64 * lut.lfo[self.lfo_freq as usize]) / 44100.0) * 256.0) as i32;
+    }
+
+    fn update_lfo(&mut self, lut: &YMF271Lut) {
+        self.lfo_phase = self.lfo_phase.wrapping_add(self.lfo_step);
+
+        let phase = ((self.lfo_phase >> LFO_SHIFT) as usize) & (LFO_LENGTH - 1);
+        self.lfo_amplitude = lut.alfo[self.lfowave as usize][phase];
+        self.lfo_phasemod = lut.plfo[self.lfowave as usize][self.pms as usize][phase];
+
+        self.calculate_step();
+    }
+
+    fn calculate_slot_volume(&self, lut: &YMF271Lut) -> i64 {
+        // Note: Actually everyone of these stores only INT32 (16.16 fixed point),
+        //       but the calculations need INT64.
+        let lfo_amplitude = self.lfo_amplitude as i64;
+        let lfo_volume: i64 = match self.ams {
+            1 => 65536 - ((lfo_amplitude * 33124) >> 16), // 5.90625dB
+            2 => 65536 - ((lfo_amplitude * 16742) >> 16), // 11.8125dB
+            3 => 65536 - ((lfo_amplitude * 4277) >> 16),  // 23.625dB
+            _ => 65536,                                   // 0dB
+        };
+
+        let env_volume =
+            (lut.env_volume[(255 - (self.volume >> ENV_VOLUME_SHIFT)) as usize] * lfo_volume) >> 16;
+
+        (env_volume * lut.total_level[self.tl as usize]) >> 16
+    }
+
+    fn attenuation(&self, lut: &YMF271Lut) -> [i64; 4] {
+        [
+            lut.attenuation[self.ch0_level as usize],
+            lut.attenuation[self.ch1_level as usize],
+            lut.attenuation[self.ch2_level as usize],
+            lut.attenuation[self.ch3_level as usize],
+        ]
+    }
+}
+
+impl YMF271Lut {
+    #[allow(clippy::needless_range_loop)]
+    fn new(clock: u32) -> Self {
+        let mut waves = vec![[0_i16; SIN_LEN]; 8];
+        let mut plfo = vec![[[0_f64; LFO_LENGTH]; 8]; 4];
+        let mut alfo = vec![[0_i32; LFO_LENGTH]; 4];
+
+        for i in 0..SIN_LEN {
+            let m = (((i * 2) + 1) as f64 * std::f64::consts::PI / SIN_LEN as f64).sin();
+            let m2 = (((i * 4) + 1) as f64 * std::f64::consts::PI / SIN_LEN as f64).sin();
+            let first_half = i < (SIN_LEN / 2);
+
+            // Waveform 0: sin(wt)    (0 <= wt <= 2PI)
+            waves[0][i] = (m * MAXOUT) as i16;
+            // Waveform 1: sin?(wt)   (0 <= wt <= PI)     -sin?(wt)  (PI <= wt <= 2PI)
+            waves[1][i] = if first_half {
+                ((m * m) * MAXOUT) as i16
+            } else {
+                ((m * m) * MINOUT) as i16
+            };
+            // Waveform 2: sin(wt)    (0 <= wt <= PI)     -sin(wt)   (PI <= wt <= 2PI)
+            waves[2][i] = if first_half {
+                (m * MAXOUT) as i16
+            } else {
+                (-m * MAXOUT) as i16
+            };
+            // Waveform 3: sin(wt)    (0 <= wt <= PI)     0
+            waves[3][i] = if first_half { (m * MAXOUT) as i16 } else { 0 };
+            // Waveform 4: sin(2wt)   (0 <= wt <= PI)     0
+            waves[4][i] = if first_half { (m2 * MAXOUT) as i16 } else { 0 };
+            // Waveform 5: |sin(2wt)| (0 <= wt <= PI)     0
+            waves[5][i] = if first_half {
+                (m2.abs() * MAXOUT) as i16
+            } else {
+                0
+            };
+            // Waveform 6:     1      (0 <= wt <= 2PI)
+            waves[6][i] = MAXOUT as i16;
+            waves[7][i] = 0;
+        }
+
+        for i in 0..LFO_LENGTH {
+            let mut plfo_wave = [0_f64; 4];
+            // LFO phase modulation
+            plfo_wave[0] = 0.0;
+
+            let fsaw_wave =
+                ((i % (LFO_LENGTH / 2)) as f64 * PLFO_MAX) / ((LFO_LENGTH / 2) - 1) as f64;
+            plfo_wave[1] = if i < (LFO_LENGTH / 2) {
+                fsaw_wave
+            } else {
+                fsaw_wave - PLFO_MAX
+            };
+
+            plfo_wave[2] = if i < (LFO_LENGTH / 2) {
+                PLFO_MAX
+            } else {
+                PLFO_MIN
+            };
+
+            let ftri_wave = ((i % (LFO_LENGTH / 4)) as f64 * PLFO_MAX) / (LFO_LENGTH / 4) as f64;
+            plfo_wave[3] = match i / (LFO_LENGTH / 4) {
+                0 => ftri_wave,
+                1 => PLFO_MAX - ftri_wave,
+                2 => -ftri_wave,
+                _ => -(PLFO_MAX - ftri_wave),
+            };
+
+            for j in 0..4 {
+                plfo[j][0][i] = 2.0_f64.powf(0.0);
+                plfo[j][1][i] = 2.0_f64.powf((3.378 * plfo_wave[j]) / 1200.0);
+                plfo[j][2][i] = 2.0_f64.powf((5.0646 * plfo_wave[j]) / 1200.0);
+                plfo[j][3][i] = 2.0_f64.powf((6.7495 * plfo_wave[j]) / 1200.0);
+                plfo[j][4][i] = 2.0_f64.powf((10.1143 * plfo_wave[j]) / 1200.0);
+                plfo[j][5][i] = 2.0_f64.powf((20.1699 * plfo_wave[j]) / 1200.0);
+                plfo[j][6][i] = 2.0_f64.powf((40.1076 * plfo_wave[j]) / 1200.0);
+                plfo[j][7][i] = 2.0_f64.powf((79.307 * plfo_wave[j]) / 1200.0);
+            }
+
+            // LFO amplitude modulation
+            alfo[0][i] = 0;
+            alfo[1][i] = ALFO_MAX - ((i as i32 * ALFO_MAX) / LFO_LENGTH as i32);
+            alfo[2][i] = if i < (LFO_LENGTH / 2) {
+                ALFO_MAX
+            } else {
+                ALFO_MIN
+            };
+            let tri_wave =
+                ((i % (LFO_LENGTH / 2)) as i32 * ALFO_MAX) / (LFO_LENGTH / 2) as i32;
+            alfo[3][i] = if i < (LFO_LENGTH / 2) {
+                ALFO_MAX - tri_wave
+            } else {
+                tri_wave
+            };
+        }
+
+        let mut env_volume = [0_i64; 256];
+        for (i, env_volume) in env_volume.iter_mut().enumerate() {
+            *env_volume = (65536.0 / 10.0_f64.powf((i as f64 / (256.0 / 96.0)) / 20.0)) as i64;
+        }
+
+        let mut attenuation = [0_i64; 16];
+        for (i, attenuation) in attenuation.iter_mut().enumerate() {
+            *attenuation = (65536.0 / 10.0_f64.powf(CHANNEL_ATTENUATION_TABLE[i] / 20.0)) as i64;
+        }
+
+        let mut total_level = [0_i64; 128];
+        for (i, total_level) in total_level.iter_mut().enumerate() {
+            let db = 0.75 * i as f64;
+            *total_level = (65536.0 / 10.0_f64.powf(db / 20.0)) as i64;
+        }
+
+        // timing may use a non-standard XTAL
+        let clock_correction = STD_CLOCK as f64 / clock as f64;
+
+        // The LFO frequency table of the manual has some known errors,
+        // so that computes it from the LFO period.
+        // (formulas by Olivier Galibert & Nicola Salmoria)
+        let mut lfo = [0_f64; 256];
+        for (i, lfo) in lfo.iter_mut().enumerate() {
+            let entry = 256 - i;
+            let period = if entry < 16 {
+                (entry & 0x0f) << 7
+            } else {
+                (0x10 + (entry & 0x0f)) << (6 + (entry >> 4))
+            };
+            *lfo = (44100.0 / period as f64) * clock_correction;
+        }
+
+        let mut ar = [0_f64; 64];
+        let mut dc = [0_f64; 64];
+        for i in 0..64 {
+            // attack/release rate in number of samples
+            ar[i] = (AR_TIME[i] * clock_correction * 44100.0) / 1000.0;
+            // decay rate in number of samples
+            dc[i] = (DC_TIME[i] * clock_correction * 44100.0) / 1000.0;
+        }
+
+        YMF271Lut {
+            waves,
+            plfo,
+            alfo,
+            ar,
+            dc,
+            lfo,
+            attenuation,
+            total_level,
+            env_volume,
+        }
+    }
+}
+
+impl YMF271 {
+    fn new() -> Self {
+        YMF271 {
+            slots: [YMF271Slot::default(); 48],
+            groups: [YMF271Group::default(); 12],
+            lut: YMF271Lut::new(STD_CLOCK),
+            rom_bank: None,
+        }
+    }
+
+    fn device_start(&mut self, clock: u32) -> u32 {
+        self.lut = YMF271Lut::new(clock);
+        self.device_reset();
+        clock / 384
+    }
+
+    fn device_reset(&mut self) {
+        for slot in self.slots.iter_mut() {
+            slot.active = false;
+            slot.volume = 0;
+        }
+    }
+
+    ///
+    /// Update PCM slot
+    ///
+    fn update_pcm(&mut self, slotnum: usize, mix: &mut [i64; 4]) {
+        let slot = &mut self.slots[slotnum];
+
+        if !slot.active {
+            return;
+        }
+
+        // loop
+        let endaddr = slot.endaddr as u64;
+        let loopaddr = slot.loopaddr as u64;
+        if (slot.stepptr >> 16) > endaddr {
+            slot.stepptr = slot.stepptr - (endaddr << 16) + (loopaddr << 16);
+            if (slot.stepptr >> 16) > endaddr {
+                // overflow
+                slot.stepptr &= 0xffff;
+                slot.stepptr |= loopaddr << 16;
+                if (slot.stepptr >> 16) > endaddr {
+                    // still overflow? (triggers in rdft2, rarely)
+                    slot.stepptr &= 0xffff;
+                    slot.stepptr |= endaddr << 16;
+                }
+            }
+        }
+
+        let sample: i16 = if slot.bits == 8 {
+            // 8bit
+            let address = slot.startaddr as usize + (slot.stepptr >> 16) as usize;
+            ((read_byte(&self.rom_bank, address & 0x7fffff) as u16) << 8) as i16
+        } else {
+            // 12bit
+            let address = slot.startaddr as usize + (slot.stepptr >> 17) as usize * 3;
+            let read = |offset: usize| read_byte(&self.rom_bank, (address + offset) & 0x7fffff);
+            if slot.stepptr & 0x10000 != 0 {
+                ((read(2) as u16) << 8 | ((read(1) as u16) << 4 & 0xf0)) as i16
+            } else {
+                ((read(0) as u16) << 8 | (read(1) as u16 & 0xf0)) as i16
+            }
+        };
+
+        slot.update_envelope();
+        slot.update_lfo(&self.lut);
+
+        let final_volume = slot.calculate_slot_volume(&self.lut);
+
+        for (mix, attenuation) in mix.iter_mut().zip(slot.attenuation(&self.lut)) {
+            let ch_vol = ((final_volume * attenuation) >> 16).min(65536);
+            *mix += (sample as i64 * ch_vol) >> 16;
+        }
+
+        // go to next step
+        slot.stepptr += slot.step as u64;
+    }
+
+    ///
+    /// Calculates the output of one FM operator
+    ///
+    fn calculate_op(&mut self, slotnum: usize, inp: OpInput) -> i64 {
+        let slot = &mut self.slots[slotnum];
+
+        slot.update_envelope();
+        slot.update_lfo(&self.lut);
+        let env = slot.calculate_slot_volume(&self.lut);
+
+        let slot_input: i64 = match inp {
+            OpInput::Feedback => {
+                // from own feedback
+                let slot_input = (slot.feedback_modulation0 + slot.feedback_modulation1) / 2;
+                slot.feedback_modulation0 = slot.feedback_modulation1;
+                slot_input
+            }
+            OpInput::Slot(inp) => {
+                // from previous slot output
+                (inp << (SIN_BITS - 2)) * MODULATION_LEVEL[slot.feedback as usize]
+            }
+            OpInput::None => 0,
+        };
+
+        let phase = (((slot.stepptr as i64).wrapping_add(slot_input) >> 16) & SIN_MASK) as usize;
+        let slot_output = self.lut.waves[slot.waveform as usize][phase] as i64;
+        let slot_output = (slot_output * env) >> 16;
+        slot.stepptr = slot.stepptr.wrapping_add(slot.step as u64);
+
+        slot_output
+    }
+
+    fn set_feedback(&mut self, slotnum: usize, inp: i64) {
+        let slot = &mut self.slots[slotnum];
+        slot.feedback_modulation1 =
+            ((inp << (SIN_BITS - 2)) * FEEDBACK_LEVEL[slot.feedback as usize]) / 16;
+    }
+
+    ///
+    /// Mix FM slot outputs
+    ///
+    fn mix_op(&self, mix: &mut [i64; 4], outputs: &[(usize, i64)]) {
+        for (ch, mix) in mix.iter_mut().enumerate() {
+            let mut out: i64 = 0;
+            for &(slotnum, output) in outputs {
+                out += output * self.slots[slotnum].attenuation(&self.lut)[ch];
+            }
+            *mix += out >> 16;
+        }
+    }
+
+    ///
+    /// 4 operator FM
+    ///
+    fn update_4op(&mut self, j: usize, mix: &mut [i64; 4]) {
+        let slot1 = j;
+        let slot2 = j + 12;
+        let slot3 = j + (2 * 12);
+        let slot4 = j + (3 * 12);
+
+        if !self.slots[slot1].active {
+            return;
+        }
+
+        let mut output1: i64 = 0;
+        let mut output2: i64 = 0;
+        let mut output3: i64 = 0;
+        let output4: i64;
+        let phase_mod1: i64;
+        let phase_mod2: i64;
+        let phase_mod3: i64;
+
+        match self.slots[slot1].algorithm {
+            // <--------|
+            // +--[S1]--|--+--[S3]--+--[S2]--+--[S4]-->
+            0 => {
+                phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                self.set_feedback(slot1, phase_mod1);
+                phase_mod3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                phase_mod2 = self.calculate_op(slot2, OpInput::Slot(phase_mod3));
+                output4 = self.calculate_op(slot4, OpInput::Slot(phase_mod2));
+            }
+            // <-----------------|
+            // +--[S1]--+--[S3]--|--+--[S2]--+--[S4]-->
+            1 => {
+                phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                phase_mod3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                self.set_feedback(slot1, phase_mod3);
+                phase_mod2 = self.calculate_op(slot2, OpInput::Slot(phase_mod3));
+                output4 = self.calculate_op(slot4, OpInput::Slot(phase_mod2));
+            }
+            // <--------|
+            // +--[S1]--|
+            //          |
+            //  --[S3]--+--[S2]--+--[S4]-->
+            2 => {
+                phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                self.set_feedback(slot1, phase_mod1);
+                phase_mod3 = self.calculate_op(slot3, OpInput::None);
+                phase_mod2 = self.calculate_op(slot2, OpInput::Slot(phase_mod1 + phase_mod3));
+                output4 = self.calculate_op(slot4, OpInput::Slot(phase_mod2));
+            }
+            //          <--------|
+            //          +--[S1]--|
+            //                   |
+            //  --[S3]--+--[S2]--+--[S4]-->
+            3 => {
+                phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                self.set_feedback(slot1, phase_mod1);
+                phase_mod3 = self.calculate_op(slot3, OpInput::None);
+                phase_mod2 = self.calculate_op(slot2, OpInput::Slot(phase_mod3));
+                output4 = self.calculate_op(slot4, OpInput::Slot(phase_mod1 + phase_mod2));
+            }
+            //              --[S2]--|
+            // <--------|           |
+            // +--[S1]--|--+--[S3]--+--[S4]-->
+            4 => {
+                phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                self.set_feedback(slot1, phase_mod1);
+                phase_mod3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                phase_mod2 = self.calculate_op(slot2, OpInput::None);
+                output4 = self.calculate_op(slot4, OpInput::Slot(phase_mod3 + phase_mod2));
+            }
+            //           --[S2]-----|
+            // <-----------------|  |
+            // +--[S1]--+--[S3]--|--+--[S4]-->
+            5 => {
+                phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                phase_mod3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                self.set_feedback(slot1, phase_mod3);
+                phase_mod2 = self.calculate_op(slot2, OpInput::None);
+                output4 = self.calculate_op(slot4, OpInput::Slot(phase_mod3 + phase_mod2));
+            }
+            //  --[S2]-----+--[S4]--|
+            //                      |
+            // <--------|           |
+            // +--[S1]--|--+--[S3]--+-->
+            6 => {
+                phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                self.set_feedback(slot1, phase_mod1);
+                output3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                phase_mod2 = self.calculate_op(slot2, OpInput::None);
+                output4 = self.calculate_op(slot4, OpInput::Slot(phase_mod2));
+            }
+            //  --[S2]--+--[S4]-----|
+            //                      |
+            // <-----------------|  |
+            // +--[S1]--+--[S3]--|--+-->
+            7 => {
+                phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                phase_mod3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                self.set_feedback(slot1, phase_mod3);
+                output3 = phase_mod3;
+                phase_mod2 = self.calculate_op(slot2, OpInput::None);
+                output4 = self.calculate_op(slot4, OpInput::Slot(phase_mod2));
+            }
+            //  --[S3]--+--[S2]--+--[S4]--|
+            //                            |
+            // <--------|                 |
+            // +--[S1]--|-----------------+-->
+            8 => {
+                phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                self.set_feedback(slot1, phase_mod1);
+                output1 = phase_mod1;
+                phase_mod3 = self.calculate_op(slot3, OpInput::None);
+                phase_mod2 = self.calculate_op(slot2, OpInput::Slot(phase_mod3));
+                output4 = self.calculate_op(slot4, OpInput::Slot(phase_mod2));
+            }
+            //          <--------|
+            //          +--[S1]--|
+            //                   |
+            //  --[S3]--|        |
+            //  --[S2]--+--[S4]--+-->
+            9 => {
+                phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                self.set_feedback(slot1, phase_mod1);
+                output1 = phase_mod1;
+                phase_mod3 = self.calculate_op(slot3, OpInput::None);
+                phase_mod2 = self.calculate_op(slot2, OpInput::None);
+                output4 = self.calculate_op(slot4, OpInput::Slot(phase_mod3 + phase_mod2));
+            }
+            //              --[S4]--|
+            //              --[S2]--|
+            // <--------|           |
+            // +--[S1]--|--+--[S3]--+-->
+            10 => {
+                phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                self.set_feedback(slot1, phase_mod1);
+                output3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                output2 = self.calculate_op(slot2, OpInput::None);
+                output4 = self.calculate_op(slot4, OpInput::None);
+            }
+            //           --[S4]-----|
+            //           --[S2]-----|
+            // <-----------------|  |
+            // +--[S1]--+--[S3]--|--+-->
+            11 => {
+                phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                phase_mod3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                self.set_feedback(slot1, phase_mod3);
+                output3 = phase_mod3;
+                output2 = self.calculate_op(slot2, OpInput::None);
+                output4 = self.calculate_op(slot4, OpInput::None);
+            }
+            //             |--+--[S4]--|
+            // <--------|  |--+--[S3]--|
+            // +--[S1]--|--|--+--[S2]--+-->
+            12 => {
+                phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                self.set_feedback(slot1, phase_mod1);
+                output3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                output2 = self.calculate_op(slot2, OpInput::Slot(phase_mod1));
+                output4 = self.calculate_op(slot4, OpInput::Slot(phase_mod1));
+            }
+            //  --[S3]--+--[S2]--|
+            //                   |
+            //  --[S4]-----------|
+            // <--------|        |
+            // +--[S1]--|--------+-->
+            13 => {
+                phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                self.set_feedback(slot1, phase_mod1);
+                output1 = phase_mod1;
+                phase_mod3 = self.calculate_op(slot3, OpInput::None);
+                output2 = self.calculate_op(slot2, OpInput::Slot(phase_mod3));
+                output4 = self.calculate_op(slot4, OpInput::None);
+            }
+            //  --[S2]-----+--[S4]--|
+            //                      |
+            // <--------|  +--[S3]--|
+            // +--[S1]--|--|--------+-->
+            14 => {
+                phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                self.set_feedback(slot1, phase_mod1);
+                output1 = phase_mod1;
+                output3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                phase_mod2 = self.calculate_op(slot2, OpInput::None);
+                output4 = self.calculate_op(slot4, OpInput::Slot(phase_mod2));
+            }
+            //  --[S4]-----|
+            //  --[S2]-----|
+            //  --[S3]-----|
+            // <--------|  |
+            // +--[S1]--|--+-->
+            _ => {
+                phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                self.set_feedback(slot1, phase_mod1);
+                output1 = phase_mod1;
+                output3 = self.calculate_op(slot3, OpInput::None);
+                output2 = self.calculate_op(slot2, OpInput::None);
+                output4 = self.calculate_op(slot4, OpInput::None);
+            }
+        }
+
+        self.mix_op(
+            mix,
+            &[
+                (slot1, output1),
+                (slot2, output2),
+                (slot3, output3),
+                (slot4, output4),
+            ],
+        );
+    }
+
+    ///
+    /// 2x 2 operator FM
+    ///
+    fn update_2op(&mut self, j: usize, mix: &mut [i64; 4]) {
+        for op in 0..2 {
+            let slot1 = j + (op * 12);
+            let slot3 = j + ((op + 2) * 12);
+
+            if !self.slots[slot1].active {
+                continue;
+            }
+
+            let mut output1: i64 = 0;
+            let output3: i64;
+            let phase_mod1: i64;
+
+            match self.slots[slot1].algorithm & 3 {
+                // <--------|
+                // +--[S1]--|--+--[S3]-->
+                0 => {
+                    phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                    self.set_feedback(slot1, phase_mod1);
+                    output3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                }
+                // <-----------------|
+                // +--[S1]--+--[S3]--|-->
+                1 => {
+                    phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                    let phase_mod3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                    self.set_feedback(slot1, phase_mod3);
+                    output3 = phase_mod3;
+                }
+                //  --[S3]-----|
+                // <--------|  |
+                // +--[S1]--|--+-->
+                2 => {
+                    phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                    self.set_feedback(slot1, phase_mod1);
+                    output1 = phase_mod1;
+                    output3 = self.calculate_op(slot3, OpInput::None);
+                }
+                //
+                // <--------|  +--[S3]--|
+                // +--[S1]--|--|--------+-->
+                _ => {
+                    phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                    self.set_feedback(slot1, phase_mod1);
+                    output1 = phase_mod1;
+                    output3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                }
+            }
+
+            self.mix_op(mix, &[(slot1, output1), (slot3, output3)]);
+        }
+    }
+
+    ///
+    /// 3 operator FM + PCM
+    ///
+    fn update_3op(&mut self, j: usize, mix: &mut [i64; 4]) {
+        let slot1 = j;
+        let slot2 = j + 12;
+        let slot3 = j + (2 * 12);
+
+        if self.slots[slot1].active {
+            let mut output1: i64 = 0;
+            let output2: i64;
+            let mut output3: i64 = 0;
+            let phase_mod1: i64;
+            let phase_mod3: i64;
+
+            match self.slots[slot1].algorithm & 7 {
+                // <--------|
+                // +--[S1]--|--+--[S3]--+--[S2]-->
+                0 => {
+                    phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                    self.set_feedback(slot1, phase_mod1);
+                    phase_mod3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                    output2 = self.calculate_op(slot2, OpInput::Slot(phase_mod3));
+                }
+                // <-----------------|
+                // +--[S1]--+--[S3]--|--+--[S2]-->
+                1 => {
+                    phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                    phase_mod3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                    self.set_feedback(slot1, phase_mod3);
+                    output2 = self.calculate_op(slot2, OpInput::Slot(phase_mod3));
+                }
+                //  --[S3]-----|
+                // <--------|  |
+                // +--[S1]--|--+--[S2]-->
+                2 => {
+                    phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                    self.set_feedback(slot1, phase_mod1);
+                    phase_mod3 = self.calculate_op(slot3, OpInput::None);
+                    output2 = self.calculate_op(slot2, OpInput::Slot(phase_mod1 + phase_mod3));
+                }
+                //  --[S3]--+--[S2]--|
+                // <--------|        |
+                // +--[S1]--|--------+-->
+                3 => {
+                    phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                    self.set_feedback(slot1, phase_mod1);
+                    output1 = phase_mod1;
+                    phase_mod3 = self.calculate_op(slot3, OpInput::None);
+                    output2 = self.calculate_op(slot2, OpInput::Slot(phase_mod3));
+                }
+                //              --[S2]--|
+                // <--------|           |
+                // +--[S1]--|--+--[S3]--+-->
+                4 => {
+                    phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                    self.set_feedback(slot1, phase_mod1);
+                    output3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                    output2 = self.calculate_op(slot2, OpInput::None);
+                }
+                //              --[S2]--|
+                // <-----------------|  |
+                // +--[S1]--+--[S3]--|--+-->
+                5 => {
+                    phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                    phase_mod3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                    self.set_feedback(slot1, phase_mod3);
+                    output3 = phase_mod3;
+                    output2 = self.calculate_op(slot2, OpInput::None);
+                }
+                //  --[S2]-----|
+                //  --[S3]-----|
+                // <--------|  |
+                // +--[S1]--|--+-->
+                6 => {
+                    phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                    self.set_feedback(slot1, phase_mod1);
+                    output1 = phase_mod1;
+                    output3 = self.calculate_op(slot3, OpInput::None);
+                    output2 = self.calculate_op(slot2, OpInput::None);
+                }
+                //              --[S2]--|
+                // <--------|  +--[S3]--|
+                // +--[S1]--|--|--------+-->
+                _ => {
+                    phase_mod1 = self.calculate_op(slot1, OpInput::Feedback);
+                    self.set_feedback(slot1, phase_mod1);
+                    output1 = phase_mod1;
+                    output3 = self.calculate_op(slot3, OpInput::Slot(phase_mod1));
+                    output2 = self.calculate_op(slot2, OpInput::None);
+                }
+            }
+
+            self.mix_op(mix, &[(slot1, output1), (slot2, output2), (slot3, output3)]);
+        }
+
+        self.update_pcm(j + (3 * 12), mix);
+    }
+
+    fn sound_stream_update(&mut self, buffer_l: &mut [f32], buffer_r: &mut [f32]) {
+        let mut mix: [i64; 4] = [0; 4];
+
+        for j in 0..12 {
+            match self.groups[j].sync {
+                // 4 operator FM
+                0 => self.update_4op(j, &mut mix),
+                // 2x 2 operator FM
+                1 => self.update_2op(j, &mut mix),
+                // 3 operator FM + PCM
+                2 => self.update_3op(j, &mut mix),
+                // PCM
+                _ => {
+                    self.update_pcm(j, &mut mix);
+                    self.update_pcm(j + 12, &mut mix);
+                    self.update_pcm(j + (2 * 12), &mut mix);
+                    self.update_pcm(j + (3 * 12), &mut mix);
+                }
+            }
+        }
+
+        // 4 channel output to stereo (ch0/ch2: left, ch1/ch3: right)
+        buffer_l[0] += convert_int((mix[0] + mix[2]) as i32, 32768);
+        buffer_r[0] += convert_int((mix[1] + mix[3]) as i32, 32768);
+    }
+
+    fn write_register(&mut self, slotnum: usize, reg: u8, data: u8) {
+        let slot = &mut self.slots[slotnum];
+
+        match reg {
+            0x0 => {
+                // ext_en, ext_out is not supported
+                if data & 1 != 0 {
+                    // key on
+                    slot.step = 0;
+                    slot.stepptr = 0;
+
+                    slot.active = true;
+
+                    slot.calculate_step();
+                    slot.init_envelope(&self.lut);
+                    slot.init_lfo(&self.lut);
+                    slot.feedback_modulation0 = 0;
+                    slot.feedback_modulation1 = 0;
+                } else if slot.active {
+                    slot.env_state = ENV_RELEASE;
+                }
+            }
+            0x1 => slot.lfo_freq = data,
+            0x2 => {
+                slot.lfowave = data & 3;
+                slot.pms = (data >> 3) & 0x7;
+                slot.ams = (data >> 6) & 0x3;
+            }
+            0x3 => {
+                // detune is not supported
+                slot.multiple = data & 0xf;
+            }
+            0x4 => slot.tl = data & 0x7f,
+            0x5 => {
+                slot.ar = data & 0x1f;
+                slot.keyscale = (data >> 5) & 0x7;
+            }
+            0x6 => slot.decay1rate = data & 0x1f,
+            0x7 => slot.decay2rate = data & 0x1f,
+            0x8 => {
+                slot.relrate = data & 0xf;
+                slot.decay1lvl = (data >> 4) & 0xf;
+            }
+            0x9 => {
+                // write frequency and block here
+                slot.fns = ((slot.fns_hi as u32) << 8 & 0x0f00) | data as u32;
+                slot.block = slot.fns_hi >> 4 & 0xf;
+            }
+            0xa => slot.fns_hi = data,
+            0xb => {
+                // accon is not supported
+                slot.waveform = data & 0x7;
+                slot.feedback = (data >> 4) & 0x7;
+            }
+            0xc => slot.algorithm = data & 0xf,
+            0xd => {
+                slot.ch0_level = data >> 4;
+                slot.ch1_level = data & 0xf;
+            }
+            0xe => {
+                slot.ch2_level = data >> 4;
+                slot.ch3_level = data & 0xf;
+            }
+            _ => { /* nothing to do */ }
+        }
+    }
+
+    fn ymf271_write_fm(&mut self, bank: usize, address: u8, data: u8) {
+        let groupnum = FM_TAB[(address & 0xf) as usize];
+        if groupnum == -1 {
+            return;
+        }
+        let groupnum = groupnum as usize;
+
+        let reg = (address >> 4) & 0xf;
+
+        // check if the register is a synchronized register
+        let sync_reg = matches!(reg, 0 | 9 | 10 | 12 | 13 | 14);
+
+        // check if the slot is key on slot for synchronizing
+        let sync_mode = match self.groups[groupnum].sync {
+            // 4 slot mode
+            0 => bank == 0,
+            // 2x 2 slot mode
+            1 => bank == 0 || bank == 1,
+            // 3 slot + 1 slot mode
+            2 => bank == 0,
+            _ => false,
+        };
+
+        // key-on slot & synced register
+        if sync_mode && sync_reg {
+            match self.groups[groupnum].sync {
+                // 4 slot mode
+                0 => {
+                    self.write_register(groupnum, reg, data);
+                    self.write_register(12 + groupnum, reg, data);
+                    self.write_register((12 * 2) + groupnum, reg, data);
+                    self.write_register((12 * 3) + groupnum, reg, data);
+                }
+                // 2x 2 slot mode
+                1 => {
+                    if bank == 0 {
+                        // Slot 1 - Slot 3
+                        self.write_register(groupnum, reg, data);
+                        self.write_register((12 * 2) + groupnum, reg, data);
+                    } else {
+                        // Slot 2 - Slot 4
+                        self.write_register(12 + groupnum, reg, data);
+                        self.write_register((12 * 3) + groupnum, reg, data);
+                    }
+                }
+                // 3 slot + 1 slot mode (1 slot is handled normally)
+                _ => {
+                    self.write_register(groupnum, reg, data);
+                    self.write_register(12 + groupnum, reg, data);
+                    self.write_register((12 * 2) + groupnum, reg, data);
+                }
+            }
+        } else {
+            // write register normally
+            self.write_register((12 * bank) + groupnum, reg, data);
+        }
+    }
+
+    fn ymf271_write_pcm(&mut self, address: u8, data: u8) {
+        let slotnum = PCM_TAB[(address & 0xf) as usize];
+        if slotnum == -1 {
+            return;
+        }
+        let slot = &mut self.slots[slotnum as usize];
+        let data = data as u32;
+
+        match (address >> 4) & 0xf {
+            0x0 => slot.startaddr = (slot.startaddr & !0xff) | data,
+            0x1 => slot.startaddr = (slot.startaddr & !0xff00) | data << 8,
+            0x2 => {
+                // altloop is not supported
+                slot.startaddr = (slot.startaddr & !0xff0000) | (data & 0x7f) << 16;
+            }
+            0x3 => slot.endaddr = (slot.endaddr & !0xff) | data,
+            0x4 => slot.endaddr = (slot.endaddr & !0xff00) | data << 8,
+            0x5 => slot.endaddr = (slot.endaddr & !0xff0000) | (data & 0x7f) << 16,
+            0x6 => slot.loopaddr = (slot.loopaddr & !0xff) | data,
+            0x7 => slot.loopaddr = (slot.loopaddr & !0xff00) | data << 8,
+            0x8 => slot.loopaddr = (slot.loopaddr & !0xff0000) | (data & 0x7f) << 16,
+            0x9 => {
+                // srcnote, srcb is not supported
+                slot.fs = (data & 0x3) as u8;
+                slot.bits = if data & 0x4 != 0 { 12 } else { 8 };
+            }
+            _ => { /* nothing to do */ }
+        }
+    }
+
+    fn ymf271_write_timer(&mut self, address: u8, data: u8) {
+        if (address & 0xf0) == 0 {
+            let groupnum = FM_TAB[(address & 0xf) as usize];
+            if groupnum == -1 {
+                return;
+            }
+            // pfm is not supported
+            self.groups[groupnum as usize].sync = data & 0x3;
+        }
+        // timer, IRQ and external memory write are not supported
+    }
+
+    ///
+    /// Write VGM 0xd1 command (port << 8 | register)
+    ///
+    fn write(&mut self, port: u32, data: u8) {
+        let address = (port & 0xff) as u8;
+        match port >> 8 {
+            bank @ PORT_FM_BANK0..=PORT_FM_BANK3 => {
+                self.ymf271_write_fm(bank as usize, address, data)
+            }
+            PORT_PCM => self.ymf271_write_pcm(address, data),
+            PORT_TIMER => self.ymf271_write_timer(address, data),
+            _ => { /* nothing to do */ }
+        }
+    }
+}
+
+impl SoundChip for YMF271 {
+    fn create(_sound_device_name: SoundChipType) -> Self {
+        YMF271::new()
+    }
+
+    fn init(&mut self, clock: u32) -> u32 {
+        self.device_start(clock)
+    }
+
+    fn reset(&mut self) {
+        self.device_reset();
+    }
+
+    fn write(&mut self, _: usize, port: u32, data: u32, _: &mut dyn SoundStream) {
+        self.write(port, data as u8);
+    }
+
+    fn tick(&mut self, _: usize, sound_stream: &mut dyn SoundStream) {
+        let mut l: [f32; 1] = [0_f32];
+        let mut r: [f32; 1] = [0_f32];
+        self.sound_stream_update(&mut l, &mut r);
+        sound_stream.push(l[0], r[0]);
+    }
+
+    fn set_rom_bank(&mut self, _ /* YMF271 has only one RomBank */: RomIndex, rombank: RomBank) {
+        self.rom_bank = rombank;
+    }
+
+    fn notify_add_rom(&mut self, _: RomIndex, _: usize) {
+        /* nothing to do */
+    }
+
+    fn set_rom_bus(&mut self, _: Option<RomBusType>) {
+        /* nothing to do */
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sound::slot::tests::{assert_frequency, generate, sampling, sound_slot};
+    use crate::sound::SoundChipType;
+
+    #[test]
+    fn ymf271_1() {
+        let mut sound_slot = sound_slot(SoundChipType::YMF271, 16934400);
+        // group 1 is 4 operator FM (VGM 0xd1 port 0-3: FM bank, register << 4 | group)
+        for bank in 0..4 {
+            // multiple 1, attack rate 31, release rate 15
+            sound_slot.write(SoundChipType::YMF271, 0, bank << 8 | 0x30, 0x01);
+            sound_slot.write(SoundChipType::YMF271, 0, bank << 8 | 0x50, 0x1f);
+            sound_slot.write(SoundChipType::YMF271, 0, bank << 8 | 0x80, 0x0f);
+            // algorithm 0 outputs slot 4 only (total level 0), slot 1-3 modulators are off
+            let tl = if bank == 3 { 0x00 } else { 0x7f };
+            sound_slot.write(SoundChipType::YMF271, 0, bank << 8 | 0x40, tl);
+        }
+        // block 4 fns 0x400, algorithm 0, channel level 0
+        sound_slot.write(SoundChipType::YMF271, 0, 0xa0, 0x44);
+        sound_slot.write(SoundChipType::YMF271, 0, 0x90, 0x00);
+        sound_slot.write(SoundChipType::YMF271, 0, 0xc0, 0x00);
+        sound_slot.write(SoundChipType::YMF271, 0, 0xd0, 0x00);
+        sound_slot.write(SoundChipType::YMF271, 0, 0xe0, 0x00);
+        assert_eq!(generate(&mut sound_slot), 0_f32);
+        // key on (2 * fns * 2^(block + 7) / 2^29 * clock / 384)
+        sound_slot.write(SoundChipType::YMF271, 0, 0x00, 0x01);
+        assert!(generate(&mut sound_slot) > 0_f32);
+        let (l, _) = sampling(&sound_slot);
+        assert_frequency(l, 44100_f32 * 2048_f32 * 2048_f32 / 536870912_f32);
+        // block 5 is one octave up
+        sound_slot.write(SoundChipType::YMF271, 0, 0xa0, 0x54);
+        sound_slot.write(SoundChipType::YMF271, 0, 0x90, 0x00);
+        generate(&mut sound_slot);
+        let (l, _) = sampling(&sound_slot);
+        assert_frequency(l, 44100_f32 * 2048_f32 * 4096_f32 / 536870912_f32);
+        // key off
+        sound_slot.write(SoundChipType::YMF271, 0, 0x00, 0x00);
+        generate(&mut sound_slot);
+        assert_eq!(generate(&mut sound_slot), 0_f32);
+    }
+}
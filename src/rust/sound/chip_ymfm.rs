@@ -41,6 +41,11 @@ pub enum ChipType {
     CHIP_YM3812 = 9,
     CHIP_YMF262 = 10,
     CHIP_YMF278B = 11,
+    CHIP_YM2610B = 12,
+    CHIP_YM3438 = 13,
+    CHIP_YMF288 = 14,
+    CHIP_YM2164 = 15,
+    CHIP_YMF289B = 16,
 }
 
 pub struct YmFm {
@@ -107,6 +112,11 @@ impl SoundChip for YmFm {
             SoundChipType::Y8950 => ChipType::CHIP_Y8950,
             SoundChipType::YMF262 => ChipType::CHIP_YMF262,
             SoundChipType::YMF278B => ChipType::CHIP_YMF278B,
+            SoundChipType::YM2610B => ChipType::CHIP_YM2610B,
+            SoundChipType::YM3438 => ChipType::CHIP_YM3438,
+            SoundChipType::YMF288 => ChipType::CHIP_YMF288,
+            SoundChipType::YM2164 => ChipType::CHIP_YM2164,
+            SoundChipType::YMF289B => ChipType::CHIP_YMF289B,
             _ => todo!(),
         };
         YmFm {
@@ -149,7 +159,8 @@ impl SoundChip for YmFm {
                 | RomIndex::YM2610_DELTA_T
                 | RomIndex::YMF278B_ROM
                 | RomIndex::YMF278B_RAM
-                | RomIndex::Y8950_ROM => unsafe {
+                | RomIndex::Y8950_ROM
+                | RomIndex::YM2608_RHYTHM => unsafe {
                     ymfm_add_rom_data(
                         self.chip_type as u16,
                         rom_index as u16,
@@ -171,3 +182,73 @@ impl SoundChip for YmFm {
         /* nothing to do */
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ChipType, YmFm};
+    use crate::sound::slot::tests::{generate, sampling, sound_slot};
+    use crate::sound::{sound_chip::SoundChip, SoundChipType};
+
+    #[test]
+    fn ymfm_variant() {
+        // chip number is the chip_type of ymfmffi.cpp
+        for (sound_chip_type, chip_type, chip_num) in [
+            (SoundChipType::YM2610B, ChipType::CHIP_YM2610B, 12),
+            (SoundChipType::YM3438, ChipType::CHIP_YM3438, 13),
+            (SoundChipType::YMF288, ChipType::CHIP_YMF288, 14),
+            (SoundChipType::YM2164, ChipType::CHIP_YM2164, 15),
+            (SoundChipType::YMF289B, ChipType::CHIP_YMF289B, 16),
+        ] {
+            let ymfm = YmFm::create(sound_chip_type);
+            assert!(ymfm.chip_type == chip_type);
+            assert_eq!(ymfm.chip_type as u16, chip_num);
+        }
+    }
+
+    #[test]
+    fn ymf288_1() {
+        let mut sound_slot = sound_slot(SoundChipType::YMF288, 15974400);
+        // SSG tone A period 0x100 and tone A only (port 0)
+        sound_slot.write(SoundChipType::YMF288, 0, 0x00, 0x00);
+        sound_slot.write(SoundChipType::YMF288, 0, 0x01, 0x01);
+        sound_slot.write(SoundChipType::YMF288, 0, 0x07, 0x3e);
+        assert_eq!(generate(&mut sound_slot), 0_f32);
+        // tone A volume 15
+        sound_slot.write(SoundChipType::YMF288, 0, 0x08, 0x0f);
+        assert!(generate(&mut sound_slot) > 0_f32);
+    }
+
+    #[test]
+    fn ymf289b_1() {
+        let mut sound_slot = sound_slot(SoundChipType::YMF289B, 33868800);
+        // OPL3 mode (port 1)
+        sound_slot.write(SoundChipType::YMF289B, 0, 0x105, 0x01);
+        // channel 1 carrier only: multiple 1, total level, attack rate 15, release rate 15
+        for (reg, data) in [
+            (0x20, 0x01),
+            (0x23, 0x01),
+            (0x40, 0x3f),
+            (0x43, 0x00),
+            (0x60, 0xf0),
+            (0x63, 0xf0),
+            (0x80, 0x0f),
+            (0x83, 0x0f),
+        ] {
+            sound_slot.write(SoundChipType::YMF289B, 0, reg, data);
+        }
+        // left and right output, fnum 0x200 block 4
+        sound_slot.write(SoundChipType::YMF289B, 0, 0xc0, 0x30);
+        sound_slot.write(SoundChipType::YMF289B, 0, 0xa0, 0x00);
+        sound_slot.write(SoundChipType::YMF289B, 0, 0xb0, 0x12);
+        assert_eq!(generate(&mut sound_slot), 0_f32);
+        // key on
+        sound_slot.write(SoundChipType::YMF289B, 0, 0xb0, 0x32);
+        assert!(generate(&mut sound_slot) > 0_f32);
+        let (l, r) = sampling(&sound_slot);
+        assert_eq!(l, r);
+        // key off
+        sound_slot.write(SoundChipType::YMF289B, 0, 0xb0, 0x12);
+        generate(&mut sound_slot);
+        assert_eq!(generate(&mut sound_slot), 0_f32);
+    }
+}
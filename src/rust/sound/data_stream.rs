@@ -16,28 +16,54 @@ impl DataBlock {
     }
 }
 
+///
+/// Data stream length mode (VGM 0x93)
+///
+pub const LENGTH_MODE_IGNORE: u8 = 0x00;
+pub const LENGTH_MODE_COMMANDS: u8 = 0x01;
+pub const LENGTH_MODE_MSEC: u8 = 0x02;
+pub const LENGTH_MODE_TO_END: u8 = 0x03;
+pub const LENGTH_MODE_BYTES: u8 = 0x0f;
+pub const LENGTH_MODE_REVERSE: u8 = 0x10;
+pub const LENGTH_MODE_LOOP: u8 = 0x80;
+
 pub struct DataStream {
     data_block_id: Option<usize>,
     frequency: u32,
     write_port: u32,
     write_reg: u32,
-    data_block_pos: usize,
+    data_width: usize,
+    step_base: usize,
+    step_size: usize,
     data_block_start_offset: usize,
-    data_block_length: usize,
+    data_block_commands: usize,
+    data_block_command_pos: usize,
+    data_block_remain_commands: usize,
+    reverse: bool,
+    looping: bool,
     data_stream_sampling_pos: f32,
     data_stream_sample_step: f32,
 }
 
 impl DataStream {
-    pub fn new(write_port: u32, write_reg: u32) -> Self {
+    ///
+    /// data_width: bytes per write command (e.g. PWM 12bit is 2)
+    ///
+    pub fn new(write_port: u32, write_reg: u32, data_width: usize) -> Self {
         DataStream {
             data_block_id: None,
             frequency: 0,
             write_port,
             write_reg,
+            data_width: data_width.max(1),
+            step_base: 0,
+            step_size: 1,
             data_block_start_offset: 0,
-            data_block_pos: 0,
-            data_block_length: 0,
+            data_block_commands: 0,
+            data_block_command_pos: 0,
+            data_block_remain_commands: 0,
+            reverse: false,
+            looping: false,
             data_stream_sampling_pos: 0_f32,
             data_stream_sample_step: 0_f32,
         }
@@ -46,18 +72,30 @@ impl DataStream {
     ///
     /// Tick stream
     ///
-    pub fn tick(&mut self) -> Option<(usize, usize, u32, u32)> {
+    /// Return (data block id, data block position, write address (port << 8 | reg), data width)
+    ///
+    pub fn tick(&mut self) -> Option<(usize, usize, u32, usize)> {
         let mut result = None;
-        if self.data_block_length > 0 {
+        if self.data_block_remain_commands > 0 {
             result = if self.data_stream_sampling_pos >= 1_f32 {
-                self.data_block_length -= 1;
+                let step = if self.reverse {
+                    self.data_block_commands - 1 - self.data_block_command_pos
+                } else {
+                    self.data_block_command_pos
+                };
                 let result = Some((
                     self.data_block_id.unwrap(/* TODO: */),
-                    self.data_block_start_offset + self.data_block_pos,
-                    self.write_port,
-                    self.write_reg,
+                    self.data_block_start_offset + step * self.data_step(),
+                    self.write_port << 8 | self.write_reg,
+                    self.data_width,
                 ));
-                self.data_block_pos += 1;
+                self.data_block_command_pos += 1;
+                self.data_block_remain_commands -= 1;
+                if self.data_block_remain_commands == 0 && self.looping {
+                    // automatically restarts when finished
+                    self.data_block_command_pos = 0;
+                    self.data_block_remain_commands = self.data_block_commands;
+                }
                 result
             } else {
                 None
@@ -71,6 +109,14 @@ impl DataStream {
         result
     }
 
+    ///
+    /// Bytes skipped after every write
+    ///
+    #[inline]
+    fn data_step(&self) -> usize {
+        self.step_size * self.data_width
+    }
+
     ///
     /// Set data stream frequency
     ///
@@ -87,34 +133,117 @@ impl DataStream {
         self.data_block_id = Some(data_block_id);
     }
 
+    ///
+    /// Return assigned data block
+    ///
+    pub fn get_data_block_id(&self) -> Option<usize> {
+        self.data_block_id
+    }
+
+    ///
+    /// Set step base and step size
+    ///
+    /// step_base: data offset added to the start offset when starting stream playback
+    /// step_size: how many data is skipped after every write
+    ///
+    pub fn set_step(&mut self, step_base: usize, step_size: usize) {
+        self.step_base = step_base;
+        self.step_size = step_size.max(1);
+    }
+
     ///
     /// Start data stream
     ///
+    /// length_mode: LENGTH_MODE_* (bit 0-3: mode, bit 4: reverse, bit 7: loop)
+    ///
     pub fn start_data_stream(
         &mut self,
         data_block_start_offset: Option<usize>,
+        length_mode: u8,
+        length: usize,
         data_block_length: usize,
     ) {
         if let Some(data_block_start_offset) = data_block_start_offset {
-            self.data_block_start_offset = data_block_start_offset;
-        } else {
-            self.data_block_start_offset = 0;
+            // catch bad value and ignore
+            self.data_block_start_offset =
+                (data_block_start_offset + self.step_base * self.data_width).min(data_block_length);
+        }
+        match length_mode & 0x0f {
+            LENGTH_MODE_IGNORE => { /* length is already set */ }
+            LENGTH_MODE_COMMANDS => self.data_block_commands = length,
+            LENGTH_MODE_MSEC => {
+                self.data_block_commands = (length as u64 * self.frequency as u64 / 1000) as usize
+            }
+            LENGTH_MODE_TO_END => {
+                self.data_block_commands =
+                    data_block_length.saturating_sub(self.data_block_start_offset) / self.data_step()
+            }
+            LENGTH_MODE_BYTES => self.data_block_commands = length / self.data_step(),
+            _ => self.data_block_commands = 0,
         }
-        self.data_block_pos = 0;
-        self.data_block_length = data_block_length;
+        self.reverse = length_mode & LENGTH_MODE_REVERSE != 0;
+        self.looping = length_mode & LENGTH_MODE_LOOP != 0;
+        self.data_block_command_pos = 0;
+        self.data_block_remain_commands = self.data_block_commands;
     }
 
     ///
     /// Stop data stream
     ///
     pub fn stop_data_stream(&mut self) {
-        self.data_block_length = 0;
+        self.data_block_remain_commands = 0;
     }
 
     ///
     /// Return data stream play state
     ///
     pub fn is_stop_data_stream(&self) -> bool {
-        self.data_block_length == 0
+        self.data_block_remain_commands == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(data_stream: &mut DataStream, ticks: usize) -> Vec<usize> {
+        (0..ticks)
+            .filter_map(|_| data_stream.tick())
+            .map(|(_, pos, _, _)| pos)
+            .collect()
+    }
+
+    #[test]
+    fn step_and_reverse() {
+        let mut data_stream = DataStream::new(0, 0, 1);
+        data_stream.set_data_block_id(0);
+        data_stream.set_frequency(44100, 44100);
+        data_stream.set_step(1, 2);
+        data_stream.start_data_stream(Some(0), LENGTH_MODE_TO_END, 0, 8);
+        assert_eq!(collect(&mut data_stream, 10), vec![1, 3, 5]);
+        assert!(data_stream.is_stop_data_stream());
+        data_stream.start_data_stream(Some(0), LENGTH_MODE_COMMANDS | LENGTH_MODE_REVERSE, 3, 8);
+        assert_eq!(collect(&mut data_stream, 10), vec![5, 3, 1]);
+    }
+
+    #[test]
+    fn loop_and_msec() {
+        let mut data_stream = DataStream::new(0, 0, 1);
+        data_stream.set_data_block_id(0);
+        data_stream.set_frequency(1000, 1000);
+        data_stream.start_data_stream(Some(4), LENGTH_MODE_MSEC | LENGTH_MODE_LOOP, 2, 8);
+        assert_eq!(collect(&mut data_stream, 6), vec![4, 5, 4, 5, 4]);
+        assert!(!data_stream.is_stop_data_stream());
+    }
+
+    #[test]
+    fn port_and_width() {
+        let mut data_stream = DataStream::new(1, 0x08, 2);
+        data_stream.set_data_block_id(0);
+        data_stream.set_frequency(44100, 44100);
+        data_stream.start_data_stream(Some(0), LENGTH_MODE_TO_END, 0, 6);
+        data_stream.tick();
+        assert_eq!(data_stream.tick(), Some((0, 0, 0x108, 2)));
+        assert_eq!(collect(&mut data_stream, 10), vec![2, 4]);
     }
 }
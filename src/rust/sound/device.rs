@@ -1,7 +1,9 @@
 // license:BSD-3-Clause
 // copyright-holders:Hiromasa Tanaka
 use super::{
-    data_stream::{DataBlock, DataStream},
+    data_stream::{
+        DataBlock, DataStream, LENGTH_MODE_BYTES, LENGTH_MODE_LOOP, LENGTH_MODE_REVERSE,
+    },
     rom::RomSet,
     sound_chip::{SoundChip},
     stream::{SoundStream, Tick},
@@ -156,9 +158,26 @@ impl SoundDevice {
     ///
     /// Attach data block to stream
     ///
-    pub fn attach_data_block_to_stream(&mut self, data_stream_id: usize, data_block_id: usize) {
+    pub fn attach_data_block_to_stream(
+        &mut self,
+        data_stream_id: usize,
+        data_block_id: usize,
+        step_base: usize,
+        step_size: usize,
+    ) {
         if let Some(data_stream) = self.data_stream.get_mut(&data_stream_id) {
             data_stream.set_data_block_id(data_block_id);
+            data_stream.set_step(step_base, step_size);
+        }
+    }
+
+    ///
+    /// Return data block attached to data stream
+    ///
+    pub fn get_data_stream_block_id(&self, data_stream_id: usize) -> Option<usize> {
+        match self.data_stream.get(&data_stream_id) {
+            Some(data_stream) => data_stream.get_data_block_id(),
+            None => None,
         }
     }
 
@@ -168,11 +187,18 @@ impl SoundDevice {
     pub fn start_data_stream(
         &mut self,
         data_stream_id: usize,
-        data_block_start_offset: usize,
+        data_block_start_offset: Option<usize>,
+        length_mode: u8,
+        length: usize,
         data_block_length: usize,
     ) {
         if let Some(data_stream) = self.data_stream.get_mut(&data_stream_id) {
-            data_stream.start_data_stream(Some(data_block_start_offset), data_block_length);
+            data_stream.start_data_stream(
+                data_block_start_offset,
+                length_mode,
+                length,
+                data_block_length,
+            );
         }
     }
 
@@ -183,11 +209,22 @@ impl SoundDevice {
         &mut self,
         data_stream_id: usize,
         data_block_id: usize,
+        flags: u8,
         data_block_length: usize,
     ) {
         if let Some(data_stream) = self.data_stream.get_mut(&data_stream_id) {
+            // flags bit 0: loop, bit 4: reverse
+            let mut length_mode = LENGTH_MODE_BYTES | (flags & LENGTH_MODE_REVERSE);
+            if flags & 0x01 != 0 {
+                length_mode |= LENGTH_MODE_LOOP;
+            }
             data_stream.set_data_block_id(data_block_id);
-            data_stream.start_data_stream(None, data_block_length);
+            data_stream.start_data_stream(
+                Some(0),
+                length_mode,
+                data_block_length,
+                data_block_length,
+            );
         }
     }
 
@@ -221,18 +258,30 @@ impl SoundDevice {
         let mut merge_data: Option<i32> = None;
         let mut merge_reg = None;
         for (_, data_stream) in self.data_stream.iter_mut() {
-            if let Some((data_block_id, data_block_pos, _write_port, write_reg)) =
+            if let Some((data_block_id, data_block_pos, write_address, data_width)) =
                 data_stream.tick()
             {
-                if let Some(data_block) = data_block.get(&data_block_id) {
-                    let data = *data_block.get_data_block().get(data_block_pos).unwrap();
+                // read data width bytes (little endian)
+                if let Some(data) = data_block
+                    .get(&data_block_id)
+                    .and_then(|data_block| {
+                        data_block
+                            .get_data_block()
+                            .get(data_block_pos..data_block_pos + data_width)
+                    })
+                    .map(|data| {
+                        data.iter()
+                            .rev()
+                            .fold(0_u32, |data, &byte| data << 8 | byte as u32)
+                    })
+                {
                     match self.data_stream_mode {
                         DataStreamMode::Parallel => {
                             // write stream command each data stream
                             self.sound_chip.write(
                                 sound_chip_index,
-                                write_reg,
-                                data as u32,
+                                write_address,
+                                data,
                                 &mut *self.sound_stream,
                             )
                         }
@@ -240,7 +289,7 @@ impl SoundDevice {
                             // merge stream as YM3012 format pcm data
                             let data = data as i8;
                             merge_data = Some(data as i32 + merge_data.unwrap_or_default());
-                            merge_reg = Some(write_reg);
+                            merge_reg = Some(write_address);
                         }
                     }
                 }
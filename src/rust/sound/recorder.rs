@@ -0,0 +1,162 @@
+// license:BSD-3-Clause
+// copyright-holders:Hiromasa Tanaka
+use super::rom::{RomBusType, RomIndex};
+use super::SoundChipType;
+
+///
+/// Sampling rate of the record position (VGM sample)
+///
+pub const RECORD_SAMPLING_RATE: u32 = 44100;
+
+///
+/// Recorded sound slot event
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SoundEvent {
+    Write {
+        sound_chip_type: SoundChipType,
+        sound_chip_index: usize,
+        port: u32,
+        data: u32,
+    },
+    AddRom {
+        sound_chip_type: SoundChipType,
+        sound_chip_index: usize,
+        rom_index: RomIndex,
+        memory: Vec<u8>,
+        start_address: usize,
+    },
+    SetRomBusType {
+        sound_chip_type: SoundChipType,
+        sound_chip_index: usize,
+        rom_bus_type: Option<RomBusType>,
+    },
+    AddDataBlock {
+        data_block_id: usize,
+        data_block: Vec<u8>,
+    },
+    AddDataStream {
+        sound_chip_type: SoundChipType,
+        sound_chip_index: usize,
+        data_stream_id: usize,
+        write_port: u32,
+        write_reg: u32,
+    },
+    SetDataStreamFrequency {
+        sound_chip_type: SoundChipType,
+        sound_chip_index: usize,
+        data_stream_id: usize,
+        frequency: u32,
+    },
+    AttachDataBlockToStream {
+        sound_chip_type: SoundChipType,
+        sound_chip_index: usize,
+        data_stream_id: usize,
+        data_block_id: usize,
+        step_base: usize,
+        step_size: usize,
+    },
+    StartDataStream {
+        sound_chip_type: SoundChipType,
+        sound_chip_index: usize,
+        data_stream_id: usize,
+        data_block_start_offset: Option<usize>,
+        length_mode: u8,
+        length: usize,
+    },
+    StartDataStreamFast {
+        sound_chip_type: SoundChipType,
+        sound_chip_index: usize,
+        data_stream_id: usize,
+        data_block_id: usize,
+        flags: u8,
+    },
+    StopDataStream {
+        sound_chip_type: SoundChipType,
+        sound_chip_index: usize,
+        data_stream_id: usize,
+    },
+}
+
+///
+/// Recorded sound slot log
+///
+#[derive(Clone, Debug, Default)]
+pub struct SoundLog {
+    /// sound chip type, number of chips and clock in the order they were added
+    pub sound_device: Vec<(SoundChipType, usize, u32)>,
+    /// record position (VGM sample) and event
+    pub event: Vec<(u64, SoundEvent)>,
+    /// event index and record position where the loop starts
+    pub loop_point: Option<(usize, u64)>,
+    /// record length (VGM sample)
+    pub total_samples: u64,
+}
+
+///
+/// Sound slot recorder
+///
+pub struct SoundRecorder {
+    position: f64,
+    position_step: f64,
+    sound_log: SoundLog,
+}
+
+impl SoundRecorder {
+    pub fn new(external_tick_rate: u32) -> Self {
+        SoundRecorder {
+            position: 0_f64,
+            position_step: RECORD_SAMPLING_RATE as f64 / external_tick_rate as f64,
+            sound_log: SoundLog::default(),
+        }
+    }
+
+    ///
+    /// Advance the record position by external ticks.
+    ///
+    pub fn update(&mut self, tick_count: usize) {
+        self.position += self.position_step * tick_count as f64;
+    }
+
+    ///
+    /// Change external tick rate.
+    ///
+    pub fn change_external_tick_rate(&mut self, external_tick_rate: u32) {
+        self.position_step = RECORD_SAMPLING_RATE as f64 / external_tick_rate as f64;
+    }
+
+    ///
+    /// Record event at the current position.
+    ///
+    pub fn record(&mut self, sound_event: SoundEvent) {
+        let position = self.get_position();
+        self.sound_log.event.push((position, sound_event));
+    }
+
+    ///
+    /// Set loop point at the current position.
+    ///
+    pub fn set_loop_point(&mut self) {
+        self.sound_log.loop_point = Some(self.get_record_point());
+    }
+
+    ///
+    /// Get the next event index and the current position.
+    ///
+    pub fn get_record_point(&self) -> (usize, u64) {
+        (self.sound_log.event.len(), self.get_position())
+    }
+
+    ///
+    /// Finish recording and return the log.
+    ///
+    pub fn finish(mut self, sound_device: &[(SoundChipType, usize, u32)]) -> SoundLog {
+        self.sound_log.total_samples = self.get_position();
+        self.sound_log.sound_device = sound_device.to_vec();
+        self.sound_log
+    }
+
+    fn get_position(&self) -> u64 {
+        self.position as u64
+    }
+}
@@ -13,10 +13,13 @@ pub enum RomIndex {
     YM2610_ADPCM = 0x82,
     YM2610_DELTA_T = 0x83,
     YMF278B_ROM = 0x84,
+    YMF271_ROM = 0x85,
     YMF278B_RAM = 0x87,
     Y8950_ROM = 0x88,
     OKIM6295_ROM = 0x8b,
     C140_ROM = 0x8d,
+    SCSP_RAM = 0xe0,
+    YM2608_RHYTHM = 0xf0,
     NOT_SUPPOTED = 0xff,
 }
 
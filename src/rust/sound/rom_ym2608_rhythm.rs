@@ -0,0 +1,196 @@
+// license:BSD-3-Clause
+/**
+ * Synthesized YM2608 internal rhythm ROM
+ *
+ * This is not a dump of the real ROM. Each instrument is made with simple
+ * oscillators and noise, and encoded to ADPCM-A in the same layout as the
+ * YM2608 internal ROM (BD, SD, TOP, HH, TOM, RIM).
+ */
+use std::f64::consts::PI;
+
+///
+/// Rhythm ROM size
+///
+pub const YM2608_RHYTHM_ROM_SIZE: usize = 0x2000;
+
+///
+/// Playback sampling rate of the rhythm (8MHz / 144 / 3)
+///
+const SAMPLING_RATE: f64 = 8_000_000_f64 / 144_f64 / 3_f64;
+
+///
+/// Instrument address (start, end) fixed in YM2608
+///
+const RHYTHM_ADDRESS: [(usize, usize); 6] = [
+    (0x0000, 0x01bf), // BD
+    (0x01c0, 0x043f), // SD
+    (0x0440, 0x1b7f), // TOP
+    (0x1b80, 0x1cff), // HH
+    (0x1d00, 0x1f7f), // TOM
+    (0x1f80, 0x1fff), // RIM
+];
+
+///
+/// ADPCM-A step table
+///
+const STEPS: [i32; 49] = [
+    16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66, 73, 80, 88, 97, 107, 118, 130,
+    143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449, 494, 544, 598, 658, 724, 796,
+    876, 963, 1060, 1166, 1282, 1411, 1552,
+];
+
+///
+/// ADPCM-A step index increment
+///
+const STEP_INC: [i32; 8] = [-1, -1, -1, -1, 2, 5, 7, 9];
+
+///
+/// Create synthesized rhythm ROM.
+///
+pub fn create_ym2608_rhythm_rom() -> Vec<u8> {
+    let mut rom: Vec<u8> = vec![0; YM2608_RHYTHM_ROM_SIZE];
+    let mut noise = Noise::new();
+    for (instrument, (start, end)) in RHYTHM_ADDRESS.iter().enumerate() {
+        let length = (end - start + 1) * 2;
+        let pcm: Vec<f64> = (0..length)
+            .map(|i| {
+                let t = i as f64 / SAMPLING_RATE;
+                match instrument {
+                    0 => bass_drum(t),
+                    1 => snare_drum(t, noise.sample()),
+                    2 => top_cymbal(t, noise.sample()),
+                    3 => hihat(t, noise.sample()),
+                    4 => tom(t),
+                    _ => rim_shot(t),
+                }
+            })
+            .collect();
+        rom[*start..=*end].copy_from_slice(&encode_adpcm_a(&pcm));
+    }
+    rom
+}
+
+fn bass_drum(t: f64) -> f64 {
+    // 120Hz to 50Hz pitch sweep
+    let phase = 2_f64 * PI * (50_f64 * t + 70_f64 * (1_f64 - (-t * 40_f64).exp()) / 40_f64);
+    phase.sin() * (-t * 60_f64).exp()
+}
+
+fn snare_drum(t: f64, noise: f64) -> f64 {
+    let tone = (2_f64 * PI * 180_f64 * t).sin() * (-t * 50_f64).exp();
+    (tone * 0.5 + noise * 0.6) * (-t * 35_f64).exp()
+}
+
+fn top_cymbal(t: f64, noise: f64) -> f64 {
+    // inharmonic square waves
+    let metal: f64 = [3140_f64, 4230_f64, 5470_f64, 6820_f64]
+        .iter()
+        .map(|f| if (f * t).fract() < 0.5 { 0.25 } else { -0.25 })
+        .sum();
+    (metal * 0.4 + noise * 0.6) * (-t * 5_f64).exp()
+}
+
+fn hihat(t: f64, noise: f64) -> f64 {
+    noise * 0.7 * (-t * 70_f64).exp()
+}
+
+fn tom(t: f64) -> f64 {
+    // 200Hz to 140Hz pitch sweep
+    let phase = 2_f64 * PI * (140_f64 * t + 60_f64 * (1_f64 - (-t * 20_f64).exp()) / 20_f64);
+    phase.sin() * (-t * 25_f64).exp()
+}
+
+fn rim_shot(t: f64) -> f64 {
+    (2_f64 * PI * 1700_f64 * t).sin() * (-t * 180_f64).exp()
+}
+
+///
+/// Encode PCM (-1.0 to 1.0) to ADPCM-A (high nibble first).
+///
+fn encode_adpcm_a(pcm: &[f64]) -> Vec<u8> {
+    let mut accumulator: i32 = 0;
+    let mut step_index: i32 = 0;
+    let mut nibbles: Vec<u8> = Vec::with_capacity(pcm.len());
+    for sample in pcm {
+        let target = (sample.clamp(-1_f64, 1_f64) * 2047_f64) as i32;
+        // choose the nibble closest to target with the same calculation as the decoder
+        let (nibble, next_accumulator) = (0..16_u8)
+            .map(|nibble| (nibble, decode_nibble(accumulator, step_index, nibble)))
+            .min_by_key(|(_, next)| (to_signed(*next) - target).abs())
+            .unwrap();
+        accumulator = next_accumulator;
+        step_index = (step_index + STEP_INC[(nibble & 7) as usize]).clamp(0, 48);
+        nibbles.push(nibble);
+    }
+    nibbles
+        .chunks(2)
+        .map(|nibble| (nibble[0] << 4) | nibble.get(1).unwrap_or(&0))
+        .collect()
+}
+
+fn decode_nibble(accumulator: i32, step_index: i32, nibble: u8) -> i32 {
+    let mut delta = (2 * (nibble & 7) as i32 + 1) * STEPS[step_index as usize] / 8;
+    if nibble & 8 != 0 {
+        delta = -delta;
+    }
+    (accumulator + delta) & 0xfff
+}
+
+fn to_signed(accumulator: i32) -> i32 {
+    (accumulator << 20) >> 20
+}
+
+///
+/// Deterministic white noise
+///
+struct Noise {
+    state: u32,
+}
+
+impl Noise {
+    fn new() -> Self {
+        Noise { state: 0x1234_5678 }
+    }
+
+    fn sample(&mut self) -> f64 {
+        // xorshift32
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state as f64 / u32::MAX as f64 * 2_f64 - 1_f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rom_layout() {
+        let rom = create_ym2608_rhythm_rom();
+        assert_eq!(rom.len(), YM2608_RHYTHM_ROM_SIZE);
+        for (start, end) in RHYTHM_ADDRESS.iter() {
+            assert!(rom[*start..=*end].iter().any(|data| *data != 0));
+        }
+    }
+
+    #[test]
+    fn encode_decode() {
+        let pcm: Vec<f64> = (0..256)
+            .map(|i| (2_f64 * PI * i as f64 / 64_f64).sin() * 0.5)
+            .collect();
+        let adpcm = encode_adpcm_a(&pcm);
+        assert_eq!(adpcm.len(), 128);
+        // decode like ymfm adpcm_a_channel
+        let mut accumulator: i32 = 0;
+        let mut step_index: i32 = 0;
+        for (i, data) in adpcm.iter().enumerate() {
+            for (j, nibble) in [data >> 4, data & 0x0f].iter().enumerate() {
+                accumulator = decode_nibble(accumulator, step_index, *nibble);
+                step_index = (step_index + STEP_INC[(nibble & 7) as usize]).clamp(0, 48);
+                let expect = (pcm[i * 2 + j] * 2047_f64) as i32;
+                assert!((to_signed(accumulator) - expect).abs() < 256);
+            }
+        }
+    }
+}
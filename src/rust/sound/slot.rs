@@ -3,16 +3,24 @@
 use std::cmp::Ordering;
 use std::collections::{HashMap, VecDeque};
 
+use super::chip_ay8910::AY8910;
 use super::chip_c140::{C140, C219};
+use super::chip_k051649::K051649;
 use super::chip_okim6258::OKIM6258;
 use super::chip_okim6295::OKIM6295;
 use super::chip_pwm::PWM;
+use super::chip_scsp::SCSP;
 use super::chip_segapcm::SEGAPCM;
 use super::chip_sn76496::SN76496;
+use super::chip_vsu::VSU;
+use super::chip_ymf271::YMF271;
 use super::chip_ymfm::YmFm;
 use super::data_stream::{DataBlock, DataStream};
 use super::device::{DataStreamMode, SoundDevice};
+use super::recorder::{SoundEvent, SoundLog, SoundRecorder};
 use super::rom::{RomBusType, RomIndex};
+#[cfg(feature = "ym2608-rhythm-fallback")]
+use super::rom_ym2608_rhythm::create_ym2608_rhythm_rom;
 use super::sound_chip::SoundChip;
 use super::stream::{
     convert_sample_f2i, LinearUpSamplingStream, NativeStream, NearestDownSampleStream,
@@ -24,6 +32,7 @@ use super::SoundChipType;
 /// Sound Slot
 ///
 pub struct SoundSlot {
+    external_tick_rate: u32,
     output_sampling_rate: u32,
     output_sampling_pos: f64,
     output_sampling_step: f64,
@@ -31,10 +40,13 @@ pub struct SoundSlot {
     output_sampling_l: Vec<f32>,
     output_sampling_r: Vec<f32>,
     output_sampling_s16le: Vec<i16>,
+    output_sampling_length: usize,
     output_sampling_buffer_l: VecDeque<f32>,
     output_sampling_buffer_r: VecDeque<f32>,
     sound_device: HashMap<SoundChipType, Vec<SoundDevice>>,
     data_block: HashMap<usize, DataBlock>,
+    sound_device_clock: Vec<(SoundChipType, usize, u32)>,
+    sound_recorder: Option<SoundRecorder>,
 }
 
 impl SoundSlot {
@@ -45,6 +57,7 @@ impl SoundSlot {
     ) -> Self {
         assert!(output_sampling_rate >= external_tick_rate);
         SoundSlot {
+            external_tick_rate,
             output_sampling_rate,
             output_sampling_pos: 0_f64,
             output_sampling_step: external_tick_rate as f64 / output_sampling_rate as f64,
@@ -52,10 +65,13 @@ impl SoundSlot {
             output_sampling_l: vec![0_f32; output_sample_chunk_size],
             output_sampling_r: vec![0_f32; output_sample_chunk_size],
             output_sampling_s16le: vec![0; output_sample_chunk_size * 2],
+            output_sampling_length: 0,
             output_sampling_buffer_l: VecDeque::with_capacity(output_sample_chunk_size * 2),
             output_sampling_buffer_r: VecDeque::with_capacity(output_sample_chunk_size * 2),
             sound_device: HashMap::new(),
             data_block: HashMap::new(),
+            sound_device_clock: Vec::new(),
+            sound_recorder: None,
         }
     }
 
@@ -68,6 +84,8 @@ impl SoundSlot {
         number_of: usize,
         clock: u32,
     ) {
+        self.sound_device_clock
+            .push((sound_chip_type, number_of, clock));
         for _ in 0..number_of {
             // create sound device
             let (mut sound_chip, rom_index): (Box<dyn SoundChip>, Option<Vec<RomIndex>>) =
@@ -83,10 +101,18 @@ impl SoundSlot {
                     | SoundChipType::Y8950
                     | SoundChipType::YM3812
                     | SoundChipType::YMF262
-                    | SoundChipType::YMF278B => {
+                    | SoundChipType::YMF278B
+                    | SoundChipType::YM2610B
+                    | SoundChipType::YM3438
+                    | SoundChipType::YMF288
+                    | SoundChipType::YM2164
+                    | SoundChipType::YMF289B => {
                         let rom_index: Option<Vec<RomIndex>> = match sound_chip_type {
-                            SoundChipType::YM2608 => Some(vec![RomIndex::YM2608_DELTA_T]),
-                            SoundChipType::YM2610 => {
+                            SoundChipType::YM2608 => {
+                                Some(vec![RomIndex::YM2608_DELTA_T, RomIndex::YM2608_RHYTHM])
+                            }
+                            SoundChipType::YMF288 => Some(vec![RomIndex::YM2608_RHYTHM]),
+                            SoundChipType::YM2610 | SoundChipType::YM2610B => {
                                 Some(vec![RomIndex::YM2610_ADPCM, RomIndex::YM2610_DELTA_T])
                             }
                             SoundChipType::Y8950 => Some(vec![RomIndex::Y8950_ROM]),
@@ -123,6 +149,21 @@ impl SoundSlot {
                         Box::new(OKIM6295::create(SoundChipType::OKIM6295)),
                         Some(vec![RomIndex::OKIM6295_ROM]),
                     ),
+                    SoundChipType::K051649 | SoundChipType::K052539 => {
+                        (Box::new(K051649::create(sound_chip_type)), None)
+                    }
+                    SoundChipType::YMF271 => (
+                        Box::new(YMF271::create(SoundChipType::YMF271)),
+                        Some(vec![RomIndex::YMF271_ROM]),
+                    ),
+                    SoundChipType::SCSP => (
+                        Box::new(SCSP::create(SoundChipType::SCSP)),
+                        Some(vec![RomIndex::SCSP_RAM]),
+                    ),
+                    SoundChipType::VSU => (Box::new(VSU::create(SoundChipType::VSU)), None),
+                    SoundChipType::AY8910 => {
+                        (Box::new(AY8910::create(SoundChipType::AY8910)), None)
+                    }
                 };
 
             // initialize sound chip
@@ -132,7 +173,12 @@ impl SoundSlot {
                 match sound_chip_sampling_rate.cmp(&self.output_sampling_rate) {
                     Ordering::Equal => Box::new(NativeStream::new()),
                     Ordering::Greater => match sound_chip_type {
-                        SoundChipType::SEGAPSG | SoundChipType::SN76489 | SoundChipType::PWM => {
+                        SoundChipType::SEGAPSG
+                        | SoundChipType::SN76489
+                        | SoundChipType::PWM
+                        | SoundChipType::K051649
+                        | SoundChipType::K052539
+                        | SoundChipType::AY8910 => {
                             Box::new(OverSampleStream::new(
                                 sound_chip_sampling_rate,
                                 self.output_sampling_rate,
@@ -162,6 +208,17 @@ impl SoundSlot {
                 .entry(sound_chip_type)
                 .or_insert_with(Vec::new)
                 .push(SoundDevice::new(sound_chip, sound_stream, rom_index));
+            // synthesized rhythm ROM (overwritten if the real ROM is added)
+            #[cfg(feature = "ym2608-rhythm-fallback")]
+            if let SoundChipType::YM2608 | SoundChipType::YMF288 = sound_chip_type {
+                let rom = create_ym2608_rhythm_rom();
+                self.sound_device
+                    .get_mut(&sound_chip_type)
+                    .unwrap()
+                    .last_mut()
+                    .unwrap()
+                    .add_rom(RomIndex::YM2608_RHYTHM, &rom, 0, rom.len() - 1);
+            }
         }
     }
 
@@ -175,16 +232,41 @@ impl SoundSlot {
         port: u32,
         data: u32,
     ) {
+        if let Some(sound_recorder) = self.sound_recorder.as_mut() {
+            sound_recorder.record(SoundEvent::Write {
+                sound_chip_type,
+                sound_chip_index,
+                port,
+                data,
+            });
+        }
         match self.find_sound_device(sound_chip_type, sound_chip_index) {
             None => { /* nothing to do */ }
             Some(sound_device) => sound_device.write(sound_chip_index, port, data),
         }
     }
 
+    ///
+    /// Write command to sound chip with 16-bit memory offset.
+    /// (offset bit 15 selects the sound chip index)
+    ///
+    pub fn write_offset16(&mut self, sound_chip_type: SoundChipType, offset: u16, data: u32) {
+        let sound_chip_index = (offset >> 15) as usize;
+        self.write(
+            sound_chip_type,
+            sound_chip_index,
+            u32::from(offset & 0x7fff),
+            data,
+        );
+    }
+
     ///
     /// Update sound chip.
     ///
     pub fn update(&mut self, tick_count: usize) {
+        if let Some(sound_recorder) = self.sound_recorder.as_mut() {
+            sound_recorder.update(tick_count);
+        }
         for _ in 0..tick_count {
             while self.output_sampling_pos < 1_f64 {
                 self.output_sampling_buffer_l.push_back(0_f32);
@@ -238,6 +320,17 @@ impl SoundSlot {
         {
             self.output_sampling_r[i] = val;
         }
+        self.output_sampling_length = chunk_size;
+    }
+
+    ///
+    /// Return the sampling of the last streamed chunk (shorter than the chunk size at the end).
+    ///
+    pub(crate) fn get_output_sampling(&self) -> (&[f32], &[f32]) {
+        (
+            &self.output_sampling_l[..self.output_sampling_length],
+            &self.output_sampling_r[..self.output_sampling_length],
+        )
     }
 
     ///
@@ -291,6 +384,18 @@ impl SoundSlot {
         start_address: usize,
         end_address: usize,
     ) {
+        if let Some(sound_recorder) = self.sound_recorder.as_mut() {
+            let end = memory
+                .len()
+                .min(end_address.saturating_sub(start_address) + 1);
+            sound_recorder.record(SoundEvent::AddRom {
+                sound_chip_type,
+                sound_chip_index,
+                rom_index,
+                memory: memory[..end].to_vec(),
+                start_address,
+            });
+        }
         if let Some(sound_device) = self.find_sound_device(sound_chip_type, sound_chip_index) {
             sound_device.add_rom(rom_index, memory, start_address, end_address);
         }
@@ -306,6 +411,13 @@ impl SoundSlot {
         _rom_index: RomIndex,
         rom_bus_type: Option<RomBusType>,
     ) {
+        if let Some(sound_recorder) = self.sound_recorder.as_mut() {
+            sound_recorder.record(SoundEvent::SetRomBusType {
+                sound_chip_type,
+                sound_chip_index,
+                rom_bus_type,
+            });
+        }
         if let Some(sound_device) = self.find_sound_device(sound_chip_type, sound_chip_index) {
             sound_device.set_rom_bus_type(rom_bus_type);
         }
@@ -315,6 +427,12 @@ impl SoundSlot {
     /// Add data bank for stream data.
     ///
     pub fn add_data_block(&mut self, data_block_id: usize, data_block: &[u8]) {
+        if let Some(sound_recorder) = self.sound_recorder.as_mut() {
+            sound_recorder.record(SoundEvent::AddDataBlock {
+                data_block_id,
+                data_block: data_block.to_vec(),
+            });
+        }
         self.data_block
             .insert(data_block_id, DataBlock::new(data_block));
     }
@@ -322,8 +440,10 @@ impl SoundSlot {
     ///
     /// Get data block borrow
     ///
-    pub fn get_data_block(&self, data_block_id: usize) -> &[u8] {
-        self.data_block.get(&data_block_id).unwrap(/* TODO */).get_data_block()
+    pub fn get_data_block(&self, data_block_id: usize) -> Option<&[u8]> {
+        self.data_block
+            .get(&data_block_id)
+            .map(|data_block| data_block.get_data_block())
     }
 
     ///
@@ -337,8 +457,30 @@ impl SoundSlot {
         write_port: u32,
         write_reg: u32,
     ) {
+        if let Some(sound_recorder) = self.sound_recorder.as_mut() {
+            sound_recorder.record(SoundEvent::AddDataStream {
+                sound_chip_type,
+                sound_chip_index,
+                data_stream_id,
+                write_port,
+                write_reg,
+            });
+        }
+        // bytes per write command
+        let data_width = match sound_chip_type {
+            SoundChipType::PWM => 2, /* 12bit */
+            _ => 1,
+        };
+        // PWM register is the low nibble of the command (as VGMPlay DAC stream control)
+        let (write_port, write_reg) = match sound_chip_type {
+            SoundChipType::PWM => (0, write_reg & 0x0f),
+            _ => (write_port, write_reg),
+        };
         if let Some(sound_device) = self.find_sound_device(sound_chip_type, sound_chip_index) {
-            sound_device.add_data_stream(data_stream_id, DataStream::new(write_port, write_reg));
+            sound_device.add_data_stream(
+                data_stream_id,
+                DataStream::new(write_port, write_reg, data_width),
+            );
         }
     }
 
@@ -366,6 +508,14 @@ impl SoundSlot {
         data_stream_id: usize,
         frequency: u32,
     ) {
+        if let Some(sound_recorder) = self.sound_recorder.as_mut() {
+            sound_recorder.record(SoundEvent::SetDataStreamFrequency {
+                sound_chip_type,
+                sound_chip_index,
+                data_stream_id,
+                frequency,
+            });
+        }
         if let Some(sound_device) = self.find_sound_device(sound_chip_type, sound_chip_index) {
             sound_device.set_data_stream_frequency(data_stream_id, frequency);
         }
@@ -380,27 +530,70 @@ impl SoundSlot {
         sound_chip_index: usize,
         data_stream_id: usize,
         data_block_id: usize,
+        step_base: usize,
+        step_size: usize,
     ) {
+        if let Some(sound_recorder) = self.sound_recorder.as_mut() {
+            sound_recorder.record(SoundEvent::AttachDataBlockToStream {
+                sound_chip_type,
+                sound_chip_index,
+                data_stream_id,
+                data_block_id,
+                step_base,
+                step_size,
+            });
+        }
         if let Some(sound_device) = self.find_sound_device(sound_chip_type, sound_chip_index) {
-            sound_device.attach_data_block_to_stream(data_stream_id, data_block_id);
+            sound_device.attach_data_block_to_stream(
+                data_stream_id,
+                data_block_id,
+                step_base,
+                step_size,
+            );
         }
     }
 
     ///
     /// Start data stream
     ///
+    /// data_block_start_offset: None is keep current offset
+    /// length_mode: bit 0-3: 0 ignore, 1 commands, 2 msec, 3 until end of data, 0xf bytes
+    ///              bit 4: reverse, bit 7: loop
+    ///
     pub fn start_data_stream(
         &mut self,
         sound_chip_type: SoundChipType,
         sound_chip_index: usize,
         data_stream_id: usize,
-        data_block_start_offset: usize,
-        data_block_length: usize,
+        data_block_start_offset: Option<usize>,
+        length_mode: u8,
+        length: usize,
     ) {
+        if let Some(sound_recorder) = self.sound_recorder.as_mut() {
+            sound_recorder.record(SoundEvent::StartDataStream {
+                sound_chip_type,
+                sound_chip_index,
+                data_stream_id,
+                data_block_start_offset,
+                length_mode,
+                length,
+            });
+        }
+        // length of data block attached to data stream
+        let data_block_id = match self.find_sound_device(sound_chip_type, sound_chip_index) {
+            Some(sound_device) => sound_device.get_data_stream_block_id(data_stream_id),
+            None => None,
+        };
+        let mut data_block_length = 0;
+        if let Some(data_block) = data_block_id.and_then(|id| self.data_block.get(&id)) {
+            data_block_length = data_block.get_data_block().len();
+        }
         if let Some(sound_device) = self.find_sound_device(sound_chip_type, sound_chip_index) {
             sound_device.start_data_stream(
                 data_stream_id,
                 data_block_start_offset,
+                length_mode,
+                length,
                 data_block_length,
             );
         }
@@ -415,7 +608,17 @@ impl SoundSlot {
         sound_chip_index: usize,
         data_stream_id: usize,
         data_block_id: usize,
+        flags: u8,
     ) {
+        if let Some(sound_recorder) = self.sound_recorder.as_mut() {
+            sound_recorder.record(SoundEvent::StartDataStreamFast {
+                sound_chip_type,
+                sound_chip_index,
+                data_stream_id,
+                data_block_id,
+                flags,
+            });
+        }
         let mut data_block_length = None;
         if let Some(data_block) = self.data_block.get(&data_block_id) {
             data_block_length = Some(data_block.get_data_block().len());
@@ -425,6 +628,7 @@ impl SoundSlot {
                 sound_device.start_data_stream_fast(
                     data_stream_id,
                     data_block_id,
+                    flags,
                     data_block_length,
                 );
             }
@@ -440,6 +644,13 @@ impl SoundSlot {
         sound_chip_index: usize,
         data_stream_id: usize,
     ) {
+        if let Some(sound_recorder) = self.sound_recorder.as_mut() {
+            sound_recorder.record(SoundEvent::StopDataStream {
+                sound_chip_type,
+                sound_chip_index,
+                data_stream_id,
+            });
+        }
         if let Some(sound_device) = self.find_sound_device(sound_chip_type, sound_chip_index) {
             sound_device.stop_data_stream(data_stream_id);
         }
@@ -466,7 +677,57 @@ impl SoundSlot {
     /// This only works correctly when the sound slot is in its initial state.
     ///
     pub fn change_external_tick_rate(&mut self, external_tick_rate: u32) {
+        self.external_tick_rate = external_tick_rate;
         self.output_sampling_step = external_tick_rate as f64 / self.output_sampling_rate as f64;
+        if let Some(sound_recorder) = self.sound_recorder.as_mut() {
+            sound_recorder.change_external_tick_rate(external_tick_rate);
+        }
+    }
+
+    ///
+    /// Start recording of writes, ROMs, data blocks and data streams.
+    ///
+    /// Data blocks already added are recorded at the start position.
+    /// ROMs and data streams must be added after recording starts.
+    ///
+    pub fn start_record(&mut self) {
+        let mut sound_recorder = SoundRecorder::new(self.external_tick_rate);
+        let mut data_block_id: Vec<&usize> = self.data_block.keys().collect();
+        data_block_id.sort();
+        for data_block_id in data_block_id {
+            sound_recorder.record(SoundEvent::AddDataBlock {
+                data_block_id: *data_block_id,
+                data_block: self.data_block[data_block_id].get_data_block().to_vec(),
+            });
+        }
+        self.sound_recorder = Some(sound_recorder);
+    }
+
+    ///
+    /// Set loop point of the recording at the current position.
+    ///
+    pub fn set_record_loop_point(&mut self) {
+        if let Some(sound_recorder) = self.sound_recorder.as_mut() {
+            sound_recorder.set_loop_point();
+        }
+    }
+
+    ///
+    /// Get the record point (next event index and position) to set a loop point later.
+    ///
+    pub fn get_record_point(&self) -> Option<(usize, u64)> {
+        self.sound_recorder
+            .as_ref()
+            .map(|sound_recorder| sound_recorder.get_record_point())
+    }
+
+    ///
+    /// Stop recording and return the recorded log.
+    ///
+    pub fn stop_record(&mut self) -> Option<SoundLog> {
+        self.sound_recorder
+            .take()
+            .map(|sound_recorder| sound_recorder.finish(&self.sound_device_clock))
     }
 
     ///
@@ -481,7 +742,7 @@ impl SoundSlot {
         let sound_device = match self.sound_device.get_mut(&sound_chip_type) {
             None => None,
             Some(vec) => {
-                if vec.len() < sound_chip_index {
+                if vec.len() <= sound_chip_index {
                     return None;
                 }
                 Some(vec)
@@ -493,3 +754,70 @@ impl SoundSlot {
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::SoundSlot;
+    use crate::sound::SoundChipType;
+
+    ///
+    /// Sound slot of the sound chip (output 44100Hz)
+    ///
+    pub(crate) fn sound_slot(sound_chip_type: SoundChipType, clock: u32) -> SoundSlot {
+        let mut sound_slot = SoundSlot::new(44100, 44100, 4410);
+        sound_slot.add_sound_device(sound_chip_type, 1, clock);
+        sound_slot
+    }
+
+    ///
+    /// Generate 1/10 sec and return the peak level of the sampling
+    ///
+    pub(crate) fn generate(sound_slot: &mut SoundSlot) -> f32 {
+        sound_slot.update(4410);
+        sound_slot.stream();
+        let (l, r) = sampling(sound_slot);
+        l.iter().chain(r.iter()).fold(0_f32, |peak, sample| {
+            assert!(sample.is_finite());
+            peak.max(sample.abs())
+        })
+    }
+
+    ///
+    /// Sampling of the last generated 1/10 sec
+    ///
+    pub(crate) fn sampling(sound_slot: &SoundSlot) -> (&[f32], &[f32]) {
+        (&sound_slot.output_sampling_l, &sound_slot.output_sampling_r)
+    }
+
+    ///
+    /// Frequency (Hz) of the 1/10 sec sampling from the rising edges across the mean
+    ///
+    pub(crate) fn frequency(sampling: &[f32]) -> usize {
+        // median of 3 samples (the over sample stream has one sample spikes)
+        let sampling: Vec<f32> = sampling
+            .windows(3)
+            .map(|w| {
+                let mut w = [w[0], w[1], w[2]];
+                w.sort_by(f32::total_cmp);
+                w[1]
+            })
+            .collect();
+        let mean = sampling.iter().sum::<f32>() / sampling.len() as f32;
+        let crossing = sampling
+            .windows(2)
+            .filter(|w| w[0] < mean && w[1] >= mean)
+            .count();
+        crossing * 10
+    }
+
+    ///
+    /// Assert the frequency within the 1/10 sec edge count error
+    ///
+    pub(crate) fn assert_frequency(sampling: &[f32], expected: f32) {
+        let frequency = frequency(sampling) as f32;
+        assert!(
+            (frequency - expected).abs() <= 20_f32,
+            "frequency {frequency} expected {expected}"
+        );
+    }
+}
@@ -31,6 +31,17 @@ pub enum SoundChipType {
     C140,
     C219,
     OKIM6295,
+    K051649,
+    K052539,
+    YMF271,
+    SCSP,
+    VSU,
+    YM2610B,
+    YM3438,
+    YMF288,
+    YM2164,
+    YMF289B,
+    AY8910,
 }
 
 ///
@@ -4,7 +4,10 @@ use std::cell::RefCell;
 use std::rc::Rc;
 
 use crate::{
-    driver::{self, VgmPlay, XgmPlay},
+    driver::{
+        self, DroPlay, GymPlay, ImfPlay, ImfRate, MdxPlay, PackReader, PlaylistPlay, S98Play, VDPMode, VgmPlay,
+        Xgm2Play, XgmPlay,
+    },
     sound::{RomBusType, RomIndex, SoundChipType, SoundSlot},
 };
 
@@ -21,6 +24,46 @@ std::thread_local!(static XGM_PLAY: XgmPlayBank = {
     Rc::new(RefCell::new(Vec::new()))
 });
 
+type Xgm2PlayBank = Rc<RefCell<Vec<Xgm2Play>>>;
+std::thread_local!(static XGM2_PLAY: Xgm2PlayBank = {
+    Rc::new(RefCell::new(Vec::new()))
+});
+
+type GymPlayBank = Rc<RefCell<Vec<GymPlay>>>;
+std::thread_local!(static GYM_PLAY: GymPlayBank = {
+    Rc::new(RefCell::new(Vec::new()))
+});
+
+type S98PlayBank = Rc<RefCell<Vec<S98Play>>>;
+std::thread_local!(static S98_PLAY: S98PlayBank = {
+    Rc::new(RefCell::new(Vec::new()))
+});
+
+type DroPlayBank = Rc<RefCell<Vec<DroPlay>>>;
+std::thread_local!(static DRO_PLAY: DroPlayBank = {
+    Rc::new(RefCell::new(Vec::new()))
+});
+
+type ImfPlayBank = Rc<RefCell<Vec<ImfPlay>>>;
+std::thread_local!(static IMF_PLAY: ImfPlayBank = {
+    Rc::new(RefCell::new(Vec::new()))
+});
+
+type MdxPlayBank = Rc<RefCell<Vec<MdxPlay>>>;
+std::thread_local!(static MDX_PLAY: MdxPlayBank = {
+    Rc::new(RefCell::new(Vec::new()))
+});
+
+type PlaylistPlayBank = Rc<RefCell<Vec<PlaylistPlay>>>;
+std::thread_local!(static PLAYLIST_PLAY: PlaylistPlayBank = {
+    Rc::new(RefCell::new(Vec::new()))
+});
+
+type PackReaderBank = Rc<RefCell<Vec<PackReader>>>;
+std::thread_local!(static PACK_READER: PackReaderBank = {
+    Rc::new(RefCell::new(Vec::new()))
+});
+
 type SoundSlotBank = Rc<RefCell<Vec<SoundSlot>>>;
 std::thread_local!(static SOUND_SLOT: SoundSlotBank = {
     Rc::new(RefCell::new(Vec::new()))
@@ -42,6 +85,38 @@ fn get_xgm_bank() -> XgmPlayBank {
     XGM_PLAY.with(|rc| rc.clone())
 }
 
+fn get_xgm2_bank() -> Xgm2PlayBank {
+    XGM2_PLAY.with(|rc| rc.clone())
+}
+
+fn get_gym_bank() -> GymPlayBank {
+    GYM_PLAY.with(|rc| rc.clone())
+}
+
+fn get_s98_bank() -> S98PlayBank {
+    S98_PLAY.with(|rc| rc.clone())
+}
+
+fn get_dro_bank() -> DroPlayBank {
+    DRO_PLAY.with(|rc| rc.clone())
+}
+
+fn get_imf_bank() -> ImfPlayBank {
+    IMF_PLAY.with(|rc| rc.clone())
+}
+
+fn get_mdx_bank() -> MdxPlayBank {
+    MDX_PLAY.with(|rc| rc.clone())
+}
+
+fn get_playlist_bank() -> PlaylistPlayBank {
+    PLAYLIST_PLAY.with(|rc| rc.clone())
+}
+
+fn get_pack_bank() -> PackReaderBank {
+    PACK_READER.with(|rc| rc.clone())
+}
+
 fn get_sound_slot_bank() -> SoundSlotBank {
     SOUND_SLOT.with(|rc| rc.clone())
 }
@@ -124,7 +199,24 @@ pub extern "C" fn xgm_create(
     output_sample_chunk_size: u32,
     memory_index_id: u32,
 ) -> bool {
-    let xgmplay = XgmPlay::new(
+    xgm_create_with_vdp_mode(
+        xgm_index_id,
+        output_sampling_rate,
+        output_sample_chunk_size,
+        memory_index_id,
+        0,
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn xgm_create_with_vdp_mode(
+    xgm_index_id: u32,
+    output_sampling_rate: u32,
+    output_sample_chunk_size: u32,
+    memory_index_id: u32,
+    vdp_mode: u32,
+) -> bool {
+    let xgmplay = XgmPlay::new_with_vdp_mode(
         SoundSlot::new(
             driver::XGM_NTSC_TICK_RATE,
             output_sampling_rate,
@@ -134,6 +226,8 @@ pub extern "C" fn xgm_create(
             .borrow_mut()
             .get(memory_index_id as usize)
             .unwrap(),
+        // 60: NTSC, 50: PAL, others: header flags
+        VDPMode::from_frame_rate(vdp_mode),
     );
     if xgmplay.is_err() {
         return false;
@@ -144,6 +238,247 @@ pub extern "C" fn xgm_create(
     true
 }
 
+#[no_mangle]
+pub extern "C" fn xgm2_create(
+    xgm2_index_id: u32,
+    output_sampling_rate: u32,
+    output_sample_chunk_size: u32,
+    memory_index_id: u32,
+) -> bool {
+    xgm2_create_with_vdp_mode(
+        xgm2_index_id,
+        output_sampling_rate,
+        output_sample_chunk_size,
+        memory_index_id,
+        0,
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn xgm2_create_with_vdp_mode(
+    xgm2_index_id: u32,
+    output_sampling_rate: u32,
+    output_sample_chunk_size: u32,
+    memory_index_id: u32,
+    vdp_mode: u32,
+) -> bool {
+    let xgm2play = Xgm2Play::new_with_vdp_mode(
+        SoundSlot::new(
+            driver::XGM_NTSC_TICK_RATE,
+            output_sampling_rate,
+            output_sample_chunk_size as usize,
+        ),
+        get_memory_bank()
+            .borrow_mut()
+            .get(memory_index_id as usize)
+            .unwrap(),
+        // 60: NTSC, 50: PAL, others: header flags
+        VDPMode::from_frame_rate(vdp_mode),
+    );
+    if xgm2play.is_err() {
+        return false;
+    }
+    get_xgm2_bank()
+        .borrow_mut()
+        .insert(xgm2_index_id as usize, xgm2play.unwrap());
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn gym_create(
+    gym_index_id: u32,
+    output_sampling_rate: u32,
+    output_sample_chunk_size: u32,
+    memory_index_id: u32,
+) -> bool {
+    let gymplay = GymPlay::new(
+        SoundSlot::new(
+            driver::GYM_TICK_RATE,
+            output_sampling_rate,
+            output_sample_chunk_size as usize,
+        ),
+        get_memory_bank()
+            .borrow_mut()
+            .get(memory_index_id as usize)
+            .unwrap(),
+    );
+    if gymplay.is_err() {
+        return false;
+    }
+    get_gym_bank()
+        .borrow_mut()
+        .insert(gym_index_id as usize, gymplay.unwrap());
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn s98_create(
+    s98_index_id: u32,
+    output_sampling_rate: u32,
+    output_sample_chunk_size: u32,
+    memory_index_id: u32,
+) -> bool {
+    let s98play = S98Play::new(
+        SoundSlot::new(
+            driver::S98_TICK_RATE,
+            output_sampling_rate,
+            output_sample_chunk_size as usize,
+        ),
+        get_memory_bank()
+            .borrow_mut()
+            .get(memory_index_id as usize)
+            .unwrap(),
+    );
+    if s98play.is_err() {
+        return false;
+    }
+    get_s98_bank()
+        .borrow_mut()
+        .insert(s98_index_id as usize, s98play.unwrap());
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn dro_create(
+    dro_index_id: u32,
+    output_sampling_rate: u32,
+    output_sample_chunk_size: u32,
+    memory_index_id: u32,
+) -> bool {
+    let droplay = DroPlay::new(
+        SoundSlot::new(
+            driver::DRO_TICK_RATE,
+            output_sampling_rate,
+            output_sample_chunk_size as usize,
+        ),
+        get_memory_bank()
+            .borrow_mut()
+            .get(memory_index_id as usize)
+            .unwrap(),
+    );
+    if droplay.is_err() {
+        return false;
+    }
+    get_dro_bank()
+        .borrow_mut()
+        .insert(dro_index_id as usize, droplay.unwrap());
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn imf_create(
+    imf_index_id: u32,
+    output_sampling_rate: u32,
+    output_sample_chunk_size: u32,
+    memory_index_id: u32,
+) -> bool {
+    imf_create_with_rate(
+        imf_index_id,
+        output_sampling_rate,
+        output_sample_chunk_size,
+        memory_index_id,
+        0,
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn imf_create_with_rate(
+    imf_index_id: u32,
+    output_sampling_rate: u32,
+    output_sample_chunk_size: u32,
+    memory_index_id: u32,
+    imf_rate: u32,
+) -> bool {
+    let imfplay = ImfPlay::new_with_rate(
+        SoundSlot::new(
+            driver::IMF_TICK_RATE,
+            output_sampling_rate,
+            output_sample_chunk_size as usize,
+        ),
+        get_memory_bank()
+            .borrow_mut()
+            .get(memory_index_id as usize)
+            .unwrap(),
+        // 280, 560 or 700, others: 560
+        ImfRate::from_tick_rate(imf_rate),
+    );
+    if imfplay.is_err() {
+        return false;
+    }
+    get_imf_bank()
+        .borrow_mut()
+        .insert(imf_index_id as usize, imfplay.unwrap());
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn mdx_create(
+    mdx_index_id: u32,
+    output_sampling_rate: u32,
+    output_sample_chunk_size: u32,
+    memory_index_id: u32,
+) -> bool {
+    let mdxplay = MdxPlay::new(
+        SoundSlot::new(
+            driver::MDX_TICK_RATE,
+            output_sampling_rate,
+            output_sample_chunk_size as usize,
+        ),
+        get_memory_bank()
+            .borrow_mut()
+            .get(memory_index_id as usize)
+            .unwrap(),
+    );
+    if mdxplay.is_err() {
+        return false;
+    }
+    get_mdx_bank()
+        .borrow_mut()
+        .insert(mdx_index_id as usize, mdxplay.unwrap());
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn playlist_create(
+    playlist_index_id: u32,
+    output_sampling_rate: u32,
+    output_sample_chunk_size: u32,
+    memory_index_id: u32,
+) -> bool {
+    let playlistplay = PlaylistPlay::new(
+        output_sampling_rate,
+        output_sample_chunk_size as usize,
+        get_memory_bank()
+            .borrow_mut()
+            .get(memory_index_id as usize)
+            .unwrap(),
+    );
+    if playlistplay.is_err() {
+        return false;
+    }
+    get_playlist_bank()
+        .borrow_mut()
+        .insert(playlist_index_id as usize, playlistplay.unwrap());
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn pack_create(pack_index_id: u32, memory_index_id: u32) -> bool {
+    let packreader = PackReader::new(
+        get_memory_bank()
+            .borrow_mut()
+            .get(memory_index_id as usize)
+            .unwrap(),
+    );
+    if packreader.is_err() {
+        return false;
+    }
+    get_pack_bank()
+        .borrow_mut()
+        .insert(pack_index_id as usize, packreader.unwrap());
+    true
+}
+
 #[no_mangle]
 pub extern "C" fn sound_slot_create(
     sound_slot_index_id: u32,
@@ -353,7 +688,7 @@ pub extern "C" fn sound_slot_get_data_block(
         .get_mut(sounde_slot_index as usize)
         .unwrap()
         .get_data_block(data_block_id as usize)
-        .as_ptr()
+        .map_or(std::ptr::null(), |data_block| data_block.as_ptr())
 }
 
 #[no_mangle]
@@ -404,6 +739,8 @@ pub extern "C" fn sound_slot_attach_data_block_to_stream(
     sound_chip_index: u32,
     data_stream_id: u32,
     data_block_id: u32,
+    step_base: u32,
+    step_size: u32,
 ) {
     get_sound_slot_bank()
         .borrow_mut()
@@ -414,6 +751,8 @@ pub extern "C" fn sound_slot_attach_data_block_to_stream(
             sound_chip_index as usize,
             data_stream_id as usize,
             data_block_id as usize,
+            step_base as usize,
+            step_size as usize,
         );
 }
 
@@ -424,8 +763,15 @@ pub extern "C" fn sound_slot_start_data_stream(
     sound_chip_index: u32,
     data_stream_id: u32,
     data_block_start_offset: u32,
+    length_mode: u32,
     data_block_length: u32,
 ) {
+    // 0xffffffff means don't change the data start offset
+    let data_block_start_offset = if data_block_start_offset == 0xffffffff {
+        None
+    } else {
+        Some(data_block_start_offset as usize)
+    };
     get_sound_slot_bank()
         .borrow_mut()
         .get_mut(sounde_slot_index as usize)
@@ -434,7 +780,8 @@ pub extern "C" fn sound_slot_start_data_stream(
             get_sound_chip_type(sound_chip_type),
             sound_chip_index as usize,
             data_stream_id as usize,
-            data_block_start_offset as usize,
+            data_block_start_offset,
+            length_mode as u8,
             data_block_length as usize,
         );
 }
@@ -446,6 +793,7 @@ pub extern "C" fn sound_slot_start_data_stream_fast(
     sound_chip_index: u32,
     data_stream_id: u32,
     data_block_id: u32,
+    flags: u32,
 ) {
     get_sound_slot_bank()
         .borrow_mut()
@@ -456,6 +804,7 @@ pub extern "C" fn sound_slot_start_data_stream_fast(
             sound_chip_index as usize,
             data_stream_id as usize,
             data_block_id as usize,
+            flags as u8,
         );
 }
 
@@ -495,6 +844,63 @@ pub extern "C" fn sound_slot_is_stop_data_stream(
         )
 }
 
+#[no_mangle]
+pub extern "C" fn sound_slot_start_record(sounde_slot_index: u32) {
+    get_sound_slot_bank()
+        .borrow_mut()
+        .get_mut(sounde_slot_index as usize)
+        .unwrap()
+        .start_record();
+}
+
+#[no_mangle]
+pub extern "C" fn sound_slot_set_record_loop_point(sounde_slot_index: u32) {
+    get_sound_slot_bank()
+        .borrow_mut()
+        .get_mut(sounde_slot_index as usize)
+        .unwrap()
+        .set_record_loop_point();
+}
+
+#[no_mangle]
+pub extern "C" fn sound_slot_stop_record(sounde_slot_index: u32, gd3_memory_index_id: u32) -> u32 {
+    let sound_log = get_sound_slot_bank()
+        .borrow_mut()
+        .get_mut(sounde_slot_index as usize)
+        .unwrap()
+        .stop_record()
+        .unwrap_or_default();
+    // GD3 tag from UTF-8 json (empty tag if not parsable)
+    let gd3: driver::Gd3 = get_memory_bank()
+        .borrow()
+        .get(gd3_memory_index_id as usize)
+        .and_then(|json| serde_json::from_slice(json).ok())
+        .unwrap_or_default();
+    // empty memory if the record is not representable in VGM
+    let vgm = driver::create_vgm(&sound_log, &gd3).unwrap_or_default();
+    // VGM file into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, vgm);
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn vgm_add_ym2608_rhythm_rom(vgm_index_id: u32, memory_index_id: u32) {
+    get_vgm_bank()
+        .borrow_mut()
+        .get_mut(vgm_index_id as usize)
+        .unwrap()
+        .add_ym2608_rhythm_rom(
+            get_memory_bank()
+                .borrow_mut()
+                .get(memory_index_id as usize)
+                .unwrap(),
+        );
+}
+
 #[no_mangle]
 pub extern "C" fn vgm_get_sampling_l_ref(vgm_index_id: u32) -> *const f32 {
     get_vgm_bank()
@@ -555,14 +961,57 @@ pub extern "C" fn vgm_get_gd3_json(vgm_index_id: u32) -> u32 {
 }
 
 #[no_mangle]
-pub extern "C" fn xgm_get_sampling_l_ref(xgm_index_id: u32) -> *const f32 {
-    get_xgm_bank()
+pub extern "C" fn vgm_get_analysis_json(vgm_index_id: u32) -> u32 {
+    let json = get_vgm_bank()
         .borrow_mut()
-        .get_mut(xgm_index_id as usize)
+        .get_mut(vgm_index_id as usize)
         .unwrap()
-        .get_sampling_l_ref()
-}
-
+        .get_vgm_analysis_json();
+    // UTF-8 json into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, json.into_bytes());
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn vgm_update_gd3_json(vgm_index_id: u32, gd3_memory_index_id: u32) -> u32 {
+    // UTF-8 json from memory
+    let json = String::from_utf8(
+        get_memory_bank()
+            .borrow()
+            .get(gd3_memory_index_id as usize)
+            .unwrap()
+            .clone(),
+    )
+    .unwrap_or_default();
+    // empty memory if the VGM file cannot be updated
+    let vgm = get_vgm_bank()
+        .borrow_mut()
+        .get_mut(vgm_index_id as usize)
+        .unwrap()
+        .update_gd3_json(&json)
+        .unwrap_or_default();
+    // VGM file into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, vgm);
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn xgm_get_sampling_l_ref(xgm_index_id: u32) -> *const f32 {
+    get_xgm_bank()
+        .borrow_mut()
+        .get_mut(xgm_index_id as usize)
+        .unwrap()
+        .get_sampling_l_ref()
+}
+
 #[no_mangle]
 pub extern "C" fn xgm_get_sampling_r_ref(xgm_index_id: u32) -> *const f32 {
     get_xgm_bank()
@@ -613,6 +1062,581 @@ pub extern "C" fn xgm_get_gd3_json(xgm_index_id: u32) -> u32 {
     memory_index_id
 }
 
+#[no_mangle]
+pub extern "C" fn xgm_track_count(xgm_index_id: u32) -> u32 {
+    get_xgm_bank()
+        .borrow_mut()
+        .get_mut(xgm_index_id as usize)
+        .unwrap()
+        .track_count() as u32
+}
+
+#[no_mangle]
+pub extern "C" fn xgm_select_track(xgm_index_id: u32, track: u32) -> bool {
+    get_xgm_bank()
+        .borrow_mut()
+        .get_mut(xgm_index_id as usize)
+        .unwrap()
+        .select_track(track as usize)
+        .is_ok()
+}
+
+#[no_mangle]
+pub extern "C" fn xgm_convert_vgm(xgm_index_id: u32) -> u32 {
+    // empty memory if the XGM file cannot be converted
+    let vgm = get_xgm_bank()
+        .borrow_mut()
+        .get_mut(xgm_index_id as usize)
+        .unwrap()
+        .convert_vgm()
+        .unwrap_or_default();
+    // VGM file into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, vgm);
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn xgm2_get_sampling_l_ref(xgm2_index_id: u32) -> *const f32 {
+    get_xgm2_bank()
+        .borrow_mut()
+        .get_mut(xgm2_index_id as usize)
+        .unwrap()
+        .get_sampling_l_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn xgm2_get_sampling_r_ref(xgm2_index_id: u32) -> *const f32 {
+    get_xgm2_bank()
+        .borrow_mut()
+        .get_mut(xgm2_index_id as usize)
+        .unwrap()
+        .get_sampling_r_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn xgm2_get_sampling_s16le_ref(xgm2_index_id: u32) -> *const i16 {
+    get_xgm2_bank()
+        .borrow_mut()
+        .get_mut(xgm2_index_id as usize)
+        .unwrap()
+        .get_output_sampling_s16le_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn xgm2_get_header_json(xgm2_index_id: u32) -> u32 {
+    let json = get_xgm2_bank()
+        .borrow_mut()
+        .get_mut(xgm2_index_id as usize)
+        .unwrap()
+        .get_xgm2_header_json();
+    // UTF-8 json into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, json.into_bytes());
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn xgm2_get_gd3_json(xgm2_index_id: u32) -> u32 {
+    let json = get_xgm2_bank()
+        .borrow_mut()
+        .get_mut(xgm2_index_id as usize)
+        .unwrap()
+        .get_xgm2_gd3_json();
+    // UTF-8 json into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, json.into_bytes());
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn xgm2_track_count(xgm2_index_id: u32) -> u32 {
+    get_xgm2_bank()
+        .borrow_mut()
+        .get_mut(xgm2_index_id as usize)
+        .unwrap()
+        .track_count() as u32
+}
+
+#[no_mangle]
+pub extern "C" fn xgm2_select_track(xgm2_index_id: u32, track: u32) -> bool {
+    get_xgm2_bank()
+        .borrow_mut()
+        .get_mut(xgm2_index_id as usize)
+        .unwrap()
+        .select_track(track as usize)
+        .is_ok()
+}
+
+#[no_mangle]
+pub extern "C" fn gym_get_sampling_l_ref(gym_index_id: u32) -> *const f32 {
+    get_gym_bank()
+        .borrow_mut()
+        .get_mut(gym_index_id as usize)
+        .unwrap()
+        .get_sampling_l_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn gym_get_sampling_r_ref(gym_index_id: u32) -> *const f32 {
+    get_gym_bank()
+        .borrow_mut()
+        .get_mut(gym_index_id as usize)
+        .unwrap()
+        .get_sampling_r_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn gym_get_sampling_s16le_ref(gym_index_id: u32) -> *const i16 {
+    get_gym_bank()
+        .borrow_mut()
+        .get_mut(gym_index_id as usize)
+        .unwrap()
+        .get_output_sampling_s16le_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn gym_get_header_json(gym_index_id: u32) -> u32 {
+    let json = get_gym_bank()
+        .borrow_mut()
+        .get_mut(gym_index_id as usize)
+        .unwrap()
+        .get_gym_header_json();
+    // UTF-8 json into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, json.into_bytes());
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn gym_get_gd3_json(gym_index_id: u32) -> u32 {
+    let json = get_gym_bank()
+        .borrow_mut()
+        .get_mut(gym_index_id as usize)
+        .unwrap()
+        .get_gym_gd3_json();
+    // UTF-8 json into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, json.into_bytes());
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn s98_add_ym2608_rhythm_rom(s98_index_id: u32, memory_index_id: u32) {
+    get_s98_bank()
+        .borrow_mut()
+        .get_mut(s98_index_id as usize)
+        .unwrap()
+        .add_ym2608_rhythm_rom(
+            get_memory_bank()
+                .borrow_mut()
+                .get(memory_index_id as usize)
+                .unwrap(),
+        );
+}
+
+#[no_mangle]
+pub extern "C" fn s98_get_sampling_l_ref(s98_index_id: u32) -> *const f32 {
+    get_s98_bank()
+        .borrow_mut()
+        .get_mut(s98_index_id as usize)
+        .unwrap()
+        .get_sampling_l_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn s98_get_sampling_r_ref(s98_index_id: u32) -> *const f32 {
+    get_s98_bank()
+        .borrow_mut()
+        .get_mut(s98_index_id as usize)
+        .unwrap()
+        .get_sampling_r_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn s98_get_sampling_s16le_ref(s98_index_id: u32) -> *const i16 {
+    get_s98_bank()
+        .borrow_mut()
+        .get_mut(s98_index_id as usize)
+        .unwrap()
+        .get_output_sampling_s16le_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn s98_get_header_json(s98_index_id: u32) -> u32 {
+    let json = get_s98_bank()
+        .borrow_mut()
+        .get_mut(s98_index_id as usize)
+        .unwrap()
+        .get_s98_header_json();
+    // UTF-8 json into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, json.into_bytes());
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn s98_get_gd3_json(s98_index_id: u32) -> u32 {
+    let json = get_s98_bank()
+        .borrow_mut()
+        .get_mut(s98_index_id as usize)
+        .unwrap()
+        .get_s98_gd3_json();
+    // UTF-8 json into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, json.into_bytes());
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn dro_get_sampling_l_ref(dro_index_id: u32) -> *const f32 {
+    get_dro_bank()
+        .borrow_mut()
+        .get_mut(dro_index_id as usize)
+        .unwrap()
+        .get_sampling_l_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn dro_get_sampling_r_ref(dro_index_id: u32) -> *const f32 {
+    get_dro_bank()
+        .borrow_mut()
+        .get_mut(dro_index_id as usize)
+        .unwrap()
+        .get_sampling_r_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn dro_get_sampling_s16le_ref(dro_index_id: u32) -> *const i16 {
+    get_dro_bank()
+        .borrow_mut()
+        .get_mut(dro_index_id as usize)
+        .unwrap()
+        .get_output_sampling_s16le_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn dro_get_header_json(dro_index_id: u32) -> u32 {
+    let json = get_dro_bank()
+        .borrow_mut()
+        .get_mut(dro_index_id as usize)
+        .unwrap()
+        .get_dro_header_json();
+    // UTF-8 json into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, json.into_bytes());
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn dro_get_gd3_json(dro_index_id: u32) -> u32 {
+    let json = get_dro_bank()
+        .borrow_mut()
+        .get_mut(dro_index_id as usize)
+        .unwrap()
+        .get_dro_gd3_json();
+    // UTF-8 json into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, json.into_bytes());
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn imf_get_sampling_l_ref(imf_index_id: u32) -> *const f32 {
+    get_imf_bank()
+        .borrow_mut()
+        .get_mut(imf_index_id as usize)
+        .unwrap()
+        .get_sampling_l_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn imf_get_sampling_r_ref(imf_index_id: u32) -> *const f32 {
+    get_imf_bank()
+        .borrow_mut()
+        .get_mut(imf_index_id as usize)
+        .unwrap()
+        .get_sampling_r_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn imf_get_sampling_s16le_ref(imf_index_id: u32) -> *const i16 {
+    get_imf_bank()
+        .borrow_mut()
+        .get_mut(imf_index_id as usize)
+        .unwrap()
+        .get_output_sampling_s16le_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn imf_get_header_json(imf_index_id: u32) -> u32 {
+    let json = get_imf_bank()
+        .borrow_mut()
+        .get_mut(imf_index_id as usize)
+        .unwrap()
+        .get_imf_header_json();
+    // UTF-8 json into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, json.into_bytes());
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn imf_get_gd3_json(imf_index_id: u32) -> u32 {
+    let json = get_imf_bank()
+        .borrow_mut()
+        .get_mut(imf_index_id as usize)
+        .unwrap()
+        .get_imf_gd3_json();
+    // UTF-8 json into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, json.into_bytes());
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn mdx_add_pdx(mdx_index_id: u32, memory_index_id: u32) -> bool {
+    get_mdx_bank()
+        .borrow_mut()
+        .get_mut(mdx_index_id as usize)
+        .unwrap()
+        .add_pdx(
+            get_memory_bank()
+                .borrow_mut()
+                .get(memory_index_id as usize)
+                .unwrap(),
+        )
+        .is_ok()
+}
+
+#[no_mangle]
+pub extern "C" fn mdx_get_sampling_l_ref(mdx_index_id: u32) -> *const f32 {
+    get_mdx_bank()
+        .borrow_mut()
+        .get_mut(mdx_index_id as usize)
+        .unwrap()
+        .get_sampling_l_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn mdx_get_sampling_r_ref(mdx_index_id: u32) -> *const f32 {
+    get_mdx_bank()
+        .borrow_mut()
+        .get_mut(mdx_index_id as usize)
+        .unwrap()
+        .get_sampling_r_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn mdx_get_sampling_s16le_ref(mdx_index_id: u32) -> *const i16 {
+    get_mdx_bank()
+        .borrow_mut()
+        .get_mut(mdx_index_id as usize)
+        .unwrap()
+        .get_output_sampling_s16le_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn mdx_get_header_json(mdx_index_id: u32) -> u32 {
+    let json = get_mdx_bank()
+        .borrow_mut()
+        .get_mut(mdx_index_id as usize)
+        .unwrap()
+        .get_mdx_header_json();
+    // UTF-8 json into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, json.into_bytes());
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn mdx_get_gd3_json(mdx_index_id: u32) -> u32 {
+    let json = get_mdx_bank()
+        .borrow_mut()
+        .get_mut(mdx_index_id as usize)
+        .unwrap()
+        .get_mdx_gd3_json();
+    // UTF-8 json into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, json.into_bytes());
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn playlist_add_file(
+    playlist_index_id: u32,
+    file_name_memory_index_id: u32,
+    memory_index_id: u32,
+) {
+    let memory_bank = get_memory_bank();
+    let memory_bank = memory_bank.borrow();
+    // UTF-8 file name as written in the playlist
+    let file_name =
+        String::from_utf8_lossy(memory_bank.get(file_name_memory_index_id as usize).unwrap());
+    get_playlist_bank()
+        .borrow_mut()
+        .get_mut(playlist_index_id as usize)
+        .unwrap()
+        .add_file(&file_name, memory_bank.get(memory_index_id as usize).unwrap());
+}
+
+#[no_mangle]
+pub extern "C" fn playlist_add_ym2608_rhythm_rom(playlist_index_id: u32, memory_index_id: u32) {
+    get_playlist_bank()
+        .borrow_mut()
+        .get_mut(playlist_index_id as usize)
+        .unwrap()
+        .add_ym2608_rhythm_rom(
+            get_memory_bank()
+                .borrow_mut()
+                .get(memory_index_id as usize)
+                .unwrap(),
+        );
+}
+
+#[no_mangle]
+pub extern "C" fn playlist_set_default_loop_count(playlist_index_id: u32, loop_count: u32) {
+    get_playlist_bank()
+        .borrow_mut()
+        .get_mut(playlist_index_id as usize)
+        .unwrap()
+        .set_default_loop_count(loop_count as usize);
+}
+
+#[no_mangle]
+pub extern "C" fn playlist_set_default_fade(playlist_index_id: u32, fade_ms: u32) {
+    get_playlist_bank()
+        .borrow_mut()
+        .get_mut(playlist_index_id as usize)
+        .unwrap()
+        .set_default_fade(fade_ms);
+}
+
+#[no_mangle]
+pub extern "C" fn playlist_get_sampling_l_ref(playlist_index_id: u32) -> *const f32 {
+    get_playlist_bank()
+        .borrow_mut()
+        .get_mut(playlist_index_id as usize)
+        .unwrap()
+        .get_sampling_l_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn playlist_get_sampling_r_ref(playlist_index_id: u32) -> *const f32 {
+    get_playlist_bank()
+        .borrow_mut()
+        .get_mut(playlist_index_id as usize)
+        .unwrap()
+        .get_sampling_r_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn playlist_get_sampling_s16le_ref(playlist_index_id: u32) -> *const i16 {
+    get_playlist_bank()
+        .borrow_mut()
+        .get_mut(playlist_index_id as usize)
+        .unwrap()
+        .get_output_sampling_s16le_ref()
+}
+
+#[no_mangle]
+pub extern "C" fn playlist_get_json(playlist_index_id: u32) -> u32 {
+    let json = get_playlist_bank()
+        .borrow_mut()
+        .get_mut(playlist_index_id as usize)
+        .unwrap()
+        .get_playlist_json();
+    // UTF-8 json into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, json.into_bytes());
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn playlist_get_track_index(playlist_index_id: u32) -> u32 {
+    get_playlist_bank()
+        .borrow_mut()
+        .get_mut(playlist_index_id as usize)
+        .unwrap()
+        .get_track_index() as u32
+}
+
+#[no_mangle]
+pub extern "C" fn pack_get_json(pack_index_id: u32) -> u32 {
+    let json = get_pack_bank()
+        .borrow_mut()
+        .get_mut(pack_index_id as usize)
+        .unwrap()
+        .get_pack_json();
+    // UTF-8 json into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, json.into_bytes());
+    // return memory index id
+    memory_index_id
+}
+
+#[no_mangle]
+pub extern "C" fn pack_read_entry(pack_index_id: u32, entry_index: u32) -> u32 {
+    // empty memory if the entry is broken (the memory is passed to vgm_create/xgm_create)
+    let file = get_pack_bank()
+        .borrow_mut()
+        .get_mut(pack_index_id as usize)
+        .unwrap()
+        .read_entry(entry_index as usize)
+        .unwrap_or_default();
+    // file into allocate memory
+    let memory_index_id = memory_get_alloc_len();
+    get_memory_bank()
+        .borrow_mut()
+        .insert(memory_index_id as usize, file);
+    // return memory index id
+    memory_index_id
+}
+
 #[no_mangle]
 pub extern "C" fn vgm_play(vgm_index_id: u32) -> usize {
     get_vgm_bank()
@@ -631,6 +1655,69 @@ pub extern "C" fn xgm_play(xgm_index_id: u32) -> usize {
         .play(true)
 }
 
+#[no_mangle]
+pub extern "C" fn xgm2_play(xgm2_index_id: u32) -> usize {
+    get_xgm2_bank()
+        .borrow_mut()
+        .get_mut(xgm2_index_id as usize)
+        .unwrap()
+        .play(true)
+}
+
+#[no_mangle]
+pub extern "C" fn gym_play(gym_index_id: u32) -> usize {
+    get_gym_bank()
+        .borrow_mut()
+        .get_mut(gym_index_id as usize)
+        .unwrap()
+        .play(true)
+}
+
+#[no_mangle]
+pub extern "C" fn s98_play(s98_index_id: u32) -> usize {
+    get_s98_bank()
+        .borrow_mut()
+        .get_mut(s98_index_id as usize)
+        .unwrap()
+        .play(true)
+}
+
+#[no_mangle]
+pub extern "C" fn dro_play(dro_index_id: u32) -> usize {
+    get_dro_bank()
+        .borrow_mut()
+        .get_mut(dro_index_id as usize)
+        .unwrap()
+        .play(true)
+}
+
+#[no_mangle]
+pub extern "C" fn imf_play(imf_index_id: u32) -> usize {
+    get_imf_bank()
+        .borrow_mut()
+        .get_mut(imf_index_id as usize)
+        .unwrap()
+        .play(true)
+}
+
+#[no_mangle]
+pub extern "C" fn mdx_play(mdx_index_id: u32) -> usize {
+    get_mdx_bank()
+        .borrow_mut()
+        .get_mut(mdx_index_id as usize)
+        .unwrap()
+        .play(true)
+}
+
+#[no_mangle]
+pub extern "C" fn playlist_play(playlist_index_id: u32) -> usize {
+    get_playlist_bank()
+        .borrow_mut()
+        .get_mut(playlist_index_id as usize)
+        .unwrap()
+        .play()
+}
+
 #[no_mangle]
 pub extern "C" fn vgm_drop(vgm_index_id: u32) {
     get_vgm_bank().borrow_mut().remove(vgm_index_id as usize);
@@ -641,6 +1728,46 @@ pub extern "C" fn xgm_drop(xgm_index_id: u32) {
     get_xgm_bank().borrow_mut().remove(xgm_index_id as usize);
 }
 
+#[no_mangle]
+pub extern "C" fn xgm2_drop(xgm2_index_id: u32) {
+    get_xgm2_bank().borrow_mut().remove(xgm2_index_id as usize);
+}
+
+#[no_mangle]
+pub extern "C" fn gym_drop(gym_index_id: u32) {
+    get_gym_bank().borrow_mut().remove(gym_index_id as usize);
+}
+
+#[no_mangle]
+pub extern "C" fn s98_drop(s98_index_id: u32) {
+    get_s98_bank().borrow_mut().remove(s98_index_id as usize);
+}
+
+#[no_mangle]
+pub extern "C" fn dro_drop(dro_index_id: u32) {
+    get_dro_bank().borrow_mut().remove(dro_index_id as usize);
+}
+
+#[no_mangle]
+pub extern "C" fn imf_drop(imf_index_id: u32) {
+    get_imf_bank().borrow_mut().remove(imf_index_id as usize);
+}
+
+#[no_mangle]
+pub extern "C" fn mdx_drop(mdx_index_id: u32) {
+    get_mdx_bank().borrow_mut().remove(mdx_index_id as usize);
+}
+
+#[no_mangle]
+pub extern "C" fn playlist_drop(playlist_index_id: u32) {
+    get_playlist_bank().borrow_mut().remove(playlist_index_id as usize);
+}
+
+#[no_mangle]
+pub extern "C" fn pack_drop(pack_index_id: u32) {
+    get_pack_bank().borrow_mut().remove(pack_index_id as usize);
+}
+
 #[no_mangle]
 pub extern "C" fn sound_slot_drop(sound_slot_index_id: u32) {
     get_sound_slot_bank()
@@ -670,6 +1797,17 @@ fn get_sound_chip_type(sound_chip_type: u32) -> SoundChipType {
         17 => SoundChipType::C140,
         18 => SoundChipType::C219,
         19 => SoundChipType::OKIM6295,
+        20 => SoundChipType::K051649,
+        21 => SoundChipType::K052539,
+        22 => SoundChipType::YMF271,
+        23 => SoundChipType::SCSP,
+        24 => SoundChipType::VSU,
+        25 => SoundChipType::YM2610B,
+        26 => SoundChipType::YM3438,
+        27 => SoundChipType::YMF288,
+        28 => SoundChipType::YM2164,
+        29 => SoundChipType::YMF289B,
+        30 => SoundChipType::AY8910,
         _ => panic!("not supported sound chip type"),
     }
 }
@@ -685,6 +1823,9 @@ fn get_rom_index(rom_index: u32) -> RomIndex {
         6 => RomIndex::SEGAPCM_ROM,
         7 => RomIndex::OKIM6295_ROM,
         8 => RomIndex::C140_ROM,
+        9 => RomIndex::YMF271_ROM,
+        10 => RomIndex::SCSP_RAM,
+        11 => RomIndex::YM2608_RHYTHM,
         _ => panic!("not support rom index"),
     }
 }
@@ -85,6 +85,28 @@ impl VgmPlay {
         panic!("vgmplay instance not exsist");
     }
 
+    ///
+    /// Get the JSON analysis of the VGM command stream.
+    ///
+    pub fn get_seq_analysis(&self) -> String {
+        if let Some(vgmplay) = self.vgmplay.as_ref() {
+            return vgmplay.get_vgm_analysis_json();
+        }
+        panic!("vgmplay instance not exsist");
+    }
+
+    ///
+    /// Create the VGM file with the GD3 tag replaced by JSON.
+    ///
+    /// An empty array is returned if the VGM file cannot be updated.
+    ///
+    pub fn update_seq_gd3(&self, gd3_json: &str) -> Vec<u8> {
+        if let Some(vgmplay) = self.vgmplay.as_ref() {
+            return vgmplay.update_gd3_json(gd3_json).unwrap_or_default();
+        }
+        panic!("vgmplay instance not exsist");
+    }
+
     ///
     /// Initialize sound driver.
     ///
@@ -104,6 +126,17 @@ impl VgmPlay {
         true
     }
 
+    ///
+    /// Add YM2608 internal rhythm ROM.
+    ///
+    pub fn add_ym2608_rhythm_rom(&mut self, rom: &[u8]) {
+        if let Some(vgmplay) = self.vgmplay.as_mut() {
+            vgmplay.add_ym2608_rhythm_rom(rom);
+            return;
+        }
+        panic!("vgmplay instance not exsist");
+    }
+
     ///
     /// Continue playing until output_sample_chunk_size is satisfied.
     ///
@@ -124,6 +157,7 @@ pub struct XgmPlay {
     output_sample_chunk_size: usize,
     xgm_file: Vec<u8>,
     xgmplay: Option<driver::XgmPlay>,
+    vdp_mode: Option<driver::VDPMode>,
 }
 
 ///
@@ -148,9 +182,17 @@ impl XgmPlay {
             output_sample_chunk_size,
             xgm_file: vec![0; data_length],
             xgmplay: None,
+            vdp_mode: None,
         }
     }
 
+    ///
+    /// Force the VDP mode before init. (60: NTSC, 50: PAL, others: header flags)
+    ///
+    pub fn set_vdp_mode(&mut self, vdp_mode: u32) {
+        self.vdp_mode = driver::VDPMode::from_frame_rate(vdp_mode);
+    }
+
     ///
     /// Return xgmdata buffer referance.
     ///
@@ -198,17 +240,50 @@ impl XgmPlay {
         panic!("xgmplay instance not exsist");
     }
 
+    ///
+    /// Return number of tracks.
+    ///
+    pub fn track_count(&self) -> usize {
+        if let Some(xgmplay) = self.xgmplay.as_ref() {
+            return xgmplay.track_count();
+        }
+        panic!("xgmplay instance not exsist");
+    }
+
+    ///
+    /// Select track and play it from the beginning.
+    ///
+    pub fn select_track(&mut self, track: usize) -> bool {
+        if let Some(xgmplay) = self.xgmplay.as_mut() {
+            return xgmplay.select_track(track).is_ok();
+        }
+        panic!("xgmplay instance not exsist");
+    }
+
+    ///
+    /// Convert the selected track to VGM file. Call this before play.
+    ///
+    /// An empty array is returned if the XGM file cannot be converted.
+    ///
+    pub fn convert_vgm(&mut self) -> Vec<u8> {
+        if let Some(xgmplay) = self.xgmplay.as_mut() {
+            return xgmplay.convert_vgm().unwrap_or_default();
+        }
+        panic!("xgmplay instance not exsist");
+    }
+
     ///
     /// Initialize sound driver.
     ///
     pub fn init(&mut self) -> bool {
-        let xgmplay = driver::XgmPlay::new(
+        let xgmplay = driver::XgmPlay::new_with_vdp_mode(
             SoundSlot::new(
                 /* default */ driver::XGM_NTSC_TICK_RATE,
                 self.output_sampling_rate,
                 self.output_sample_chunk_size,
             ),
-            self.xgm_file.as_slice()
+            self.xgm_file.as_slice(),
+            self.vdp_mode,
         );
         if xgmplay.is_err() {
             return false;
@@ -230,3 +305,961 @@ impl XgmPlay {
         panic!("xgmplay instance not exsist");
     }
 }
+
+#[wasm_bindgen]
+pub struct Xgm2Play {
+    output_sampling_rate: u32,
+    output_sample_chunk_size: usize,
+    xgm2_file: Vec<u8>,
+    xgm2play: Option<driver::Xgm2Play>,
+    vdp_mode: Option<driver::VDPMode>,
+}
+
+///
+/// Xgm2Play WebAssembly Interface
+///
+#[wasm_bindgen]
+impl Xgm2Play {
+    ///
+    /// constructor
+    ///
+    #[wasm_bindgen(constructor)]
+    pub fn from(
+        output_sampling_rate: u32,
+        output_sample_chunk_size: usize,
+        data_length: usize,
+    ) -> Self {
+        #[cfg(feature = "console_error_panic_hook")]
+        console_error_panic_hook::set_once();
+
+        Xgm2Play {
+            output_sampling_rate,
+            output_sample_chunk_size,
+            xgm2_file: vec![0; data_length],
+            xgm2play: None,
+            vdp_mode: None,
+        }
+    }
+
+    ///
+    /// Force the VDP mode before init. (60: NTSC, 50: PAL, others: header flags)
+    ///
+    pub fn set_vdp_mode(&mut self, vdp_mode: u32) {
+        self.vdp_mode = driver::VDPMode::from_frame_rate(vdp_mode);
+    }
+
+    ///
+    /// Return xgm2data buffer referance.
+    ///
+    pub fn get_seq_data_ref(&mut self) -> *mut u8 {
+        self.xgm2_file.as_mut_ptr()
+    }
+
+    ///
+    /// Return sampling_l buffer referance.
+    ///
+    pub fn get_sampling_l_ref(&self) -> *const f32 {
+        if let Some(xgm2play) = self.xgm2play.as_ref() {
+            return xgm2play.get_sampling_l_ref();
+        }
+        panic!("xgm2play instance not exsist");
+    }
+
+    ///
+    /// Return sampling_r buffer referance.
+    ///
+    pub fn get_sampling_r_ref(&self) -> *const f32 {
+        if let Some(xgm2play) = self.xgm2play.as_ref() {
+            return xgm2play.get_sampling_r_ref();
+        }
+        panic!("xgm2play instance not exsist");
+    }
+
+    ///
+    /// Get the JSON parsed from the header of the XGM2 file.
+    ///
+    pub fn get_seq_header(&self) -> String {
+        if let Some(xgm2play) = self.xgm2play.as_ref() {
+            return xgm2play.get_xgm2_header_json();
+        }
+        panic!("xgm2play instance not exsist");
+    }
+
+    ///
+    /// Get the JSON parsed GD3 of the XGM2 file.
+    ///
+    pub fn get_seq_gd3(&self) -> String {
+        if let Some(xgm2play) = self.xgm2play.as_ref() {
+            return xgm2play.get_xgm2_gd3_json();
+        }
+        panic!("xgm2play instance not exsist");
+    }
+
+    ///
+    /// Return number of tracks.
+    ///
+    pub fn track_count(&self) -> usize {
+        if let Some(xgm2play) = self.xgm2play.as_ref() {
+            return xgm2play.track_count();
+        }
+        panic!("xgm2play instance not exsist");
+    }
+
+    ///
+    /// Select track and play it from the beginning.
+    ///
+    pub fn select_track(&mut self, track: usize) -> bool {
+        if let Some(xgm2play) = self.xgm2play.as_mut() {
+            return xgm2play.select_track(track).is_ok();
+        }
+        panic!("xgm2play instance not exsist");
+    }
+
+    ///
+    /// Initialize sound driver.
+    ///
+    pub fn init(&mut self) -> bool {
+        let xgm2play = driver::Xgm2Play::new_with_vdp_mode(
+            SoundSlot::new(
+                /* default */ driver::XGM_NTSC_TICK_RATE,
+                self.output_sampling_rate,
+                self.output_sample_chunk_size,
+            ),
+            self.xgm2_file.as_slice(),
+            self.vdp_mode,
+        );
+        if xgm2play.is_err() {
+            return false;
+        }
+        self.xgm2play = Some(xgm2play.unwrap());
+        true
+    }
+
+    ///
+    /// Continue playing until output_sample_chunk_size is satisfied.
+    ///
+    /// The number of times the song has been looped will be returned.
+    /// In the case of an infinite loop, the std::usize::MAX value is always returned.
+    ///
+    pub fn play(&mut self) -> usize {
+        if let Some(xgm2play) = self.xgm2play.as_mut() {
+            return xgm2play.play(true)
+        }
+        panic!("xgm2play instance not exsist");
+    }
+}
+
+#[wasm_bindgen]
+pub struct GymPlay {
+    output_sampling_rate: u32,
+    output_sample_chunk_size: usize,
+    gym_file: Vec<u8>,
+    gymplay: Option<driver::GymPlay>,
+}
+
+///
+/// GymPlay WebAssembly Interface
+///
+#[wasm_bindgen]
+impl GymPlay {
+    ///
+    /// constructor
+    ///
+    #[wasm_bindgen(constructor)]
+    pub fn from(
+        output_sampling_rate: u32,
+        output_sample_chunk_size: usize,
+        data_length: usize,
+    ) -> Self {
+        #[cfg(feature = "console_error_panic_hook")]
+        console_error_panic_hook::set_once();
+
+        GymPlay {
+            output_sampling_rate,
+            output_sample_chunk_size,
+            gym_file: vec![0; data_length],
+            gymplay: None,
+        }
+    }
+
+    ///
+    /// Return gymdata buffer referance.
+    ///
+    pub fn get_seq_data_ref(&mut self) -> *mut u8 {
+        self.gym_file.as_mut_ptr()
+    }
+
+    ///
+    /// Return sampling_l buffer referance.
+    ///
+    pub fn get_sampling_l_ref(&self) -> *const f32 {
+        if let Some(gymplay) = self.gymplay.as_ref() {
+            return gymplay.get_sampling_l_ref();
+        }
+        panic!("gymplay instance not exsist");
+    }
+
+    ///
+    /// Return sampling_r buffer referance.
+    ///
+    pub fn get_sampling_r_ref(&self) -> *const f32 {
+        if let Some(gymplay) = self.gymplay.as_ref() {
+            return gymplay.get_sampling_r_ref();
+        }
+        panic!("gymplay instance not exsist");
+    }
+
+    ///
+    /// Get the JSON parsed from the header of the GYM file.
+    ///
+    pub fn get_seq_header(&self) -> String {
+        if let Some(gymplay) = self.gymplay.as_ref() {
+            return gymplay.get_gym_header_json();
+        }
+        panic!("gymplay instance not exsist");
+    }
+
+    ///
+    /// Get the JSON parsed GD3 of the GYM file.
+    ///
+    pub fn get_seq_gd3(&self) -> String {
+        if let Some(gymplay) = self.gymplay.as_ref() {
+            return gymplay.get_gym_gd3_json();
+        }
+        panic!("gymplay instance not exsist");
+    }
+
+    ///
+    /// Initialize sound driver.
+    ///
+    pub fn init(&mut self) -> bool {
+        let gymplay = driver::GymPlay::new(
+            SoundSlot::new(
+                driver::GYM_TICK_RATE,
+                self.output_sampling_rate,
+                self.output_sample_chunk_size,
+            ),
+            self.gym_file.as_slice()
+        );
+        if gymplay.is_err() {
+            return false;
+        }
+        self.gymplay = Some(gymplay.unwrap());
+        true
+    }
+
+    ///
+    /// Continue playing until output_sample_chunk_size is satisfied.
+    ///
+    /// The number of times the song has been looped will be returned.
+    /// In the case of an infinite loop, the std::usize::MAX value is always returned.
+    ///
+    pub fn play(&mut self) -> usize {
+        if let Some(gymplay) = self.gymplay.as_mut() {
+            return gymplay.play(true)
+        }
+        panic!("gymplay instance not exsist");
+    }
+}
+
+#[wasm_bindgen]
+pub struct S98Play {
+    output_sampling_rate: u32,
+    output_sample_chunk_size: usize,
+    s98_file: Vec<u8>,
+    s98play: Option<driver::S98Play>,
+}
+
+///
+/// S98Play WebAssembly Interface
+///
+#[wasm_bindgen]
+impl S98Play {
+    ///
+    /// constructor
+    ///
+    #[wasm_bindgen(constructor)]
+    pub fn from(
+        output_sampling_rate: u32,
+        output_sample_chunk_size: usize,
+        data_length: usize,
+    ) -> Self {
+        #[cfg(feature = "console_error_panic_hook")]
+        console_error_panic_hook::set_once();
+
+        S98Play {
+            output_sampling_rate,
+            output_sample_chunk_size,
+            s98_file: vec![0; data_length],
+            s98play: None,
+        }
+    }
+
+    ///
+    /// Return s98data buffer referance.
+    ///
+    pub fn get_seq_data_ref(&mut self) -> *mut u8 {
+        self.s98_file.as_mut_ptr()
+    }
+
+    ///
+    /// Return sampling_l buffer referance.
+    ///
+    pub fn get_sampling_l_ref(&self) -> *const f32 {
+        if let Some(s98play) = self.s98play.as_ref() {
+            return s98play.get_sampling_l_ref();
+        }
+        panic!("s98play instance not exsist");
+    }
+
+    ///
+    /// Return sampling_r buffer referance.
+    ///
+    pub fn get_sampling_r_ref(&self) -> *const f32 {
+        if let Some(s98play) = self.s98play.as_ref() {
+            return s98play.get_sampling_r_ref();
+        }
+        panic!("s98play instance not exsist");
+    }
+
+    ///
+    /// Get the JSON parsed from the header of the S98 file.
+    ///
+    pub fn get_seq_header(&self) -> String {
+        if let Some(s98play) = self.s98play.as_ref() {
+            return s98play.get_s98_header_json();
+        }
+        panic!("s98play instance not exsist");
+    }
+
+    ///
+    /// Get the JSON parsed GD3 of the S98 file.
+    ///
+    pub fn get_seq_gd3(&self) -> String {
+        if let Some(s98play) = self.s98play.as_ref() {
+            return s98play.get_s98_gd3_json();
+        }
+        panic!("s98play instance not exsist");
+    }
+
+    ///
+    /// Add YM2608 internal rhythm ROM.
+    ///
+    pub fn add_ym2608_rhythm_rom(&mut self, rom: &[u8]) {
+        if let Some(s98play) = self.s98play.as_mut() {
+            s98play.add_ym2608_rhythm_rom(rom);
+            return;
+        }
+        panic!("s98play instance not exsist");
+    }
+
+    ///
+    /// Initialize sound driver.
+    ///
+    pub fn init(&mut self) -> bool {
+        let s98play = driver::S98Play::new(
+            SoundSlot::new(
+                driver::S98_TICK_RATE,
+                self.output_sampling_rate,
+                self.output_sample_chunk_size,
+            ),
+            self.s98_file.as_slice()
+        );
+        if s98play.is_err() {
+            return false;
+        }
+        self.s98play = Some(s98play.unwrap());
+        true
+    }
+
+    ///
+    /// Continue playing until output_sample_chunk_size is satisfied.
+    ///
+    /// The number of times the song has been looped will be returned.
+    /// In the case of an infinite loop, the std::usize::MAX value is always returned.
+    ///
+    pub fn play(&mut self) -> usize {
+        if let Some(s98play) = self.s98play.as_mut() {
+            return s98play.play(true)
+        }
+        panic!("s98play instance not exsist");
+    }
+}
+
+#[wasm_bindgen]
+pub struct DroPlay {
+    output_sampling_rate: u32,
+    output_sample_chunk_size: usize,
+    dro_file: Vec<u8>,
+    droplay: Option<driver::DroPlay>,
+}
+
+///
+/// DroPlay WebAssembly Interface
+///
+#[wasm_bindgen]
+impl DroPlay {
+    ///
+    /// constructor
+    ///
+    #[wasm_bindgen(constructor)]
+    pub fn from(
+        output_sampling_rate: u32,
+        output_sample_chunk_size: usize,
+        data_length: usize,
+    ) -> Self {
+        #[cfg(feature = "console_error_panic_hook")]
+        console_error_panic_hook::set_once();
+
+        DroPlay {
+            output_sampling_rate,
+            output_sample_chunk_size,
+            dro_file: vec![0; data_length],
+            droplay: None,
+        }
+    }
+
+    ///
+    /// Return drodata buffer referance.
+    ///
+    pub fn get_seq_data_ref(&mut self) -> *mut u8 {
+        self.dro_file.as_mut_ptr()
+    }
+
+    ///
+    /// Return sampling_l buffer referance.
+    ///
+    pub fn get_sampling_l_ref(&self) -> *const f32 {
+        if let Some(droplay) = self.droplay.as_ref() {
+            return droplay.get_sampling_l_ref();
+        }
+        panic!("droplay instance not exsist");
+    }
+
+    ///
+    /// Return sampling_r buffer referance.
+    ///
+    pub fn get_sampling_r_ref(&self) -> *const f32 {
+        if let Some(droplay) = self.droplay.as_ref() {
+            return droplay.get_sampling_r_ref();
+        }
+        panic!("droplay instance not exsist");
+    }
+
+    ///
+    /// Get the JSON parsed from the header of the DRO file.
+    ///
+    pub fn get_seq_header(&self) -> String {
+        if let Some(droplay) = self.droplay.as_ref() {
+            return droplay.get_dro_header_json();
+        }
+        panic!("droplay instance not exsist");
+    }
+
+    ///
+    /// Get the JSON parsed GD3 of the DRO file.
+    ///
+    pub fn get_seq_gd3(&self) -> String {
+        if let Some(droplay) = self.droplay.as_ref() {
+            return droplay.get_dro_gd3_json();
+        }
+        panic!("droplay instance not exsist");
+    }
+
+    ///
+    /// Initialize sound driver.
+    ///
+    pub fn init(&mut self) -> bool {
+        let droplay = driver::DroPlay::new(
+            SoundSlot::new(
+                driver::DRO_TICK_RATE,
+                self.output_sampling_rate,
+                self.output_sample_chunk_size,
+            ),
+            self.dro_file.as_slice()
+        );
+        if droplay.is_err() {
+            return false;
+        }
+        self.droplay = Some(droplay.unwrap());
+        true
+    }
+
+    ///
+    /// Continue playing until output_sample_chunk_size is satisfied.
+    ///
+    /// The number of times the song has been looped will be returned.
+    /// In the case of an infinite loop, the std::usize::MAX value is always returned.
+    ///
+    pub fn play(&mut self) -> usize {
+        if let Some(droplay) = self.droplay.as_mut() {
+            return droplay.play(true)
+        }
+        panic!("droplay instance not exsist");
+    }
+}
+
+#[wasm_bindgen]
+pub struct ImfPlay {
+    output_sampling_rate: u32,
+    output_sample_chunk_size: usize,
+    imf_rate: u32,
+    imf_file: Vec<u8>,
+    imfplay: Option<driver::ImfPlay>,
+}
+
+///
+/// ImfPlay WebAssembly Interface
+///
+#[wasm_bindgen]
+impl ImfPlay {
+    ///
+    /// constructor
+    ///
+    #[wasm_bindgen(constructor)]
+    pub fn from(
+        output_sampling_rate: u32,
+        output_sample_chunk_size: usize,
+        data_length: usize,
+    ) -> Self {
+        #[cfg(feature = "console_error_panic_hook")]
+        console_error_panic_hook::set_once();
+
+        ImfPlay {
+            output_sampling_rate,
+            output_sample_chunk_size,
+            imf_rate: 0,
+            imf_file: vec![0; data_length],
+            imfplay: None,
+        }
+    }
+
+    ///
+    /// Return imfdata buffer referance.
+    ///
+    pub fn get_seq_data_ref(&mut self) -> *mut u8 {
+        self.imf_file.as_mut_ptr()
+    }
+
+    ///
+    /// Return sampling_l buffer referance.
+    ///
+    pub fn get_sampling_l_ref(&self) -> *const f32 {
+        if let Some(imfplay) = self.imfplay.as_ref() {
+            return imfplay.get_sampling_l_ref();
+        }
+        panic!("imfplay instance not exsist");
+    }
+
+    ///
+    /// Return sampling_r buffer referance.
+    ///
+    pub fn get_sampling_r_ref(&self) -> *const f32 {
+        if let Some(imfplay) = self.imfplay.as_ref() {
+            return imfplay.get_sampling_r_ref();
+        }
+        panic!("imfplay instance not exsist");
+    }
+
+    ///
+    /// Get the JSON parsed from the header of the IMF file.
+    ///
+    pub fn get_seq_header(&self) -> String {
+        if let Some(imfplay) = self.imfplay.as_ref() {
+            return imfplay.get_imf_header_json();
+        }
+        panic!("imfplay instance not exsist");
+    }
+
+    ///
+    /// Get the JSON parsed GD3 of the IMF file.
+    ///
+    pub fn get_seq_gd3(&self) -> String {
+        if let Some(imfplay) = self.imfplay.as_ref() {
+            return imfplay.get_imf_gd3_json();
+        }
+        panic!("imfplay instance not exsist");
+    }
+
+    ///
+    /// Set the timer rate (280, 560 or 700) before init.
+    ///
+    pub fn set_imf_rate(&mut self, imf_rate: u32) {
+        self.imf_rate = imf_rate;
+    }
+
+    ///
+    /// Initialize sound driver.
+    ///
+    pub fn init(&mut self) -> bool {
+        let imfplay = driver::ImfPlay::new_with_rate(
+            SoundSlot::new(
+                driver::IMF_TICK_RATE,
+                self.output_sampling_rate,
+                self.output_sample_chunk_size,
+            ),
+            self.imf_file.as_slice(),
+            driver::ImfRate::from_tick_rate(self.imf_rate),
+        );
+        if imfplay.is_err() {
+            return false;
+        }
+        self.imfplay = Some(imfplay.unwrap());
+        true
+    }
+
+    ///
+    /// Continue playing until output_sample_chunk_size is satisfied.
+    ///
+    /// The number of times the song has been looped will be returned.
+    /// In the case of an infinite loop, the std::usize::MAX value is always returned.
+    ///
+    pub fn play(&mut self) -> usize {
+        if let Some(imfplay) = self.imfplay.as_mut() {
+            return imfplay.play(true)
+        }
+        panic!("imfplay instance not exsist");
+    }
+}
+
+#[wasm_bindgen]
+pub struct MdxPlay {
+    output_sampling_rate: u32,
+    output_sample_chunk_size: usize,
+    mdx_file: Vec<u8>,
+    mdxplay: Option<driver::MdxPlay>,
+}
+
+///
+/// MdxPlay WebAssembly Interface
+///
+#[wasm_bindgen]
+impl MdxPlay {
+    ///
+    /// constructor
+    ///
+    #[wasm_bindgen(constructor)]
+    pub fn from(
+        output_sampling_rate: u32,
+        output_sample_chunk_size: usize,
+        data_length: usize,
+    ) -> Self {
+        #[cfg(feature = "console_error_panic_hook")]
+        console_error_panic_hook::set_once();
+
+        MdxPlay {
+            output_sampling_rate,
+            output_sample_chunk_size,
+            mdx_file: vec![0; data_length],
+            mdxplay: None,
+        }
+    }
+
+    ///
+    /// Return mdxdata buffer referance.
+    ///
+    pub fn get_seq_data_ref(&mut self) -> *mut u8 {
+        self.mdx_file.as_mut_ptr()
+    }
+
+    ///
+    /// Return sampling_l buffer referance.
+    ///
+    pub fn get_sampling_l_ref(&self) -> *const f32 {
+        if let Some(mdxplay) = self.mdxplay.as_ref() {
+            return mdxplay.get_sampling_l_ref();
+        }
+        panic!("mdxplay instance not exsist");
+    }
+
+    ///
+    /// Return sampling_r buffer referance.
+    ///
+    pub fn get_sampling_r_ref(&self) -> *const f32 {
+        if let Some(mdxplay) = self.mdxplay.as_ref() {
+            return mdxplay.get_sampling_r_ref();
+        }
+        panic!("mdxplay instance not exsist");
+    }
+
+    ///
+    /// Get the JSON parsed from the header of the MDX file.
+    ///
+    pub fn get_seq_header(&self) -> String {
+        if let Some(mdxplay) = self.mdxplay.as_ref() {
+            return mdxplay.get_mdx_header_json();
+        }
+        panic!("mdxplay instance not exsist");
+    }
+
+    ///
+    /// Get the JSON parsed GD3 of the MDX file.
+    ///
+    pub fn get_seq_gd3(&self) -> String {
+        if let Some(mdxplay) = self.mdxplay.as_ref() {
+            return mdxplay.get_mdx_gd3_json();
+        }
+        panic!("mdxplay instance not exsist");
+    }
+
+    ///
+    /// Add PDX (ADPCM samples) after init.
+    ///
+    pub fn add_pdx(&mut self, pdx: &[u8]) -> bool {
+        if let Some(mdxplay) = self.mdxplay.as_mut() {
+            return mdxplay.add_pdx(pdx).is_ok();
+        }
+        panic!("mdxplay instance not exsist");
+    }
+
+    ///
+    /// Initialize sound driver.
+    ///
+    pub fn init(&mut self) -> bool {
+        let mdxplay = driver::MdxPlay::new(
+            SoundSlot::new(
+                driver::MDX_TICK_RATE,
+                self.output_sampling_rate,
+                self.output_sample_chunk_size,
+            ),
+            self.mdx_file.as_slice()
+        );
+        if mdxplay.is_err() {
+            return false;
+        }
+        self.mdxplay = Some(mdxplay.unwrap());
+        true
+    }
+
+    ///
+    /// Continue playing until output_sample_chunk_size is satisfied.
+    ///
+    /// The number of times the song has been looped will be returned.
+    /// In the case of an infinite loop, the std::usize::MAX value is always returned.
+    ///
+    pub fn play(&mut self) -> usize {
+        if let Some(mdxplay) = self.mdxplay.as_mut() {
+            return mdxplay.play(true)
+        }
+        panic!("mdxplay instance not exsist");
+    }
+}
+
+#[wasm_bindgen]
+pub struct PlaylistPlay {
+    output_sampling_rate: u32,
+    output_sample_chunk_size: usize,
+    m3u_file: Vec<u8>,
+    playlistplay: Option<driver::PlaylistPlay>,
+}
+
+///
+/// PlaylistPlay WebAssembly Interface
+///
+#[wasm_bindgen]
+impl PlaylistPlay {
+    ///
+    /// constructor
+    ///
+    #[wasm_bindgen(constructor)]
+    pub fn from(
+        output_sampling_rate: u32,
+        output_sample_chunk_size: usize,
+        data_length: usize,
+    ) -> Self {
+        #[cfg(feature = "console_error_panic_hook")]
+        console_error_panic_hook::set_once();
+
+        PlaylistPlay {
+            output_sampling_rate,
+            output_sample_chunk_size,
+            m3u_file: vec![0; data_length],
+            playlistplay: None,
+        }
+    }
+
+    ///
+    /// Return m3u buffer referance.
+    ///
+    pub fn get_seq_data_ref(&mut self) -> *mut u8 {
+        self.m3u_file.as_mut_ptr()
+    }
+
+    ///
+    /// Return sampling_l buffer referance.
+    ///
+    pub fn get_sampling_l_ref(&self) -> *const f32 {
+        if let Some(playlistplay) = self.playlistplay.as_ref() {
+            return playlistplay.get_sampling_l_ref();
+        }
+        panic!("playlistplay instance not exsist");
+    }
+
+    ///
+    /// Return sampling_r buffer referance.
+    ///
+    pub fn get_sampling_r_ref(&self) -> *const f32 {
+        if let Some(playlistplay) = self.playlistplay.as_ref() {
+            return playlistplay.get_sampling_r_ref();
+        }
+        panic!("playlistplay instance not exsist");
+    }
+
+    ///
+    /// Get the JSON parsed entries of the playlist.
+    ///
+    pub fn get_playlist(&self) -> String {
+        if let Some(playlistplay) = self.playlistplay.as_ref() {
+            return playlistplay.get_playlist_json();
+        }
+        panic!("playlistplay instance not exsist");
+    }
+
+    ///
+    /// Get the index of the playing entry.
+    ///
+    pub fn get_track_index(&self) -> usize {
+        if let Some(playlistplay) = self.playlistplay.as_ref() {
+            return playlistplay.get_track_index();
+        }
+        panic!("playlistplay instance not exsist");
+    }
+
+    ///
+    /// Add the file of the entry (file name as written in the playlist) after init.
+    ///
+    pub fn add_file(&mut self, file_name: &str, file: &[u8]) {
+        if let Some(playlistplay) = self.playlistplay.as_mut() {
+            playlistplay.add_file(file_name, file);
+            return;
+        }
+        panic!("playlistplay instance not exsist");
+    }
+
+    ///
+    /// Add YM2608 internal rhythm ROM.
+    ///
+    pub fn add_ym2608_rhythm_rom(&mut self, rom: &[u8]) {
+        if let Some(playlistplay) = self.playlistplay.as_mut() {
+            playlistplay.add_ym2608_rhythm_rom(rom);
+            return;
+        }
+        panic!("playlistplay instance not exsist");
+    }
+
+    ///
+    /// Set the loop count of the entry without the override (default is 1).
+    ///
+    pub fn set_default_loop_count(&mut self, loop_count: usize) {
+        if let Some(playlistplay) = self.playlistplay.as_mut() {
+            playlistplay.set_default_loop_count(loop_count);
+            return;
+        }
+        panic!("playlistplay instance not exsist");
+    }
+
+    ///
+    /// Set the fade out (ms) of the entry without the override (default is 5000).
+    ///
+    pub fn set_default_fade(&mut self, fade_ms: u32) {
+        if let Some(playlistplay) = self.playlistplay.as_mut() {
+            playlistplay.set_default_fade(fade_ms);
+            return;
+        }
+        panic!("playlistplay instance not exsist");
+    }
+
+    ///
+    /// Initialize playlist driver.
+    ///
+    pub fn init(&mut self) -> bool {
+        let playlistplay = driver::PlaylistPlay::new(
+            self.output_sampling_rate,
+            self.output_sample_chunk_size,
+            self.m3u_file.as_slice(),
+        );
+        if playlistplay.is_err() {
+            return false;
+        }
+        self.playlistplay = Some(playlistplay.unwrap());
+        true
+    }
+
+    ///
+    /// Continue playing until output_sample_chunk_size is satisfied.
+    ///
+    /// The next track continues without gaps.
+    /// At the end of the playlist, the std::usize::MAX value is returned.
+    ///
+    pub fn play(&mut self) -> usize {
+        if let Some(playlistplay) = self.playlistplay.as_mut() {
+            return playlistplay.play()
+        }
+        panic!("playlistplay instance not exsist");
+    }
+}
+
+#[wasm_bindgen]
+pub struct PackReader {
+    zip_file: Vec<u8>,
+    packreader: Option<driver::PackReader>,
+}
+
+///
+/// PackReader WebAssembly Interface
+///
+#[wasm_bindgen]
+impl PackReader {
+    ///
+    /// constructor
+    ///
+    #[wasm_bindgen(constructor)]
+    pub fn from(data_length: usize) -> Self {
+        #[cfg(feature = "console_error_panic_hook")]
+        console_error_panic_hook::set_once();
+
+        PackReader {
+            zip_file: vec![0; data_length],
+            packreader: None,
+        }
+    }
+
+    ///
+    /// Return zip buffer referance.
+    ///
+    pub fn get_seq_data_ref(&mut self) -> *mut u8 {
+        self.zip_file.as_mut_ptr()
+    }
+
+    ///
+    /// Get the JSON entries (file name, title and length) of the pack.
+    ///
+    pub fn get_pack(&self) -> String {
+        if let Some(packreader) = self.packreader.as_ref() {
+            return packreader.get_pack_json();
+        }
+        panic!("packreader instance not exsist");
+    }
+
+    ///
+    /// Read the file of the entry for VgmPlay/XgmPlay (empty if the entry is broken).
+    ///
+    pub fn read_entry(&self, index: usize) -> Vec<u8> {
+        if let Some(packreader) = self.packreader.as_ref() {
+            return packreader.read_entry(index).unwrap_or_default();
+        }
+        panic!("packreader instance not exsist");
+    }
+
+    ///
+    /// Initialize pack reader.
+    ///
+    pub fn init(&mut self) -> bool {
+        let packreader = driver::PackReader::new(self.zip_file.as_slice());
+        if packreader.is_err() {
+            return false;
+        }
+        self.packreader = Some(packreader.unwrap());
+        true
+    }
+}